@@ -629,6 +629,34 @@ impl Builder {
         })
     }
 
+    pub fn range_expr<E>(self, start: Option<E>, end: Option<E>) -> P<Expr>
+    where
+        E: Make<P<Expr>>,
+    {
+        let start = start.map(|e| e.make(&self));
+        let end = end.map(|e| e.make(&self));
+        P(Expr {
+            id: self.id,
+            node: ExprKind::Range(start, end, RangeLimits::HalfOpen),
+            span: self.span,
+            attrs: self.attrs.into(),
+        })
+    }
+
+    pub fn range_inclusive_expr<E>(self, start: E, end: E) -> P<Expr>
+    where
+        E: Make<P<Expr>>,
+    {
+        let start = start.make(&self);
+        let end = end.make(&self);
+        P(Expr {
+            id: self.id,
+            node: ExprKind::Range(Some(start), Some(end), RangeLimits::Closed),
+            span: self.span,
+            attrs: self.attrs.into(),
+        })
+    }
+
     pub fn unary_expr<O, E>(self, op: O, a: E) -> P<Expr>
     where
         O: Make<UnOp>,
@@ -1136,6 +1164,20 @@ impl Builder {
         })
     }
 
+    pub fn tuple_struct_pat<Pa, Pt>(self, path: Pa, pats: Vec<Pt>) -> P<Pat>
+    where
+        Pa: Make<Path>,
+        Pt: Make<P<Pat>>,
+    {
+        let path = path.make(&self);
+        let pats: Vec<P<Pat>> = pats.into_iter().map(|x| x.make(&self)).collect();
+        P(Pat {
+            id: self.id,
+            node: PatKind::TupleStruct(path, pats, None),
+            span: self.span,
+        })
+    }
+
     pub fn qpath_pat<Pa>(self, qself: Option<QSelf>, path: Pa) -> P<Pat>
     where
         Pa: Make<Path>,
@@ -1332,6 +1374,22 @@ impl Builder {
         })
     }
 
+    pub fn dyn_trait_ty<Pa>(self, path: Pa) -> P<Ty>
+    where
+        Pa: Make<Path>,
+    {
+        let path = path.make(&self);
+        let bound = GenericBound::Trait(
+            PolyTraitRef::new(vec![], path, self.span),
+            TraitBoundModifier::None,
+        );
+        P(Ty {
+            id: self.id,
+            node: TyKind::TraitObject(vec![bound], TraitObjectSyntax::Dyn),
+            span: self.span,
+        })
+    }
+
     pub fn ident_ty<I>(self, name: I) -> P<Ty>
     where
         I: Make<Ident>,
@@ -1676,6 +1734,34 @@ impl Builder {
         )
     }
 
+    pub fn trait_impl_item<Pa, T>(self, trait_path: Pa, ty: T, items: Vec<ImplItem>) -> P<Item>
+    where
+        Pa: Make<Path>,
+        T: Make<P<Ty>>,
+    {
+        let path = trait_path.make(&self);
+        let ty = ty.make(&self);
+        Self::item(
+            keywords::Invalid.ident(),
+            self.attrs,
+            self.vis,
+            self.span,
+            self.id,
+            ItemKind::Impl(
+                self.unsafety,
+                ImplPolarity::Positive,
+                Defaultness::Final,
+                self.generics,
+                Some(TraitRef {
+                    path,
+                    ref_id: DUMMY_NODE_ID,
+                }),
+                ty,
+                items,
+            ),
+        )
+    }
+
     pub fn extern_crate_item<I>(self, name: I, rename: Option<I>) -> P<Item>
     where
         I: Make<Ident>,
@@ -1693,9 +1779,8 @@ impl Builder {
     }
 
     // `use <path>;` item
-    // TODO: for now, we only support simple paths with an optional rename;
-    // if we ever need them, we should add support for globs and nested trees,
-    // e.g., `use foo::*;` and `use foo::{a, b, c};`
+    // Only simple paths with an optional rename; see `use_multiple_item` and
+    // `use_glob_item` for nested trees and globs
     pub fn use_item<Pa, I>(self, path: Pa, rename: Option<I>) -> P<Item>
     where
         Pa: Make<Path>,
@@ -1752,6 +1837,27 @@ impl Builder {
         )
     }
 
+    // `use <path>::*;` item
+    pub fn use_glob_item<Pa>(self, path: Pa) -> P<Item>
+    where
+        Pa: Make<Path>,
+    {
+        let path = path.make(&self);
+        let use_tree = UseTree {
+            span: DUMMY_SP,
+            prefix: path,
+            kind: UseTreeKind::Glob,
+        };
+        Self::item(
+            keywords::Invalid.ident(),
+            self.attrs,
+            self.vis,
+            self.span,
+            self.id,
+            ItemKind::Use(P(use_tree)),
+        )
+    }
+
     pub fn foreign_items(self, items: Vec<ForeignItem>) -> P<Item> {
         let fgn_mod = ForeignMod {
             abi: self.abi,
@@ -1793,6 +1899,54 @@ impl Builder {
         }
     }
 
+    pub fn method_impl_item<I, D, B>(self, name: I, decl: D, block: B) -> ImplItem
+    where
+        I: Make<Ident>,
+        D: Make<P<FnDecl>>,
+        B: Make<P<Block>>,
+    {
+        let name = name.make(&self);
+        let decl = decl.make(&self);
+        let block = block.make(&self);
+        let header = FnHeader {
+            unsafety: self.unsafety,
+            asyncness: dummy_spanned(IsAsync::NotAsync),
+            constness: dummy_spanned(self.constness),
+            abi: self.abi,
+        };
+        let kind = ImplItemKind::Method(MethodSig { header, decl }, block);
+        Self::impl_item_(
+            name,
+            self.attrs,
+            self.vis,
+            Defaultness::Final,
+            self.generics,
+            self.span,
+            self.id,
+            kind,
+        )
+    }
+
+    pub fn ty_impl_item<I, T>(self, name: I, ty: T) -> ImplItem
+    where
+        I: Make<Ident>,
+        T: Make<P<Ty>>,
+    {
+        let name = name.make(&self);
+        let ty = ty.make(&self);
+        let kind = ImplItemKind::Type(ty);
+        Self::impl_item_(
+            name,
+            self.attrs,
+            self.vis,
+            Defaultness::Final,
+            self.generics,
+            self.span,
+            self.id,
+            kind,
+        )
+    }
+
     pub fn mac_impl_item<M>(self, mac: M) -> ImplItem
     where
         M: Make<Mac>,
@@ -15,6 +15,7 @@
 #include "clang/Tooling/CommonOptionsParser.h"
 
 #include "clang/AST/DeclVisitor.h"
+#include "clang/AST/Mangle.h"
 #include "clang/AST/RecordLayout.h"
 #include "clang/AST/RecursiveASTVisitor.h"
 #include "clang/AST/StmtVisitor.h"
@@ -67,6 +68,46 @@ void cbor_encode_string_array(CborEncoder *encoder,
     cbor_encoder_close_container(encoder, &array);
 }
 
+// Encode a variable of an OpenMP clause as the id of its declaration, or null
+// if it is not a plain variable
+void cbor_encode_clause_var(CborEncoder *encoder, const Expr *E) {
+    auto DRE = dyn_cast<DeclRefExpr>(E->IgnoreParenImpCasts());
+    if (DRE)
+        cbor_encode_uint(encoder,
+                         uintptr_t(DRE->getDecl()->getCanonicalDecl()));
+    else
+        cbor_encode_null(encoder);
+}
+
+// Does the value of this constant expression depend on the size, alignment,
+// or layout of types on the target?
+bool isLayoutDependent(const Stmt *S) {
+    if (!S)
+        return false;
+    if (isa<UnaryExprOrTypeTraitExpr>(S) || isa<OffsetOfExpr>(S))
+        return true;
+    for (auto child : S->children()) {
+        if (isLayoutDependent(child))
+            return true;
+    }
+    return false;
+}
+
+// Declarations in a C++ declaration context, including the ones in its
+// `extern "C"` blocks and, if requested, in its namespaces
+std::vector<Decl *> memberDecls(const DeclContext *DC, bool inNamespaces) {
+    std::vector<Decl *> decls;
+    for (auto d : DC->decls()) {
+        if (isa<LinkageSpecDecl>(d) || (inNamespaces && isa<NamespaceDecl>(d))) {
+            auto inner = memberDecls(cast<DeclContext>(d), inNamespaces);
+            decls.insert(decls.end(), inner.begin(), inner.end());
+        } else {
+            decls.push_back(d);
+        }
+    }
+    return decls;
+}
+
 std::string make_realpath(std::string const &path) {
     if (auto abs_path = realpath(path.c_str(), nullptr)) {
         auto result = std::string(abs_path);
@@ -386,6 +427,18 @@ class TypeEncoder final : public TypeVisitor<TypeEncoder> {
         VisitQualType(pointee);
     }
 
+    // C++ references are translated as pointers, with the dereferences and
+    // address-ofs that Clang leaves implicit added by the importer.
+    void VisitReferenceType(const ReferenceType *T) {
+        auto pointee = T->getPointeeType();
+        auto qt = encodeQualType(pointee);
+
+        encodeType(T, TagReferenceType,
+                   [qt](CborEncoder *local) { cbor_encode_uint(local, qt); });
+
+        VisitQualType(pointee);
+    }
+
     void VisitTypedefType(const TypedefType *T);
 
     void VisitTypeOfType(const TypeOfType *T) {
@@ -458,7 +511,7 @@ class TranslateASTVisitor final
 
     // Template required because Decl and Stmt don't share a common base class
     void encode_entry_raw(void *ast, ASTEntryTag tag, SourceLocation loc,
-                          const QualType ty, bool rvalue, bool isVaList,
+                          SourceLocation endLoc, const QualType ty, bool rvalue, bool isVaList,
                           bool encodeMacroExpansions,
                           const std::vector<void *> &childIds,
                           std::function<void(CborEncoder *)> extra) {
@@ -508,7 +561,11 @@ class TranslateASTVisitor final
         }
         cbor_encoder_close_container(&local, &childEnc);
 
-        // 9.. - Extra entries
+        // 9 - End line number
+        // 10 - End column number
+        encodeSourceEndPos(&local, endLoc);
+
+        // 11.. - Extra entries
         extra(&local);
 
         cbor_encoder_close_container(encoder, &local);
@@ -530,11 +587,13 @@ class TranslateASTVisitor final
         auto encodeMacroExpansions = true;
 #if CLANG_VERSION_MAJOR < 8
         SourceLocation loc = ast->getLocStart();
+        SourceLocation endLoc = ast->getLocEnd();
 #else
         SourceLocation loc = ast->getBeginLoc();
+        SourceLocation endLoc = ast->getEndLoc();
 #endif // CLANG_VERSION_MAJOR
-        encode_entry_raw(ast, tag, loc, ty, ast->isRValue(), isVaList, encodeMacroExpansions,
-                         childIds, extra);
+        encode_entry_raw(ast, tag, loc, endLoc, ty, ast->isRValue(), isVaList,
+                         encodeMacroExpansions, childIds, extra);
         typeEncoder.VisitQualType(ty);
     }
 
@@ -547,11 +606,13 @@ class TranslateASTVisitor final
         auto encodeMacroExpansions = false;
 #if CLANG_VERSION_MAJOR < 8
         SourceLocation loc = ast->getLocStart();
+        SourceLocation endLoc = ast->getLocEnd();
 #else
         SourceLocation loc = ast->getBeginLoc();
+        SourceLocation endLoc = ast->getEndLoc();
 #endif // CLANG_VERSION_MAJOR
-        encode_entry_raw(ast, tag, loc, s, rvalue, isVaList, encodeMacroExpansions,
-                         childIds, extra);
+        encode_entry_raw(ast, tag, loc, endLoc, s, rvalue, isVaList,
+                         encodeMacroExpansions, childIds, extra);
     }
 
     void encode_entry(
@@ -560,7 +621,12 @@ class TranslateASTVisitor final
         std::function<void(CborEncoder *)> extra = [](CborEncoder *) {}) {
         auto rvalue = false;
         auto encodeMacroExpansions = false;
-        encode_entry_raw(ast, tag, ast->getLocation(), T, rvalue,
+#if CLANG_VERSION_MAJOR < 8
+        SourceLocation endLoc = ast->getLocEnd();
+#else
+        SourceLocation endLoc = ast->getEndLoc();
+#endif // CLANG_VERSION_MAJOR
+        encode_entry_raw(ast, tag, ast->getLocation(), endLoc, T, rvalue,
                          isVaList(ast, T), encodeMacroExpansions, childIds, extra);
     }
 
@@ -573,7 +639,12 @@ class TranslateASTVisitor final
         std::function<void(CborEncoder *)> extra = [](CborEncoder *) {}) {
         auto rvalue = false;
         auto encodeMacroExpansions = false;
-        encode_entry_raw(ast, tag, loc, T, rvalue,
+#if CLANG_VERSION_MAJOR < 8
+        SourceLocation endLoc = ast->getLocEnd();
+#else
+        SourceLocation endLoc = ast->getEndLoc();
+#endif // CLANG_VERSION_MAJOR
+        encode_entry_raw(ast, tag, loc, endLoc, T, rvalue,
                          isVaList(ast, T), encodeMacroExpansions, childIds, extra);
     }
 
@@ -661,6 +732,33 @@ class TranslateASTVisitor final
     // Override the default behavior of the RecursiveASTVisitor
     bool shouldVisitImplicitCode() const { return true; }
 
+    // Only the subset of C++ that corresponds to C is translated. Templates
+    // and member functions are skipped entirely, since their bodies contain
+    // dependent types and expressions we have no encoding for.
+    bool TraverseDecl(Decl *D) {
+        if (!D)
+            return true;
+
+        if (isa<TemplateDecl>(D) || D->isTemplated()) {
+            if (isa<TemplateDecl>(D))
+                printWarning("Encountered unsupported C++ template", D);
+            return true;
+        }
+
+        if (isa<CXXMethodDecl>(D)) {
+            if (!D->isImplicit())
+                printWarning("Encountered unsupported C++ member function", D);
+            return true;
+        }
+
+        if (auto RD = dyn_cast<CXXRecordDecl>(D)) {
+            if (RD->isInjectedClassName())
+                return true;
+        }
+
+        return RecursiveASTVisitor::TraverseDecl(D);
+    }
+
     // Return the filenames as a vector. Indices correspond to file IDs.
     std::vector<string> getFilenames() const {
         // Store filenames in order
@@ -687,7 +785,8 @@ class TranslateASTVisitor final
             std::vector<void *> childIds(Info.Expressions.begin(),
                                          Info.Expressions.end());
 
-            encode_entry_raw(Mac, tag, Mac->getDefinitionLoc(), QualType(), false,
+            encode_entry_raw(Mac, tag, Mac->getDefinitionLoc(),
+                             Mac->getDefinitionEndLoc(), QualType(), false,
                              false, false, childIds, [Name](CborEncoder *local) {
                                  cbor_encode_string(local, Name.str());
                              });
@@ -724,6 +823,19 @@ class TranslateASTVisitor final
         cbor_encode_uint(enc, col);
     }
 
+    // Encode the line and column of the last token of a node. The file is
+    // assumed to be the same as the one of the beginning of the node.
+    void encodeSourceEndPos(CborEncoder *enc, SourceLocation loc) {
+        auto &manager = Context->getSourceManager();
+
+        if (manager.isMacroArgExpansion(loc) ||
+            manager.isMacroBodyExpansion(loc))
+            loc = manager.getFileLoc(loc);
+
+        cbor_encode_uint(enc, manager.getPresumedLineNumber(loc));
+        cbor_encode_uint(enc, manager.getPresumedColumnNumber(loc));
+    }
+
     //
     // Statements
     //
@@ -806,6 +918,45 @@ class TranslateASTVisitor final
         return true;
     }
 
+    /*
+     Represents an OpenMP `#pragma omp parallel for` directive
+     Children:
+     - the associated `for` statement
+     Extras:
+     - array of clauses, each an array of the clause name and its arguments:
+       the reduction identifier and the variables of `reduction` clauses,
+       the variables of `private` clauses and none for other clauses
+     */
+    bool VisitOMPParallelForDirective(OMPParallelForDirective *D) {
+        auto CS = cast<CapturedStmt>(D->getAssociatedStmt());
+        std::vector<void *> childIds = {CS->getCapturedStmt()};
+        encode_entry(D, TagOMPParallelForDirective, childIds,
+                     [D](CborEncoder *extras) {
+            CborEncoder clauses;
+            cbor_encoder_create_array(extras, &clauses,
+                                      CborIndefiniteLength);
+            for (auto C : D->clauses()) {
+                CborEncoder clause;
+                cbor_encoder_create_array(&clauses, &clause,
+                                          CborIndefiniteLength);
+                cbor_encode_text_stringz(
+                    &clause, getOpenMPClauseName(C->getClauseKind()));
+                if (auto R = dyn_cast<OMPReductionClause>(C)) {
+                    cbor_encode_string(
+                        &clause, R->getNameInfo().getName().getAsString());
+                    for (auto E : R->varlists())
+                        cbor_encode_clause_var(&clause, E);
+                } else if (auto P = dyn_cast<OMPPrivateClause>(C)) {
+                    for (auto E : P->varlists())
+                        cbor_encode_clause_var(&clause, E);
+                }
+                cbor_encoder_close_container(&clauses, &clause);
+            }
+            cbor_encoder_close_container(extras, &clauses);
+        });
+        return true;
+    }
+
     bool VisitWhileStmt(WhileStmt *WS) {
         std::vector<void *> childIds = {WS->getCond(), WS->getBody()};
         encode_entry(WS, TagWhileStmt, childIds);
@@ -1040,8 +1191,38 @@ class TranslateASTVisitor final
         return true;
     }
 
+    /*
+     Represents a C11 generic selection, `_Generic(controlling, associations)`
+     Children:
+     - controlling expression
+     - expressions of the associations, in source order
+     Extras:
+     - index of the selected association
+     - types of the associations, in source order, or null for `default`
+     */
     bool VisitGenericSelectionExpr(GenericSelectionExpr *E) {
-        printWarning("Encountered unsupported generic selection expression", E);
+        std::vector<void *> childIds{E->getControllingExpr()};
+        for (unsigned i = 0; i < E->getNumAssocs(); i++) {
+            childIds.push_back(E->getAssocExpr(i));
+        }
+        encode_entry(E, TagGenericSelectionExpr, childIds,
+                     [this, E](CborEncoder *extras) {
+                         cbor_encode_uint(extras, E->getResultIndex());
+                         for (unsigned i = 0; i < E->getNumAssocs(); i++) {
+                             if (E->getAssocTypeSourceInfo(i)) {
+                                 auto qt = typeEncoder.encodeQualType(
+                                     E->getAssocType(i));
+                                 cbor_encode_uint(extras, qt);
+                             } else {
+                                 cbor_encode_null(extras);
+                             }
+                         }
+                     });
+        for (unsigned i = 0; i < E->getNumAssocs(); i++) {
+            if (E->getAssocTypeSourceInfo(i)) {
+                typeEncoder.VisitQualType(E->getAssocType(i));
+            }
+        }
         return true;
     }
 
@@ -1447,6 +1628,11 @@ class TranslateASTVisitor final
 
         auto decl = DRE->getDecl()->getCanonicalDecl();
 
+        // C++ member functions are not translated, the member calls and
+        // overloaded operators referring to them are diagnosed instead.
+        if (isa<CXXMethodDecl>(decl))
+            return true;
+
         std::vector<void *> childIds{decl};
         encode_entry(DRE, TagDeclRefExpr, childIds);
 
@@ -1458,6 +1644,11 @@ class TranslateASTVisitor final
     }
 
     bool VisitCallExpr(CallExpr *CE) {
+        // Calls of C++ member functions and overloaded operators are handled
+        // with the rest of C++ below
+        if (isa<CXXMemberCallExpr>(CE) || isa<CXXOperatorCallExpr>(CE))
+            return true;
+
         std::vector<void *> childIds = {CE->getCallee()};
         for (auto x : CE->arguments()) {
             childIds.push_back(x);
@@ -1515,11 +1706,176 @@ class TranslateASTVisitor final
         return true;
     }
 
+    /*
+     Represents an Apple block literal, `^(params) { body }`
+     Children:
+     - canonical parameter declarations
+     - block body
+     Extras:
+     - array of [captured variable, captured by reference] pairs
+     */
+    bool VisitBlockExpr(BlockExpr *E) {
+        auto BD = E->getBlockDecl();
+
+        std::vector<void *> childIds;
+        for (auto x : BD->parameters()) {
+            auto cd = x->getCanonicalDecl();
+            childIds.push_back(cd);
+            TraverseDecl(cd);
+        }
+        childIds.push_back(BD->getBody());
+
+        encode_entry(E, TagBlockExpr, childIds, [BD](CborEncoder *extras) {
+            CborEncoder captures;
+            cbor_encoder_create_array(extras, &captures, BD->getNumCaptures());
+            for (auto const &C : BD->captures()) {
+                CborEncoder capture;
+                cbor_encoder_create_array(&captures, &capture, 2);
+                cbor_encode_uint(&capture, uintptr_t(C.getVariable()));
+                cbor_encode_boolean(&capture, C.isByRef());
+                cbor_encoder_close_container(&captures, &capture);
+            }
+            cbor_encoder_close_container(extras, &captures);
+        });
+        return true;
+    }
+
     bool VisitGNUNullExpr(GNUNullExpr *E) {
         printWarning("Encountered unsupported GNU extension: null expression", E);
         return true;
     }
 
+    //
+    // C++
+    //
+
+    bool VisitStmt(Stmt *S) {
+        StringRef name = S->getStmtClassName();
+        if ((name.startswith("CXX") || isa<LambdaExpr>(S)) && !isSupportedCXX(S))
+            printWarning("Encountered unsupported C++ construct: " + name.str(), S);
+        return true;
+    }
+
+    // `nullptr` is always converted to a pointer type by an implicit
+    // NullToPointer cast, so we encode it as the null pointer constant `0`.
+    bool VisitCXXNullPtrLiteralExpr(CXXNullPtrLiteralExpr *E) {
+        auto ty = Context->IntTy;
+#if CLANG_VERSION_MAJOR < 8
+        SourceLocation loc = E->getLocStart();
+        SourceLocation endLoc = E->getLocEnd();
+#else
+        SourceLocation loc = E->getBeginLoc();
+        SourceLocation endLoc = E->getEndLoc();
+#endif // CLANG_VERSION_MAJOR
+        encode_entry_raw(E, TagIntegerLiteral, loc, endLoc, ty, true, false,
+                         true, {}, [](CborEncoder *array) {
+                             cbor_encode_uint(array, 0);
+                             cbor_encode_uint(array, 10);
+                         });
+        typeEncoder.VisitQualType(ty);
+        return true;
+    }
+
+    bool VisitCXXBoolLiteralExpr(CXXBoolLiteralExpr *E) {
+        auto value = E->getValue();
+        std::vector<void *> childIds;
+        encode_entry(E, TagIntegerLiteral, childIds, [value](CborEncoder *array) {
+            cbor_encode_uint(array, value);
+            cbor_encode_uint(array, 10);
+        });
+        return true;
+    }
+
+    // Copies of C structs are trivial copy or move constructions in C++,
+    // which we encode as their (parenthesized) argument.
+    bool VisitCXXConstructExpr(CXXConstructExpr *E) {
+        if (E->getNumArgs() == 1 && isTrivialConstruction(E)) {
+            std::vector<void *> childIds{E->getArg(0)};
+            encode_entry(E, TagParenExpr, childIds);
+        }
+        return true;
+    }
+
+    bool VisitMaterializeTemporaryExpr(MaterializeTemporaryExpr *E) {
+        std::vector<void *> childIds{E->GetTemporaryExpr()};
+        encode_entry(E, TagParenExpr, childIds);
+        return true;
+    }
+
+    // Assignments of C structs call the trivial assignment operator in C++
+    bool VisitCXXOperatorCallExpr(CXXOperatorCallExpr *E) {
+        if (!isTrivialAssignment(E))
+            return true;
+
+        std::vector<void *> childIds{E->getArg(0), E->getArg(1)};
+        encode_entry(E, TagBinaryOperator, childIds, [this](CborEncoder *array) {
+            cbor_encode_string(array, "=");
+            encode_qualtype(array, QualType());
+            encode_qualtype(array, QualType());
+        });
+        return true;
+    }
+
+    static bool isTrivialConstruction(const CXXConstructExpr *E) {
+        auto ctor = E->getConstructor();
+        if (!ctor->isTrivial() || isa<CXXTemporaryObjectExpr>(E))
+            return false;
+        return E->getNumArgs() == 0 ||
+               (E->getNumArgs() == 1 && ctor->isCopyOrMoveConstructor());
+    }
+
+    static bool isTrivialAssignment(const CXXOperatorCallExpr *E) {
+        auto method = dyn_cast_or_null<CXXMethodDecl>(E->getDirectCallee());
+        return method && method->isTrivial() && E->getNumArgs() == 2 &&
+               (method->isCopyAssignmentOperator() ||
+                method->isMoveAssignmentOperator());
+    }
+
+    static bool isSupportedCXX(Stmt *S) {
+        if (isa<CXXNullPtrLiteralExpr>(S) || isa<CXXBoolLiteralExpr>(S))
+            return true;
+        if (auto CE = dyn_cast<CXXConstructExpr>(S))
+            return isTrivialConstruction(CE);
+        if (auto OC = dyn_cast<CXXOperatorCallExpr>(S))
+            return isTrivialAssignment(OC);
+        return false;
+    }
+
+    /*
+     C++ namespace, translated as a module
+     Children:
+     - member declarations, including nested namespaces
+     Extras:
+     - name as string, empty for anonymous and inline namespaces
+     */
+    bool VisitNamespaceDecl(NamespaceDecl *D) {
+        std::vector<void *> childIds;
+        for (auto x : memberDecls(D, false)) {
+            if (isa<NamespaceDecl>(x) || x->isCanonicalDecl())
+                childIds.push_back(x);
+        }
+
+        encode_entry(D, TagNamespaceDecl, childIds, QualType(),
+                     [D](CborEncoder *array) {
+                         auto name = D->isAnonymousNamespace() || D->isInline()
+                                         ? string()
+                                         : D->getNameAsString();
+                         cbor_encode_string(array, name);
+                     });
+        return true;
+    }
+
+    bool VisitCXXRecordDecl(CXXRecordDecl *D) {
+        if (D->hasDefinition() && D->isCanonicalDecl()) {
+            auto def = D->getDefinition();
+            if (def->getNumBases() > 0)
+                printWarning("Encountered unsupported C++ base classes", def);
+            if (def->isPolymorphic())
+                printWarning("Encountered unsupported C++ virtual functions", def);
+        }
+        return true;
+    }
+
 
     //
     // Declarations
@@ -1606,6 +1962,23 @@ class TranslateASTVisitor final
                 }
 
                 cbor_encoder_close_container(array, &attr_info);
+
+                // Functions with C++ language linkage have mangled symbols
+                std::unique_ptr<MangleContext> mangler(
+                    Context->createMangleContext());
+                if (FD->getLanguageLinkage() == CXXLanguageLinkage &&
+                    mangler->shouldMangleDeclName(FD)) {
+                    std::string symbol;
+                    llvm::raw_string_ostream os(symbol);
+                    mangler->mangleName(FD, os);
+                    os.flush();
+                    // Drop the marker of names given with `asm` labels
+                    if (!symbol.empty() && symbol[0] == '\01')
+                        symbol.erase(0, 1);
+                    cbor_encode_string(array, symbol);
+                } else {
+                    cbor_encode_null(array);
+                }
             });
         typeEncoder.VisitQualType(functionType);
 
@@ -1647,7 +2020,14 @@ class TranslateASTVisitor final
             }
         }
 
-        std::vector<void *> childIds{(void *)VD->getAnyInitializer()};
+        auto init = VD->getAnyInitializer();
+        // Trivial default construction in C++ leaves the variable uninitialized
+        if (auto CE = dyn_cast_or_null<CXXConstructExpr>(init)) {
+            if (CE->getNumArgs() == 0 && isTrivialConstruction(CE))
+                init = nullptr;
+        }
+
+        std::vector<void *> childIds{(void *)init};
 
         // Use the type from the definition in case the extern was an incomplete
         // type
@@ -1844,6 +2224,9 @@ class TranslateASTVisitor final
                          } else {
                              cbor_encode_uint(local, value.getZExtValue());
                          }
+
+                         cbor_encode_boolean(local,
+                                             isLayoutDependent(D->getInitExpr()));
                      });
         return true;
     }
@@ -2066,6 +2449,18 @@ class TranslateASTVisitor final
             CharSourceRange::getCharRange(E->getSourceRange()));
     }
 
+    void printWarning(std::string Message, Stmt *S) {
+#if CLANG_VERSION_MAJOR < 8
+        SourceLocation loc = S->getLocStart();
+#else
+        SourceLocation loc = S->getBeginLoc();
+#endif // CLANG_VERSION_MAJOR
+        auto DiagBuilder = getDiagBuilder(loc, DiagnosticsEngine::Warning);
+        DiagBuilder.AddString(Message);
+        DiagBuilder.AddSourceRange(
+            CharSourceRange::getCharRange(S->getSourceRange()));
+    }
+
     void printError(std::string Message, Stmt *S) {
 #if CLANG_VERSION_MAJOR < 8
         SourceLocation loc = S->getLocStart();
@@ -2171,9 +2566,11 @@ class TranslateConsumer : public clang::ASTConsumer {
             visitor.encodeMacros();
             cbor_encoder_close_container(&outer, &array);
 
-            // 2. Track all of the top-level declarations
+            // 2. Track all of the top-level declarations. Declarations in C++
+            // namespaces are top-level as well, the namespaces only determine
+            // the modules they are translated into.
             cbor_encoder_create_array(&outer, &array, CborIndefiniteLength);
-            for (auto d : translation_unit->decls()) {
+            for (auto d : memberDecls(translation_unit, true)) {
                 bool emit_decl = true;
                 if (d->isCanonicalDecl()) {
                     emit_decl = true;
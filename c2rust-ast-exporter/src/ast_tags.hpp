@@ -26,6 +26,8 @@ enum ASTEntryTag {
     TagMacroObjectDef,
     TagMacroFunctionDef,
 
+    TagNamespaceDecl,
+
     TagCompoundStmt = 100,
     TagReturnStmt,
     TagIfStmt,
@@ -46,6 +48,7 @@ enum ASTEntryTag {
 
     TagAsmStmt,
     TagAttributedStmt,
+    TagOMPParallelForDirective,
 
     TagBinaryOperator = 200,
     TagUnaryOperator,
@@ -81,6 +84,12 @@ enum ASTEntryTag {
     TagStmtExpr,
     TagChooseExpr,
 
+    // Clang extensions
+    TagBlockExpr,
+
+    // C11
+    TagGenericSelectionExpr,
+
     TagIntegerLiteral = 300,
     TagStringLiteral,
     TagCharacterLiteral,
@@ -136,6 +145,8 @@ enum TypeTag {
     TagBlockPointer,
     TagComplexType,
     TagHalf,
+
+    TagReferenceType,
 };
 
 enum StringTypeTag {
@@ -29,6 +29,8 @@ pub struct AstNode {
     pub fileid: u64,
     pub line: u64,
     pub column: u64,
+    pub end_line: u64,
+    pub end_column: u64,
     pub file_path: Option<PathBuf>,
     pub type_id: Option<u64>,
     pub rvalue: LRValue,
@@ -51,6 +53,7 @@ pub struct CommentNode {
     pub fileid: u64,
     pub line: u64,
     pub column: u64,
+    pub file_path: Option<PathBuf>,
     pub string: String,
 }
 
@@ -110,11 +113,18 @@ pub fn process(items: Value) -> error::Result<AstContext> {
         Vec<(u64, u64, u64, ByteBuf)>,
     ) = from_value(items)?;
 
+    let file_path_of = |fileid: u64| match file_paths[fileid as usize].as_str() {
+        "" => None,
+        "?" => None,
+        path => Some(Path::new(path).to_path_buf()),
+    };
+
     for (fileid, line, column, bytes) in raw_comments {
         comments.push(CommentNode {
             fileid,
             line,
             column,
+            file_path: file_path_of(fileid),
             string: String::from_utf8_lossy(&bytes).to_string(),
         })
     }
@@ -133,11 +143,7 @@ pub fn process(items: Value) -> error::Result<AstContext> {
 
             let type_id: Option<u64> = expect_opt_u64(&entry[6]).unwrap();
             let fileid = entry[3].as_u64().unwrap();
-            let file_path = match file_paths[fileid as usize].as_str() {
-                "" => None,
-                "?" => None,
-                path => Some(Path::new(path).to_path_buf()),
-            };
+            let file_path = file_path_of(fileid);
 
             let macro_expansions = entry[8]
                 .as_array()
@@ -152,6 +158,8 @@ pub fn process(items: Value) -> error::Result<AstContext> {
                 fileid,
                 line: entry[4].as_u64().unwrap(),
                 column: entry[5].as_u64().unwrap(),
+                end_line: entry[9].as_u64().unwrap(),
+                end_column: entry[10].as_u64().unwrap(),
                 type_id,
                 file_path,
                 rvalue: if entry[7].as_boolean().unwrap() {
@@ -160,7 +168,7 @@ pub fn process(items: Value) -> error::Result<AstContext> {
                     LRValue::LValue
                 },
                 macro_expansions,
-                extras: entry[9..].to_vec(),
+                extras: entry[11..].to_vec(),
             };
 
             asts.insert(entry_id, node);
@@ -29,18 +29,34 @@ pub fn get_untyped_ast(
     extra_args: &[&str],
     debug: bool,
 ) -> Result<clang_ast::AstContext, Error> {
-    let cbors = get_ast_cbors(file_path, cc_db, extra_args, debug);
-    let buffer = cbors.values().next().ok_or(Error::new(
-        ErrorKind::InvalidData,
-        "Could not parse input file",
-    ))?;
+    let buffer = get_ast_cbor(file_path, cc_db, extra_args, debug)?;
+    untyped_ast_from_cbor(&buffer)
+}
 
-    // let cbor_path = file_path.with_extension("cbor");
-    // let mut cbor_file = File::create(&cbor_path)?;
-    // cbor_file.write_all(&buffer[..])?;
-    // eprintln!("Dumped CBOR to {}", cbor_path.to_string_lossy());
+/// Export the Clang AST of a translation unit as CBOR
+pub fn get_ast_cbor(
+    file_path: &Path,
+    cc_db: &Path,
+    extra_args: &[&str],
+    debug: bool,
+) -> Result<Vec<u8>, Error> {
+    let cbors = get_ast_cbors(file_path, cc_db, extra_args, debug);
+    cbors
+        .into_iter()
+        .next()
+        .map(|(_, buffer)| buffer)
+        .ok_or(Error::new(
+            ErrorKind::InvalidData,
+            "Could not parse input file",
+        ))
+}
 
-    let items: Value = from_slice(&buffer[..]).unwrap();
+/// Process a Clang AST that was exported as CBOR
+pub fn untyped_ast_from_cbor(buffer: &[u8]) -> Result<clang_ast::AstContext, Error> {
+    let items: Value = match from_slice(buffer) {
+        Ok(items) => items,
+        Err(e) => return Err(Error::new(ErrorKind::InvalidData, format!("{:}", e))),
+    };
 
     match clang_ast::process(items) {
         Ok(cxt) => Ok(cxt),
@@ -0,0 +1,592 @@
+//! Runtime support for calls to the `printf` family of functions that c2rust translates into
+//! `write!` with `--translate-printf`.
+//!
+//! Conversions that Rust's formatting doesn't reproduce exactly are translated into a [`Conv`],
+//! which formats one C conversion specification and its argument like `printf` does. The writers
+//! of this crate have an inherent `write_fmt` method returning what the C function returns, so
+//! that `write!` stands in for `printf`, `fprintf`, `sprintf` and `snprintf` without any trait
+//! imports.
+//!
+//! Formatting goes through `core::fmt`, which only handles UTF-8. A `Conv` formatted by one of
+//! the writers of this crate bypasses it, so that the bytes of `%s` and `%c` arguments are written
+//! as they are. Formatted any other way, such as with `format!`, bytes that are not valid UTF-8
+//! become U+FFFD.
+//!
+//! [`Conv`]: struct.Conv.html
+
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::{c_char, c_int, c_void};
+
+extern "C" {
+    fn fwrite(ptr: *const c_void, size: usize, nitems: usize, stream: *mut c_void) -> usize;
+
+    #[cfg_attr(
+        any(target_os = "macos", target_os = "ios", target_os = "freebsd"),
+        link_name = "__stdoutp"
+    )]
+    static mut stdout: *mut c_void;
+
+    #[cfg_attr(
+        any(target_os = "macos", target_os = "ios", target_os = "freebsd"),
+        link_name = "__stderrp"
+    )]
+    static mut stderr: *mut c_void;
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Arg {
+    Int(i64),
+    UInt(u64),
+    Double(f64),
+    Char(c_int),
+    Str(*const c_char),
+    Ptr(*const c_void),
+}
+
+/// A C conversion specification, such as `%-08.3f`, along with its argument
+#[derive(Copy, Clone, Debug)]
+pub struct Conv {
+    spec: &'static str,
+    width: Option<c_int>,
+    precision: Option<c_int>,
+    arg: Arg,
+}
+
+/// A signed integer conversion: `%d` or `%i`
+pub fn int(spec: &'static str, value: i64) -> Conv {
+    Conv::new(spec, Arg::Int(value))
+}
+
+/// An unsigned integer conversion: `%u`, `%o`, `%x` or `%X`
+pub fn uint(spec: &'static str, value: u64) -> Conv {
+    Conv::new(spec, Arg::UInt(value))
+}
+
+/// A floating point conversion: `%f`, `%F`, `%e`, `%E`, `%g`, `%G`, `%a` or `%A`
+pub fn double(spec: &'static str, value: f64) -> Conv {
+    Conv::new(spec, Arg::Double(value))
+}
+
+/// A character conversion: `%c`
+pub fn byte(spec: &'static str, value: c_int) -> Conv {
+    Conv::new(spec, Arg::Char(value))
+}
+
+/// A string conversion: `%s`
+///
+/// # Safety
+///
+/// Unless it is null, `value` has to point to a NUL-terminated string or to at least as many
+/// bytes as the precision of the conversion, and stay valid until the `Conv` is formatted.
+pub unsafe fn string(spec: &'static str, value: *const c_char) -> Conv {
+    Conv::new(spec, Arg::Str(value))
+}
+
+/// A pointer conversion: `%p`
+pub fn pointer(spec: &'static str, value: *const c_void) -> Conv {
+    Conv::new(spec, Arg::Ptr(value))
+}
+
+impl Conv {
+    fn new(spec: &'static str, arg: Arg) -> Self {
+        Conv {
+            spec,
+            width: None,
+            precision: None,
+            arg,
+        }
+    }
+
+    /// The width argument of a conversion with a `*` width
+    pub fn width(self, width: c_int) -> Self {
+        Conv {
+            width: Some(width),
+            ..self
+        }
+    }
+
+    /// The precision argument of a conversion with a `*` precision
+    pub fn precision(self, precision: c_int) -> Self {
+        Conv {
+            precision: Some(precision),
+            ..self
+        }
+    }
+}
+
+/// The parsed flags, width, precision and conversion of a `Conv`
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    conv: u8,
+}
+
+impl Spec {
+    fn parse(conv: &Conv) -> Spec {
+        let bytes = conv.spec.as_bytes();
+        let mut spec = Spec::default();
+        // Skip the `%`
+        let mut i = 1;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                b'-' => spec.left = true,
+                b'+' => spec.plus = true,
+                b' ' => spec.space = true,
+                b'#' => spec.alt = true,
+                b'0' => spec.zero = true,
+                _ => break,
+            }
+            i += 1;
+        }
+
+        if i < bytes.len() && bytes[i] == b'*' {
+            i += 1;
+            let width = conv.width.unwrap_or(0);
+            // A negative width is a `-` flag
+            if width < 0 {
+                spec.left = true;
+            }
+            spec.width = width.wrapping_abs() as u32 as usize;
+        } else {
+            spec.width = parse_number(bytes, &mut i);
+        }
+
+        if i < bytes.len() && bytes[i] == b'.' {
+            i += 1;
+            if i < bytes.len() && bytes[i] == b'*' {
+                i += 1;
+                // A negative precision is as if it was omitted
+                spec.precision = match conv.precision {
+                    Some(precision) if precision >= 0 => Some(precision as usize),
+                    _ => None,
+                };
+            } else {
+                spec.precision = Some(parse_number(bytes, &mut i));
+            }
+        }
+
+        // Our arguments already have the types of the length modifiers
+        while i < bytes.len() && b"hlLqjzt".contains(&bytes[i]) {
+            i += 1;
+        }
+        spec.conv = bytes.get(i).cloned().unwrap_or(b'd');
+        spec
+    }
+
+    fn is_upper(&self) -> bool {
+        self.conv.is_ascii_uppercase()
+    }
+
+    /// The sign of a signed conversion
+    fn sign(&self, is_negative: bool) -> &'static str {
+        if is_negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    /// Pad `prefix` and `body` to the width, with zeros between them if `zero_pad` allows it
+    fn pad(&self, prefix: &str, body: &[u8], zero_pad: bool) -> Vec<u8> {
+        let padding = self.width.saturating_sub(prefix.len() + body.len());
+        let mut output = Vec::with_capacity(prefix.len() + body.len() + padding);
+        if self.left {
+            output.extend_from_slice(prefix.as_bytes());
+            output.extend_from_slice(body);
+            output.resize(output.len() + padding, b' ');
+        } else if self.zero && zero_pad {
+            output.extend_from_slice(prefix.as_bytes());
+            output.resize(output.len() + padding, b'0');
+            output.extend_from_slice(body);
+        } else {
+            output.resize(padding, b' ');
+            output.extend_from_slice(prefix.as_bytes());
+            output.extend_from_slice(body);
+        }
+        output
+    }
+}
+
+fn parse_number(bytes: &[u8], i: &mut usize) -> usize {
+    let mut n = 0;
+    while *i < bytes.len() && bytes[*i].is_ascii_digit() {
+        n = n * 10 + (bytes[*i] - b'0') as usize;
+        *i += 1;
+    }
+    n
+}
+
+thread_local! {
+    /// The output of the writer formatting on this thread, if any, which a `Conv` appends its
+    /// bytes to directly
+    static OUTPUT: RefCell<Option<Vec<u8>>> = RefCell::new(None);
+}
+
+/// Format `args` into bytes, with the bytes of every `Conv` as they are
+fn format(args: fmt::Arguments) -> Vec<u8> {
+    struct Output;
+
+    impl fmt::Write for Output {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            OUTPUT.with(|output| {
+                let mut output = output.borrow_mut();
+                output.as_mut().unwrap().extend_from_slice(s.as_bytes());
+            });
+            Ok(())
+        }
+    }
+
+    let outer = OUTPUT.with(|output| output.replace(Some(vec![])));
+    // Neither `Output` nor `Conv` return errors
+    let _ = fmt::write(&mut Output, args);
+    OUTPUT.with(|output| output.replace(outer)).unwrap()
+}
+
+impl fmt::Display for Conv {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes = self.to_bytes();
+        let is_direct = OUTPUT.with(|output| match *output.borrow_mut() {
+            Some(ref mut output) => {
+                output.extend_from_slice(&bytes);
+                true
+            }
+            None => false,
+        });
+        if is_direct {
+            Ok(())
+        } else {
+            f.write_str(&String::from_utf8_lossy(&bytes))
+        }
+    }
+}
+
+impl Conv {
+    /// The bytes `printf` writes for this conversion
+    fn to_bytes(self) -> Vec<u8> {
+        let spec = Spec::parse(&self);
+        match self.arg {
+            Arg::Int(value) => {
+                let digits = integer_digits(&spec, value.wrapping_abs() as u64);
+                // A precision turns off zero padding for integers
+                let zero_pad = spec.precision.is_none();
+                spec.pad(spec.sign(value < 0), digits.as_bytes(), zero_pad)
+            }
+            Arg::UInt(value) => {
+                let mut digits = integer_digits(&spec, value);
+                let prefix = match spec.conv {
+                    b'o' if spec.alt && !digits.starts_with('0') => {
+                        digits.insert(0, '0');
+                        ""
+                    }
+                    b'x' if spec.alt && value != 0 => "0x",
+                    b'X' if spec.alt && value != 0 => "0X",
+                    _ => "",
+                };
+                spec.pad(prefix, digits.as_bytes(), spec.precision.is_none())
+            }
+            Arg::Double(value) => {
+                let sign = spec.sign(value.is_sign_negative());
+                if !value.is_finite() {
+                    let body = match (value.is_nan(), spec.is_upper()) {
+                        (true, false) => "nan",
+                        (true, true) => "NAN",
+                        (false, false) => "inf",
+                        (false, true) => "INF",
+                    };
+                    return spec.pad(sign, body.as_bytes(), false);
+                }
+                let value = value.abs();
+                match spec.conv {
+                    b'a' | b'A' => {
+                        let (prefix, body) = hex_float(&spec, value);
+                        spec.pad(&format!("{}{}", sign, prefix), body.as_bytes(), true)
+                    }
+                    _ => spec.pad(sign, decimal_float(&spec, value).as_bytes(), true),
+                }
+            }
+            Arg::Char(value) => spec.pad("", &[value as u8], false),
+            Arg::Str(ptr) => {
+                let body = if ptr.is_null() {
+                    // glibc prints a null string in full or not at all
+                    match spec.precision {
+                        Some(precision) if precision < 6 => &b""[..],
+                        _ => &b"(null)"[..],
+                    }
+                } else {
+                    match spec.precision {
+                        Some(precision) => {
+                            let mut len = 0;
+                            while len < precision && unsafe { *ptr.add(len) } != 0 {
+                                len += 1;
+                            }
+                            unsafe { std::slice::from_raw_parts(ptr as *const u8, len) }
+                        }
+                        None => unsafe { CStr::from_ptr(ptr).to_bytes() },
+                    }
+                };
+                spec.pad("", body, false)
+            }
+            Arg::Ptr(ptr) => {
+                if ptr.is_null() {
+                    spec.pad("", b"(nil)", false)
+                } else {
+                    let sign = spec.sign(false);
+                    let prefix = format!("{}0x", sign);
+                    spec.pad(&prefix, format!("{:x}", ptr as usize).as_bytes(), true)
+                }
+            }
+        }
+    }
+}
+
+/// The digits of an integer conversion, with at least as many digits as the precision
+fn integer_digits(spec: &Spec, value: u64) -> String {
+    if spec.precision == Some(0) && value == 0 {
+        return String::new();
+    }
+    let digits = match spec.conv {
+        b'o' => format!("{:o}", value),
+        b'x' => format!("{:x}", value),
+        b'X' => format!("{:X}", value),
+        _ => value.to_string(),
+    };
+    let precision = spec.precision.unwrap_or(0);
+    format!("{:0>1$}", digits, precision)
+}
+
+/// `%f`, `%e` and `%g` of a finite, non-negative value
+fn decimal_float(spec: &Spec, value: f64) -> String {
+    let precision = spec.precision.unwrap_or(6);
+    let mut body = match spec.conv {
+        b'f' | b'F' => {
+            let mut body = format!("{:.*}", precision, value);
+            if spec.alt && precision == 0 {
+                body.push('.');
+            }
+            body
+        }
+        b'e' | b'E' => exponential(value, precision, spec.alt),
+        _ => {
+            // `%g` uses the style of `%e` for small and large exponents, and drops trailing
+            // zeros unless there's a `#` flag
+            let precision = if precision == 0 { 1 } else { precision };
+            let exponent = decimal_exponent(value, precision - 1);
+            let mut body = if exponent < -4 || exponent >= precision as i32 {
+                exponential(value, precision - 1, spec.alt)
+            } else {
+                let mut body = format!("{:.*}", (precision as i32 - 1 - exponent) as usize, value);
+                if spec.alt && !body.contains('.') {
+                    body.push('.');
+                }
+                body
+            };
+            if !spec.alt {
+                body = strip_trailing_zeros(&body);
+            }
+            body
+        }
+    };
+    if spec.is_upper() {
+        body.make_ascii_uppercase();
+    }
+    body
+}
+
+/// The exponent of a value printed with `%.*e`, after rounding to the precision
+fn decimal_exponent(value: f64, precision: usize) -> i32 {
+    let formatted = format!("{:.*e}", precision, value);
+    let exponent = &formatted[formatted.find('e').unwrap() + 1..];
+    exponent.parse().unwrap()
+}
+
+/// `%e` of a non-negative value: a mantissa and an exponent of at least two digits
+fn exponential(value: f64, precision: usize, alt: bool) -> String {
+    let formatted = format!("{:.*e}", precision, value);
+    let e = formatted.find('e').unwrap();
+    let mut mantissa = formatted[..e].to_string();
+    if alt && precision == 0 {
+        mantissa.push('.');
+    }
+    let exponent: i32 = formatted[e + 1..].parse().unwrap();
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+}
+
+/// Remove the trailing zeros of the fraction of a `%g` conversion, and its decimal point if
+/// nothing remains of the fraction
+fn strip_trailing_zeros(body: &str) -> String {
+    let (number, exponent) = match body.find('e') {
+        Some(e) => body.split_at(e),
+        None => (body, ""),
+    };
+    let number = if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        number
+    };
+    format!("{}{}", number, exponent)
+}
+
+/// `%a` of a finite, non-negative value, split into the `0x` prefix and the rest
+fn hex_float(spec: &Spec, value: f64) -> (&'static str, String) {
+    const FRACTION_DIGITS: usize = 13;
+    let bits = value.to_bits();
+    let biased_exponent = (bits >> 52) as i32;
+    let fraction = bits & ((1 << 52) - 1);
+    let (mut mantissa, exponent) = if value == 0.0 {
+        (0, 0)
+    } else if biased_exponent == 0 {
+        // Subnormal numbers are printed with a leading 0
+        (fraction, -1022)
+    } else {
+        ((1 << 52) | fraction, biased_exponent - 1023)
+    };
+
+    let digits = match spec.precision {
+        Some(precision) if precision < FRACTION_DIGITS => {
+            // Round half to even to the precision
+            let shift = (FRACTION_DIGITS - precision) * 4;
+            let rest = mantissa & ((1 << shift) - 1);
+            let half = 1 << (shift - 1);
+            mantissa >>= shift;
+            if rest > half || (rest == half && mantissa & 1 == 1) {
+                mantissa += 1;
+            }
+            precision
+        }
+        _ => FRACTION_DIGITS,
+    };
+    let lead = mantissa >> (digits * 4);
+    let fraction = mantissa & ((1 << (digits * 4)) - 1);
+    let mut fraction = if digits == 0 {
+        String::new()
+    } else {
+        format!("{:01$x}", fraction, digits)
+    };
+    match spec.precision {
+        Some(precision) => {
+            while fraction.len() < precision {
+                fraction.push('0');
+            }
+        }
+        None => fraction = fraction.trim_end_matches('0').to_string(),
+    }
+
+    let point = if fraction.is_empty() && !spec.alt {
+        ""
+    } else {
+        "."
+    };
+    let sign = if exponent < 0 { '-' } else { '+' };
+    let body = format!("{}{}{}p{}{}", lead, point, fraction, sign, exponent.abs());
+    if spec.is_upper() {
+        ("0X", body.to_ascii_uppercase())
+    } else {
+        ("0x", body)
+    }
+}
+
+/// Write the output to a C `FILE *` stream, returning the number of bytes written or -1 on an
+/// error
+unsafe fn write_stream(stream: *mut c_void, args: fmt::Arguments) -> c_int {
+    let output = format(args);
+    let written = fwrite(
+        output.as_slice().as_ptr() as *const c_void,
+        1,
+        output.len(),
+        stream,
+    );
+    if written < output.len() {
+        -1
+    } else {
+        output.len() as c_int
+    }
+}
+
+/// Writes formatted output to the standard output, like `printf`
+pub struct Stdout;
+
+impl Stdout {
+    /// Write the output through C's `stdout`, in order with the rest of its output, returning
+    /// the number of bytes written or -1 on an error
+    pub fn write_fmt(&mut self, args: fmt::Arguments) -> c_int {
+        unsafe { write_stream(stdout, args) }
+    }
+}
+
+/// Writes formatted output to the standard error, like `fprintf(stderr, ..)`
+pub struct Stderr;
+
+impl Stderr {
+    /// Write the output through C's `stderr`, in order with the rest of its output, returning
+    /// the number of bytes written or -1 on an error
+    pub fn write_fmt(&mut self, args: fmt::Arguments) -> c_int {
+        unsafe { write_stream(stderr, args) }
+    }
+}
+
+/// Writes formatted output to a C `FILE *` stream, like `fprintf`
+pub struct File(pub *mut c_void);
+
+impl File {
+    /// Write the output, returning the number of bytes written or -1 on an error
+    ///
+    /// # Safety
+    ///
+    /// The stream has to be a valid `FILE *` open for writing.
+    pub unsafe fn write_fmt(&mut self, args: fmt::Arguments) -> c_int {
+        write_stream(self.0, args)
+    }
+}
+/// Writes formatted output into a C buffer, like `sprintf` or, if it has a size, `snprintf`
+pub struct Buffer {
+    buf: *mut c_char,
+    size: Option<usize>,
+}
+
+impl Buffer {
+    /// A buffer large enough for any output, like the one passed to `sprintf`
+    pub fn new(buf: *mut c_char) -> Self {
+        Buffer { buf, size: None }
+    }
+
+    /// A buffer of `size` bytes, like the one passed to `snprintf`
+    pub fn with_size(buf: *mut c_char, size: usize) -> Self {
+        Buffer {
+            buf,
+            size: Some(size),
+        }
+    }
+
+    /// Write as much of the output as fits into the buffer followed by a NUL byte, returning
+    /// the length of the whole output
+    ///
+    /// # Safety
+    ///
+    /// Unless its size is 0, the buffer has to be valid for writes of its size or, without a size,
+    /// of the whole output and its NUL byte.
+    pub unsafe fn write_fmt(&mut self, args: fmt::Arguments) -> c_int {
+        let output = format(args);
+        let len = match self.size {
+            Some(0) => return output.len() as c_int,
+            Some(size) => output.len().min(size - 1),
+            None => output.len(),
+        };
+        std::ptr::copy_nonoverlapping(output.as_slice().as_ptr() as *const c_char, self.buf, len);
+        *self.buf.add(len) = 0;
+        output.len() as c_int
+    }
+}
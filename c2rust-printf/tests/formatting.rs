@@ -0,0 +1,240 @@
+extern crate c2rust_printf;
+
+use c2rust_printf::{byte, double, int, pointer, string, uint, Buffer, Conv, File};
+use std::ffi::CStr;
+use std::fmt::Write;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+
+extern "C" {
+    fn snprintf(buf: *mut c_char, size: usize, format: *const c_char, ...) -> c_int;
+    fn tmpfile() -> *mut c_void;
+    fn fputs(s: *const c_char, stream: *mut c_void) -> c_int;
+    fn rewind(stream: *mut c_void);
+    fn fread(ptr: *mut c_void, size: usize, nitems: usize, stream: *mut c_void) -> usize;
+    fn fclose(stream: *mut c_void) -> c_int;
+}
+
+fn to_str(buf: &[c_char]) -> &str {
+    unsafe { CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap()
+}
+
+fn format(conv: Conv) -> String {
+    let mut output = String::new();
+    write!(output, "{}", conv).unwrap();
+    output
+}
+
+/// Check that a `Conv` formats its argument like `snprintf` does
+macro_rules! check {
+    ($spec:expr, $conv:expr, $($arg:expr),+) => {{
+        let mut buf = [0 as c_char; 512];
+        let spec = concat!($spec, "\0");
+        unsafe {
+            snprintf(buf.as_mut_ptr(), buf.len(), spec.as_ptr() as *const c_char, $($arg),+);
+        }
+        assert_eq!(format($conv), to_str(&buf), "{}", $spec);
+    }};
+}
+
+#[test]
+fn test_signed() {
+    for &value in &[0, 1, -1, 42, -42, i32::max_value(), i32::min_value()] {
+        check!("%d", int("%d", value as i64), value);
+        check!("%i", int("%i", value as i64), value);
+        check!("%5d", int("%5d", value as i64), value);
+        check!("%-5d", int("%-5d", value as i64), value);
+        check!("%05d", int("%05d", value as i64), value);
+        check!("%+d", int("%+d", value as i64), value);
+        check!("% d", int("% d", value as i64), value);
+        check!("%+05d", int("%+05d", value as i64), value);
+        check!("%.3d", int("%.3d", value as i64), value);
+        check!("%.0d", int("%.0d", value as i64), value);
+        check!("%08.3d", int("%08.3d", value as i64), value);
+        check!("%-08d", int("%-08d", value as i64), value);
+    }
+    for &value in &[0, -1, i64::max_value(), i64::min_value()] {
+        check!("%ld", int("%ld", value), value);
+        check!("%20lld", int("%20lld", value), value);
+    }
+}
+
+#[test]
+fn test_unsigned() {
+    for &value in &[0, 1, 42, 255, u32::max_value(), -1i32 as u32] {
+        check!("%u", uint("%u", value as u64), value);
+        check!("%x", uint("%x", value as u64), value);
+        check!("%X", uint("%X", value as u64), value);
+        check!("%o", uint("%o", value as u64), value);
+        check!("%#x", uint("%#x", value as u64), value);
+        check!("%#X", uint("%#X", value as u64), value);
+        check!("%#o", uint("%#o", value as u64), value);
+        check!("%#.0o", uint("%#.0o", value as u64), value);
+        check!("%#010x", uint("%#010x", value as u64), value);
+        check!("%-#10x", uint("%-#10x", value as u64), value);
+        check!("%.5x", uint("%.5x", value as u64), value);
+        check!("%08X", uint("%08X", value as u64), value);
+    }
+    check!("%lx", uint("%lx", u64::max_value()), u64::max_value());
+    check!("%hhx", uint("%hhx", 255), 255);
+}
+
+#[test]
+fn test_star() {
+    check!("%*d", int("%*d", 42).width(6), 6, 42);
+    check!("%*d", int("%*d", 42).width(-6), -6, 42);
+    check!("%.*d", int("%.*d", 42).precision(4), 4, 42);
+    check!("%.*d", int("%.*d", 42).precision(-4), -4, 42);
+    check!(
+        "%*.*f",
+        double("%*.*f", 1.23456).width(10).precision(2),
+        10,
+        2,
+        1.23456
+    );
+}
+
+#[test]
+fn test_float() {
+    let values = [
+        0.0,
+        -0.0,
+        1.0,
+        -1.5,
+        0.1,
+        1.2345678901234567,
+        123456789.0,
+        1e-5,
+        0.0001,
+        1e100,
+        -2.5e-300,
+        9.9999999,
+        0.5,
+        2.5,
+        5e-324,
+        std::f64::MAX,
+        std::f64::INFINITY,
+        std::f64::NEG_INFINITY,
+        std::f64::NAN,
+    ];
+    for &value in &values {
+        check!("%f", double("%f", value), value);
+        check!("%F", double("%F", value), value);
+        check!("%.0f", double("%.0f", value), value);
+        check!("%#.0f", double("%#.0f", value), value);
+        check!("%10.3f", double("%10.3f", value), value);
+        check!("%-10.3f", double("%-10.3f", value), value);
+        check!("%+010.2f", double("%+010.2f", value), value);
+        check!("%e", double("%e", value), value);
+        check!("%E", double("%E", value), value);
+        check!("%.0e", double("%.0e", value), value);
+        check!("%#.0e", double("%#.0e", value), value);
+        check!("%12.3e", double("%12.3e", value), value);
+        check!("%g", double("%g", value), value);
+        check!("%G", double("%G", value), value);
+        check!("%.0g", double("%.0g", value), value);
+        check!("%.10g", double("%.10g", value), value);
+        check!("%#g", double("%#g", value), value);
+        check!("%010g", double("%010g", value), value);
+        check!("%a", double("%a", value), value);
+        check!("%A", double("%A", value), value);
+        check!("%.0a", double("%.0a", value), value);
+        check!("%.2a", double("%.2a", value), value);
+        check!("%#.0a", double("%#.0a", value), value);
+        check!("%020a", double("%020a", value), value);
+    }
+}
+
+#[test]
+fn test_char() {
+    check!("%c", byte("%c", b'x' as c_int), b'x' as c_int);
+    check!("%3c", byte("%3c", b'x' as c_int), b'x' as c_int);
+    check!("%-3c", byte("%-3c", b'x' as c_int), b'x' as c_int);
+}
+
+#[test]
+fn test_string() {
+    let s = "hello\0".as_ptr() as *const c_char;
+    let null = ptr::null::<c_char>();
+    let conv = |spec, s| unsafe { string(spec, s) };
+    check!("%s", conv("%s", s), s);
+    check!("%10s", conv("%10s", s), s);
+    check!("%-10s", conv("%-10s", s), s);
+    check!("%.3s", conv("%.3s", s), s);
+    check!("%.*s", conv("%.*s", s).precision(2), 2, s);
+    check!("%s", conv("%s", null), null);
+    check!("%.3s", conv("%.3s", null), null);
+
+    // A precision doesn't need a terminated string
+    let unterminated = [b'a' as c_char, b'b' as c_char];
+    assert_eq!(format(conv("%.2s", unterminated.as_ptr())), "ab");
+}
+
+#[test]
+fn test_pointer() {
+    let value = 0x1234abcd as *const c_void;
+    check!("%p", pointer("%p", value), value);
+    check!("%20p", pointer("%20p", value), value);
+    check!("%-20p", pointer("%-20p", value), value);
+    check!("%p", pointer("%p", ptr::null()), ptr::null::<c_void>());
+}
+
+#[test]
+fn test_buffer() {
+    let mut buf = [1 as c_char; 8];
+    let (ab, abcdef) = ("ab", "abcdef");
+    let n = unsafe { write!(Buffer::new(buf.as_mut_ptr()), "{}-{}", 12, ab) };
+    assert_eq!(n, 5);
+    assert_eq!(to_str(&buf), "12-ab");
+
+    let n = unsafe { write!(Buffer::with_size(buf.as_mut_ptr(), 4), "{}", abcdef) };
+    assert_eq!(n, 6);
+    assert_eq!(to_str(&buf), "abc");
+
+    let n = unsafe { write!(Buffer::with_size(ptr::null_mut(), 0), "{}", abcdef) };
+    assert_eq!(n, 6);
+}
+
+#[test]
+fn test_raw_bytes() {
+    let mut buf = [1 as c_char; 16];
+    let s = b"\xff\xfeab\0".as_ptr() as *const c_char;
+    let n = unsafe {
+        write!(
+            Buffer::new(buf.as_mut_ptr()),
+            "<{}|{}>",
+            string("%3.3s", s),
+            byte("%-2c", 0xe9),
+        )
+    };
+    assert_eq!(n, 8);
+    let bytes = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_bytes();
+    assert_eq!(bytes, b"<\xff\xfea|\xe9 >");
+
+    // Outside of the writers, the bytes are replaced
+    assert_eq!(format(unsafe { string("%s", s) }), "\u{fffd}\u{fffd}ab");
+}
+
+#[test]
+fn test_file() {
+    unsafe {
+        let stream = tmpfile();
+        assert!(!stream.is_null());
+        fputs(b"a\0".as_ptr() as *const c_char, stream);
+        let n = write!(
+            File(stream),
+            "{}{}",
+            string("%s", b"\xffb\0".as_ptr() as *const c_char),
+            1
+        );
+        assert_eq!(n, 3);
+        fputs(b"c\0".as_ptr() as *const c_char, stream);
+
+        // The output is in order with that of C stdio
+        let mut buf = [0u8; 16];
+        rewind(stream);
+        let len = fread(buf.as_mut_ptr() as *mut c_void, 1, buf.len(), stream);
+        assert_eq!(&buf[..len], b"a\xffb1c");
+        fclose(stream);
+    }
+}
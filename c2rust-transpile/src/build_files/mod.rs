@@ -1,6 +1,7 @@
 extern crate handlebars;
 extern crate pathdiff;
 
+use std::collections::HashSet;
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -11,8 +12,11 @@ use self::pathdiff::diff_paths;
 use serde_json::json;
 
 use super::TranspilerConfig;
+use crate::compile_cmds::{LinkInfo, Target, TargetKind};
+use crate::header_modules::WrittenModule;
 use crate::CrateSet;
 use crate::PragmaSet;
+use crate::PragmaVec;
 use crate::convert_type::RESERVED_NAMES;
 
 #[derive(Debug, Copy, Clone)]
@@ -65,6 +69,7 @@ pub fn emit_build_files(
     modules: Vec<PathBuf>,
     pragmas: PragmaSet,
     crates: CrateSet,
+    link_info: &LinkInfo,
 ) -> Option<PathBuf> {
     let mut reg = Handlebars::new();
 
@@ -75,12 +80,235 @@ pub fn emit_build_files(
     reg.register_template_string("build.rs", include_str!("build.rs.hbs"))
         .unwrap();
 
-    emit_cargo_toml(tcfg, &reg, &build_dir, &crates);
-    if tcfg.translate_valist {
+    let info = CrateInfo {
+        name: tcfg
+            .output_dir
+            .as_ref()
+            .and_then(|x| x.file_name().map(|x| x.to_string_lossy().into_owned()))
+            .unwrap_or("c2rust".into()),
+        root_rs_file: get_root_rs_file_name(tcfg),
+        is_bin: tcfg.main.is_some(),
+        main_module: get_module_name(&tcfg.main),
+        deps: vec![],
+        workspace_member: false,
+        link_info,
+    };
+
+    emit_cargo_toml(tcfg, &reg, &build_dir, &info, &crates);
+    if needs_rust_toolchain(tcfg, pragmas.contains(&("feature", "c_variadic"))) {
+        emit_rust_toolchain(tcfg, &build_dir);
+    }
+    if let Some(ref target) = tcfg.target {
+        emit_cargo_config(tcfg, &build_dir, target);
+    }
+    emit_build_rs(tcfg, &reg, &build_dir, link_info);
+    emit_lib_rs(tcfg, &reg, &build_dir, &info, modules, pragmas, &crates)
+}
+
+/// A translated module and what the crate it ends up in needs for it
+pub struct TranslatedModule {
+    pub path: PathBuf,
+    /// The translation unit the module was translated from
+    pub source: PathBuf,
+    pub pragmas: PragmaVec,
+    pub crates: CrateSet,
+    /// Does the module define the `main` function?
+    pub has_main: bool,
+}
+
+/// The library crate of a workspace holding the header modules, which are shared by all crates
+/// of the workspace
+pub const HEADERS_CRATE: &str = "c2rust_headers";
+
+/// Emit a Cargo workspace with a crate for every target of the original build: a library
+/// crate for each library and a binary crate for each executable. The crates include the
+/// modules of their translation units from where they were translated, so translation units
+/// shared by several targets are translated only once. The header modules go into a library
+/// crate of their own that the crates using them depend on, so that the crates agree on the
+/// types declared in headers.
+pub fn emit_workspace(
+    tcfg: &TranspilerConfig,
+    build_dir: &Path,
+    targets: &[Target],
+    modules: &[TranslatedModule],
+    header_modules: &[WrittenModule],
+) {
+    let mut reg = Handlebars::new();
+
+    reg.register_template_string("Cargo.toml", include_str!("Cargo.toml.hbs"))
+        .unwrap();
+    reg.register_template_string("lib.rs", include_str!("lib.rs.hbs"))
+        .unwrap();
+    reg.register_template_string("build.rs", include_str!("build.rs.hbs"))
+        .unwrap();
+
+    let mut members = vec![];
+    if !header_modules.is_empty() {
+        // The header modules may need any of the features and crates the translation units
+        // need
+        let (pragmas, crates) = crate_needs(modules);
+
+        let link_info = LinkInfo::default();
+        let info = CrateInfo {
+            name: HEADERS_CRATE.to_string(),
+            root_rs_file: "lib.rs",
+            is_bin: false,
+            main_module: None,
+            deps: vec![],
+            workspace_member: true,
+            link_info: &link_info,
+        };
+        let crate_dir = create_crate_dir(build_dir, HEADERS_CRATE);
+        let paths = header_modules
+            .iter()
+            .map(|module| module.path.clone())
+            .collect();
+        emit_cargo_toml(tcfg, &reg, &crate_dir, &info, &crates);
+        emit_lib_rs(tcfg, &reg, &crate_dir, &info, paths, pragmas, &crates);
+        members.push(HEADERS_CRATE.to_string());
+    }
+
+    let mut included = HashSet::new();
+    for target in targets {
+        let crate_name = get_module_name(&Some(target.name.clone())).unwrap();
+        let crate_dir = create_crate_dir(build_dir, &crate_name);
+
+        let target_modules: Vec<&TranslatedModule> = modules
+            .iter()
+            .filter(|module| in_target(target, &module.source))
+            .collect();
+        included.extend(target_modules.iter().map(|module| &module.path));
+
+        let is_bin = target.kind == TargetKind::Bin;
+        let main_module = target_modules
+            .iter()
+            .find(|module| module.has_main)
+            .and_then(|module| module.path.file_stem())
+            .map(|stem| stem.to_string_lossy().into_owned());
+        if is_bin && main_module.is_none() {
+            warn!(
+                "None of the translation units of {} defines main",
+                target.name
+            );
+        }
+
+        let (pragmas, crates) = crate_needs(target_modules.iter().cloned());
+
+        let mut deps: Vec<String> = target
+            .deps
+            .iter()
+            .filter_map(|dep| get_module_name(&Some(dep.clone())))
+            .collect();
+        if uses_header_modules(target, header_modules) {
+            deps.push(HEADERS_CRATE.to_string());
+        }
+        let info = CrateInfo {
+            name: crate_name.clone(),
+            root_rs_file: if is_bin { "main.rs" } else { "lib.rs" },
+            is_bin,
+            main_module: get_module_name(&main_module),
+            deps,
+            workspace_member: true,
+            link_info: &target.link_info,
+        };
+        let paths = target_modules
+            .iter()
+            .map(|module| module.path.clone())
+            .collect();
+        emit_cargo_toml(tcfg, &reg, &crate_dir, &info, &crates);
+        emit_build_rs(tcfg, &reg, &crate_dir, &target.link_info);
+        emit_lib_rs(tcfg, &reg, &crate_dir, &info, paths, pragmas, &crates);
+        members.push(crate_name);
+    }
+
+    for module in modules {
+        if !included.contains(&module.path) {
+            warn!(
+                "{} is not part of any target of the build, leaving it out of the workspace",
+                module.source.display()
+            );
+        }
+    }
+
+    let mut output = "[workspace]\nmembers = [\n".to_string();
+    for member in &members {
+        output.push_str(&format!("    \"{}\",\n", member));
+    }
+    output.push_str("]\n");
+    maybe_write_to_file(
+        &build_dir.join("Cargo.toml"),
+        output,
+        tcfg.overwrite_existing,
+    );
+    let uses_c_variadic = modules.iter().any(|module| {
+        module
+            .pragmas
+            .iter()
+            .any(|&(key, ref vals)| key == "feature" && vals.contains(&"c_variadic"))
+    });
+    if needs_rust_toolchain(tcfg, uses_c_variadic) {
         emit_rust_toolchain(tcfg, &build_dir);
     }
-    emit_build_rs(tcfg, &reg, &build_dir);
-    emit_lib_rs(tcfg, &reg, &build_dir, modules, pragmas, &crates)
+    if let Some(ref target) = tcfg.target {
+        emit_cargo_config(tcfg, &build_dir, target);
+    }
+}
+
+/// Is the translation unit of `source` compiled into `target`?
+fn in_target(target: &Target, source: &Path) -> bool {
+    let canonical = source.canonicalize();
+    target
+        .files
+        .contains(canonical.as_ref().map_or(source, |path| path))
+}
+
+/// The features and crates that a crate including `modules` needs
+fn crate_needs<'a, I>(modules: I) -> (PragmaSet, CrateSet)
+where
+    I: IntoIterator<Item = &'a TranslatedModule>,
+{
+    let mut pragmas = PragmaSet::new();
+    let mut crates = CrateSet::new();
+    for module in modules {
+        for &(key, ref vals) in &module.pragmas {
+            pragmas.extend(vals.iter().map(|&val| (key, val)));
+        }
+        crates.extend(module.crates.iter().cloned());
+    }
+    pragmas.sort();
+    crates.sort();
+    (pragmas, crates)
+}
+
+/// Does the crate of `target` import from any of the header modules?
+fn uses_header_modules(target: &Target, header_modules: &[WrittenModule]) -> bool {
+    header_modules
+        .iter()
+        .any(|module| module.users.iter().any(|user| in_target(target, user)))
+}
+
+fn create_crate_dir(build_dir: &Path, crate_name: &str) -> PathBuf {
+    let crate_dir = build_dir.join(crate_name);
+    if !crate_dir.exists() {
+        fs::create_dir(&crate_dir).expect(&format!(
+            "couldn't create crate directory: {}",
+            crate_dir.display()
+        ));
+    }
+    crate_dir
+}
+
+/// What differs between the crates emitted for a translation
+struct CrateInfo<'a> {
+    name: String,
+    root_rs_file: &'a str,
+    is_bin: bool,
+    /// The module defining the `main` function of a binary
+    main_module: Option<String>,
+    /// The crates of the workspace this crate depends on
+    deps: Vec<String>,
+    workspace_member: bool,
+    link_info: &'a LinkInfo,
 }
 
 #[derive(Serialize)]
@@ -101,7 +329,7 @@ fn get_root_rs_file_name(tcfg: &TranspilerConfig) -> &str {
 /// Make sure that module name:
 /// - does not contain illegal characters,
 /// - does not clash with reserved keywords.
-fn get_module_name(main: &Option<String>) -> Option<String> {
+pub(crate) fn get_module_name(main: &Option<String>) -> Option<String> {
     if let Some(ref name) = main {
         // module names cannot contain periods or dashes
         let mut module = name.chars().map(|c|
@@ -120,9 +348,37 @@ fn get_module_name(main: &Option<String>) -> Option<String> {
     None
 }
 
-/// Emit `build.rs` to make it easier to link in native libraries
-fn emit_build_rs(tcfg: &TranspilerConfig, reg: &Handlebars, build_dir: &Path) -> Option<PathBuf> {
-    let json = json!({});
+/// Emit `build.rs` linking in the native libraries of the original build
+fn emit_build_rs(
+    tcfg: &TranspilerConfig,
+    reg: &Handlebars,
+    build_dir: &Path,
+    link_info: &LinkInfo,
+) -> Option<PathBuf> {
+    // Quote everything as Rust string literals up front, since handlebars only knows how to
+    // escape HTML
+    let pkg_config = link_info
+        .pkg_config
+        .iter()
+        .map(|package| format!("{:?}", package))
+        .collect::<Vec<_>>();
+    let search_paths = link_info
+        .search_paths
+        .iter()
+        .map(|path| format!("cargo:rustc-link-search=native={}", path.display()))
+        .map(|directive| format!("{:?}", directive))
+        .collect::<Vec<_>>();
+    let libs = link_info
+        .libs
+        .iter()
+        .map(|lib| format!("cargo:rustc-link-lib={}", lib))
+        .map(|directive| format!("{:?}", directive))
+        .collect::<Vec<_>>();
+    let json = json!({
+        "pkg_config": pkg_config,
+        "search_paths": search_paths,
+        "libs": libs,
+    });
     let output = reg.render("build.rs", &json).unwrap();
     let output_path = build_dir.join("build.rs");
     maybe_write_to_file(&output_path, output, tcfg.overwrite_existing)
@@ -134,6 +390,7 @@ fn emit_lib_rs(
     tcfg: &TranspilerConfig,
     reg: &Handlebars,
     build_dir: &Path,
+    info: &CrateInfo,
     modules: Vec<PathBuf>,
     pragmas: PragmaSet,
     crates: &CrateSet,
@@ -156,7 +413,7 @@ fn emit_lib_rs(
         })
         .collect::<Vec<_>>();
 
-    let file_name = get_root_rs_file_name(tcfg);
+    let file_name = info.root_rs_file;
     let rs_xcheck_backend = tcfg.cross_check_backend.replace("-", "_");
     let json = json!({
         "root_rs_file": file_name,
@@ -164,11 +421,12 @@ fn emit_lib_rs(
         "translate_valist": tcfg.translate_valist,
         "cross_checks": tcfg.cross_checks,
         "cross_check_backend": rs_xcheck_backend,
-        "main_module": get_module_name(&tcfg.main),
+        "main_module": info.main_module,
         "plugin_args": plugin_args,
         "modules": modules,
         "pragmas": pragmas,
         "crates": crates,
+        "deps": info.deps,
     });
 
     let output_path = build_dir.join(file_name);
@@ -179,25 +437,136 @@ fn emit_lib_rs(
 
 /// If we translate variadic functions, the output will only compile
 /// on a nightly toolchain until the `c_variadics` feature is stable.
+/// With `--stable-variadics`, that is only the case if some of them
+/// keep the C variadic ABI.
+fn needs_rust_toolchain(tcfg: &TranspilerConfig, uses_c_variadic: bool) -> bool {
+    tcfg.translate_valist && (!tcfg.stable_variadics || uses_c_variadic)
+}
+
+/// Pin the nightly toolchain that the `c_variadics` feature needs
 fn emit_rust_toolchain(tcfg: &TranspilerConfig, build_dir: &Path) {
     let output_path = build_dir.join("rust-toolchain");
     let output = include_str!("../../rust-toolchain").to_string();
     maybe_write_to_file(&output_path, output, tcfg.overwrite_existing);
 }
 
-fn emit_cargo_toml(tcfg: &TranspilerConfig, reg: &Handlebars, build_dir: &Path, crates: &CrateSet) {
+/// Record the target we translated for in `.cargo/config`, since the sizes and layouts of
+/// its types are baked into the translation.
+fn emit_cargo_config(tcfg: &TranspilerConfig, build_dir: &Path, target: &str) {
+    let config_dir = build_dir.join(".cargo");
+    if !config_dir.exists() {
+        fs::create_dir(&config_dir).expect(&format!(
+            "couldn't create cargo config directory: {}",
+            config_dir.display()
+        ));
+    }
+    let rust_target = match rust_target_triple(target) {
+        Some(rust_target) => rust_target,
+        None => {
+            warn!(
+                "No Rust target corresponds to {}, not writing {}; \
+                 target a specific architecture version such as thumbv7em-none-eabi instead",
+                target,
+                config_dir.join("config").display()
+            );
+            return;
+        }
+    };
+    let output = format!("[build]\ntarget = \"{}\"\n", rust_target);
+    maybe_write_to_file(&config_dir.join("config"), output, tcfg.overwrite_existing);
+}
+
+/// Operating systems of clang target triples, which may carry a version, e.g. `macosx10.14`
+const TRIPLE_OSES: &[&str] = &[
+    "linux",
+    "darwin",
+    "macos",
+    "ios",
+    "windows",
+    "win32",
+    "mingw32",
+    "none",
+    "freebsd",
+    "netbsd",
+    "openbsd",
+    "dragonfly",
+];
+
+/// Map a clang target triple to the name of the matching rustc target. Clang accepts triples
+/// without a vendor, with an OS version, or with aliases, e.g. `i686-linux-gnu`,
+/// `x86_64-apple-macosx10.14` or `x86_64-w64-mingw32`, while rustc only accepts its own names,
+/// e.g. `i686-unknown-linux-gnu`, `x86_64-apple-darwin` or `x86_64-pc-windows-gnu`. Triples with
+/// an OS we don't know are used as they are. Returns `None` for bare-metal ARM triples without an
+/// architecture version, such as `arm-none-eabi`, since rustc has no target for them.
+fn rust_target_triple(triple: &str) -> Option<String> {
+    let mut components = triple.split('-');
+    let arch = match components.next()? {
+        "arm64" => "aarch64",
+        "i386" => "i686",
+        arch => arch,
+    };
+    // The vendor comes before the OS, if at all
+    let components: Vec<&str> = components.collect();
+    let os_index = match components
+        .iter()
+        .position(|component| TRIPLE_OSES.iter().any(|os| component.starts_with(os)))
+    {
+        Some(os_index) => os_index,
+        None => return Some(triple.to_string()),
+    };
+    let os = components[os_index];
+    let env = components.get(os_index + 1).cloned();
+
+    let rust_triple = if os.starts_with("darwin") || os.starts_with("macos") {
+        format!("{}-apple-darwin", arch)
+    } else if os.starts_with("ios") {
+        format!("{}-apple-ios", arch)
+    } else if os.starts_with("windows") || os.starts_with("win32") || os == "mingw32" {
+        let is_gnu = os == "mingw32" || env.map_or(false, |env| env.starts_with("gnu"));
+        let env = if is_gnu { "gnu" } else { "msvc" };
+        format!("{}-pc-windows-{}", arch, env)
+    } else if os == "linux" {
+        match env {
+            Some(env) if env.starts_with("android") => format!("{}-linux-{}", arch, env),
+            Some(env) => format!("{}-unknown-linux-{}", arch, env),
+            None => format!("{}-unknown-linux-gnu", arch),
+        }
+    } else if os == "none" {
+        match env {
+            _ if arch == "arm" || arch == "armeb" => return None,
+            Some(env) => format!("{}-none-{}", arch, env),
+            None => format!("{}-unknown-none", arch),
+        }
+    } else {
+        let os = os.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        format!("{}-unknown-{}", arch, os)
+    };
+    Some(rust_triple)
+}
+
+fn emit_cargo_toml(
+    tcfg: &TranspilerConfig,
+    reg: &Handlebars,
+    build_dir: &Path,
+    info: &CrateInfo,
+    crates: &CrateSet,
+) {
     // rust_checks_path is gone because we don't want to refer to the source
     // path but instead want the cross-check libs to be installed via cargo.
     let json = json!({
-        "crate_name": tcfg.output_dir.as_ref().and_then(
-            |x| x.file_name().map(|x| x.to_string_lossy())
-        ).unwrap_or("c2rust".into()),
-        "root_rs_file": get_root_rs_file_name(tcfg),
-        "main_module": tcfg.main.is_some(),
+        "crate_name": info.name,
+        "root_rs_file": info.root_rs_file,
+        "main_module": info.is_bin,
+        "workspace_member": info.workspace_member,
+        "deps": info.deps,
         "cross_checks": tcfg.cross_checks,
         "cross_check_backend": tcfg.cross_check_backend,
         "c2rust_bitfields": crates.contains("c2rust_bitfields"),
+        "c2rust_printf": crates.contains("c2rust_printf"),
         "f128": crates.contains("f128"),
+        "rayon": crates.contains("rayon"),
+        "smallvec": crates.contains("smallvec"),
+        "pkg_config": !info.link_info.pkg_config.is_empty(),
     });
     let file_name = "Cargo.toml";
     let output_path = build_dir.join(file_name);
@@ -222,3 +591,158 @@ fn maybe_write_to_file(output_path: &Path, output: String, overwrite: bool) -> O
 
     Some(PathBuf::from(output_path))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translated_module(name: &str) -> TranslatedModule {
+        TranslatedModule {
+            path: PathBuf::from(format!("/build/{}.rs", name)),
+            source: PathBuf::from(format!("/build/{}.c", name)),
+            pragmas: vec![],
+            crates: CrateSet::new(),
+            has_main: false,
+        }
+    }
+
+    #[test]
+    fn header_modules_of_crates() {
+        let target = |name: &str, files: &[&str]| Target {
+            name: name.to_string(),
+            kind: TargetKind::Lib,
+            files: files.iter().map(PathBuf::from).collect(),
+            deps: vec![],
+            link_info: LinkInfo::default(),
+        };
+        let util = target("util", &["/build/util.c"]);
+        let app = target("app", &["/build/app.c", "/build/cli.c"]);
+        let modules = vec![
+            translated_module("util"),
+            translated_module("app"),
+            translated_module("cli"),
+        ];
+        let header_modules = vec![
+            WrittenModule {
+                path: PathBuf::from("/build/util_h.rs"),
+                users: vec![
+                    PathBuf::from("/build/util.c"),
+                    PathBuf::from("/build/app.c"),
+                ],
+            },
+            WrittenModule {
+                path: PathBuf::from("/build/cli_h.rs"),
+                users: vec![PathBuf::from("/build/cli.c")],
+            },
+        ];
+
+        let crate_modules = |target: &Target| {
+            let target_modules: Vec<&TranslatedModule> = modules
+                .iter()
+                .filter(|module| in_target(target, &module.source))
+                .collect();
+            crate_module_paths(target, &target_modules, &header_modules)
+        };
+        assert_eq!(
+            crate_modules(&util),
+            vec![
+                PathBuf::from("/build/util.rs"),
+                PathBuf::from("/build/util_h.rs"),
+            ]
+        );
+        assert_eq!(
+            crate_modules(&app),
+            vec![
+                PathBuf::from("/build/app.rs"),
+                PathBuf::from("/build/cli.rs"),
+                PathBuf::from("/build/util_h.rs"),
+                PathBuf::from("/build/cli_h.rs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn linux_targets() {
+        assert_eq!(
+            rust_target_triple("i686-linux-gnu"),
+            Some("i686-unknown-linux-gnu".to_string())
+        );
+        assert_eq!(
+            rust_target_triple("x86_64-pc-linux-gnu"),
+            Some("x86_64-unknown-linux-gnu".to_string())
+        );
+        assert_eq!(
+            rust_target_triple("arm-linux-gnueabihf"),
+            Some("arm-unknown-linux-gnueabihf".to_string())
+        );
+        assert_eq!(
+            rust_target_triple("aarch64-unknown-linux-musl"),
+            Some("aarch64-unknown-linux-musl".to_string())
+        );
+        assert_eq!(
+            rust_target_triple("aarch64-linux-android"),
+            Some("aarch64-linux-android".to_string())
+        );
+        assert_eq!(
+            rust_target_triple("x86_64-linux"),
+            Some("x86_64-unknown-linux-gnu".to_string())
+        );
+    }
+
+    #[test]
+    fn apple_targets() {
+        assert_eq!(
+            rust_target_triple("x86_64-apple-darwin"),
+            Some("x86_64-apple-darwin".to_string())
+        );
+        assert_eq!(
+            rust_target_triple("x86_64-apple-macosx10.14.0"),
+            Some("x86_64-apple-darwin".to_string())
+        );
+        assert_eq!(
+            rust_target_triple("arm64-apple-ios12.0"),
+            Some("aarch64-apple-ios".to_string())
+        );
+    }
+
+    #[test]
+    fn windows_targets() {
+        assert_eq!(
+            rust_target_triple("x86_64-pc-windows-msvc"),
+            Some("x86_64-pc-windows-msvc".to_string())
+        );
+        assert_eq!(
+            rust_target_triple("i386-pc-win32"),
+            Some("i686-pc-windows-msvc".to_string())
+        );
+        assert_eq!(
+            rust_target_triple("x86_64-w64-mingw32"),
+            Some("x86_64-pc-windows-gnu".to_string())
+        );
+        assert_eq!(
+            rust_target_triple("x86_64-windows-gnu"),
+            Some("x86_64-pc-windows-gnu".to_string())
+        );
+    }
+
+    #[test]
+    fn bare_metal_targets() {
+        assert_eq!(
+            rust_target_triple("thumbv7em-none-eabihf"),
+            Some("thumbv7em-none-eabihf".to_string())
+        );
+        assert_eq!(rust_target_triple("arm-none-eabi"), None);
+    }
+
+    #[test]
+    fn other_targets() {
+        assert_eq!(
+            rust_target_triple("x86_64-freebsd12.0"),
+            Some("x86_64-unknown-freebsd".to_string())
+        );
+        assert_eq!(
+            rust_target_triple("wasm32-unknown-unknown"),
+            Some("wasm32-unknown-unknown".to_string())
+        );
+    }
+}
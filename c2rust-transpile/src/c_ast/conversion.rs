@@ -112,13 +112,23 @@ fn located<T>(node: &AstNode, t: T) -> Located<T> {
             fileid: node.fileid,
             file_path: node.file_path.clone(),
         }),
+        end_loc: Some(SrcLoc {
+            line: node.end_line,
+            column: node.end_column,
+            fileid: node.fileid,
+            file_path: node.file_path.clone(),
+        }),
         kind: t,
     }
 }
 
 /// Wrap something into a `Located` node without any location information
 fn not_located<T>(t: T) -> Located<T> {
-    Located { loc: None, kind: t }
+    Located {
+        loc: None,
+        end_loc: None,
+        kind: t,
+    }
 }
 
 fn parse_cast_kind(kind: &str) -> CastKind {
@@ -173,6 +183,9 @@ fn parse_attributes(attributes: &[Value]) -> IndexSet<Attribute> {
             "always_inline" => {
                 attrs.insert(Attribute::AlwaysInline);
             }
+            "blocks" => {
+                attrs.insert(Attribute::Blocks);
+            }
             "cold" => {
                 attrs.insert(Attribute::Cold);
             }
@@ -216,6 +229,12 @@ pub struct ConversionContext {
 
     /// Typed context we are building up during the conversion
     pub typed_context: TypedAstContext,
+
+    /// Names of the C++ namespaces of declarations, outermost first
+    namespaces: HashMap<ClangId, Vec<String>>,
+
+    /// Return statements of functions that return C++ references, with the reference types
+    reference_returns: HashMap<ClangId, ClangId>,
 }
 
 impl ConversionContext {
@@ -234,6 +253,107 @@ impl ConversionContext {
             processed_nodes: HashMap::new(),
             visit_as,
             typed_context: TypedAstContext::new(),
+            namespaces: Self::namespaces(untyped_context),
+            reference_returns: HashMap::new(),
+        }
+    }
+
+    /// Find the names of the C++ namespaces of the declarations in them. Anonymous and inline
+    /// namespaces have no names, since their members belong to the enclosing namespace.
+    fn namespaces(untyped_context: &AstContext) -> HashMap<ClangId, Vec<String>> {
+        let mut enclosing: HashMap<ClangId, ClangId> = HashMap::new();
+        for (&id, node) in &untyped_context.ast_nodes {
+            if node.tag == ASTEntryTag::TagNamespaceDecl {
+                for member in node.children.iter().filter_map(|&x| x) {
+                    enclosing.insert(member, id);
+                }
+            }
+        }
+
+        let mut namespaces = HashMap::new();
+        for &decl in enclosing.keys() {
+            let mut path = vec![];
+            let mut cur = decl;
+            while let Some(&namespace) = enclosing.get(&cur) {
+                let name = untyped_context.ast_nodes[&namespace].extras[0]
+                    .as_string()
+                    .expect("Expected to find namespace name");
+                if !name.is_empty() {
+                    path.push(name.to_owned());
+                }
+                cur = namespace;
+            }
+            if !path.is_empty() {
+                path.reverse();
+                namespaces.insert(decl, path);
+            }
+        }
+        namespaces
+    }
+
+    /// Follow the sugar of a type of the Clang AST, including typedefs, to the type it stands for
+    fn desugared_type<'a>(
+        untyped_context: &'a AstContext,
+        type_id: ClangId,
+    ) -> Option<&'a TypeNode> {
+        let mut type_id = type_id;
+        loop {
+            let ty_node = untyped_context
+                .type_nodes
+                .get(&(type_id & TypeNode::ID_MASK))?;
+            type_id = match ty_node.tag {
+                TypeTag::TagParenType | TypeTag::TagElaboratedType | TypeTag::TagAttributedType => {
+                    ty_node.extras[0].as_u64()?
+                }
+                TypeTag::TagTypedefType => {
+                    let decl = ty_node.extras[0].as_u64()?;
+                    untyped_context.ast_nodes.get(&decl)?.type_id?
+                }
+                _ => return Some(ty_node),
+            };
+        }
+    }
+
+    /// Is this type of the Clang AST a C++ reference, which we translate as a pointer?
+    fn is_reference(untyped_context: &AstContext, type_id: ClangId) -> bool {
+        Self::desugared_type(untyped_context, type_id)
+            .map_or(false, |ty_node| ty_node.tag == TypeTag::TagReferenceType)
+    }
+
+    /// The return type and parameter types of a function type, or of the function type a pointer
+    /// points to
+    fn signature(untyped_context: &AstContext, type_id: ClangId) -> Option<Vec<ClangId>> {
+        let mut ty_node = Self::desugared_type(untyped_context, type_id)?;
+        if ty_node.tag == TypeTag::TagPointer {
+            ty_node = Self::desugared_type(untyped_context, ty_node.extras[0].as_u64()?)?;
+        }
+        if ty_node.tag != TypeTag::TagFunctionType {
+            return None;
+        }
+        ty_node.extras[0]
+            .as_array()?
+            .iter()
+            .map(Value::as_u64)
+            .collect()
+    }
+
+    /// Find the return statements of a function body. Gotos and references to declarations are
+    /// not followed, and neither are the bodies of nested blocks.
+    fn find_returns(untyped_context: &AstContext, node_id: ClangId, returns: &mut Vec<ClangId>) {
+        let node = match untyped_context.ast_nodes.get(&node_id) {
+            Some(node) => node,
+            None => return,
+        };
+        match node.tag {
+            ASTEntryTag::TagReturnStmt => {
+                returns.push(node_id);
+            }
+            ASTEntryTag::TagGotoStmt | ASTEntryTag::TagDeclRefExpr | ASTEntryTag::TagBlockExpr => {}
+            _ => {
+                for &child in node.children.iter().filter_map(|x| x.as_ref()) {
+                    Self::find_returns(untyped_context, child, returns);
+                }
+            }
         }
     }
 
@@ -307,6 +427,21 @@ impl ConversionContext {
         self.typed_context.c_decls.insert(CDeclId(id), decl);
     }
 
+    /// Add an expression node that is not present in the Clang AST, located at `node`
+    fn add_synthetic_expr(&mut self, node: &AstNode, expr: CExprKind) -> CExprId {
+        let new_expr_id = self.id_mapper.fresh_id();
+        self.add_expr(new_expr_id, located(node, expr));
+        self.processed_nodes.insert(new_expr_id, node_types::EXPR);
+        CExprId(new_expr_id)
+    }
+
+    /// Take the address of an expression that a C++ reference of type `ty` is bound to, since we
+    /// translate references as pointers
+    fn bind_reference(&mut self, node: &AstNode, ty: CQualTypeId, expr: CExprId) -> CExprId {
+        let address_of = CExprKind::Unary(ty, UnOp::AddressOf, expr, LRValue::RValue);
+        self.add_synthetic_expr(node, address_of)
+    }
+
     /// Clang has `Expression <: Statement`, but we want to make that explicit via the
     /// `CStmtKind::Expr` statement constructor. This function automatically converts expressions
     /// into statements depending on the expected type argument.
@@ -347,9 +482,9 @@ impl ConversionContext {
                     line: raw_comment.line,
                     column: raw_comment.column,
                     fileid: raw_comment.fileid,
-                    // Can/Should we get file paths for comments?
-                    file_path: None,
+                    file_path: raw_comment.file_path.clone(),
                 }),
+                end_loc: None,
                 kind: raw_comment.string.clone(),
             };
             self.typed_context.comments.push(comment);
@@ -381,6 +516,13 @@ impl ConversionContext {
                 self.typed_context.c_decls_top.push(CDeclId(new_id));
             }
 
+            if let Some(namespace) = self.namespaces.get(&node_id) {
+                let namespace = namespace.clone();
+                self.typed_context
+                    .namespaces
+                    .insert(CDeclId(new_id), namespace);
+            }
+
             self.visit_node(untyped_context, node_id, new_id, expected_ty)
         }
     }
@@ -507,6 +649,18 @@ impl ConversionContext {
                     self.processed_nodes.insert(new_id, OTHER_TYPE);
                 }
 
+                // C++ references are translated as pointers
+                TypeTag::TagReferenceType if expected_ty & OTHER_TYPE != 0 => {
+                    let referenced = ty_node.extras[0]
+                        .as_u64()
+                        .expect("Reference child not found");
+                    let referenced_new = self.visit_qualified_type(referenced);
+
+                    let pointer_ty = CTypeKind::Pointer(referenced_new);
+                    self.add_type(new_id, not_located(pointer_ty));
+                    self.processed_nodes.insert(new_id, OTHER_TYPE);
+                }
+
                 TypeTag::TagBlockPointer if expected_ty & OTHER_TYPE != 0 => {
                     let pointed = ty_node.extras[0]
                         .as_u64()
@@ -768,7 +922,13 @@ impl ConversionContext {
                 }
 
                 ASTEntryTag::TagReturnStmt if expected_ty & OTHER_STMT != 0 => {
-                    let return_expr_opt = node.children[0].map(|id| self.visit_expr(id));
+                    let mut return_expr_opt = node.children[0].map(|id| self.visit_expr(id));
+
+                    if let Some(&ret_old) = self.reference_returns.get(&node_id) {
+                        let ret = self.visit_qualified_type(ret_old);
+                        return_expr_opt =
+                            return_expr_opt.map(|e| self.bind_reference(node, ret, e));
+                    }
 
                     let return_stmt = CStmtKind::Return(return_expr_opt);
 
@@ -835,6 +995,50 @@ impl ConversionContext {
                     self.processed_nodes.insert(new_id, OTHER_STMT);
                 }
 
+                ASTEntryTag::TagOMPParallelForDirective if expected_ty & OTHER_STMT != 0 => {
+                    let for_stmt_old = node.children[0].expect("Parallel for loop not found");
+                    let for_stmt = self.visit_stmt(for_stmt_old);
+
+                    let clauses = node.extras[0]
+                        .as_array()
+                        .expect("Expected OpenMP clauses array")
+                        .iter()
+                        .map(|clause| {
+                            let clause = clause.as_array().expect("Expected OpenMP clause");
+                            let name = clause[0]
+                                .as_string()
+                                .expect("Expected OpenMP clause name")
+                                .to_owned();
+                            let (args, vars) = match name.as_str() {
+                                "reduction" => clause[1..].split_at(1),
+                                _ => clause[1..].split_at(0),
+                            };
+                            let vars: Option<Vec<CDeclId>> = vars
+                                .iter()
+                                .map(|var| {
+                                    var.as_u64()
+                                        .map(|var| CDeclId(self.visit_node_type(var, VAR_DECL)))
+                                })
+                                .collect();
+                            match (name.as_str(), vars) {
+                                ("reduction", Some(vars)) => {
+                                    let op =
+                                        args[0].as_string().expect("Expected reduction identifier");
+                                    let op = op.trim_start_matches("operator").to_owned();
+                                    OmpClause::Reduction(op, vars)
+                                }
+                                ("private", Some(vars)) => OmpClause::Private(vars),
+                                _ => OmpClause::Other(name),
+                            }
+                        })
+                        .collect();
+
+                    let parallel_for = CStmtKind::OmpParallelFor { for_stmt, clauses };
+
+                    self.add_stmt(new_id, located(node, parallel_for));
+                    self.processed_nodes.insert(new_id, OTHER_STMT);
+                }
+
                 ASTEntryTag::TagWhileStmt if expected_ty & OTHER_STMT != 0 => {
                     let condition_old = node.children[0].expect("While loop condition not found");
                     let condition = self.visit_expr(condition_old);
@@ -1164,7 +1368,7 @@ impl ConversionContext {
                     let func_old = node.children[0].expect("Expected function for function call");
                     let func = self.visit_expr(func_old);
 
-                    let args: Vec<CExprId> = node
+                    let mut args: Vec<CExprId> = node
                         .children
                         .iter()
                         .skip(1)
@@ -1177,7 +1381,34 @@ impl ConversionContext {
                     let ty_old = node.type_id.expect("Expected expression to have type");
                     let ty = self.visit_qualified_type(ty_old);
 
-                    let call = CExprKind::Call(ty, func, args);
+                    // Arguments for C++ reference parameters are passed by address, and returned
+                    // references are dereferenced
+                    let signature = untyped_context
+                        .ast_nodes
+                        .get(&func_old)
+                        .and_then(|func_node| func_node.type_id)
+                        .and_then(|func_ty| Self::signature(untyped_context, func_ty));
+                    let (ret_old, params_old) =
+                        match signature.as_ref().and_then(|sig| sig.split_first()) {
+                            Some((&ret_old, params_old)) => (Some(ret_old), params_old),
+                            None => (None, &[][..]),
+                        };
+                    for (arg, &param_old) in args.iter_mut().zip(params_old) {
+                        if Self::is_reference(untyped_context, param_old) {
+                            let param = self.visit_qualified_type(param_old);
+                            *arg = self.bind_reference(node, param, *arg);
+                        }
+                    }
+
+                    let call = match ret_old {
+                        Some(ret_old) if Self::is_reference(untyped_context, ret_old) => {
+                            let ret = self.visit_qualified_type(ret_old);
+                            let ptr =
+                                self.add_synthetic_expr(node, CExprKind::Call(ret, func, args));
+                            CExprKind::Unary(ty, UnOp::Deref, ptr, node.rvalue)
+                        }
+                        _ => CExprKind::Call(ty, func, args),
+                    };
 
                     self.expr_possibly_as_stmt(expected_ty, new_id, node, call);
                 }
@@ -1279,7 +1510,24 @@ impl ConversionContext {
                     let ty_old = node.type_id.expect("Expected expression to have type");
                     let ty = self.visit_qualified_type(ty_old);
 
-                    let decl = CExprKind::DeclRef(ty, declaration, node.rvalue);
+                    // Variables of C++ reference types are pointers to dereference
+                    let reference_ty = untyped_context
+                        .ast_nodes
+                        .get(&declaration_old)
+                        .filter(|decl_node| decl_node.tag == ASTEntryTag::TagVarDecl)
+                        .and_then(|decl_node| decl_node.type_id)
+                        .filter(|&decl_ty| Self::is_reference(untyped_context, decl_ty));
+
+                    let decl = match reference_ty {
+                        Some(reference_ty_old) => {
+                            let reference_ty = self.visit_qualified_type(reference_ty_old);
+                            let pointer =
+                                CExprKind::DeclRef(reference_ty, declaration, LRValue::RValue);
+                            let pointer = self.add_synthetic_expr(node, pointer);
+                            CExprKind::Unary(ty, UnOp::Deref, pointer, node.rvalue)
+                        }
+                        None => CExprKind::DeclRef(ty, declaration, node.rvalue),
+                    };
 
                     self.expr_possibly_as_stmt(expected_ty, new_id, node, decl);
                 }
@@ -1570,6 +1818,74 @@ impl ConversionContext {
                     self.expr_possibly_as_stmt(expected_ty, new_id, node, e)
                 }
 
+                ASTEntryTag::TagGenericSelectionExpr => {
+                    let controlling = node.children[0]
+                        .expect("GenericSelectionExpr controlling expression not found");
+                    let controlling = self.visit_expr(controlling);
+
+                    let associations = node.children[1..]
+                        .iter()
+                        .zip(&node.extras[1..])
+                        .map(|(expr, ctype)| {
+                            let expr = expr.expect("GenericSelectionExpr association not found");
+                            GenericAssociation {
+                                ctype: ctype.as_u64().map(|ty| self.visit_qualified_type(ty)),
+                                expr: self.visit_expr(expr),
+                            }
+                        })
+                        .collect();
+
+                    let ty = node.type_id.expect("Expected expression to have type");
+                    let ty = self.visit_qualified_type(ty);
+
+                    let selected = node.extras[0]
+                        .as_u64()
+                        .expect("Expected selected association")
+                        as usize;
+
+                    let e = CExprKind::GenericSelection(ty, controlling, associations, selected);
+
+                    self.expr_possibly_as_stmt(expected_ty, new_id, node, e)
+                }
+
+                ASTEntryTag::TagBlockExpr if expected_ty & (EXPR | STMT) != 0 => {
+                    let (body_id, parameter_ids) = node
+                        .children
+                        .split_last()
+                        .expect("Expected to find a block body");
+                    let body = self.visit_stmt(body_id.expect("Block body not found"));
+
+                    let parameters = parameter_ids
+                        .iter()
+                        .map(|id| {
+                            let param = id.expect("Block parameter decl not found");
+                            CDeclId(self.visit_node_type(param, VAR_DECL))
+                        })
+                        .collect();
+
+                    let captures = node.extras[0]
+                        .as_array()
+                        .expect("Expected block captures array")
+                        .iter()
+                        .map(|capture| {
+                            let capture = capture.as_array().expect("Expected block capture");
+                            let variable = capture[0].as_u64().expect("Expected captured variable");
+                            let variable = CDeclId(self.visit_node_type(variable, VAR_DECL));
+                            let by_ref = capture[1]
+                                .as_boolean()
+                                .expect("Expected capture by reference flag");
+                            BlockCapture { variable, by_ref }
+                        })
+                        .collect();
+
+                    let ty_old = node.type_id.expect("Expected expression to have type");
+                    let ty = self.visit_qualified_type(ty_old);
+
+                    let block = CExprKind::Block(ty, parameters, body, captures);
+
+                    self.expr_possibly_as_stmt(expected_ty, new_id, node, block)
+                }
+
                 // Declarations
                 ASTEntryTag::TagFunctionDecl if expected_ty & OTHER_DECL != 0 => {
                     let name = node.extras[0]
@@ -1600,6 +1916,12 @@ impl ConversionContext {
                         .expect("Expected to find attributes");
                     let attrs = parse_attributes(attributes);
 
+                    if let Some(symbol) = node.extras[7].as_string() {
+                        self.typed_context
+                            .symbol_names
+                            .insert(CDeclId(new_id), symbol.to_owned());
+                    }
+
                     // The always_inline attribute implies inline even if the
                     // inline keyword is not present.
                     is_inline |= attrs.contains(&Attribute::AlwaysInline);
@@ -1614,6 +1936,19 @@ impl ConversionContext {
                         .split_last()
                         .expect("Expected to find a function body");
 
+                    // Functions returning C++ references return pointers. Their returns are
+                    // recorded before the body is visited, since visiting converts them.
+                    let ret_old = Self::signature(untyped_context, typ_old).map(|sig| sig[0]);
+                    if let (Some(ret_old), Some(body_old)) = (ret_old, *body_id) {
+                        if Self::is_reference(untyped_context, ret_old) {
+                            let mut returns = vec![];
+                            Self::find_returns(untyped_context, body_old, &mut returns);
+                            for ret_stmt in returns {
+                                self.reference_returns.insert(ret_stmt, ret_old);
+                            }
+                        }
+                    }
+
                     let body = body_id.map(|b| self.visit_stmt(b));
 
                     let parameters = parameter_ids
@@ -1700,8 +2035,15 @@ impl ConversionContext {
                         Value::I64(n) => ConstIntExpr::I(n),
                         _ => panic!("Expected constant int expr"),
                     };
+                    let is_layout_dependent = node.extras[2]
+                        .as_boolean()
+                        .expect("Expected to find layout dependence of enum constant");
 
-                    let enum_constant_decl = CDeclKind::EnumConstant { name, value };
+                    let enum_constant_decl = CDeclKind::EnumConstant {
+                        name,
+                        value,
+                        is_layout_dependent,
+                    };
 
                     self.add_decl(new_id, located(node, enum_constant_decl));
                     self.processed_nodes.insert(new_id, ENUM_CON);
@@ -1739,6 +2081,12 @@ impl ConversionContext {
                         .expect("Expected to find type on variable declaration");
                     let typ = self.visit_qualified_type(typ_id);
 
+                    let initializer = if Self::is_reference(untyped_context, typ_id) {
+                        initializer.map(|init| self.bind_reference(node, typ, init))
+                    } else {
+                        initializer
+                    };
+
                     let attrs = parse_attributes(attributes);
 
                     let variable_decl = CDeclKind::Variable {
@@ -1,6 +1,6 @@
 use crate::c_ast::*;
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum SomeId {
     Stmt(CStmtId),
     Expr(CExprId),
@@ -66,7 +66,14 @@ fn immediate_expr_children(kind: &CExprKind) -> Vec<SomeId> {
         | CompoundLiteral(_, e)
         | Predefined(_, e)
         | VAArg(_, e) => intos![e],
+        // The controlling expression is not evaluated
+        GenericSelection(_, _, ref associations, selected) => intos![associations[selected].expr],
         Statements(_, s) => vec![s.into()],
+        Block(_, ref params, body, _) => {
+            let mut res: Vec<SomeId> = params.iter().map(|&x| x.into()).collect();
+            res.push(body.into());
+            res
+        }
     }
 }
 
@@ -103,6 +110,9 @@ fn immediate_expr_children_all_types(kind: &CExprKind) -> Vec<SomeId> {
         Conditional(_, c, t, e)
         | Choose(_, c, t, e, _) => intos![c, t, e],
         BinaryConditional(_, c, t) => intos![c, t],
+        GenericSelection(_, c, ref associations, selected) => {
+            intos![c, associations[selected].expr]
+        }
         InitList(_, ref xs, _, _) => xs.iter().map(|&x| x.into()).collect(),
         Member(_, e, _, _, _) | Predefined(_, e) => intos![e],
         // Normally we don't step into the result type annotation field, because it's not really
@@ -116,6 +126,11 @@ fn immediate_expr_children_all_types(kind: &CExprKind) -> Vec<SomeId> {
             intos![qty.ctype, e]
         }
         Statements(_, s) => vec![s.into()],
+        Block(_, ref params, body, _) => {
+            let mut res: Vec<SomeId> = params.iter().map(|&x| x.into()).collect();
+            res.push(body.into());
+            res
+        }
     }
 }
 
@@ -239,6 +254,8 @@ fn immediate_stmt_children(kind: &CStmtKind) -> Vec<SomeId> {
             }
             res
         }
+
+        OmpParallelFor { for_stmt, .. } => intos![for_stmt],
     }
 }
 
@@ -282,7 +299,7 @@ fn immediate_type_children(kind: &CTypeKind) -> Vec<SomeId> {
     }
 }
 
-fn immediate_children(context: &TypedAstContext, s_or_e: SomeId) -> Vec<SomeId> {
+pub fn immediate_children(context: &TypedAstContext, s_or_e: SomeId) -> Vec<SomeId> {
     match s_or_e {
         SomeId::Stmt(stmt_id) => immediate_stmt_children(&context[stmt_id].kind),
         SomeId::Expr(expr_id) => immediate_expr_children(&context[expr_id].kind),
@@ -291,7 +308,7 @@ fn immediate_children(context: &TypedAstContext, s_or_e: SomeId) -> Vec<SomeId>
     }
 }
 
-fn immediate_children_all_types(context: &TypedAstContext, s_or_e: SomeId) -> Vec<SomeId> {
+pub fn immediate_children_all_types(context: &TypedAstContext, s_or_e: SomeId) -> Vec<SomeId> {
     match s_or_e {
         SomeId::Stmt(stmt_id) => immediate_stmt_children(&context[stmt_id].kind),
         SomeId::Expr(expr_id) => immediate_expr_children_all_types(&context[expr_id].kind),
@@ -0,0 +1,1497 @@
+//! Versioned JSON export of the typed C AST.
+//!
+//! With `--emit-ast-json`, the typed AST of every translation unit is written next to its
+//! translation as `<module>.ast.json`, so that other tools can reuse the Clang frontend of the
+//! transpiler. The document is an object with the fields
+//!
+//! - `schema_version`: the `SCHEMA_VERSION` the document follows
+//! - `files`: paths of the source files, by their file ids
+//! - `top_decls`: ids of the top-level declarations in source order
+//! - `main`: id of the `main` function, or `null`
+//! - `types`, `decls`, `exprs` and `stmts`: the nodes of the AST, by their ids
+//! - `parents`: the records and enums of fields and enum constants
+//! - `macro_expansions`: the stacks of macros that expressions were expanded from
+//! - `namespaces`: the names of the C++ namespaces of declarations, outermost first
+//! - `symbol_names`: the mangled symbol names of C++ functions
+//! - `comments`: the comments attached to declarations (`decls`), to statements (`stmts`), to
+//!   the expressions inside of statements that they precede (`exprs`) and after the last
+//!   statement of compound statements (`block_ends`), and the ones that could not be attached
+//!   (`unattached`)
+//!
+//! Ids are integers, written as strings where they are object keys. Nodes are objects with a
+//! `kind` and the fields of that kind, and comments objects with their `text`. Both have the
+//! `loc` and `end_loc` of their first and last token, which are either `null` or objects with a
+//! `fileid`, `line` and `column`. Qualified types are objects with the id of the `type` and its
+//! `const`, `restrict` and `volatile` qualifiers. Operators are written as their C tokens, and
+//! cast kinds by the names Clang gives them. All maps are ordered by their keys, so the same
+//! input always gives the same document.
+//!
+//! The document is built from the types of this module rather than from the AST itself, so that
+//! changes to the AST don't change the format by accident. `SCHEMA_VERSION` is bumped on every
+//! change of the format that is not just the addition of a field or kind.
+
+use std::collections::BTreeMap;
+
+use super::*;
+
+/// Version of the format of exported ASTs
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct ExportedAst<'a> {
+    schema_version: u32,
+    files: BTreeMap<u64, &'a str>,
+    top_decls: Vec<u64>,
+    main: Option<u64>,
+    types: BTreeMap<u64, Node<Type>>,
+    decls: BTreeMap<u64, Node<Decl>>,
+    exprs: BTreeMap<u64, Node<Expr>>,
+    stmts: BTreeMap<u64, Node<Stmt>>,
+    parents: BTreeMap<u64, u64>,
+    macro_expansions: BTreeMap<u64, Vec<u64>>,
+    namespaces: BTreeMap<u64, &'a [String]>,
+    symbol_names: BTreeMap<u64, &'a str>,
+    comments: ExportedComments,
+}
+
+#[derive(Serialize)]
+struct ExportedComments {
+    decls: BTreeMap<u64, Vec<Node<Comment>>>,
+    stmts: BTreeMap<u64, Vec<Node<Comment>>>,
+    exprs: BTreeMap<u64, Vec<Node<Comment>>>,
+    block_ends: BTreeMap<u64, Vec<Node<Comment>>>,
+    unattached: Vec<Node<Comment>>,
+}
+
+/// A node with the locations of its first and last token
+#[derive(Serialize)]
+struct Node<K> {
+    #[serde(flatten)]
+    kind: K,
+    loc: Option<Loc>,
+    end_loc: Option<Loc>,
+}
+
+impl<'a, T, K: From<&'a T>> From<&'a Located<T>> for Node<K> {
+    fn from(located: &'a Located<T>) -> Self {
+        Node {
+            kind: K::from(&located.kind),
+            loc: located.loc.as_ref().map(Loc::from),
+            end_loc: located.end_loc.as_ref().map(Loc::from),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Loc {
+    fileid: u64,
+    line: u64,
+    column: u64,
+}
+
+impl<'a> From<&'a SrcLoc> for Loc {
+    fn from(loc: &'a SrcLoc) -> Self {
+        Loc {
+            fileid: loc.fileid,
+            line: loc.line,
+            column: loc.column,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Comment {
+    text: String,
+}
+
+impl<'a> From<&'a String> for Comment {
+    fn from(text: &'a String) -> Self {
+        Comment { text: text.clone() }
+    }
+}
+
+#[derive(Serialize)]
+struct QualType {
+    #[serde(rename = "type")]
+    ctype: u64,
+    #[serde(rename = "const")]
+    is_const: bool,
+    #[serde(rename = "restrict")]
+    is_restrict: bool,
+    #[serde(rename = "volatile")]
+    is_volatile: bool,
+}
+
+fn qual_type(qual_type: CQualTypeId) -> QualType {
+    QualType {
+        ctype: qual_type.ctype.0,
+        is_const: qual_type.qualifiers.is_const,
+        is_restrict: qual_type.qualifiers.is_restrict,
+        is_volatile: qual_type.qualifiers.is_volatile,
+    }
+}
+
+fn ids<T: Copy>(ids: &[T], id: impl Fn(T) -> u64) -> Vec<u64> {
+    ids.iter().map(|&x| id(x)).collect()
+}
+
+fn decl_ids(decl_ids: &[CDeclId]) -> Vec<u64> {
+    ids(decl_ids, |id| id.0)
+}
+
+fn expr_ids(expr_ids: &[CExprId]) -> Vec<u64> {
+    ids(expr_ids, |id| id.0)
+}
+
+/// A constant integer, written as a JSON number
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ConstInt {
+    Unsigned(u64),
+    Signed(i64),
+}
+
+impl From<ConstIntExpr> for ConstInt {
+    fn from(value: ConstIntExpr) -> Self {
+        match value {
+            ConstIntExpr::U(value) => ConstInt::Unsigned(value),
+            ConstIntExpr::I(value) => ConstInt::Signed(value),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind")]
+enum Type {
+    Void,
+    Bool,
+    Char,
+    SChar,
+    Short,
+    Int,
+    Long,
+    LongLong,
+    UChar,
+    UShort,
+    UInt,
+    ULong,
+    ULongLong,
+    Float,
+    Double,
+    LongDouble,
+    Half,
+    Int128,
+    UInt128,
+    Complex {
+        element: u64,
+    },
+    Pointer {
+        pointee: QualType,
+    },
+    BlockPointer {
+        pointee: QualType,
+    },
+    ConstantArray {
+        element: u64,
+        len: usize,
+    },
+    IncompleteArray {
+        element: u64,
+    },
+    VariableArray {
+        element: u64,
+        len: Option<u64>,
+    },
+    TypeOf {
+        underlying: u64,
+    },
+    TypeOfExpr {
+        expr: u64,
+    },
+    Function {
+        ret: QualType,
+        params: Vec<QualType>,
+        is_variadic: bool,
+        is_noreturn: bool,
+        has_prototype: bool,
+    },
+    Typedef {
+        decl: u64,
+    },
+    Decayed {
+        underlying: u64,
+    },
+    Elaborated {
+        underlying: u64,
+    },
+    Paren {
+        underlying: u64,
+    },
+    Struct {
+        decl: u64,
+    },
+    Union {
+        decl: u64,
+    },
+    Enum {
+        decl: u64,
+    },
+    BuiltinFn,
+    Attributed {
+        underlying: QualType,
+        attribute: Option<Attr>,
+    },
+    Vector {
+        element: QualType,
+        len: usize,
+    },
+}
+
+impl<'a> From<&'a CTypeKind> for Type {
+    fn from(kind: &'a CTypeKind) -> Self {
+        match *kind {
+            CTypeKind::Void => Type::Void,
+            CTypeKind::Bool => Type::Bool,
+            CTypeKind::Char => Type::Char,
+            CTypeKind::SChar => Type::SChar,
+            CTypeKind::Short => Type::Short,
+            CTypeKind::Int => Type::Int,
+            CTypeKind::Long => Type::Long,
+            CTypeKind::LongLong => Type::LongLong,
+            CTypeKind::UChar => Type::UChar,
+            CTypeKind::UShort => Type::UShort,
+            CTypeKind::UInt => Type::UInt,
+            CTypeKind::ULong => Type::ULong,
+            CTypeKind::ULongLong => Type::ULongLong,
+            CTypeKind::Float => Type::Float,
+            CTypeKind::Double => Type::Double,
+            CTypeKind::LongDouble => Type::LongDouble,
+            CTypeKind::Half => Type::Half,
+            CTypeKind::Int128 => Type::Int128,
+            CTypeKind::UInt128 => Type::UInt128,
+            CTypeKind::Complex(element) => Type::Complex { element: element.0 },
+            CTypeKind::Pointer(pointee) => Type::Pointer {
+                pointee: qual_type(pointee),
+            },
+            CTypeKind::BlockPointer(pointee) => Type::BlockPointer {
+                pointee: qual_type(pointee),
+            },
+            CTypeKind::ConstantArray(element, len) => Type::ConstantArray {
+                element: element.0,
+                len,
+            },
+            CTypeKind::IncompleteArray(element) => Type::IncompleteArray { element: element.0 },
+            CTypeKind::VariableArray(element, len) => Type::VariableArray {
+                element: element.0,
+                len: len.map(|len| len.0),
+            },
+            CTypeKind::TypeOf(underlying) => Type::TypeOf {
+                underlying: underlying.0,
+            },
+            CTypeKind::TypeOfExpr(expr) => Type::TypeOfExpr { expr: expr.0 },
+            CTypeKind::Function(ret, ref params, is_variadic, is_noreturn, has_prototype) => {
+                Type::Function {
+                    ret: qual_type(ret),
+                    params: params.iter().cloned().map(qual_type).collect(),
+                    is_variadic,
+                    is_noreturn,
+                    has_prototype,
+                }
+            }
+            CTypeKind::Typedef(decl) => Type::Typedef { decl: decl.0 },
+            CTypeKind::Decayed(underlying) => Type::Decayed {
+                underlying: underlying.0,
+            },
+            CTypeKind::Elaborated(underlying) => Type::Elaborated {
+                underlying: underlying.0,
+            },
+            CTypeKind::Paren(underlying) => Type::Paren {
+                underlying: underlying.0,
+            },
+            CTypeKind::Struct(decl) => Type::Struct { decl: decl.0 },
+            CTypeKind::Union(decl) => Type::Union { decl: decl.0 },
+            CTypeKind::Enum(decl) => Type::Enum { decl: decl.0 },
+            CTypeKind::BuiltinFn => Type::BuiltinFn,
+            CTypeKind::Attributed(underlying, ref attribute) => Type::Attributed {
+                underlying: qual_type(underlying),
+                attribute: attribute.as_ref().map(Attr::from),
+            },
+            CTypeKind::Vector(element, len) => Type::Vector {
+                element: qual_type(element),
+                len,
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind")]
+enum Attr {
+    Alias { target: String },
+    AlwaysInline,
+    Blocks,
+    Cold,
+    GnuInline,
+    NoInline,
+    NoReturn,
+    NotNull,
+    Nullable,
+    Section { name: String },
+    Used,
+}
+
+impl<'a> From<&'a Attribute> for Attr {
+    fn from(attribute: &'a Attribute) -> Self {
+        match *attribute {
+            Attribute::Alias(ref target) => Attr::Alias {
+                target: target.clone(),
+            },
+            Attribute::AlwaysInline => Attr::AlwaysInline,
+            Attribute::Blocks => Attr::Blocks,
+            Attribute::Cold => Attr::Cold,
+            Attribute::GnuInline => Attr::GnuInline,
+            Attribute::NoInline => Attr::NoInline,
+            Attribute::NoReturn => Attr::NoReturn,
+            Attribute::NotNull => Attr::NotNull,
+            Attribute::Nullable => Attr::Nullable,
+            Attribute::Section(ref name) => Attr::Section { name: name.clone() },
+            Attribute::Used => Attr::Used,
+        }
+    }
+}
+
+fn attrs(attributes: &IndexSet<Attribute>) -> Vec<Attr> {
+    attributes.iter().map(Attr::from).collect()
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind")]
+enum Decl {
+    Function {
+        name: String,
+        #[serde(rename = "type")]
+        ctype: u64,
+        parameters: Vec<u64>,
+        body: Option<u64>,
+        is_global: bool,
+        is_inline: bool,
+        is_implicit: bool,
+        is_extern: bool,
+        attributes: Vec<Attr>,
+    },
+    Variable {
+        name: String,
+        #[serde(rename = "type")]
+        ctype: QualType,
+        initializer: Option<u64>,
+        has_static_duration: bool,
+        has_thread_duration: bool,
+        is_externally_visible: bool,
+        is_defn: bool,
+        attributes: Vec<Attr>,
+    },
+    Enum {
+        name: Option<String>,
+        variants: Vec<u64>,
+        integral_type: Option<QualType>,
+    },
+    EnumConstant {
+        name: String,
+        value: ConstInt,
+        is_layout_dependent: bool,
+    },
+    Typedef {
+        name: String,
+        #[serde(rename = "type")]
+        ctype: QualType,
+        is_implicit: bool,
+    },
+    Struct {
+        name: Option<String>,
+        fields: Option<Vec<u64>>,
+        is_packed: bool,
+        manual_alignment: Option<u64>,
+        max_field_alignment: Option<u64>,
+        platform_byte_size: u64,
+        platform_alignment: u64,
+    },
+    Union {
+        name: Option<String>,
+        fields: Option<Vec<u64>>,
+    },
+    Field {
+        name: String,
+        #[serde(rename = "type")]
+        ctype: QualType,
+        bitfield_width: Option<u64>,
+        platform_bit_offset: u64,
+        platform_type_bitwidth: u64,
+    },
+    MacroObject {
+        name: String,
+        replacements: Vec<u64>,
+    },
+    NonCanonicalDecl {
+        canonical_decl: u64,
+    },
+}
+
+impl<'a> From<&'a CDeclKind> for Decl {
+    fn from(kind: &'a CDeclKind) -> Self {
+        match *kind {
+            CDeclKind::Function {
+                is_global,
+                is_inline,
+                is_implicit,
+                is_extern,
+                typ,
+                ref name,
+                ref parameters,
+                body,
+                ref attrs,
+            } => Decl::Function {
+                name: name.clone(),
+                ctype: typ.0,
+                parameters: decl_ids(parameters),
+                body: body.map(|body| body.0),
+                is_global,
+                is_inline,
+                is_implicit,
+                is_extern,
+                attributes: self::attrs(attrs),
+            },
+            CDeclKind::Variable {
+                has_static_duration,
+                has_thread_duration,
+                is_externally_visible,
+                is_defn,
+                ref ident,
+                initializer,
+                typ,
+                ref attrs,
+            } => Decl::Variable {
+                name: ident.clone(),
+                ctype: qual_type(typ),
+                initializer: initializer.map(|initializer| initializer.0),
+                has_static_duration,
+                has_thread_duration,
+                is_externally_visible,
+                is_defn,
+                attributes: self::attrs(attrs),
+            },
+            CDeclKind::Enum {
+                ref name,
+                ref variants,
+                integral_type,
+            } => Decl::Enum {
+                name: name.clone(),
+                variants: decl_ids(variants),
+                integral_type: integral_type.map(qual_type),
+            },
+            CDeclKind::EnumConstant {
+                ref name,
+                value,
+                is_layout_dependent,
+            } => Decl::EnumConstant {
+                name: name.clone(),
+                value: ConstInt::from(value),
+                is_layout_dependent,
+            },
+            CDeclKind::Typedef {
+                ref name,
+                typ,
+                is_implicit,
+            } => Decl::Typedef {
+                name: name.clone(),
+                ctype: qual_type(typ),
+                is_implicit,
+            },
+            CDeclKind::Struct {
+                ref name,
+                ref fields,
+                is_packed,
+                manual_alignment,
+                max_field_alignment,
+                platform_byte_size,
+                platform_alignment,
+            } => Decl::Struct {
+                name: name.clone(),
+                fields: fields.as_ref().map(|fields| decl_ids(fields)),
+                is_packed,
+                manual_alignment,
+                max_field_alignment,
+                platform_byte_size,
+                platform_alignment,
+            },
+            CDeclKind::Union {
+                ref name,
+                ref fields,
+            } => Decl::Union {
+                name: name.clone(),
+                fields: fields.as_ref().map(|fields| decl_ids(fields)),
+            },
+            CDeclKind::Field {
+                ref name,
+                typ,
+                bitfield_width,
+                platform_bit_offset,
+                platform_type_bitwidth,
+            } => Decl::Field {
+                name: name.clone(),
+                ctype: qual_type(typ),
+                bitfield_width,
+                platform_bit_offset,
+                platform_type_bitwidth,
+            },
+            CDeclKind::MacroObject {
+                ref name,
+                ref replacements,
+            } => Decl::MacroObject {
+                name: name.clone(),
+                replacements: expr_ids(replacements),
+            },
+            CDeclKind::NonCanonicalDecl { canonical_decl } => Decl::NonCanonicalDecl {
+                canonical_decl: canonical_decl.0,
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind")]
+enum Literal {
+    Integer { value: u64, base: u32 },
+    Character { value: u64 },
+    Floating { value: f64, text: String },
+    String { bytes: Vec<u8>, unit_width: u8 },
+}
+
+impl<'a> From<&'a CLiteral> for Literal {
+    fn from(literal: &'a CLiteral) -> Self {
+        match *literal {
+            CLiteral::Integer(value, base) => Literal::Integer {
+                value,
+                base: match base {
+                    IntBase::Dec => 10,
+                    IntBase::Hex => 16,
+                    IntBase::Oct => 8,
+                },
+            },
+            CLiteral::Character(value) => Literal::Character { value },
+            CLiteral::Floating(value, ref text) => Literal::Floating {
+                value,
+                text: text.clone(),
+            },
+            CLiteral::String(ref bytes, unit_width) => Literal::String {
+                bytes: bytes.clone(),
+                unit_width,
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind")]
+enum Offset {
+    Constant {
+        value: u64,
+    },
+    Variable {
+        record_type: QualType,
+        field: u64,
+        index: u64,
+    },
+}
+
+impl<'a> From<&'a OffsetOfKind> for Offset {
+    fn from(kind: &'a OffsetOfKind) -> Self {
+        match *kind {
+            OffsetOfKind::Constant(value) => Offset::Constant { value },
+            OffsetOfKind::Variable(record_type, field, index) => Offset::Variable {
+                record_type: qual_type(record_type),
+                field: field.0,
+                index: index.0,
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind")]
+enum Designation {
+    Index { index: u64 },
+    Range { first: u64, last: u64 },
+    Field { field: u64 },
+}
+
+impl<'a> From<&'a Designator> for Designation {
+    fn from(designator: &'a Designator) -> Self {
+        match *designator {
+            Designator::Index(index) => Designation::Index { index },
+            Designator::Range(first, last) => Designation::Range { first, last },
+            Designator::Field(field) => Designation::Field { field: field.0 },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Capture {
+    variable: u64,
+    by_ref: bool,
+}
+
+/// A `_Generic` association; the `default` association has no type
+#[derive(Serialize)]
+struct Association {
+    #[serde(rename = "type")]
+    ctype: Option<QualType>,
+    expr: u64,
+}
+
+fn unop(op: UnOp) -> &'static str {
+    match op {
+        UnOp::AddressOf => "&",
+        UnOp::Deref => "*",
+        UnOp::Plus => "+",
+        UnOp::Negate => "-",
+        UnOp::Complement => "~",
+        UnOp::Not => "!",
+        UnOp::PreIncrement => "++x",
+        UnOp::PostIncrement => "x++",
+        UnOp::PreDecrement => "--x",
+        UnOp::PostDecrement => "x--",
+        UnOp::Real => "__real",
+        UnOp::Imag => "__imag",
+        UnOp::Extension => "__extension__",
+        UnOp::Coawait => "co_await",
+    }
+}
+
+fn untypeop(op: UnTypeOp) -> &'static str {
+    match op {
+        UnTypeOp::SizeOf => "sizeof",
+        UnTypeOp::AlignOf => "_Alignof",
+        UnTypeOp::PreferredAlignOf => "__alignof",
+    }
+}
+
+fn binop(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Multiply => "*",
+        BinOp::Divide => "/",
+        BinOp::Modulus => "%",
+        BinOp::Add => "+",
+        BinOp::Subtract => "-",
+        BinOp::ShiftLeft => "<<",
+        BinOp::ShiftRight => ">>",
+        BinOp::Less => "<",
+        BinOp::Greater => ">",
+        BinOp::LessEqual => "<=",
+        BinOp::GreaterEqual => ">=",
+        BinOp::EqualEqual => "==",
+        BinOp::NotEqual => "!=",
+        BinOp::BitAnd => "&",
+        BinOp::BitXor => "^",
+        BinOp::BitOr => "|",
+        BinOp::And => "&&",
+        BinOp::Or => "||",
+        BinOp::AssignAdd => "+=",
+        BinOp::AssignSubtract => "-=",
+        BinOp::AssignMultiply => "*=",
+        BinOp::AssignDivide => "/=",
+        BinOp::AssignModulus => "%=",
+        BinOp::AssignBitXor => "^=",
+        BinOp::AssignShiftLeft => "<<=",
+        BinOp::AssignShiftRight => ">>=",
+        BinOp::AssignBitOr => "|=",
+        BinOp::AssignBitAnd => "&=",
+        BinOp::Assign => "=",
+        BinOp::Comma => ",",
+    }
+}
+
+fn cast_kind(kind: CastKind) -> &'static str {
+    match kind {
+        CastKind::BitCast => "BitCast",
+        CastKind::LValueToRValue => "LValueToRValue",
+        CastKind::NoOp => "NoOp",
+        CastKind::ToUnion => "ToUnion",
+        CastKind::ArrayToPointerDecay => "ArrayToPointerDecay",
+        CastKind::FunctionToPointerDecay => "FunctionToPointerDecay",
+        CastKind::NullToPointer => "NullToPointer",
+        CastKind::IntegralToPointer => "IntegralToPointer",
+        CastKind::PointerToIntegral => "PointerToIntegral",
+        CastKind::ToVoid => "ToVoid",
+        CastKind::IntegralCast => "IntegralCast",
+        CastKind::IntegralToBoolean => "IntegralToBoolean",
+        CastKind::IntegralToFloating => "IntegralToFloating",
+        CastKind::FloatingToIntegral => "FloatingToIntegral",
+        CastKind::FloatingToBoolean => "FloatingToBoolean",
+        CastKind::BooleanToSignedIntegral => "BooleanToSignedIntegral",
+        CastKind::PointerToBoolean => "PointerToBoolean",
+        CastKind::FloatingCast => "FloatingCast",
+        CastKind::FloatingRealToComplex => "FloatingRealToComplex",
+        CastKind::FloatingComplexToReal => "FloatingComplexToReal",
+        CastKind::FloatingComplexCast => "FloatingComplexCast",
+        CastKind::FloatingComplexToIntegralComplex => "FloatingComplexToIntegralComplex",
+        CastKind::IntegralRealToComplex => "IntegralRealToComplex",
+        CastKind::IntegralComplexToReal => "IntegralComplexToReal",
+        CastKind::IntegralComplexToBoolean => "IntegralComplexToBoolean",
+        CastKind::IntegralComplexCast => "IntegralComplexCast",
+        CastKind::IntegralComplexToFloatingComplex => "IntegralComplexToFloatingComplex",
+        CastKind::BuiltinFnToFnPtr => "BuiltinFnToFnPtr",
+        CastKind::ConstCast => "ConstCast",
+        CastKind::VectorSplat => "VectorSplat",
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind")]
+enum Expr {
+    Literal {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        value: Literal,
+    },
+    Unary {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        op: &'static str,
+        operand: u64,
+        is_lvalue: bool,
+    },
+    UnaryType {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        op: &'static str,
+        operand: Option<u64>,
+        operand_type: QualType,
+    },
+    OffsetOf {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        offset: Offset,
+    },
+    Binary {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        op: &'static str,
+        lhs: u64,
+        rhs: u64,
+        /// Type the left-hand side of a compound assignment is computed in
+        computation_lhs_type: Option<QualType>,
+        /// Type the result of a compound assignment is computed in
+        computation_result_type: Option<QualType>,
+    },
+    ImplicitCast {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        expr: u64,
+        cast: &'static str,
+        union_field: Option<u64>,
+        is_lvalue: bool,
+    },
+    ExplicitCast {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        expr: u64,
+        cast: &'static str,
+        union_field: Option<u64>,
+        is_lvalue: bool,
+    },
+    DeclRef {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        decl: u64,
+        is_lvalue: bool,
+    },
+    Call {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        callee: u64,
+        args: Vec<u64>,
+    },
+    Member {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        base: u64,
+        field: u64,
+        is_arrow: bool,
+        is_lvalue: bool,
+    },
+    ArraySubscript {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        lhs: u64,
+        rhs: u64,
+        is_lvalue: bool,
+    },
+    Conditional {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        condition: u64,
+        then_expr: u64,
+        else_expr: u64,
+    },
+    BinaryConditional {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        condition: u64,
+        else_expr: u64,
+    },
+    InitList {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        initializers: Vec<u64>,
+        union_field: Option<u64>,
+        syntactic_form: Option<u64>,
+    },
+    ImplicitValueInit {
+        #[serde(rename = "type")]
+        ctype: QualType,
+    },
+    Paren {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        expr: u64,
+    },
+    CompoundLiteral {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        initializer: u64,
+    },
+    Predefined {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        name: u64,
+    },
+    Statements {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        stmt: u64,
+    },
+    VAArg {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        va_list: u64,
+    },
+    ShuffleVector {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        args: Vec<u64>,
+    },
+    ConvertVector {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        args: Vec<u64>,
+    },
+    DesignatedInit {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        designators: Vec<Designation>,
+        initializer: u64,
+    },
+    Choose {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        condition: u64,
+        true_expr: u64,
+        false_expr: u64,
+        condition_is_true: bool,
+    },
+    Block {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        parameters: Vec<u64>,
+        body: u64,
+        captures: Vec<Capture>,
+    },
+    GenericSelection {
+        #[serde(rename = "type")]
+        ctype: QualType,
+        controlling: u64,
+        associations: Vec<Association>,
+        selected: usize,
+    },
+    BadExpr,
+}
+
+impl<'a> From<&'a CExprKind> for Expr {
+    fn from(kind: &'a CExprKind) -> Self {
+        match *kind {
+            CExprKind::Literal(ty, ref value) => Expr::Literal {
+                ctype: qual_type(ty),
+                value: Literal::from(value),
+            },
+            CExprKind::Unary(ty, op, operand, lrvalue) => Expr::Unary {
+                ctype: qual_type(ty),
+                op: unop(op),
+                operand: operand.0,
+                is_lvalue: lrvalue.is_lvalue(),
+            },
+            CExprKind::UnaryType(ty, op, operand, operand_type) => Expr::UnaryType {
+                ctype: qual_type(ty),
+                op: untypeop(op),
+                operand: operand.map(|operand| operand.0),
+                operand_type: qual_type(operand_type),
+            },
+            CExprKind::OffsetOf(ty, ref offset) => Expr::OffsetOf {
+                ctype: qual_type(ty),
+                offset: Offset::from(offset),
+            },
+            CExprKind::Binary(ty, op, lhs, rhs, computation_lhs_type, computation_result_type) => {
+                Expr::Binary {
+                    ctype: qual_type(ty),
+                    op: binop(op),
+                    lhs: lhs.0,
+                    rhs: rhs.0,
+                    computation_lhs_type: computation_lhs_type.map(qual_type),
+                    computation_result_type: computation_result_type.map(qual_type),
+                }
+            }
+            CExprKind::ImplicitCast(ty, expr, kind, union_field, lrvalue) => Expr::ImplicitCast {
+                ctype: qual_type(ty),
+                expr: expr.0,
+                cast: cast_kind(kind),
+                union_field: union_field.map(|field| field.0),
+                is_lvalue: lrvalue.is_lvalue(),
+            },
+            CExprKind::ExplicitCast(ty, expr, kind, union_field, lrvalue) => Expr::ExplicitCast {
+                ctype: qual_type(ty),
+                expr: expr.0,
+                cast: cast_kind(kind),
+                union_field: union_field.map(|field| field.0),
+                is_lvalue: lrvalue.is_lvalue(),
+            },
+            CExprKind::DeclRef(ty, decl, lrvalue) => Expr::DeclRef {
+                ctype: qual_type(ty),
+                decl: decl.0,
+                is_lvalue: lrvalue.is_lvalue(),
+            },
+            CExprKind::Call(ty, callee, ref args) => Expr::Call {
+                ctype: qual_type(ty),
+                callee: callee.0,
+                args: expr_ids(args),
+            },
+            CExprKind::Member(ty, base, field, member_kind, lrvalue) => Expr::Member {
+                ctype: qual_type(ty),
+                base: base.0,
+                field: field.0,
+                is_arrow: match member_kind {
+                    MemberKind::Arrow => true,
+                    MemberKind::Dot => false,
+                },
+                is_lvalue: lrvalue.is_lvalue(),
+            },
+            CExprKind::ArraySubscript(ty, lhs, rhs, lrvalue) => Expr::ArraySubscript {
+                ctype: qual_type(ty),
+                lhs: lhs.0,
+                rhs: rhs.0,
+                is_lvalue: lrvalue.is_lvalue(),
+            },
+            CExprKind::Conditional(ty, condition, then_expr, else_expr) => Expr::Conditional {
+                ctype: qual_type(ty),
+                condition: condition.0,
+                then_expr: then_expr.0,
+                else_expr: else_expr.0,
+            },
+            CExprKind::BinaryConditional(ty, condition, else_expr) => Expr::BinaryConditional {
+                ctype: qual_type(ty),
+                condition: condition.0,
+                else_expr: else_expr.0,
+            },
+            CExprKind::InitList(ty, ref initializers, union_field, syntactic_form) => {
+                Expr::InitList {
+                    ctype: qual_type(ty),
+                    initializers: expr_ids(initializers),
+                    union_field: union_field.map(|field| field.0),
+                    syntactic_form: syntactic_form.map(|form| form.0),
+                }
+            }
+            CExprKind::ImplicitValueInit(ty) => Expr::ImplicitValueInit {
+                ctype: qual_type(ty),
+            },
+            CExprKind::Paren(ty, expr) => Expr::Paren {
+                ctype: qual_type(ty),
+                expr: expr.0,
+            },
+            CExprKind::CompoundLiteral(ty, initializer) => Expr::CompoundLiteral {
+                ctype: qual_type(ty),
+                initializer: initializer.0,
+            },
+            CExprKind::Predefined(ty, name) => Expr::Predefined {
+                ctype: qual_type(ty),
+                name: name.0,
+            },
+            CExprKind::Statements(ty, stmt) => Expr::Statements {
+                ctype: qual_type(ty),
+                stmt: stmt.0,
+            },
+            CExprKind::VAArg(ty, va_list) => Expr::VAArg {
+                ctype: qual_type(ty),
+                va_list: va_list.0,
+            },
+            CExprKind::ShuffleVector(ty, ref args) => Expr::ShuffleVector {
+                ctype: qual_type(ty),
+                args: expr_ids(args),
+            },
+            CExprKind::ConvertVector(ty, ref args) => Expr::ConvertVector {
+                ctype: qual_type(ty),
+                args: expr_ids(args),
+            },
+            CExprKind::DesignatedInitExpr(ty, ref designators, initializer) => {
+                Expr::DesignatedInit {
+                    ctype: qual_type(ty),
+                    designators: designators.iter().map(Designation::from).collect(),
+                    initializer: initializer.0,
+                }
+            }
+            CExprKind::Choose(ty, condition, true_expr, false_expr, condition_is_true) => {
+                Expr::Choose {
+                    ctype: qual_type(ty),
+                    condition: condition.0,
+                    true_expr: true_expr.0,
+                    false_expr: false_expr.0,
+                    condition_is_true,
+                }
+            }
+            CExprKind::Block(ty, ref parameters, body, ref captures) => Expr::Block {
+                ctype: qual_type(ty),
+                parameters: decl_ids(parameters),
+                body: body.0,
+                captures: captures
+                    .iter()
+                    .map(|capture| Capture {
+                        variable: capture.variable.0,
+                        by_ref: capture.by_ref,
+                    })
+                    .collect(),
+            },
+            CExprKind::GenericSelection(ty, controlling, ref associations, selected) => {
+                Expr::GenericSelection {
+                    ctype: qual_type(ty),
+                    controlling: controlling.0,
+                    associations: associations
+                        .iter()
+                        .map(|association| Association {
+                            ctype: association.ctype.map(qual_type),
+                            expr: association.expr.0,
+                        })
+                        .collect(),
+                    selected,
+                }
+            }
+            CExprKind::BadExpr => Expr::BadExpr,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Operand {
+    constraints: String,
+    expr: u64,
+}
+
+fn asm_operands(operands: &[AsmOperand]) -> Vec<Operand> {
+    operands
+        .iter()
+        .map(|operand| Operand {
+            constraints: operand.constraints.clone(),
+            expr: operand.expression.0,
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind")]
+enum Clause {
+    Reduction {
+        identifier: String,
+        variables: Vec<u64>,
+    },
+    Private {
+        variables: Vec<u64>,
+    },
+    Other {
+        name: String,
+    },
+}
+
+impl<'a> From<&'a OmpClause> for Clause {
+    fn from(clause: &'a OmpClause) -> Self {
+        match *clause {
+            OmpClause::Reduction(ref identifier, ref variables) => Clause::Reduction {
+                identifier: identifier.clone(),
+                variables: decl_ids(variables),
+            },
+            OmpClause::Private(ref variables) => Clause::Private {
+                variables: decl_ids(variables),
+            },
+            OmpClause::Other(ref name) => Clause::Other { name: name.clone() },
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind")]
+enum Stmt {
+    Label {
+        body: u64,
+    },
+    Case {
+        expr: u64,
+        value: ConstInt,
+        body: u64,
+    },
+    Default {
+        body: u64,
+    },
+    Compound {
+        stmts: Vec<u64>,
+    },
+    Expr {
+        expr: u64,
+    },
+    Empty,
+    If {
+        condition: u64,
+        then_stmt: u64,
+        else_stmt: Option<u64>,
+    },
+    Switch {
+        scrutinee: u64,
+        body: u64,
+    },
+    While {
+        condition: u64,
+        body: u64,
+    },
+    DoWhile {
+        body: u64,
+        condition: u64,
+    },
+    For {
+        init: Option<u64>,
+        condition: Option<u64>,
+        increment: Option<u64>,
+        body: u64,
+    },
+    Goto {
+        label: u64,
+    },
+    Break,
+    Continue,
+    Return {
+        expr: Option<u64>,
+    },
+    Decls {
+        decls: Vec<u64>,
+    },
+    Asm {
+        asm: String,
+        inputs: Vec<Operand>,
+        outputs: Vec<Operand>,
+        clobbers: Vec<String>,
+        is_volatile: bool,
+    },
+    OmpParallelFor {
+        for_stmt: u64,
+        clauses: Vec<Clause>,
+    },
+}
+
+impl<'a> From<&'a CStmtKind> for Stmt {
+    fn from(kind: &'a CStmtKind) -> Self {
+        match *kind {
+            CStmtKind::Label(body) => Stmt::Label { body: body.0 },
+            CStmtKind::Case(expr, body, value) => Stmt::Case {
+                expr: expr.0,
+                value: ConstInt::from(value),
+                body: body.0,
+            },
+            CStmtKind::Default(body) => Stmt::Default { body: body.0 },
+            CStmtKind::Compound(ref stmts) => Stmt::Compound {
+                stmts: ids(stmts, |id| id.0),
+            },
+            CStmtKind::Expr(expr) => Stmt::Expr { expr: expr.0 },
+            CStmtKind::Empty => Stmt::Empty,
+            CStmtKind::If {
+                scrutinee,
+                true_variant,
+                false_variant,
+            } => Stmt::If {
+                condition: scrutinee.0,
+                then_stmt: true_variant.0,
+                else_stmt: false_variant.map(|stmt| stmt.0),
+            },
+            CStmtKind::Switch { scrutinee, body } => Stmt::Switch {
+                scrutinee: scrutinee.0,
+                body: body.0,
+            },
+            CStmtKind::While { condition, body } => Stmt::While {
+                condition: condition.0,
+                body: body.0,
+            },
+            CStmtKind::DoWhile { body, condition } => Stmt::DoWhile {
+                body: body.0,
+                condition: condition.0,
+            },
+            CStmtKind::ForLoop {
+                init,
+                condition,
+                increment,
+                body,
+            } => Stmt::For {
+                init: init.map(|init| init.0),
+                condition: condition.map(|condition| condition.0),
+                increment: increment.map(|increment| increment.0),
+                body: body.0,
+            },
+            CStmtKind::Goto(label) => Stmt::Goto { label: label.0 },
+            CStmtKind::Break => Stmt::Break,
+            CStmtKind::Continue => Stmt::Continue,
+            CStmtKind::Return(expr) => Stmt::Return {
+                expr: expr.map(|expr| expr.0),
+            },
+            CStmtKind::Decls(ref decls) => Stmt::Decls {
+                decls: decl_ids(decls),
+            },
+            CStmtKind::Asm {
+                ref asm,
+                ref inputs,
+                ref outputs,
+                ref clobbers,
+                is_volatile,
+            } => Stmt::Asm {
+                asm: asm.clone(),
+                inputs: asm_operands(inputs),
+                outputs: asm_operands(outputs),
+                clobbers: clobbers.clone(),
+                is_volatile,
+            },
+            CStmtKind::OmpParallelFor {
+                for_stmt,
+                ref clauses,
+            } => Stmt::OmpParallelFor {
+                for_stmt: for_stmt.0,
+                clauses: clauses.iter().map(Clause::from).collect(),
+            },
+        }
+    }
+}
+
+/// Export the nodes of a map by their ids, in the order of the ids
+fn nodes<'a, Id, T, K>(
+    map: impl IntoIterator<Item = (&'a Id, &'a Located<T>)>,
+    id: impl Fn(Id) -> u64,
+) -> BTreeMap<u64, Node<K>>
+where
+    Id: Copy + 'a,
+    T: 'a,
+    K: From<&'a T>,
+{
+    map.into_iter()
+        .map(|(&key, node)| (id(key), Node::from(node)))
+        .collect()
+}
+
+fn comment_nodes<'a, Id: Copy + 'a>(
+    map: impl IntoIterator<Item = (&'a Id, &'a Vec<Located<String>>)>,
+    id: impl Fn(Id) -> u64,
+) -> BTreeMap<u64, Vec<Node<Comment>>> {
+    map.into_iter()
+        .map(|(&key, comments)| (id(key), comments.iter().map(Node::from).collect()))
+        .collect()
+}
+
+/// Serialize the typed AST of a translation unit, including its comments
+pub fn export_json(ast_context: &mut TypedAstContext) -> String {
+    // Attaching the comments takes them out of the context
+    let comments = ast_context.comments.clone();
+    let comment_context = CommentContext::new(ast_context);
+    ast_context.comments = comments;
+
+    let exported = ExportedAst {
+        schema_version: SCHEMA_VERSION,
+        files: ast_context
+            .c_files
+            .iter()
+            .map(|(&id, path)| (id, path.as_str()))
+            .collect(),
+        top_decls: decl_ids(&ast_context.c_decls_top),
+        main: ast_context.c_main.map(|main| main.0),
+        types: nodes(&ast_context.c_types, |id: CTypeId| id.0),
+        decls: nodes(&ast_context.c_decls, |id: CDeclId| id.0),
+        exprs: nodes(&ast_context.c_exprs, |id: CExprId| id.0),
+        stmts: nodes(&ast_context.c_stmts, |id: CStmtId| id.0),
+        parents: ast_context
+            .parents
+            .iter()
+            .map(|(child, parent)| (child.0, parent.0))
+            .collect(),
+        macro_expansions: ast_context
+            .macro_expansions
+            .iter()
+            .map(|(expr, macros)| (expr.0, decl_ids(macros)))
+            .collect(),
+        namespaces: ast_context
+            .namespaces
+            .iter()
+            .map(|(decl, names)| (decl.0, names.as_slice()))
+            .collect(),
+        symbol_names: ast_context
+            .symbol_names
+            .iter()
+            .map(|(decl, symbol)| (decl.0, symbol.as_str()))
+            .collect(),
+        comments: ExportedComments {
+            decls: comment_nodes(&comment_context.decl_comments, |id: CDeclId| id.0),
+            stmts: comment_nodes(&comment_context.stmt_comments, |id: CStmtId| id.0),
+            exprs: comment_nodes(&comment_context.expr_comments, |id: CExprId| id.0),
+            block_ends: comment_nodes(&comment_context.block_end_comments, |id: CStmtId| id.0),
+            unattached: comment_context
+                .unmatched_comments
+                .iter()
+                .map(Node::from)
+                .collect(),
+        },
+    };
+    serde_json::to_string_pretty(&exported).expect("Could not serialize the AST")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loc(line: u64, column: u64) -> Option<SrcLoc> {
+        Some(SrcLoc {
+            fileid: 0,
+            line,
+            column,
+            file_path: Some(PathBuf::from("add.c")),
+        })
+    }
+
+    fn located<T>(kind: T, begin: (u64, u64), end: (u64, u64)) -> Located<T> {
+        Located {
+            loc: loc(begin.0, begin.1),
+            end_loc: loc(end.0, end.1),
+            kind,
+        }
+    }
+
+    /// The AST of `int add(int x) { return x + 1; }`
+    fn add_function() -> TypedAstContext {
+        let mut ast_context = TypedAstContext::new();
+        let int = CQualTypeId::new(CTypeId(1));
+        let rvalue = LRValue::RValue;
+
+        ast_context.c_files.insert(0, "add.c".to_string());
+        ast_context.c_types.insert(
+            CTypeId(1),
+            Located {
+                loc: None,
+                end_loc: None,
+                kind: CTypeKind::Int,
+            },
+        );
+        ast_context.c_types.insert(
+            CTypeId(2),
+            Located {
+                loc: None,
+                end_loc: None,
+                kind: CTypeKind::Function(int, vec![int], false, false, true),
+            },
+        );
+
+        let function = CDeclKind::Function {
+            is_global: true,
+            is_inline: false,
+            is_implicit: false,
+            is_extern: false,
+            typ: CTypeId(2),
+            name: "add".to_string(),
+            parameters: vec![CDeclId(11)],
+            body: Some(CStmtId(20)),
+            attrs: IndexSet::new(),
+        };
+        let parameter = CDeclKind::Variable {
+            has_static_duration: false,
+            has_thread_duration: false,
+            is_externally_visible: false,
+            is_defn: true,
+            ident: "x".to_string(),
+            initializer: None,
+            typ: int,
+            attrs: IndexSet::new(),
+        };
+        ast_context
+            .c_decls
+            .insert(CDeclId(10), located(function, (1, 1), (1, 33)));
+        ast_context
+            .c_decls
+            .insert(CDeclId(11), located(parameter, (1, 9), (1, 13)));
+        ast_context.c_decls_top.push(CDeclId(10));
+
+        let body = CStmtKind::Compound(vec![CStmtId(21)]);
+        let ret = CStmtKind::Return(Some(CExprId(30)));
+        ast_context
+            .c_stmts
+            .insert(CStmtId(20), located(body, (1, 16), (1, 33)));
+        ast_context
+            .c_stmts
+            .insert(CStmtId(21), located(ret, (1, 18), (1, 29)));
+
+        let sum = CExprKind::Binary(int, BinOp::Add, CExprId(31), CExprId(33), None, None);
+        let x = CExprKind::ImplicitCast(int, CExprId(32), CastKind::LValueToRValue, None, rvalue);
+        let x_ref = CExprKind::DeclRef(int, CDeclId(11), LRValue::LValue);
+        let one = CExprKind::Literal(int, CLiteral::Integer(1, IntBase::Dec));
+        ast_context
+            .c_exprs
+            .insert(CExprId(30), located(sum, (1, 25), (1, 29)));
+        ast_context
+            .c_exprs
+            .insert(CExprId(31), located(x, (1, 25), (1, 25)));
+        ast_context
+            .c_exprs
+            .insert(CExprId(32), located(x_ref, (1, 25), (1, 25)));
+        ast_context
+            .c_exprs
+            .insert(CExprId(33), located(one, (1, 29), (1, 29)));
+
+        ast_context
+    }
+
+    #[test]
+    fn golden_export() {
+        let exported = export_json(&mut add_function());
+        assert_eq!(exported, include_str!("json_golden.json").trim_end());
+    }
+
+    #[test]
+    fn expression_comments() {
+        // `return x + /* one */ 1;`, with the comment in front of the `1`
+        let mut ast_context = add_function();
+        ast_context
+            .comments
+            .push(located("/* one */".to_string(), (1, 27), (1, 27)));
+
+        let exported = export_json(&mut ast_context);
+        let json: serde_json::Value = serde_json::from_str(&exported).unwrap();
+        assert_eq!(
+            json["comments"]["exprs"],
+            serde_json::json!({
+                "33": [
+                    {
+                        "text": "/* one */",
+                        "loc": { "fileid": 0, "line": 1, "column": 27 },
+                        "end_loc": { "fileid": 0, "line": 1, "column": 27 }
+                    }
+                ]
+            })
+        );
+        assert_eq!(json["comments"]["stmts"], serde_json::json!({}));
+        // Exporting leaves the comments in the context
+        assert_eq!(ast_context.comments.len(), 1);
+    }
+}
@@ -1,6 +1,7 @@
 use c2rust_ast_exporter::clang_ast::LRValue;
 use indexmap::{IndexMap, IndexSet};
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{HashMap, HashSet};
+use std::mem;
 use std::ops::Index;
 use std::path::PathBuf;
 
@@ -32,6 +33,7 @@ use super::diagnostics::Diagnostic;
 
 mod conversion;
 pub mod iterators;
+pub mod json;
 mod print;
 
 /// AST context containing all of the nodes in the Clang AST
@@ -53,6 +55,12 @@ pub struct TypedAstContext {
     // map expressions to the stack of macros they were expanded from
     pub macro_expansions: HashMap<CExprId, Vec<CDeclId>>,
 
+    // map declarations in C++ namespaces to the names of their namespaces, outermost first
+    pub namespaces: HashMap<CDeclId, Vec<String>>,
+
+    // map C++ functions to their mangled symbol names
+    pub symbol_names: HashMap<CDeclId, String>,
+
     pub comments: Vec<Located<String>>,
 
     // The key is the typedef decl being squashed away,
@@ -63,8 +71,17 @@ pub struct TypedAstContext {
 /// Comments associated with a typed AST context
 #[derive(Debug, Clone)]
 pub struct CommentContext {
-    decl_comments: HashMap<CDeclId, Vec<String>>,
-    stmt_comments: HashMap<CStmtId, Vec<String>>,
+    decl_comments: HashMap<CDeclId, Vec<Located<String>>>,
+    stmt_comments: HashMap<CStmtId, Vec<Located<String>>>,
+
+    /// Comments inside of a statement, in front of one of its expressions
+    expr_comments: HashMap<CExprId, Vec<Located<String>>>,
+
+    /// Comments after the last statement of a compound statement
+    block_end_comments: HashMap<CStmtId, Vec<Located<String>>>,
+
+    /// Comments for which no node to attach them to was found
+    unmatched_comments: Vec<Located<String>>,
 }
 
 impl TypedAstContext {
@@ -80,6 +97,8 @@ impl TypedAstContext {
             c_files: HashMap::new(),
             parents: HashMap::new(),
             macro_expansions: HashMap::new(),
+            namespaces: HashMap::new(),
+            symbol_names: HashMap::new(),
 
             comments: vec![],
             prenamed_decls: IndexMap::new(),
@@ -94,6 +113,22 @@ impl TypedAstContext {
         self.c_decls.iter_mut()
     }
 
+    pub fn iter_exprs(&self) -> std::collections::hash_map::Iter<CExprId, CExpr> {
+        self.c_exprs.iter()
+    }
+
+    pub fn iter_stmts(&self) -> std::collections::hash_map::Iter<CStmtId, CStmt> {
+        self.c_stmts.iter()
+    }
+
+    /// The name of the symbol of a function, which is mangled for C++ functions that are not
+    /// `extern "C"`
+    pub fn symbol_name<'a>(&'a self, decl_id: CDeclId, name: &'a str) -> &'a str {
+        self.symbol_names
+            .get(&decl_id)
+            .map_or(name, |symbol| symbol.as_str())
+    }
+
     pub fn get_decl(&self, key: &CDeclId) -> Option<&CDecl> {
         self.c_decls.get(key)
     }
@@ -112,6 +147,32 @@ impl TypedAstContext {
         }
     }
 
+    /// Does the value of this constant expression depend on the size, alignment, or layout of
+    /// types on the target, e.g. through `sizeof` or `offsetof`?
+    pub fn is_layout_dependent(&self, expr_id: CExprId) -> bool {
+        use self::iterators::{DFExpr, SomeId};
+
+        DFExpr::new(self, expr_id.into())
+            .flat_map(SomeId::expr)
+            .any(|expr_id| match self[expr_id].kind {
+                CExprKind::UnaryType(..) | CExprKind::OffsetOf(..) => true,
+                _ => false,
+            })
+    }
+
+    /// The indices of the `_Generic` associations selected in this expression
+    pub fn generic_selections(&self, expr_id: CExprId) -> Vec<usize> {
+        use self::iterators::{DFExpr, SomeId};
+
+        DFExpr::new(self, expr_id.into())
+            .flat_map(SomeId::expr)
+            .filter_map(|expr_id| match self[expr_id].kind {
+                CExprKind::GenericSelection(_, _, _, selected) => Some(selected),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Predicate for struct, union, and enum declarations without
     /// bodies. These forward declarations are suitable for use as
     /// the targets of pointers
@@ -130,6 +191,15 @@ impl TypedAstContext {
         }
     }
 
+    /// Predicate for block pointers
+    pub fn is_block_pointer(&self, typ: CTypeId) -> bool {
+        if let CTypeKind::BlockPointer(_) = self.resolve_type(typ).kind {
+            true
+        } else {
+            false
+        }
+    }
+
     /// Predicate for function pointers
     pub fn is_function_pointer(&self, typ: CTypeId) -> bool {
         let resolved_ctype = self.resolve_type(typ);
@@ -236,7 +306,8 @@ impl TypedAstContext {
             CExprKind::Literal(_, _) |
             CExprKind::DeclRef(_, _, _) |
             CExprKind::UnaryType(_, _, _, _) |
-            CExprKind::OffsetOf(..) => true,
+            CExprKind::OffsetOf(..) |
+            CExprKind::Block(..) => true,
 
             CExprKind::DesignatedInitExpr(_,_,e) |
             CExprKind::ImplicitCast(_, e, _, _, _) |
@@ -253,6 +324,9 @@ impl TypedAstContext {
             CExprKind::Conditional(_, c, lhs, rhs) => self.is_expr_pure(c) && self.is_expr_pure(lhs) && self.is_expr_pure(rhs),
             CExprKind::BinaryConditional(_, c, rhs) => self.is_expr_pure(c) && self.is_expr_pure(rhs),
             CExprKind::Choose(_, c, lhs, rhs, _) => self.is_expr_pure(c) && self.is_expr_pure(lhs) && self.is_expr_pure(rhs),
+            CExprKind::GenericSelection(_, _, ref associations, selected) => {
+                self.is_expr_pure(associations[selected].expr)
+            }
         }
     }
 
@@ -280,17 +354,9 @@ impl TypedAstContext {
     }
 
     pub fn prune_unused_decls(&mut self) {
-        use self::iterators::{DFNodes, SomeId};
-        // Starting from a set of root declarations, walk each one to find declarations it
-        // depends on. Then walk each of those, recursively.
-
-        // Declarations we still need to walk.  Everything in here is also in `used`.
-        let mut to_walk: Vec<CDeclId> = Vec::new();
-        // Declarations accessible from a root.
-        let mut used: HashSet<CDeclId> = HashSet::new();
-
-        // Mark all the roots as used.  Roots are all top-level functions and variables that might
-        // be visible from another compilation unit.
+        // Roots are all top-level functions and variables that might be visible from another
+        // compilation unit.
+        let mut roots = vec![];
         for &decl_id in &self.c_decls_top {
             let decl = self.index(decl_id);
             match decl.kind {
@@ -299,28 +365,35 @@ impl TypedAstContext {
                     is_global: true,
                     is_inline: false,
                     ..
-                } => {
-                    to_walk.push(decl_id);
-                    used.insert(decl_id);
-                }
+                } => roots.push(decl_id),
                 CDeclKind::Variable {
                     is_defn: true,
                     is_externally_visible: true,
                     ..
-                } => {
-                    to_walk.push(decl_id);
-                    used.insert(decl_id);
-                }
+                } => roots.push(decl_id),
                 CDeclKind::Variable { ref attrs, .. } | CDeclKind::Function { ref attrs, .. }
                     if attrs.contains(&Attribute::Used) =>
                 {
-                    to_walk.push(decl_id);
-                    used.insert(decl_id);
+                    roots.push(decl_id)
                 }
                 _ => {}
             }
         }
 
+        self.prune_unreachable_decls(roots);
+    }
+
+    /// Prune the declarations that none of the `roots` depend on
+    pub fn prune_unreachable_decls(&mut self, roots: Vec<CDeclId>) {
+        use self::iterators::{DFNodes, SomeId};
+        // Starting from a set of root declarations, walk each one to find declarations it
+        // depends on. Then walk each of those, recursively.
+
+        // Declarations accessible from a root.
+        let mut used: HashSet<CDeclId> = roots.iter().cloned().collect();
+        // Declarations we still need to walk.  Everything in here is also in `used`.
+        let mut to_walk: Vec<CDeclId> = roots;
+
         // Add all referenced macros to the set of used decls
         // used.extend(self.macro_expansions.values().flatten());
 
@@ -426,130 +499,334 @@ impl TypedAstContext {
     }
 }
 
+/// Position of a node or comment inside of a file, as a line and column
+type FilePos = (u64, u64);
+
+fn file_pos(loc: &SrcLoc) -> FilePos {
+    (loc.line, loc.column)
+}
+
+/// Given nodes sorted by their start, maintain the stack of those whose range encloses `pos` and
+/// return the end and the ID of the innermost one. The positions passed in across calls must not
+/// decrease.
+fn enclosing_node<T: Copy>(
+    stack: &mut Vec<(FilePos, T)>,
+    nodes: &[(FilePos, FilePos, T)],
+    next: &mut usize,
+    pos: FilePos,
+) -> Option<(FilePos, T)> {
+    while let Some(&(begin, end, id)) = nodes.get(*next) {
+        if begin > pos {
+            break;
+        }
+        while stack.last().map_or(false, |&(top_end, _)| top_end < begin) {
+            stack.pop();
+        }
+        stack.push((end, id));
+        *next += 1;
+    }
+    while stack.last().map_or(false, |&(end, _)| end < pos) {
+        stack.pop();
+    }
+    stack.last().cloned()
+}
+
+/// Node a comment gets attached to
+enum CommentTarget {
+    Decl(CDeclId),
+    Stmt(CStmtId),
+    Expr(CExprId),
+    BlockEnd(CStmtId),
+    Nothing,
+}
+
 impl CommentContext {
     pub fn empty() -> CommentContext {
         CommentContext {
             decl_comments: HashMap::new(),
             stmt_comments: HashMap::new(),
+            expr_comments: HashMap::new(),
+            block_end_comments: HashMap::new(),
+            unmatched_comments: vec![],
         }
     }
 
     // Try to match up every comment with a declaration or a statement
     pub fn new(ast_context: &mut TypedAstContext) -> CommentContext {
-        // Group and sort declarations by file and by position
-        let mut decls: HashMap<u64, Vec<(SrcLoc, CDeclId)>> = HashMap::new();
+        let comments = mem::replace(&mut ast_context.comments, vec![]);
+        let ast_context = &*ast_context;
+
+        // Group declarations by file
+        let mut decls: HashMap<u64, Vec<(FilePos, FilePos, CDeclId)>> = HashMap::new();
         for (decl_id, ref loc_decl) in &ast_context.c_decls {
-            if let Some(ref loc) = loc_decl.loc {
-                decls
-                    .entry(loc.fileid)
-                    .or_insert(vec![])
-                    .push((loc.clone(), *decl_id));
+            if let (&Some(ref loc), &Some(ref end_loc)) = (&loc_decl.loc, &loc_decl.end_loc) {
+                decls.entry(loc.fileid).or_insert(vec![]).push((
+                    file_pos(loc),
+                    file_pos(end_loc),
+                    *decl_id,
+                ));
             }
         }
-        decls.iter_mut().for_each(|(_, v)| v.sort());
 
-        // Group and sort statements by file and by position
-        let mut stmts: HashMap<u64, Vec<(SrcLoc, CStmtId)>> = HashMap::new();
+        // Group compound statements by file
+        let mut blocks: HashMap<u64, Vec<(FilePos, FilePos, CStmtId)>> = HashMap::new();
         for (stmt_id, ref loc_stmt) in &ast_context.c_stmts {
-            if let Some(ref loc) = loc_stmt.loc {
-                stmts
-                    .entry(loc.fileid)
-                    .or_insert(vec![])
-                    .push((loc.clone(), *stmt_id));
+            if let CStmtKind::Compound(_) = loc_stmt.kind {
+                if let (&Some(ref loc), &Some(ref end_loc)) = (&loc_stmt.loc, &loc_stmt.end_loc) {
+                    blocks.entry(loc.fileid).or_insert(vec![]).push((
+                        file_pos(loc),
+                        file_pos(end_loc),
+                        *stmt_id,
+                    ));
+                }
             }
         }
-        stmts.iter_mut().for_each(|(_, v)| v.sort());
-
-        let mut decl_comments_map: HashMap<CDeclId, BTreeMap<SrcLoc, String>> = HashMap::new();
-        let mut stmt_comments_map: HashMap<CStmtId, BTreeMap<SrcLoc, String>> = HashMap::new();
-
-        let empty_vec1 = &vec![];
-        let empty_vec2 = &vec![];
-
-        // Match comments to declarations and statements
-        while let Some(Located { loc, kind: str }) = ast_context.comments.pop() {
-            if let Some(loc) = loc {
-                let this_file_decls = decls.get(&loc.fileid).unwrap_or(empty_vec1);
-                let this_file_stmts = stmts.get(&loc.fileid).unwrap_or(empty_vec2);
-
-                // Find the closest declaration and statement
-                let decl_ix = this_file_decls
-                    .binary_search_by_key(&loc.line, |&(ref l, _)| l.line)
-                    .unwrap_or_else(|x| x);
-                let stmt_ix = this_file_stmts
-                    .binary_search_by_key(&loc.line, |&(ref l, _)| l.line)
-                    .unwrap_or_else(|x| x);
-
-                // Prefer the one that is higher up (biasing towards declarations if there is a tie)
-                match (this_file_decls.get(decl_ix), this_file_stmts.get(stmt_ix)) {
-                    (Some(&(ref l1, d)), Some(&(ref l2, s))) => {
-                        if l1 > l2 {
-                            stmt_comments_map
-                                .entry(s)
-                                .or_insert(BTreeMap::new())
-                                .insert(loc, str);
-                        } else {
-                            decl_comments_map
-                                .entry(d)
-                                .or_insert(BTreeMap::new())
-                                .insert(loc, str);
-                        }
-                    }
-                    (Some(&(_, d)), None) => {
-                        decl_comments_map
-                            .entry(d)
-                            .or_insert(BTreeMap::new())
-                            .insert(loc, str);
-                    }
-                    (None, Some(&(_, s))) => {
-                        stmt_comments_map
-                            .entry(s)
-                            .or_insert(BTreeMap::new())
-                            .insert(loc, str);
-                    }
-                    (None, None) => {
-                        diag!(
-                            Diagnostic::Comments,
-                            "Didn't find a target node for the comment '{}'",
-                            str
-                        );
+
+        // Group comments by file
+        let mut context = CommentContext::empty();
+        let mut file_comments: HashMap<u64, Vec<Located<String>>> = HashMap::new();
+        for comment in comments {
+            match comment.loc.as_ref().map(|loc| loc.fileid) {
+                Some(fileid) => file_comments.entry(fileid).or_insert(vec![]).push(comment),
+                None => context.unmatched_comments.push(comment),
+            }
+        }
+
+        for (fileid, comments) in file_comments {
+            let mut decls = decls.remove(&fileid).unwrap_or(vec![]);
+            let mut blocks = blocks.remove(&fileid).unwrap_or(vec![]);
+            decls.sort();
+            blocks.sort();
+            context.match_comments(ast_context, comments, &decls, &blocks);
+        }
+
+        context
+    }
+
+    /// Match the comments of a file to the declarations and compound statements of that file
+    /// (both sorted by position)
+    fn match_comments(
+        &mut self,
+        ast_context: &TypedAstContext,
+        mut comments: Vec<Located<String>>,
+        decls: &[(FilePos, FilePos, CDeclId)],
+        blocks: &[(FilePos, FilePos, CStmtId)],
+    ) {
+        comments.sort_by_key(|comment| file_pos(comment.loc.as_ref().unwrap()));
+
+        let mut decl_ends: Vec<(FilePos, CDeclId)> = decls
+            .iter()
+            .map(|&(_, end, decl_id)| (end, decl_id))
+            .collect();
+        decl_ends.sort();
+
+        let mut open_decls = vec![];
+        let mut next_decl = 0;
+        let mut open_blocks = vec![];
+        let mut next_block = 0;
+
+        for comment in comments {
+            let fileid = comment.loc.as_ref().unwrap().fileid;
+            let pos = file_pos(comment.loc.as_ref().unwrap());
+            let block = enclosing_node(&mut open_blocks, blocks, &mut next_block, pos);
+            let decl = enclosing_node(&mut open_decls, decls, &mut next_decl, pos);
+
+            let target = match block {
+                // Inside of a function body, comments go with statements
+                Some((_, block_id)) => {
+                    Self::block_target(ast_context, block_id, fileid, pos, &comment.kind)
+                }
+
+                None => {
+                    // A comment after a declaration, on the same line, is about that declaration
+                    let ix = match decl_ends.binary_search_by_key(&pos, |&(end, _)| end) {
+                        Ok(ix) | Err(ix) => ix,
+                    };
+                    let trailing = ix
+                        .checked_sub(1)
+                        .map(|ix| decl_ends[ix])
+                        .filter(|&(end, _)| end.0 == pos.0);
+
+                    // Otherwise, it is about the next declaration, unless that is outside of the
+                    // declaration enclosing the comment
+                    let next = decls
+                        .get(next_decl)
+                        .filter(|&&(begin, _, _)| decl.map_or(true, |(end, _)| begin <= end))
+                        .map(|&(_, _, decl_id)| decl_id);
+
+                    match (trailing, next, decl) {
+                        (Some((_, decl_id)), _, _) => CommentTarget::Decl(decl_id),
+                        (None, Some(decl_id), _) => CommentTarget::Decl(decl_id),
+                        (None, None, Some((_, decl_id))) => CommentTarget::Decl(decl_id),
+                        (None, None, None) => CommentTarget::Nothing,
                     }
-                };
+                }
+            };
+
+            match target {
+                CommentTarget::Decl(decl_id) => {
+                    let decl_id = match ast_context[decl_id].kind {
+                        CDeclKind::NonCanonicalDecl { canonical_decl } => canonical_decl,
+                        _ => decl_id,
+                    };
+                    self.decl_comments
+                        .entry(decl_id)
+                        .or_insert(vec![])
+                        .push(comment)
+                }
+                CommentTarget::Stmt(stmt_id) => self
+                    .stmt_comments
+                    .entry(stmt_id)
+                    .or_insert(vec![])
+                    .push(comment),
+                CommentTarget::Expr(expr_id) => self
+                    .expr_comments
+                    .entry(expr_id)
+                    .or_insert(vec![])
+                    .push(comment),
+                CommentTarget::BlockEnd(stmt_id) => self
+                    .block_end_comments
+                    .entry(stmt_id)
+                    .or_insert(vec![])
+                    .push(comment),
+                CommentTarget::Nothing => self.unmatched_comments.push(comment),
             }
         }
+    }
 
-        // Flatten out the nested comment maps
-        let decl_comments = decl_comments_map
-            .into_iter()
-            .map(|(decl_id, map)| (decl_id, map.into_iter().map(|(_, v)| v).collect()))
-            .map(|(decl_id, comments)| {
-                let decl_id = if let CDeclKind::NonCanonicalDecl { canonical_decl } = ast_context[decl_id].kind {
-                    canonical_decl
-                } else {
-                    decl_id
-                };
-                (decl_id, comments)
+    /// Find the statement of a compound statement a comment inside it belongs to
+    fn block_target(
+        ast_context: &TypedAstContext,
+        block_id: CStmtId,
+        fileid: u64,
+        pos: FilePos,
+        comment: &str,
+    ) -> CommentTarget {
+        let stmts = match ast_context[block_id].kind {
+            CStmtKind::Compound(ref stmts) => stmts,
+            _ => return CommentTarget::Nothing,
+        };
+        let stmts: Vec<(FilePos, FilePos, CStmtId)> = stmts
+            .iter()
+            .filter_map(|&stmt_id| match &ast_context[stmt_id] {
+                &Located {
+                    loc: Some(ref loc),
+                    end_loc: Some(ref end_loc),
+                    ..
+                } => Some((file_pos(loc), file_pos(end_loc), stmt_id)),
+                _ => None,
             })
             .collect();
-        let stmt_comments = stmt_comments_map
-            .into_iter()
-            .map(|(decl_id, map)| (decl_id, map.into_iter().map(|(_, v)| v).collect()))
-            .collect();
 
-        CommentContext {
-            decl_comments,
-            stmt_comments,
+        // A comment inside of a statement goes with the expression after it, if there is one.
+        // Otherwise, it goes with the statement, as does a comment after it on the same line.
+        if let Some(&(_, end, stmt_id)) = stmts.iter().rev().find(|&&(begin, _, _)| begin < pos) {
+            if end > pos {
+                if let Some(expr_id) = Self::next_expr(ast_context, stmt_id, fileid, pos) {
+                    return CommentTarget::Expr(expr_id);
+                }
+                diag!(
+                    Diagnostic::Comments,
+                    "Moving comment '{}' in front of the statement containing it",
+                    comment
+                );
+                return CommentTarget::Stmt(stmt_id);
+            }
+            if end.0 == pos.0 {
+                return CommentTarget::Stmt(stmt_id);
+            }
+        }
+
+        // Otherwise, it goes with the next statement or at the end of the block
+        match stmts.iter().find(|&&(begin, _, _)| begin > pos) {
+            Some(&(_, _, stmt_id)) => CommentTarget::Stmt(stmt_id),
+            None => CommentTarget::BlockEnd(block_id),
+        }
+    }
+
+    /// Find the outermost expression of a statement that starts first after `pos`
+    fn next_expr(
+        ast_context: &TypedAstContext,
+        stmt_id: CStmtId,
+        fileid: u64,
+        pos: FilePos,
+    ) -> Option<CExprId> {
+        use self::iterators::{DFExpr, SomeId};
+
+        let mut next: Option<(FilePos, FilePos, CExprId)> = None;
+        for node in DFExpr::new(ast_context, SomeId::Stmt(stmt_id)) {
+            let expr_id = match node {
+                SomeId::Expr(expr_id) => expr_id,
+                _ => continue,
+            };
+            let (begin, end) = match &ast_context[expr_id] {
+                &Located {
+                    loc: Some(ref loc),
+                    end_loc: Some(ref end_loc),
+                    ..
+                } if loc.fileid == fileid => (file_pos(loc), file_pos(end_loc)),
+                _ => continue,
+            };
+            // Parents are visited before their children, so only a strictly larger range can
+            // replace an expression starting at the same position
+            let is_better = begin > pos
+                && next.map_or(true, |(next_begin, next_end, _)| {
+                    begin < next_begin || begin == next_begin && end > next_end
+                });
+            if is_better {
+                next = Some((begin, end, expr_id));
+            }
         }
+        next.map(|(_, _, expr_id)| expr_id)
     }
 
     // Extract the comment for a given declaration
     pub fn remove_decl_comment(&mut self, decl_id: CDeclId) -> Vec<String> {
-        self.decl_comments.remove(&decl_id).unwrap_or(vec![])
+        Self::comment_texts(self.decl_comments.remove(&decl_id))
     }
 
     // Extract the comment for a given statement
     pub fn remove_stmt_comment(&mut self, stmt_id: CStmtId) -> Vec<String> {
-        self.stmt_comments.remove(&stmt_id).unwrap_or(vec![])
+        Self::comment_texts(self.stmt_comments.remove(&stmt_id))
+    }
+
+    // Extract the comment in front of a given expression
+    pub fn remove_expr_comment(&mut self, expr_id: CExprId) -> Vec<String> {
+        Self::comment_texts(self.expr_comments.remove(&expr_id))
+    }
+
+    // Extract the comment after the last statement of a given compound statement
+    pub fn remove_block_end_comment(&mut self, stmt_id: CStmtId) -> Vec<String> {
+        Self::comment_texts(self.block_end_comments.remove(&stmt_id))
+    }
+
+    fn comment_texts(comments: Option<Vec<Located<String>>>) -> Vec<String> {
+        comments
+            .unwrap_or(vec![])
+            .into_iter()
+            .map(|comment| comment.kind)
+            .collect()
+    }
+
+    /// Comments that were not matched with any node, or whose node did not extract them. Comments
+    /// of declarations are only included if `include_decl` holds for the declaration, since most
+    /// declarations that were not translated are just unused.
+    pub fn unplaced_comments<F>(&self, include_decl: F) -> Vec<&Located<String>>
+    where
+        F: Fn(CDeclId) -> bool,
+    {
+        let mut comments: Vec<&Located<String>> = self
+            .decl_comments
+            .iter()
+            .filter(|&(&decl_id, _)| include_decl(decl_id))
+            .flat_map(|(_, comments)| comments)
+            .chain(self.stmt_comments.values().flatten())
+            .chain(self.expr_comments.values().flatten())
+            .chain(self.block_end_comments.values().flatten())
+            .chain(&self.unmatched_comments)
+            .collect();
+        comments.sort_by(|c1, c2| c1.loc.cmp(&c2.loc));
+        comments
     }
 }
 
@@ -569,6 +846,7 @@ impl Index<CExprId> for TypedAstContext {
     fn index(&self, index: CExprId) -> &CExpr {
         static BADEXPR: CExpr = Located {
             loc: None,
+            end_loc: None,
             kind: CExprKind::BadExpr,
         };
         match self.c_exprs.get(&index) {
@@ -620,6 +898,8 @@ pub struct SrcLoc {
 #[derive(Debug, Clone)]
 pub struct Located<T> {
     pub loc: Option<SrcLoc>,
+    /// Position of the last token of the node
+    pub end_loc: Option<SrcLoc>,
     pub kind: T,
 }
 
@@ -666,6 +946,8 @@ pub enum CDeclKind {
     EnumConstant {
         name: String,
         value: ConstIntExpr,
+        // the value was computed using `sizeof`, `alignof` or `offsetof`
+        is_layout_dependent: bool,
     },
 
     // Typedef
@@ -833,6 +1115,13 @@ pub enum CExprKind {
     // GNU choose expr. Condition, true expr, false expr, was condition true?
     Choose(CQualTypeId, CExprId, CExprId, CExprId, bool),
 
+    // Apple block literal. Parameters, body, captured variables
+    Block(CQualTypeId, Vec<CDeclId>, CStmtId, Vec<BlockCapture>),
+
+    // C11 generic selection - controlling expression, associations and the index of the
+    // selected one. Only the expression of the selected association is evaluated.
+    GenericSelection(CQualTypeId, CExprId, Vec<GenericAssociation>, usize),
+
     BadExpr,
 }
 
@@ -882,6 +1171,8 @@ impl CExprKind {
             | CExprKind::ConvertVector(ty, _)
             | CExprKind::DesignatedInitExpr(ty, _, _) => Some(ty),
             | CExprKind::Choose(ty, _, _, _, _) => Some(ty),
+            | CExprKind::Block(ty, _, _, _) => Some(ty),
+            | CExprKind::GenericSelection(ty, _, _, _) => Some(ty),
         }
     }
 
@@ -1145,6 +1436,12 @@ pub enum CStmtKind {
         clobbers: Vec<String>,
         is_volatile: bool,
     },
+
+    // OpenMP `parallel for` directive and the loop it applies to
+    OmpParallelFor {
+        for_stmt: CStmtId,
+        clauses: Vec<OmpClause>,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -1153,6 +1450,33 @@ pub struct AsmOperand {
     pub expression: CExprId,
 }
 
+/// A clause of an OpenMP directive
+#[derive(Clone, Debug)]
+pub enum OmpClause {
+    /// `reduction(op: vars)` with the reduction identifier, e.g. `+` or `max`
+    Reduction(String, Vec<CDeclId>),
+    /// `private(vars)`
+    Private(Vec<CDeclId>),
+    /// Any other clause, or a clause on something other than plain variables, by its name
+    Other(String),
+}
+
+/// An association of a `_Generic` selection: the expression it selects for a type, or for
+/// any other type if it is the `default` association
+#[derive(Copy, Clone, Debug)]
+pub struct GenericAssociation {
+    pub ctype: Option<CQualTypeId>,
+    pub expr: CExprId,
+}
+
+/// A variable captured by a block literal. Variables declared `__block` are
+/// captured by reference, all others are copied into the block.
+#[derive(Copy, Clone, Debug)]
+pub struct BlockCapture {
+    pub variable: CDeclId,
+    pub by_ref: bool,
+}
+
 /// Type qualifiers (6.7.3)
 #[derive(Debug, Copy, Clone, Default, PartialEq)]
 pub struct Qualifiers {
@@ -1317,6 +1641,8 @@ pub enum Attribute {
     Alias(String),
     /// __attribute__((always_inline, __always_inline__))
     AlwaysInline,
+    /// __block (__attribute__((__blocks__(byref))))
+    Blocks,
     /// __attribute__((cold, __cold__))
     Cold,
     /// __attribute__((gnu_inline, __gnu_inline__))
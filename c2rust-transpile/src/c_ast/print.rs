@@ -226,6 +226,33 @@ impl<W: Write> Printer<W> {
                 self.writer.write_all(b")")
             }
 
+            Some(&CExprKind::GenericSelection(_, controlling, ref associations, _)) => {
+                self.writer.write_all(b"_Generic(")?;
+                self.print_expr(controlling, context)?;
+                for association in associations {
+                    self.writer.write_all(b", ")?;
+                    match association.ctype {
+                        Some(ctype) => self.print_qtype(ctype, None, context)?,
+                        None => self.writer.write_all(b"default")?,
+                    }
+                    self.writer.write_all(b": ")?;
+                    self.print_expr(association.expr, context)?;
+                }
+                self.writer.write_all(b")")
+            }
+
+            Some(&CExprKind::Block(_, ref params, body, _)) => {
+                self.writer.write_all(b"^(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i != 0 {
+                        self.writer.write_all(b", ")?;
+                    }
+                    self.print_decl_name(*param, context)?;
+                }
+                self.writer.write_all(b") ")?;
+                self.print_stmt(body, false, false, context)
+            }
+
             None => panic!("Could not find expression with ID {:?}", expr_id),
             // _ => unimplemented!("Printer::print_expr"),
         }
@@ -489,6 +516,11 @@ impl<W: Write> Printer<W> {
                 Ok(())
             }
 
+            Some(&CStmtKind::OmpParallelFor { for_stmt, .. }) => {
+                self.writer.write_all(b"#pragma omp parallel for\n")?;
+                self.print_stmt(for_stmt, newline, false, context)
+            }
+
             None => panic!("Could not find statement with ID {:?}", stmt_id),
 
             _ => unimplemented!("Printer::print_stmt"),
@@ -839,3 +871,63 @@ impl<W: Write> Printer<W> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unlocated<T>(kind: T) -> Located<T> {
+        Located {
+            loc: None,
+            end_loc: None,
+            kind,
+        }
+    }
+
+    fn int_literal(ast_context: &mut TypedAstContext, id: u64, value: u64) -> CExprId {
+        let int = CQualTypeId::new(CTypeId(1));
+        let literal = CExprKind::Literal(int, CLiteral::Integer(value, IntBase::Dec));
+        ast_context.c_exprs.insert(CExprId(id), unlocated(literal));
+        CExprId(id)
+    }
+
+    #[test]
+    fn generic_selection_associations() {
+        let mut ast_context = TypedAstContext::new();
+        let int = CQualTypeId::new(CTypeId(1));
+        let double = CQualTypeId::new(CTypeId(2));
+        ast_context
+            .c_types
+            .insert(CTypeId(1), unlocated(CTypeKind::Int));
+        ast_context
+            .c_types
+            .insert(CTypeId(2), unlocated(CTypeKind::Double));
+
+        let controlling = int_literal(&mut ast_context, 10, 0);
+        let associations = vec![
+            GenericAssociation {
+                ctype: Some(int),
+                expr: int_literal(&mut ast_context, 11, 1),
+            },
+            GenericAssociation {
+                ctype: Some(double),
+                expr: int_literal(&mut ast_context, 12, 2),
+            },
+            GenericAssociation {
+                ctype: None,
+                expr: int_literal(&mut ast_context, 13, 3),
+            },
+        ];
+        let selection = CExprKind::GenericSelection(int, controlling, associations, 0);
+        ast_context
+            .c_exprs
+            .insert(CExprId(20), unlocated(selection));
+
+        let mut printer = Printer::new(vec![]);
+        printer.print_expr(CExprId(20), &ast_context).unwrap();
+        assert_eq!(
+            String::from_utf8(printer.writer).unwrap(),
+            "_Generic(0, int: 1, double: 2, default: 3)"
+        );
+    }
+}
@@ -0,0 +1,709 @@
+//! This module handles converting a control-flow graph `Cfg` directly into structured Rust
+//! (`loop`s, labelled blocks, `break`s and `continue`s) without any `current_block` variable.
+//!
+//! This is the dominator tree based algorithm from Norman Ramsey's "Beyond Relooper" (ICFP 2022):
+//!
+//!   * every block that is the target of a back edge becomes a labelled `loop`, and jumping back
+//!     to it becomes a `continue` to that label
+//!   * every block with more than one forward predecessor (and every block reached by leaving a
+//!     loop) is placed right after a labelled block, and jumping to it becomes a `break` out of
+//!     that labelled block
+//!   * every other block is placed inline at the only place that jumps to it
+//!
+//! This works for any reducible CFG. Irreducible CFGs are first made reducible by duplicating
+//! blocks, as long as the duplicated code stays within a budget. If that fails, we give up and
+//! let the caller fall back on Relooper.
+
+use super::structures::{ExitStyle, StructuredAST, StructuredStatement};
+use super::*;
+
+type Ast<S> = StructuredAST<P<Expr>, P<Pat>, Label, S>;
+
+/// Maximum number of statements (counting terminators) node splitting is allowed to duplicate
+/// while making a CFG reducible
+const SPLIT_BUDGET: usize = 64;
+
+/// Convert the CFG into loops and labelled blocks. Returns the declarations that had to be lifted
+/// to the top of the output and the structured body, or `None` if the CFG is irreducible and can't
+/// be made reducible within the node splitting budget (in which case `store` is left untouched).
+pub fn eliminate_gotos(
+    cfg: &Cfg<Label, StmtOrDecl>, // the control flow graph to structure
+    store: &mut DeclStmtStore,    // store of what to do with declarations
+    live_in: IndexSet<CDeclId>,   // declarations we assume are live going into this graph
+) -> Option<(Vec<Stmt>, Ast<StmtOrComment>)> {
+    let mut blocks = cfg.nodes.clone();
+    let graph = split_nodes(cfg.entries, &mut blocks)?;
+
+    let mut state = GotoEliminationState::new(graph, blocks, live_in);
+    let ast = state.tree(0);
+
+    // These are declarations we need to lift
+    let lift_me = state.lifted;
+
+    // These are the statements that emerge from these lifts
+    let lifted_stmts: Vec<Stmt> = lift_me
+        .iter()
+        .flat_map(|&decl: &CDeclId| store.extract_decl(decl).unwrap())
+        .collect();
+
+    Some((lifted_stmts, place_decls(ast, &lift_me, store)))
+}
+
+/// Checks if there are any labelled blocks anywhere. Only if so will the output need the
+/// `label_break_value` feature.
+pub fn has_block<S>(ast: &Ast<S>) -> bool {
+    match ast {
+        StructuredAST::Block(..) => true,
+        StructuredAST::Append(first, rest) => has_block(first) || has_block(rest),
+        StructuredAST::Match(_, cases) => cases.iter().any(|(_, body)| has_block(body)),
+        StructuredAST::If(_, then, els) => has_block(then) || has_block(els),
+        StructuredAST::GotoTable(cases, then) => {
+            cases.iter().any(|(_, body)| has_block(body)) || has_block(then)
+        }
+        StructuredAST::Loop(_, body) => has_block(body),
+        StructuredAST::Empty
+        | StructuredAST::Singleton(_)
+        | StructuredAST::Goto(_)
+        | StructuredAST::Exit(..) => false,
+    }
+}
+
+/// The reachable part of a CFG, with blocks numbered in reverse postorder. This means that the
+/// entry is `0` and that an edge goes backwards in the numbering exactly when it is a retreating
+/// edge.
+struct Graph {
+    /// Labels of the reachable blocks, in reverse postorder
+    labels: Vec<Label>,
+
+    /// Successors of every block (with one entry per edge)
+    succs: Vec<Vec<usize>>,
+
+    /// Predecessors of every block (with one entry per edge)
+    preds: Vec<Vec<usize>>,
+
+    /// Immediate dominator of every block (the entry is its own immediate dominator)
+    idom: Vec<usize>,
+}
+
+impl Graph {
+    fn new(entry: Label, blocks: &IndexMap<Label, BasicBlock<Label, StmtOrDecl>>) -> Graph {
+        // Reverse postorder, using an explicit stack of blocks and how many of their successors
+        // have already been visited
+        let mut visited: IndexSet<Label> = IndexSet::new();
+        let mut postorder: Vec<Label> = vec![];
+        let mut stack: Vec<(Label, usize)> = vec![(entry, 0)];
+        visited.insert(entry);
+        while let Some((lbl, next)) = stack.pop() {
+            match blocks[&lbl].terminator.get_labels().get(next) {
+                Some(&&succ) => {
+                    stack.push((lbl, next + 1));
+                    if visited.insert(succ) {
+                        stack.push((succ, 0));
+                    }
+                }
+                None => postorder.push(lbl),
+            }
+        }
+        let labels: Vec<Label> = postorder.into_iter().rev().collect();
+        let index: IndexMap<Label, usize> = labels
+            .iter()
+            .enumerate()
+            .map(|(i, &lbl)| (lbl, i))
+            .collect();
+
+        let succs: Vec<Vec<usize>> = labels
+            .iter()
+            .map(|lbl| {
+                blocks[lbl]
+                    .terminator
+                    .get_labels()
+                    .into_iter()
+                    .map(|succ| index[succ])
+                    .collect()
+            })
+            .collect();
+        let mut preds: Vec<Vec<usize>> = vec![vec![]; labels.len()];
+        for (from, tos) in succs.iter().enumerate() {
+            for &to in tos {
+                preds[to].push(from);
+            }
+        }
+
+        // Immediate dominators, following "A Simple, Fast Dominance Algorithm" (Cooper, Harvey,
+        // and Kennedy). `None` means not yet computed.
+        let mut idom: Vec<Option<usize>> = vec![None; labels.len()];
+        idom[0] = Some(0);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in 1..labels.len() {
+                let mut new_idom: Option<usize> = None;
+                for &p in &preds[b] {
+                    if idom[p].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => p,
+                        Some(mut other) => {
+                            let mut p = p;
+                            while p != other {
+                                while p > other {
+                                    p = idom[p].unwrap();
+                                }
+                                while other > p {
+                                    other = idom[other].unwrap();
+                                }
+                            }
+                            p
+                        }
+                    });
+                }
+                if idom[b] != new_idom {
+                    idom[b] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        Graph {
+            labels,
+            succs,
+            preds,
+            idom: idom.into_iter().map(Option::unwrap).collect(),
+        }
+    }
+
+    /// Does block `a` dominate block `b`?
+    fn dominates(&self, a: usize, mut b: usize) -> bool {
+        loop {
+            if a == b {
+                return true;
+            }
+            if b == 0 {
+                return false;
+            }
+            b = self.idom[b];
+        }
+    }
+
+    /// Find a retreating edge whose target does not dominate its source. There are none exactly
+    /// when the CFG is reducible.
+    fn irreducible_edge(&self) -> Option<(usize, usize)> {
+        self.succs.iter().enumerate().find_map(|(from, tos)| {
+            tos.iter()
+                .find(|&&to| to <= from && !self.dominates(to, from))
+                .map(|&to| (from, to))
+        })
+    }
+
+    /// Blocks of the natural loop with header `header`: the header and every block that can reach
+    /// a back edge into the header without going through the header
+    fn natural_loop(&self, header: usize) -> IndexSet<usize> {
+        let mut body: IndexSet<usize> = IndexSet::new();
+        body.insert(header);
+        let mut to_visit: Vec<usize> = self.preds[header]
+            .iter()
+            .cloned()
+            .filter(|&p| p >= header)
+            .collect();
+        while let Some(b) = to_visit.pop() {
+            if body.insert(b) {
+                to_visit.extend(self.preds[b].iter().cloned());
+            }
+        }
+        body
+    }
+}
+
+/// Make the CFG reducible by duplicating blocks that are entered from more than one place into a
+/// loop. Blocks containing declarations are never duplicated, since a declaration can only be
+/// extracted from the `DeclStmtStore` once.
+fn split_nodes(
+    entry: Label,
+    blocks: &mut IndexMap<Label, BasicBlock<Label, StmtOrDecl>>,
+) -> Option<Graph> {
+    let mut budget = SPLIT_BUDGET;
+
+    // Copies get synthetic labels not used anywhere else in the CFG
+    let mut next_id: u64 = blocks
+        .iter()
+        .flat_map(|(lbl, bb)| {
+            let mut lbls = bb.terminator.get_labels();
+            lbls.push(lbl);
+            lbls
+        })
+        .filter_map(|lbl| match lbl {
+            &Label::Synthetic(id) => Some(id + 1),
+            &Label::FromC(_) => None,
+        })
+        .max()
+        .unwrap_or(0);
+
+    loop {
+        let graph = Graph::new(entry, blocks);
+        let (from, to) = match graph.irreducible_edge() {
+            None => return Some(graph),
+            Some((from, to)) => (graph.labels[from], graph.labels[to]),
+        };
+
+        let copy = blocks[&to].clone();
+        let has_decls = !copy.defined.is_empty()
+            || copy.body.iter().any(|s| match s {
+                StmtOrDecl::Decl(_) => true,
+                _ => false,
+            });
+        let cost = copy.body.len() + 1;
+        if has_decls || cost > budget {
+            return None;
+        }
+        budget -= cost;
+
+        // Redirect the offending edge to the copy
+        let copy_lbl = Label::Synthetic(next_id);
+        next_id += 1;
+        for lbl in blocks[&from].terminator.get_labels_mut() {
+            if *lbl == to {
+                *lbl = copy_lbl;
+            }
+        }
+        blocks.insert(copy_lbl, copy);
+    }
+}
+
+/// This is the state we close over while structuring. Like `RelooperState`, it accumulates
+/// information about which declarations were supposed to be in scope before they were declared.
+struct GotoEliminationState {
+    graph: Graph,
+
+    /// Blocks of the CFG, whose bodies are taken out as they get placed
+    blocks: IndexMap<Label, BasicBlock<Label, StmtOrDecl>>,
+
+    /// Natural loop of every loop header
+    loops: IndexMap<usize, IndexSet<usize>>,
+
+    /// Blocks placed after a labelled block, grouped by the block whose code that labelled block
+    /// wraps (in increasing order)
+    placed_after: Vec<Vec<usize>>,
+
+    /// Is this block placed after a labelled block (as opposed to inline)?
+    is_placed_after: Vec<bool>,
+
+    /// scopes of declarations seen so far
+    scopes: Vec<IndexSet<CDeclId>>,
+
+    /// Declarations that will have to be lifted to the top of the output
+    lifted: IndexSet<CDeclId>,
+}
+
+impl GotoEliminationState {
+    fn new(
+        graph: Graph,
+        blocks: IndexMap<Label, BasicBlock<Label, StmtOrDecl>>,
+        live_in: IndexSet<CDeclId>,
+    ) -> Self {
+        let n = graph.labels.len();
+
+        let loops: IndexMap<usize, IndexSet<usize>> = (0..n)
+            .filter(|&b| graph.preds[b].iter().any(|&p| p >= b))
+            .map(|header| (header, graph.natural_loop(header)))
+            .collect();
+
+        let mut placed_after: Vec<Vec<usize>> = vec![vec![]; n];
+        let mut is_placed_after: Vec<bool> = vec![false; n];
+        for b in 1..n {
+            let idom = graph.idom[b];
+
+            // Blocks reached by leaving a loop go after that loop. If several loops are left at
+            // once, the block goes after the outermost one. Headers of enclosing loops dominate
+            // the headers of the loops they enclose, so they come first in reverse postorder.
+            let exited_loop = loops
+                .iter()
+                .find(|&(_, body)| body.contains(&idom) && !body.contains(&b))
+                .map(|(&header, _)| header);
+
+            let forward_preds = graph.preds[b].iter().filter(|&&p| p < b).count();
+            if let Some(header) = exited_loop {
+                placed_after[header].push(b);
+                is_placed_after[b] = true;
+            } else if forward_preds > 1 {
+                placed_after[idom].push(b);
+                is_placed_after[b] = true;
+            }
+        }
+
+        GotoEliminationState {
+            graph,
+            blocks,
+            loops,
+            placed_after,
+            is_placed_after,
+            scopes: vec![live_in],
+            lifted: IndexSet::new(),
+        }
+    }
+
+    fn open_scope(&mut self) {
+        self.scopes.push(IndexSet::new());
+    }
+
+    fn close_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn in_scope(&self, decl: CDeclId) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(&decl))
+    }
+
+    fn add_to_scope(&mut self, decl: CDeclId) {
+        self.scopes
+            .last_mut()
+            .expect("add_to_scope: no scopes found")
+            .insert(decl);
+    }
+
+    fn add_to_top_scope(&mut self, decl: CDeclId) {
+        self.scopes
+            .first_mut()
+            .expect("add_to_top_scope: no scopes found")
+            .insert(decl);
+    }
+
+    fn scoped<F: FnOnce(&mut Self) -> Ast<StmtOrDecl>>(&mut self, f: F) -> Ast<StmtOrDecl> {
+        self.open_scope();
+        let ast = f(self);
+        self.close_scope();
+        ast
+    }
+
+    /// Code for a block and everything it dominates
+    fn tree(&mut self, b: usize) -> Ast<StmtOrDecl> {
+        let lbl = self.graph.labels[b];
+        let mut inside: Vec<usize> = self.placed_after[b].clone();
+
+        match self.loops.get(&b).cloned() {
+            None => self.blocks_around(inside, |slf| slf.block(b)),
+            Some(loop_body) => {
+                // Blocks reached by leaving the loop go after it
+                let outside: Vec<usize> = inside
+                    .iter()
+                    .cloned()
+                    .filter(|c| !loop_body.contains(c))
+                    .collect();
+                inside.retain(|c| loop_body.contains(c));
+
+                self.blocks_around(outside, |slf| {
+                    let mut body = slf.scoped(|slf| slf.blocks_around(inside, |slf| slf.block(b)));
+                    remove_tail_exit(&mut body, ExitStyle::Continue, lbl);
+                    StructuredAST::mk_loop(Some(lbl), body)
+                })
+            }
+        }
+    }
+
+    /// Wrap code in one labelled block per block in `after` (highest in reverse postorder
+    /// outermost), each followed by the code for that block
+    fn blocks_around<F: FnOnce(&mut Self) -> Ast<StmtOrDecl>>(
+        &mut self,
+        mut after: Vec<usize>,
+        inner: F,
+    ) -> Ast<StmtOrDecl> {
+        let b = match after.pop() {
+            None => return inner(self),
+            Some(b) => b,
+        };
+        let lbl = self.graph.labels[b];
+
+        let mut body = self.scoped(|slf| slf.blocks_around(after, inner));
+        remove_tail_exit(&mut body, ExitStyle::Break, lbl);
+
+        // A labelled block around just a loop can be replaced by breaking out of the loop
+        let block = match body {
+            StructuredAST::Loop(Some(loop_lbl), mut loop_body) => {
+                rename_breaks(&mut loop_body, lbl, loop_lbl);
+                StructuredAST::Loop(Some(loop_lbl), loop_body)
+            }
+            body => StructuredAST::mk_block(lbl, body),
+        };
+
+        block.mk_append(self.tree(b))
+    }
+
+    /// Code for the body and terminator of a single block
+    fn block(&mut self, b: usize) -> Ast<StmtOrDecl> {
+        let lbl = self.graph.labels[b];
+        let bb = &mut self.blocks[&lbl];
+        let body = std::mem::replace(&mut bb.body, vec![]);
+        let terminator = std::mem::replace(&mut bb.terminator, End);
+        let live = bb.live.clone();
+        let defined = bb.defined.clone();
+
+        // Flag declarations for everything that is live going in but not already in scope.
+        for l in live {
+            if !self.in_scope(l) {
+                self.add_to_top_scope(l);
+                self.lifted.insert(l);
+            }
+        }
+
+        // Bring into scope things that are defined here
+        for d in defined {
+            self.add_to_scope(d);
+        }
+
+        let body = body.into_iter().fold(StructuredAST::empty(), |acc, s| {
+            acc.mk_append(StructuredAST::mk_singleton(s))
+        });
+
+        let terminator = match terminator {
+            End => StructuredAST::empty(),
+            Jump(to) => self.branch(b, to),
+            Branch(cond, then, els) => {
+                let then = self.scoped(|slf| slf.branch(b, then));
+                let els = self.scoped(|slf| slf.branch(b, els));
+
+                // Prefer exiting early over nesting the rest of the code in an `else`
+                match (is_exit(&then), is_exit(&els)) {
+                    (false, true) => {
+                        StructuredAST::mk_if(cond, StructuredAST::empty(), els).mk_append(then)
+                    }
+                    (true, false) => {
+                        StructuredAST::mk_if(cond, then, StructuredAST::empty()).mk_append(els)
+                    }
+                    _ => StructuredAST::mk_if(cond, then, els),
+                }
+            }
+            Switch { expr, cases } => {
+                let cases = cases
+                    .into_iter()
+                    .map(|(pats, to)| (pats, self.scoped(|slf| slf.branch(b, to))))
+                    .collect();
+                StructuredAST::mk_match(expr, cases)
+            }
+        };
+
+        body.mk_append(terminator)
+    }
+
+    /// Code for going from block `from` to block `to`
+    fn branch(&mut self, from: usize, to: Label) -> Ast<StmtOrDecl> {
+        let to_idx = self
+            .graph
+            .labels
+            .iter()
+            .position(|&lbl| lbl == to)
+            .expect("branch: target not in CFG");
+
+        if to_idx <= from {
+            StructuredAST::mk_exit(ExitStyle::Continue, Some(to))
+        } else if self.is_placed_after[to_idx] {
+            StructuredAST::mk_exit(ExitStyle::Break, Some(to))
+        } else {
+            self.tree(to_idx)
+        }
+    }
+}
+
+fn is_exit<S>(ast: &Ast<S>) -> bool {
+    match ast {
+        StructuredAST::Exit(..) => true,
+        _ => false,
+    }
+}
+
+/// Remove exits to `label` that are redundant because they are the last thing to happen anyway
+fn remove_tail_exit<S>(ast: &mut Ast<S>, exit_style: ExitStyle, label: Label) {
+    match ast {
+        StructuredAST::Exit(style, Some(lbl)) if *style == exit_style && *lbl == label => {}
+        StructuredAST::Append(first, rest) => {
+            if let StructuredAST::Empty = **rest {
+                remove_tail_exit(first, exit_style, label)
+            } else {
+                remove_tail_exit(rest, exit_style, label)
+            }
+            return;
+        }
+        StructuredAST::If(_, then, els) => {
+            remove_tail_exit(then, exit_style, label);
+            remove_tail_exit(els, exit_style, label);
+            return;
+        }
+        StructuredAST::Match(_, cases) => {
+            for (_, body) in cases {
+                remove_tail_exit(body, exit_style, label);
+            }
+            return;
+        }
+        // Falling off the end of a labelled block continues right after it
+        StructuredAST::Block(_, body) => {
+            remove_tail_exit(body, exit_style, label);
+            return;
+        }
+        _ => return,
+    }
+    *ast = StructuredAST::Empty;
+}
+
+/// Turn every `break` out of labelled block `from` into a `break` out of loop `to`
+fn rename_breaks<S>(ast: &mut Ast<S>, from: Label, to: Label) {
+    match ast {
+        StructuredAST::Exit(ExitStyle::Break, Some(lbl)) if *lbl == from => *lbl = to,
+        StructuredAST::Append(first, rest) => {
+            rename_breaks(first, from, to);
+            rename_breaks(rest, from, to);
+        }
+        StructuredAST::Match(_, cases) => {
+            for (_, body) in cases {
+                rename_breaks(body, from, to);
+            }
+        }
+        StructuredAST::If(_, then, els) => {
+            rename_breaks(then, from, to);
+            rename_breaks(els, from, to);
+        }
+        StructuredAST::GotoTable(cases, then) => {
+            for (_, body) in cases {
+                rename_breaks(body, from, to);
+            }
+            rename_breaks(then, from, to);
+        }
+        StructuredAST::Loop(_, body) | StructuredAST::Block(_, body) => {
+            rename_breaks(body, from, to)
+        }
+        StructuredAST::Empty
+        | StructuredAST::Singleton(_)
+        | StructuredAST::Goto(_)
+        | StructuredAST::Exit(..) => {}
+    }
+}
+
+/// Replace all `StmtOrDecl::Decl` with either a declaration with initializer or only an
+/// initializer.
+fn place_decls(
+    ast: Ast<StmtOrDecl>,
+    lift_me: &IndexSet<CDeclId>,
+    store: &mut DeclStmtStore,
+) -> Ast<StmtOrComment> {
+    match ast {
+        StructuredAST::Empty => StructuredAST::Empty,
+        StructuredAST::Singleton(s) => s
+            .place_decls(lift_me, store)
+            .into_iter()
+            .fold(StructuredAST::empty(), |acc, s| {
+                acc.mk_append(StructuredAST::mk_singleton(s))
+            }),
+        StructuredAST::Append(first, rest) => {
+            let first = place_decls(*first, lift_me, store);
+            let rest = place_decls(*rest, lift_me, store);
+            first.mk_append(rest)
+        }
+        StructuredAST::Goto(lbl) => StructuredAST::Goto(lbl),
+        StructuredAST::Match(cond, cases) => {
+            let cases = cases
+                .into_iter()
+                .map(|(pats, body)| (pats, place_decls(body, lift_me, store)))
+                .collect();
+            StructuredAST::Match(cond, cases)
+        }
+        StructuredAST::If(cond, then, els) => {
+            let then = place_decls(*then, lift_me, store);
+            let els = place_decls(*els, lift_me, store);
+            StructuredAST::mk_if(cond, then, els)
+        }
+        StructuredAST::GotoTable(cases, then) => {
+            let cases = cases
+                .into_iter()
+                .map(|(lbl, body)| (lbl, place_decls(body, lift_me, store)))
+                .collect();
+            let then = place_decls(*then, lift_me, store);
+            StructuredAST::mk_goto_table(cases, then)
+        }
+        StructuredAST::Loop(lbl, body) => {
+            StructuredAST::mk_loop(lbl, place_decls(*body, lift_me, store))
+        }
+        StructuredAST::Exit(exit_style, lbl) => StructuredAST::Exit(exit_style, lbl),
+        StructuredAST::Block(lbl, body) => {
+            StructuredAST::mk_block(lbl, place_decls(*body, lift_me, store))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a CFG from the successors of each synthetic label. Blocks with two successors end in
+    /// a branch on a dummy condition.
+    fn cfg(edges: &[&[u64]]) -> IndexMap<Label, BasicBlock<Label, StmtOrDecl>> {
+        edges
+            .iter()
+            .enumerate()
+            .map(|(i, succs)| {
+                let terminator = match *succs {
+                    [] => End,
+                    [to] => Jump(Label::Synthetic(*to)),
+                    [t, f] => Branch(
+                        mk().ident_expr("c"),
+                        Label::Synthetic(*t),
+                        Label::Synthetic(*f),
+                    ),
+                    _ => panic!("too many successors"),
+                };
+                (Label::Synthetic(i as u64), BasicBlock::new(terminator))
+            })
+            .collect()
+    }
+
+    fn index(graph: &Graph, lbl: u64) -> usize {
+        graph
+            .labels
+            .iter()
+            .position(|&l| l == Label::Synthetic(lbl))
+            .unwrap()
+    }
+
+    #[test]
+    fn dominators_of_a_loop() {
+        syntax::with_globals(|| {
+            // 0 -> 1, 1 -> {2, 3}, 2 -> 1
+            let blocks = cfg(&[&[1], &[2, 3], &[1], &[]]);
+            let graph = Graph::new(Label::Synthetic(0), &blocks);
+            assert_eq!(graph.labels[0], Label::Synthetic(0));
+            assert!(graph.irreducible_edge().is_none());
+
+            let header = index(&graph, 1);
+            let latch = index(&graph, 2);
+            let exit = index(&graph, 3);
+            assert!(graph.dominates(header, latch));
+            assert!(graph.dominates(header, exit));
+            assert!(!graph.dominates(latch, exit));
+
+            let body = graph.natural_loop(header);
+            assert!(body.contains(&header) && body.contains(&latch));
+            assert!(!body.contains(&exit));
+        })
+    }
+
+    #[test]
+    fn unreachable_blocks_are_ignored() {
+        syntax::with_globals(|| {
+            let blocks = cfg(&[&[2], &[2], &[]]);
+            let graph = Graph::new(Label::Synthetic(0), &blocks);
+            assert_eq!(graph.labels, vec![Label::Synthetic(0), Label::Synthetic(2)]);
+        })
+    }
+
+    #[test]
+    fn node_splitting_makes_cfg_reducible() {
+        syntax::with_globals(|| {
+            // The loop between 1 and 2 can be entered through either of them
+            let mut blocks = cfg(&[&[1, 2], &[2, 3], &[1, 3], &[]]);
+            assert!(Graph::new(Label::Synthetic(0), &blocks)
+                .irreducible_edge()
+                .is_some());
+
+            let graph = split_nodes(Label::Synthetic(0), &mut blocks).unwrap();
+            assert!(graph.irreducible_edge().is_none());
+            assert_eq!(blocks.len(), 5);
+        })
+    }
+}
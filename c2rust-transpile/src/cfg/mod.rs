@@ -27,7 +27,9 @@ use std::io::Write;
 use std::ops::Deref;
 use std::ops::Index;
 use syntax;
-use syntax::ast::{Arm, Expr, ExprKind, Lit, LitIntType, LitKind, Pat, Stmt, StmtKind};
+use syntax::ast::{
+    Arm, Expr, ExprKind, Lit, LitIntType, LitKind, NodeId, Pat, Stmt, StmtKind, DUMMY_NODE_ID,
+};
 use syntax::print::pprust;
 use syntax::ptr::P;
 use syntax_pos::DUMMY_SP;
@@ -45,9 +47,11 @@ use crate::with_stmts::WithStmts;
 use c2rust_ast_builder::mk;
 
 mod inc_cleanup;
+pub mod labeled_blocks;
 pub mod loops;
 pub mod multiples;
 pub mod relooper;
+pub mod stats;
 pub mod structures;
 
 use crate::cfg::inc_cleanup::IncCleanup;
@@ -428,6 +432,8 @@ impl GenTerminator<StructureLabel<StmtOrDecl>> {
 pub struct SwitchCases {
     cases: Vec<(P<Pat>, Label)>,
     default: Option<Label>,
+    /// The Rust enum whose variants the switch matches instead of integers
+    rust_enum: Option<CEnumId>,
 }
 
 /// A Rust statement, or a C declaration, or a comment
@@ -562,6 +568,14 @@ pub enum ImplicitReturnType {
     ///
     /// TODO: document
     StmtExpr(ExprContext, CExprId, Label),
+
+    /// Bodies nested in a Rust expression, such as the arms of a `match`, break out of the
+    /// labelled block around them at their end
+    Break(Label),
+
+    /// Loop bodies translated on their own, such as the iterations of parallel loops, break out
+    /// of the labelled block around them at their end and on a `continue`
+    Iteration(Label),
 }
 
 /// A complete control-flow graph
@@ -597,10 +611,28 @@ impl Cfg<Label, StmtOrDecl> {
             IndexSet::new(),
         ));
 
+        // A `continue` in an iteration body jumps to its end
+        let continue_entry = match ret {
+            ImplicitReturnType::Iteration(_) => {
+                let continue_entry = cfg_builder.fresh_label();
+                cfg_builder.continue_labels.push(continue_entry);
+                Some(continue_entry)
+            }
+            _ => None,
+        };
+
         translator.with_scope(|| -> Result<(), TranslationError> {
-            let body_exit =
+            let mut body_exit =
                 cfg_builder.convert_stmts_help(translator, ctx, stmt_ids, Some(ret), entry)?;
 
+            if let Some(continue_entry) = continue_entry {
+                if let Some(body_exit) = body_exit {
+                    let wip = cfg_builder.new_wip_block(body_exit);
+                    cfg_builder.add_wip_block(wip, Jump(continue_entry));
+                }
+                body_exit = Some(continue_entry);
+            }
+
             if let Some(body_exit) = body_exit {
                 let mut wip = cfg_builder.new_wip_block(body_exit);
 
@@ -623,16 +655,27 @@ impl Cfg<Label, StmtOrDecl> {
                             translator.panic("Reached end of non-void function without returning");
                         wip.body.push(StmtOrDecl::Stmt(mk().semi_stmt(ret_expr)));
                     }
+                    ImplicitReturnType::Break(brk_label)
+                    | ImplicitReturnType::Iteration(brk_label) => {
+                        wip.body
+                            .push(StmtOrDecl::Stmt(mk().semi_stmt(mk().break_expr_value(
+                                Some(brk_label.pretty_print()),
+                                None as Option<P<Expr>>,
+                            ))));
+                    }
                     ImplicitReturnType::StmtExpr(ctx, expr_id, brk_label) => {
-                        let (stmts, val) = translator
-                            .convert_expr(ctx, expr_id)?
-                            .discard_unsafe();
+                        let (mut stmts, val) =
+                            translator.convert_expr(ctx, expr_id)?.discard_unsafe();
+                        stmts.push(mk().semi_stmt(
+                            mk().break_expr_value(Some(brk_label.pretty_print()), Some(val)),
+                        ));
+
+                        // The value of the statement expression is a C statement of its own
+                        let expr_loc = translator.ast_context[expr_id].loc.as_ref();
+                        tag_first_stmt(&mut stmts, translator.src_loc_node_id(expr_loc));
 
                         wip.body
                             .extend(stmts.into_iter().map(|s| StmtOrDecl::Stmt(s)));
-                        wip.body.push(StmtOrDecl::Stmt(mk().semi_stmt(
-                            mk().break_expr_value(Some(brk_label.pretty_print()), Some(val)),
-                        )));
                     }
                 };
 
@@ -1000,6 +1043,25 @@ impl DeclStmtInfo {
             decl_and_assign: Some(Vec::new()),
         }
     }
+
+    /// Give the first statement of each variant the `NodeId` `id` for the source map.
+    fn tag(&mut self, id: NodeId) {
+        for stmts in vec![&mut self.decl, &mut self.assign, &mut self.decl_and_assign] {
+            if let Some(stmts) = stmts {
+                tag_first_stmt(stmts, id);
+            }
+        }
+    }
+}
+
+/// Give the first statement translated from a C statement the `NodeId` that the source map
+/// points to the C statement.
+fn tag_first_stmt(stmts: &mut [Stmt], id: NodeId) {
+    if let Some(stmt) = stmts.first_mut() {
+        if stmt.id == DUMMY_NODE_ID {
+            stmt.id = id;
+        }
+    }
 }
 
 impl DeclStmtStore {
@@ -1385,6 +1447,10 @@ impl CfgBuilder {
 
         let mut wip = self.new_wip_block(entry);
 
+        // Statements and branch conditions translated from this statement point back to it in
+        // the source map
+        let node_id = translator.src_loc_node_id(translator.ast_context[stmt_id].loc.as_ref());
+
         // Add statement comment into current block right before the current statement
         for cmmt in translator
             .comment_context
@@ -1400,7 +1466,9 @@ impl CfgBuilder {
 
                 CStmtKind::Decls(ref decls) => {
                     for decl in decls {
-                        let info = translator.convert_decl_stmt_info(ctx, *decl)?;
+                        let mut info = translator.convert_decl_stmt_info(ctx, *decl)?;
+                        let decl_loc = translator.ast_context[*decl].loc.as_ref();
+                        info.tag(translator.src_loc_node_id(decl_loc));
                         self.last_per_stmt_mut()
                             .decls_seen
                             .store
@@ -1422,14 +1490,28 @@ impl CfgBuilder {
                 }
 
                 CStmtKind::Return(expr) => {
-                    let val = match expr.map(|i| translator.convert_expr(ctx.used(), i)) {
-                        Some(r) => Some(r?),
+                    let val = match expr {
+                        Some(expr) => {
+                            let ty = translator.ast_context[expr]
+                                .kind
+                                .get_type()
+                                .ok_or_else(|| format_err!("bad return type"))?;
+                            let val = match translator.return_type() {
+                                Some(ret_ty) => {
+                                    translator.convert_expr_as(ctx.used(), expr, ret_ty.ctype)?
+                                }
+                                None => translator.convert_expr(ctx.used(), expr)?,
+                            };
+                            // Rust enums are returned as integers
+                            Some(val.result_map(|val| translator.rust_enum_to_boundary(ty, val))?)
+                        }
                         None => None,
                     };
 
-                    let (stmts, ret_val) = WithStmts::with_stmts_opt(val).discard_unsafe();
+                    let (mut stmts, ret_val) = WithStmts::with_stmts_opt(val).discard_unsafe();
+                    stmts.push(mk().expr_stmt(mk().return_expr(ret_val)));
+                    tag_first_stmt(&mut stmts, node_id);
                     wip.extend(stmts);
-                    wip.push_stmt(mk().expr_stmt(mk().return_expr(ret_val)));
 
                     self.add_wip_block(wip, End);
 
@@ -1450,7 +1532,11 @@ impl CfgBuilder {
                     };
 
                     // Condition
-                    let (stmts, val) = translator.convert_condition(ctx, true, scrutinee)?.discard_unsafe();
+                    let (mut stmts, mut val) = translator
+                        .convert_condition(ctx, true, scrutinee)?
+                        .discard_unsafe();
+                    tag_first_stmt(&mut stmts, node_id);
+                    val.id = node_id;
                     wip.extend(stmts);
                     let cond_val = translator.ast_context[scrutinee].kind.get_bool();
                     self.add_wip_block(
@@ -1504,7 +1590,11 @@ impl CfgBuilder {
                     self.open_loop();
 
                     // Condition
-                    let (stmts, val) = translator.convert_condition(ctx, true, condition)?.discard_unsafe();
+                    let (mut stmts, mut val) = translator
+                        .convert_condition(ctx, true, condition)?
+                        .discard_unsafe();
+                    tag_first_stmt(&mut stmts, node_id);
+                    val.id = node_id;
                     let cond_val = translator.ast_context[condition].kind.get_bool();
                     let mut cond_wip = self.new_wip_block(cond_entry);
                     cond_wip.extend(stmts);
@@ -1570,7 +1660,11 @@ impl CfgBuilder {
                     self.continue_labels.pop();
 
                     // Condition
-                    let (stmts, val) = translator.convert_condition(ctx, true, condition)?.discard_unsafe();
+                    let (mut stmts, mut val) = translator
+                        .convert_condition(ctx, true, condition)?
+                        .discard_unsafe();
+                    tag_first_stmt(&mut stmts, node_id);
+                    val.id = node_id;
                     let cond_val = translator.ast_context[condition].kind.get_bool();
                     let mut cond_wip = self.new_wip_block(cond_entry);
                     cond_wip.extend(stmts);
@@ -1619,9 +1713,11 @@ impl CfgBuilder {
 
                         // Condition
                         if let Some(cond) = condition {
-                            let (stmts, val) = translator
+                            let (mut stmts, mut val) = translator
                                 .convert_condition(ctx, true, cond)?
                                 .discard_unsafe();
+                            tag_first_stmt(&mut stmts, node_id);
+                            val.id = node_id;
                             let cond_val = translator.ast_context[cond].kind.get_bool();
                             let mut cond_wip = slf.new_wip_block(cond_entry);
                             cond_wip.extend(stmts);
@@ -1710,7 +1806,33 @@ impl CfgBuilder {
                         comp_entry,
                     )?;
 
-                    Ok(next_lbl.map(|l| self.new_wip_block(l)))
+                    // Add comments after the last statement of the block. If the block does not
+                    // fall through, they go at the end of the last basic block translated from it.
+                    let end_cmmts = translator
+                        .comment_context
+                        .borrow_mut()
+                        .remove_block_end_comment(stmt_id);
+                    match next_lbl {
+                        Some(l) => {
+                            let mut wip = self.new_wip_block(l);
+                            for cmmt in end_cmmts {
+                                wip.push_comment(cmmt);
+                            }
+                            Ok(Some(wip))
+                        }
+                        None => {
+                            match self.last_per_stmt_mut().nodes.values_mut().last() {
+                                Some(bb) => bb
+                                    .body
+                                    .extend(end_cmmts.into_iter().map(StmtOrDecl::Comment)),
+                                None => translator
+                                    .comment_store
+                                    .borrow_mut()
+                                    .add_unplaced_comments(end_cmmts),
+                            }
+                            Ok(None)
+                        }
+                    }
                 }
 
                 CStmtKind::Expr(expr) => 'case_blk: {
@@ -1731,7 +1853,9 @@ impl CfgBuilder {
                         }
                     }
 
-                    wip.extend(translator.convert_expr(ctx.unused(), expr)?.into_stmts());
+                    let mut stmts = translator.convert_expr(ctx.unused(), expr)?.into_stmts();
+                    tag_first_stmt(&mut stmts, node_id);
+                    wip.extend(stmts);
 
                     // If we can tell the expression is going to diverge, there is no falling through to
                     // the next block.
@@ -1767,11 +1891,22 @@ impl CfgBuilder {
                     Ok(None)
                 }
 
-                CStmtKind::Case(_case_expr, sub_stmt, cie) => {
+                CStmtKind::Case(case_expr, sub_stmt, cie) => {
+                    if translator.ast_context.is_layout_dependent(case_expr) {
+                        translator.warn_layout_dependent(
+                            &translator.ast_context[stmt_id].loc,
+                            "The value of this case label",
+                        );
+                    }
                     self.last_per_stmt_mut().saw_unmatched_case = true;
                     let this_label = Label::FromC(stmt_id);
                     self.add_wip_block(wip, Jump(this_label));
 
+                    let switch_cases = self.switch_expr_cases.last_mut().ok_or(format_err!(
+                        "Cannot find the 'switch' wrapping this ({:?}) 'case' statement",
+                        stmt_id,
+                    ))?;
+
                     // Case
                     let branch = match cie {
                         ConstIntExpr::U(n) => {
@@ -1787,14 +1922,16 @@ impl CfgBuilder {
                             mk().lit_expr(mk().int_lit((-n) as u128, LitIntType::Unsuffixed)),
                         ),
                     };
-                    self.switch_expr_cases
-                        .last_mut()
-                        .ok_or(format_err!(
-                            "Cannot find the 'switch' wrapping this ({:?}) 'case' statement",
-                            stmt_id,
-                        ))?
-                        .cases
-                        .push((mk().lit_pat(branch), this_label));
+                    match switch_cases.rust_enum {
+                        // Rust enums can't hold values other than their variants, so the case
+                        // is only reachable by falling through from the one before
+                        Some(enum_id) => {
+                            if let Some(pat) = translator.rust_enum_case_pat(enum_id, cie) {
+                                switch_cases.cases.push((pat, this_label));
+                            }
+                        }
+                        None => switch_cases.cases.push((mk().lit_pat(branch), this_label)),
+                    }
 
                     // Sub stmt
                     let sub_stmt_next =
@@ -1820,6 +1957,18 @@ impl CfgBuilder {
                     Ok(sub_stmt_next.map(|l| self.new_wip_block(l)))
                 }
 
+                // Switches over the tag of a tagged union bind the union field of each case
+                CStmtKind::Switch {
+                    scrutinee,
+                    body: switch_body,
+                } if translator.is_tagged_union_switch(scrutinee, switch_body) => {
+                    let mut stmts =
+                        translator.convert_tagged_union_switch(ctx, scrutinee, switch_body)?;
+                    tag_first_stmt(&mut stmts, node_id);
+                    wip.extend(stmts);
+                    Ok(Some(wip))
+                }
+
                 CStmtKind::Switch {
                     scrutinee,
                     body: switch_body,
@@ -1827,10 +1976,14 @@ impl CfgBuilder {
                     let next_label = self.fresh_label();
                     let body_label = self.fresh_label();
 
-                    // Convert the condition
-                    let (stmts, val) = translator
+                    // Convert the condition. Switches on Rust enums match their variants.
+                    let rust_enum = translator.switch_rust_enum(scrutinee);
+                    let scrutinee = rust_enum.map_or(scrutinee, |(expr_id, _)| expr_id);
+                    let (mut stmts, mut val) = translator
                         .convert_expr(ctx.used(), scrutinee)?
                         .discard_unsafe();
+                    tag_first_stmt(&mut stmts, node_id);
+                    val.id = node_id;
                     wip.extend(stmts);
 
                     let wip_label = wip.label;
@@ -1841,7 +1994,10 @@ impl CfgBuilder {
                     let saw_unmatched_case = self.last_per_stmt_mut().saw_unmatched_case;
                     let saw_unmatched_default = self.last_per_stmt_mut().saw_unmatched_default;
                     self.break_labels.push(next_label);
-                    self.switch_expr_cases.push(SwitchCases::default());
+                    self.switch_expr_cases.push(SwitchCases {
+                        rust_enum: rust_enum.map(|(_, enum_id)| enum_id),
+                        ..SwitchCases::default()
+                    });
 
                     let body_stuff =
                         self.convert_stmt_help(translator, ctx, switch_body, in_tail, body_label)?;
@@ -1895,6 +2051,27 @@ impl CfgBuilder {
                     )?);
                     Ok(Some(wip))
                 }
+
+                CStmtKind::OmpParallelFor {
+                    for_stmt,
+                    ref clauses,
+                } => match translator.convert_parallel_for(ctx, stmt_id, for_stmt, clauses)? {
+                    Some(mut stmts) => {
+                        tag_first_stmt(&mut stmts, node_id);
+                        wip.extend(stmts);
+                        Ok(Some(wip))
+                    }
+
+                    // The loop is outside of what we can parallelize, so it runs sequentially
+                    None => {
+                        let loop_entry = self.fresh_label();
+                        self.add_wip_block(wip, Jump(loop_entry));
+                        let next_lbl =
+                            self.convert_stmt_help(translator, ctx, for_stmt, in_tail, loop_entry)?;
+
+                        Ok(next_lbl.map(|l| self.new_wip_block(l)))
+                    }
+                },
             };
         let out_wip: Option<WipBlock> = out_wip?; // This statement exists to help type inference...
 
@@ -1,6 +1,7 @@
 //! This modules handles converting a a control-flow graph `Cfg` into `Vec<Structure>`, optionally
 //! simplifying the latter.
 
+use super::stats::CfgStats;
 use super::*;
 
 /// Convert the CFG into a sequence of structures
@@ -11,6 +12,7 @@ pub fn reloop(
     use_c_loop_info: bool,       // use the loop information in the CFG (slower, but better)
     use_c_multiple_info: bool,   // use the multiple information in the CFG (slower, but better)
     live_in: IndexSet<CDeclId>,  // declarations we assume are live going into this graph
+    stats: &mut CfgStats,        // metrics on which loops and branches were matched to C
 ) -> (Vec<Stmt>, Vec<Structure<StmtOrComment>>) {
     let entries: IndexSet<Label> = vec![cfg.entries].into_iter().collect();
     let blocks = cfg
@@ -43,6 +45,9 @@ pub fn reloop(
     };
     let mut state = RelooperState::new(loop_info, multiple_info, live_in);
     state.relooper(entries, blocks, &mut relooped_with_decls, false);
+    stats.add(&state.stats);
+    stats.use_c_loop_info |= use_c_loop_info;
+    stats.use_c_multiple_info |= use_c_multiple_info;
 
     // These are declarations we need to lift
     let lift_me = state.lifted;
@@ -80,6 +85,9 @@ struct RelooperState {
 
     /// Information about multiples
     multiple_info: Option<MultipleInfo<Label>>,
+
+    /// Which loops and multiples were matched to the initial C
+    stats: CfgStats,
 }
 
 impl RelooperState {
@@ -93,6 +101,7 @@ impl RelooperState {
             lifted: IndexSet::new(),
             loop_info,
             multiple_info,
+            stats: CfgStats::default(),
         }
     }
 
@@ -385,7 +394,11 @@ impl RelooperState {
             }
 
             // If matching an existing loop didn't work, fall back on a heuristic
-            if !matched_existing_loop {
+            if matched_existing_loop {
+                self.stats.c_loops += 1;
+            } else {
+                self.stats.heuristic_loops += 1;
+
                 loops::heuristic_loop_body(
                     &predecessor_map,
                     &mut body_blocks,
@@ -493,6 +506,12 @@ impl RelooperState {
             (vec![], all_handlers)
         };
 
+        if recognized_c_multiple {
+            self.stats.c_multiples += 1;
+        } else {
+            self.stats.heuristic_multiples += 1;
+        }
+
         let disable_heuristics = follow_entries == entries;
         result.push(Structure::Multiple {
             entries,
@@ -0,0 +1,147 @@
+//! This module collects metrics on how well the control flow of a function could be structured,
+//! which `--cfg-stats` reports per function as `-Wcfg-stats` diagnostics.
+
+use super::*;
+
+use std::cmp;
+use std::fmt;
+use syntax::ast::{Block, Mac};
+use syntax::visit::{self, Visitor};
+
+/// Control-flow quality metrics of one function, summed over all of the CFGs it was converted
+/// from (there are several when relooping incrementally)
+#[derive(Clone, Debug, Default)]
+pub struct CfgStats {
+    /// Basic blocks in the CFGs
+    pub basic_blocks: usize,
+
+    /// `current_block` variables that had to be declared
+    pub current_blocks: usize,
+
+    /// `Multiple` structures left in the output of the relooper
+    pub multiples: usize,
+
+    /// Deepest nesting of blocks in the translated function body
+    pub nesting_depth: usize,
+
+    /// Whether loop information from the C source was used (`use_c_loop_info`)
+    pub use_c_loop_info: bool,
+
+    /// Loops whose body matched a loop in the C source
+    pub c_loops: usize,
+
+    /// Loops whose body had to be picked heuristically
+    pub heuristic_loops: usize,
+
+    /// Whether branching information from the C source was used (`use_c_multiple_info`)
+    pub use_c_multiple_info: bool,
+
+    /// `Multiple` structures that matched a branch point in the C source
+    pub c_multiples: usize,
+
+    /// `Multiple` structures that did not match a branch point in the C source
+    pub heuristic_multiples: usize,
+}
+
+impl CfgStats {
+    /// Start the metrics of a CFG
+    pub fn new<S>(cfg: &Cfg<Label, S>) -> Self {
+        CfgStats {
+            basic_blocks: cfg.nodes.len(),
+            ..CfgStats::default()
+        }
+    }
+
+    /// Add the metrics of another CFG of the same function
+    pub fn add(&mut self, other: &CfgStats) {
+        self.basic_blocks += other.basic_blocks;
+        self.current_blocks += other.current_blocks;
+        self.multiples += other.multiples;
+        self.nesting_depth = cmp::max(self.nesting_depth, other.nesting_depth);
+        self.use_c_loop_info |= other.use_c_loop_info;
+        self.c_loops += other.c_loops;
+        self.heuristic_loops += other.heuristic_loops;
+        self.use_c_multiple_info |= other.use_c_multiple_info;
+        self.c_multiples += other.c_multiples;
+        self.heuristic_multiples += other.heuristic_multiples;
+    }
+}
+
+impl fmt::Display for CfgStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} basic blocks, {} current_block, {} Multiple, nesting depth {}",
+            self.basic_blocks, self.current_blocks, self.multiples, self.nesting_depth,
+        )?;
+        if self.use_c_loop_info {
+            let loops = self.c_loops + self.heuristic_loops;
+            write!(f, ", C loops honored {}/{}", self.c_loops, loops)?;
+        } else {
+            write!(f, ", C loops ignored")?;
+        }
+        if self.use_c_multiple_info {
+            let multiples = self.c_multiples + self.heuristic_multiples;
+            write!(f, ", C branches honored {}/{}", self.c_multiples, multiples)?;
+        } else {
+            write!(f, ", C branches ignored")?;
+        }
+        Ok(())
+    }
+}
+
+/// Count the `Multiple` structures anywhere
+pub fn count_multiples<Stmt>(root: &[Structure<Stmt>]) -> usize {
+    root.iter()
+        .map(|structure| match structure {
+            &Structure::Simple { ref terminator, .. } => terminator
+                .get_labels()
+                .into_iter()
+                .map(|structure_label| match structure_label {
+                    &StructureLabel::Nested(ref nested) => count_multiples(nested),
+                    _ => 0,
+                })
+                .sum(),
+            &Structure::Multiple {
+                ref branches,
+                ref then,
+                ..
+            } => {
+                1 + branches
+                    .values()
+                    .map(|branch| count_multiples(branch))
+                    .sum::<usize>()
+                    + count_multiples(then)
+            }
+            &Structure::Loop { ref body, .. } => count_multiples(body),
+        })
+        .sum()
+}
+
+/// Deepest nesting of blocks (loop bodies, branches, labelled blocks, ...) in some statements
+pub fn nesting_depth(stmts: &[Stmt]) -> usize {
+    struct NestingDepth {
+        depth: usize,
+        max_depth: usize,
+    }
+
+    impl<'ast> Visitor<'ast> for NestingDepth {
+        fn visit_block(&mut self, block: &'ast Block) {
+            self.depth += 1;
+            self.max_depth = cmp::max(self.max_depth, self.depth);
+            visit::walk_block(self, block);
+            self.depth -= 1;
+        }
+
+        fn visit_mac(&mut self, _mac: &'ast Mac) {}
+    }
+
+    let mut visitor = NestingDepth {
+        depth: 0,
+        max_depth: 0,
+    };
+    for stmt in stmts {
+        visitor.visit_stmt(stmt);
+    }
+    visitor.max_depth
+}
@@ -15,6 +15,24 @@ pub fn structured_cfg(
     let ast: StructuredAST<P<Expr>, P<Pat>, Label, StmtOrComment> =
         structured_cfg_help(vec![], &IndexSet::new(), root, &mut IndexSet::new())?;
 
+    Ok(structured_ast_into_stmts(
+        ast,
+        comment_store,
+        current_block,
+        debug_labels,
+        cut_out_trailing_ret,
+    ))
+}
+
+/// Convert a structured AST (produced either from Relooper structures or directly from a CFG) into
+/// Rust statements
+pub fn structured_ast_into_stmts(
+    ast: StructuredAST<P<Expr>, P<Pat>, Label, StmtOrComment>,
+    comment_store: &mut comment_store::CommentStore,
+    current_block: P<Expr>,
+    debug_labels: bool,
+    cut_out_trailing_ret: bool,
+) -> Vec<Stmt> {
     let s = StructureState {
         enable_comments: true,
         debug_labels,
@@ -23,24 +41,23 @@ pub fn structured_cfg(
     let mut queued = vec![];
     let mut stmts = vec![];
     s.into_stmt(ast, comment_store, &mut queued, &mut stmts);
-    if !queued.is_empty() {
-        eprintln!("Did not find a statement for comments {:?}", queued);
-    }
 
     // If the very last statement in the vector is a `return`, we can either cut it out or replace
-    // it with the returned value.
+    // it with the returned value. A `return` with comments is kept, so that they aren't lost.
     if cut_out_trailing_ret {
         match stmts.last().cloned() {
             Some(Stmt {
                 node: StmtKind::Expr(ref ret),
+                span,
                 ..
             })
             | Some(Stmt {
                 node: StmtKind::Semi(ref ret),
+                span,
                 ..
             }) => {
                 match ret.node {
-                    ExprKind::Ret(None) => {
+                    ExprKind::Ret(None) if span == DUMMY_SP => {
                         stmts.pop();
                     }
                     // TODO: why does libsyntax print a ';' after this even if it is 'Expr' and not 'Semi'
@@ -55,11 +72,17 @@ pub fn structured_cfg(
         }
     }
 
-    Ok(stmts)
+    // Comments after the last statement
+    match stmts.last_mut() {
+        Some(last) => last.span = comment_store.add_trailing_comment_lines(last.span, queued),
+        None => comment_store.add_unplaced_comments(queued),
+    }
+
+    stmts
 }
 
 /// Ways of exiting from a loop body
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum ExitStyle {
     /// Jumps to the beginning of the loop body
     Continue,
@@ -117,6 +140,9 @@ pub trait StructuredStatement: Sized {
         exit_style: ExitStyle,  // `break` or a `continue`
         label: Option<Self::L>, // which loop are we breaking
     ) -> Self;
+
+    /// Make a labelled block, which can be exited early with a `break` to its label
+    fn mk_block(lbl: Self::L, body: Self) -> Self;
 }
 
 /// Defunctionalized version of `StructuredStatement` trait
@@ -141,6 +167,7 @@ pub enum StructuredAST<E, P, L, S> {
     ),
     Loop(Option<L>, Box<StructuredAST<E, P, L, S>>),
     Exit(ExitStyle, Option<L>),
+    Block(L, Box<StructuredAST<E, P, L, S>>),
 }
 
 impl<E, P, L, S> StructuredStatement for StructuredAST<E, P, L, S> {
@@ -184,6 +211,10 @@ impl<E, P, L, S> StructuredStatement for StructuredAST<E, P, L, S> {
     fn mk_exit(exit_style: ExitStyle, label: Option<Self::L>) -> Self {
         StructuredAST::Exit(exit_style, label)
     }
+
+    fn mk_block(lbl: Self::L, body: Self) -> Self {
+        StructuredAST::Block(lbl, Box::new(body))
+    }
 }
 
 /// Recursive helper for `structured_cfg`
@@ -373,6 +404,27 @@ struct StructureState {
 }
 
 impl StructureState {
+    /// Convert the body of a nested statement. Comments left over after the last statement of the
+    /// body stay at its end, unless the body is empty, in which case they are passed on to the
+    /// enclosing statements.
+    fn into_body(
+        &self,
+        ast: StructuredAST<P<Expr>, P<Pat>, Label, StmtOrComment>,
+        comment_store: &mut comment_store::CommentStore,
+        queued_comments: &mut Vec<String>,
+    ) -> Vec<Stmt> {
+        let mut body_comments = vec![];
+        let mut output = vec![];
+        self.into_stmt(ast, comment_store, &mut body_comments, &mut output);
+        match output.last_mut() {
+            Some(last) => {
+                last.span = comment_store.add_trailing_comment_lines(last.span, body_comments)
+            }
+            None => queued_comments.extend(body_comments),
+        }
+        output
+    }
+
     pub fn into_stmt(
         &self,
         ast: StructuredAST<P<Expr>, P<Pat>, Label, StmtOrComment>,
@@ -420,15 +472,12 @@ impl StructureState {
                 // Make a `match`.
 
                 let s = comment_store.add_comment_lines(queued_comments.drain(..).collect());
+                let id = cond.id;
 
                 let arms: Vec<Arm> = cases
                     .into_iter()
                     .map(|(pats, stmts)| -> Arm {
-                        let stmts = {
-                            let mut output = vec![];
-                            self.into_stmt(stmts, comment_store, queued_comments, &mut output);
-                            output
-                        };
+                        let stmts = self.into_body(stmts, comment_store, queued_comments);
 
                         let body = mk().block_expr(mk().block(stmts));
                         mk().arm(pats, None as Option<P<Expr>>, body)
@@ -437,7 +486,7 @@ impl StructureState {
 
                 let e = mk().match_expr(cond, arms);
 
-                output.push(mk().span(s).expr_stmt(e));
+                output.push(mk().span(s).id(id).expr_stmt(e));
             }
 
             If(cond, then, els) => {
@@ -449,18 +498,10 @@ impl StructureState {
                 //
 
                 let s = comment_store.add_comment_lines(queued_comments.drain(..).collect());
+                let id = cond.id;
 
-                let then = {
-                    let mut output = vec![];
-                    self.into_stmt(*then, comment_store, queued_comments, &mut output);
-                    output
-                };
-
-                let mut els = {
-                    let mut output = vec![];
-                    self.into_stmt(*els, comment_store, queued_comments, &mut output);
-                    output
-                };
+                let then = self.into_body(*then, comment_store, queued_comments);
+                let mut els = self.into_body(*els, comment_store, queued_comments);
 
                 let mut if_stmt = match (then.is_empty(), els.is_empty()) {
                     (true, true) => mk().semi_stmt(cond),
@@ -483,7 +524,9 @@ impl StructureState {
                             }
                         }
 
-                        let is_els_expr = els.len() == 1 && is_expr(&els[0].node);
+                        // Unwrapping the statement would lose its comments
+                        let is_els_expr =
+                            els.len() == 1 && is_expr(&els[0].node) && els[0].span == DUMMY_SP;
 
                         let els_branch = if is_els_expr {
                             match els.swap_remove(0).node {
@@ -500,6 +543,7 @@ impl StructureState {
                 };
 
                 if_stmt.span = s;
+                if_stmt.id = id;
                 output.push(if_stmt);
             }
 
@@ -511,11 +555,7 @@ impl StructureState {
                 let mut arms: Vec<Arm> = cases
                     .into_iter()
                     .map(|(lbl, stmts)| -> Arm {
-                        let stmts = {
-                            let mut output = vec![];
-                            self.into_stmt(stmts, comment_store, queued_comments, &mut output);
-                            output
-                        };
+                        let stmts = self.into_body(stmts, comment_store, queued_comments);
 
                         let lbl_expr = if self.debug_labels {
                             lbl.to_string_expr()
@@ -528,11 +568,7 @@ impl StructureState {
                     })
                     .collect();
 
-                let then = {
-                    let mut output = vec![];
-                    self.into_stmt(*then, comment_store, queued_comments, &mut output);
-                    output
-                };
+                let then = self.into_body(*then, comment_store, queued_comments);
 
                 arms.push(mk().arm(
                     vec![mk().wild_pat()],
@@ -553,17 +589,15 @@ impl StructureState {
 
                 let s = comment_store.add_comment_lines(queued_comments.drain(..).collect());
 
-                let body = {
-                    let mut output = vec![];
-                    self.into_stmt(*body, comment_store, queued_comments, &mut output);
-                    output
-                };
+                let body = self.into_body(*body, comment_store, queued_comments);
 
                 // TODO: this is ugly but it needn't be. We are just pattern matching on particular ASTs.
+                // The `if` is dropped, so it can't have comments.
                 if let Some(&Stmt {
                     node: syntax::ast::StmtKind::Expr(ref expr),
+                    id,
                     ..
-                }) = body.iter().nth(0)
+                }) = body.iter().nth(0).filter(|stmt| stmt.span == DUMMY_SP)
                 {
                     if let syntax::ast::ExprKind::If(ref cond, ref thn, None) = expr.node {
                         if let &syntax::ast::Block {
@@ -578,14 +612,26 @@ impl StructureState {
                                     ..
                                 }) = stmts.iter().nth(0)
                                 {
-                                    if let syntax::ast::ExprKind::Break(None, None) = expr.node {
-                                        let e = mk().while_expr(
-                                            not(cond),
-                                            mk().block(body.iter().skip(1).cloned().collect()),
-                                            lbl.map(|l| l.pretty_print()),
-                                        );
-                                        output.push(mk().span(s).expr_stmt(e));
-                                        return;
+                                    if let syntax::ast::ExprKind::Break(ref brk_lbl, None) =
+                                        expr.node
+                                    {
+                                        // The `break` has to exit this very loop
+                                        let exits_loop = match (brk_lbl, lbl) {
+                                            (None, _) => true,
+                                            (Some(brk_lbl), Some(lbl)) => {
+                                                brk_lbl.ident.to_string() == lbl.pretty_print()
+                                            }
+                                            (Some(_), None) => false,
+                                        };
+                                        if exits_loop {
+                                            let e = mk().while_expr(
+                                                not(cond),
+                                                mk().block(body.iter().skip(1).cloned().collect()),
+                                                lbl.map(|l| l.pretty_print()),
+                                            );
+                                            output.push(mk().span(s).id(id).expr_stmt(e));
+                                            return;
+                                        }
                                     }
                                 }
                             }
@@ -598,6 +644,18 @@ impl StructureState {
                 output.push(mk().span(s).expr_stmt(e));
             }
 
+            Block(lbl, body) => {
+                // Make a labelled block, which `break`s to its label exit early.
+
+                let s = comment_store.add_comment_lines(queued_comments.drain(..).collect());
+
+                let body = self.into_body(*body, comment_store, queued_comments);
+
+                let e = mk().labelled_block_expr(mk().block(body), lbl.pretty_print());
+
+                output.push(mk().span(s).expr_stmt(e));
+            }
+
             Exit(exit_style, lbl) => {
                 // Make a (possibly labelled) `break` or `continue`.
 
@@ -1,12 +1,21 @@
 use std::collections::HashSet;
+use std::env;
 use std::ffi::OsStr;
 use std::fs::File;
 use std::path::{Path, PathBuf};
 
 use failure::Error;
+use indexmap::IndexSet;
 use regex::Regex;
 
-#[derive(Deserialize, Debug)]
+use crate::diagnostics::Diagnostic;
+use crate::TranspilerConfig;
+
+mod targets;
+
+pub use self::targets::{get_targets, Target, TargetKind};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct CompileCmd {
     /// The working directory of the compilation. All paths specified in the command
     /// or file fields must be either absolute or relative to this directory.
@@ -40,19 +49,285 @@ impl CompileCmd {
             },
         }
     }
+
+    /// The arguments of the compile command
+    fn args(&self) -> Vec<String> {
+        match self.command {
+            Some(ref command) => split_command(command),
+            None => self.arguments.clone(),
+        }
+    }
+}
+
+/// A step of the original build. Link commands files have the format of compile databases,
+/// except that their entries don't need a `file`.
+#[derive(Deserialize, Debug)]
+struct BuildCmd {
+    directory: PathBuf,
+    file: Option<PathBuf>,
+    command: Option<String>,
+    #[serde(default)]
+    arguments: Vec<String>,
+}
+
+impl BuildCmd {
+    fn args(&self) -> Vec<String> {
+        match self.command {
+            Some(ref command) => split_command(command),
+            None => self.arguments.clone(),
+        }
+    }
+}
+
+/// Split a command into its arguments. Double quotes and backslashes are the only special
+/// characters in the commands of compile databases.
+fn split_command(command: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut arg: Option<String> = None;
+    let mut in_quotes = false;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    arg.get_or_insert_with(String::new).push(c);
+                }
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                arg.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !in_quotes => args.extend(arg.take()),
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    args
+}
+
+/// Does a command compile without linking?
+fn is_compile_only(args: &[String]) -> bool {
+    args.iter()
+        .any(|arg| arg == "-c" || arg == "-S" || arg == "-E")
+}
+
+/// The files produced by a build step
+fn outputs(cmd: &BuildCmd, args: &[String]) -> Vec<PathBuf> {
+    let mut outputs = vec![];
+    let mut args = args.iter();
+    let is_archiver = args
+        .next()
+        .and_then(|program| Path::new(program).file_name())
+        .map_or(false, |program| {
+            let program = program.to_string_lossy();
+            program == "ar" || program.ends_with("-ar")
+        });
+    while let Some(arg) = args.next() {
+        if arg == "-o" {
+            outputs.extend(args.next().map(|output| cmd.directory.join(output)));
+        } else if arg.starts_with("-o") {
+            outputs.push(cmd.directory.join(&arg[2..]));
+        } else if is_archiver && library_name(arg).is_some() {
+            // `ar` writes the first archive on its command line
+            outputs.push(cmd.directory.join(arg));
+            break;
+        }
+    }
+    outputs
+}
+
+/// The name of a library file as passed to `-l`, e.g. `z` for `libz.so.1`
+fn library_name(path: &str) -> Option<&str> {
+    let file_name = Path::new(path).file_name()?.to_str()?;
+    let is_library = [".a", ".so", ".dylib"]
+        .iter()
+        .any(|ext| file_name.ends_with(ext))
+        || file_name.contains(".so.");
+    if !is_library || path.starts_with('-') {
+        return None;
+    }
+    let name = if file_name.starts_with("lib") {
+        &file_name[3..]
+    } else {
+        file_name
+    };
+    Some(&name[..name.find('.').unwrap()])
+}
+
+/// The native libraries and frameworks linked by the original build, to be linked in by the
+/// `build.rs` of the translated crate
+#[derive(Default, Debug)]
+pub struct LinkInfo {
+    /// Libraries in the format of `cargo:rustc-link-lib`, e.g. `z` or `static=foo`
+    pub libs: IndexSet<String>,
+    /// Directories to search for libraries
+    pub search_paths: IndexSet<PathBuf>,
+    /// Packages the original build looked up with `pkg-config`
+    pub pkg_config: IndexSet<String>,
+}
+
+impl LinkInfo {
+    /// Add the libraries linked by a link command. Libraries that the build produces itself
+    /// are skipped, since they get translated as well.
+    fn add_link_args(&mut self, directory: &Path, args: &[String], produced: &HashSet<String>) {
+        let directory = &absolute_path(directory);
+        let mut libs = vec![];
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "-l" {
+                libs.extend(args.next().cloned());
+            } else if arg.starts_with("-l") {
+                libs.push(arg[2..].to_string());
+            } else if arg == "-L" {
+                let path = args.next().map(|path| directory.join(path));
+                self.search_paths.extend(path);
+            } else if arg.starts_with("-L") {
+                self.search_paths.insert(directory.join(&arg[2..]));
+            } else if arg == "-framework" {
+                let framework = args.next().map(|name| format!("framework={}", name));
+                self.libs.extend(framework);
+            } else if arg == "-pthread" {
+                libs.push("pthread".to_string());
+            } else if let Some(name) = library_name(arg) {
+                if produced.contains(name) {
+                    continue;
+                }
+                if arg.ends_with(".a") {
+                    self.libs.insert(format!("static={}", name));
+                } else {
+                    self.libs.insert(name.to_string());
+                }
+                if let Some(dir) = directory.join(arg).parent() {
+                    self.search_paths.insert(dir.to_path_buf());
+                }
+            }
+        }
+        self.libs
+            .extend(libs.into_iter().filter(|lib| !produced.contains(lib)));
+    }
+
+    /// Add the libraries and search paths given on the command line
+    fn add_cli_args(&mut self, tcfg: &TranspilerConfig) {
+        self.libs.extend(tcfg.link_libs.iter().cloned());
+        self.search_paths.extend(
+            tcfg.link_search_paths
+                .iter()
+                .map(|path| absolute_path(path)),
+        );
+    }
+
+    /// Add the packages of the `pkg-config` invocations in a command, which build systems
+    /// that record commands before the shell expands them leave in
+    fn add_pkg_config(&mut self, command: &str) {
+        let re = Regex::new(r"pkg-config((?:\s+[\w.+=-]+)+)").unwrap();
+        for captures in re.captures_iter(command) {
+            let packages = captures[1]
+                .split_whitespace()
+                .filter(|arg| !arg.starts_with('-'))
+                .map(String::from);
+            self.pkg_config.extend(packages);
+        }
+    }
+}
+
+/// Resolve a path relative to the directory the transpiler runs in. The search paths end up in
+/// `build.rs`, which cargo runs in the directory of the crate instead.
+fn absolute_path(path: &Path) -> PathBuf {
+    match env::current_dir() {
+        Ok(cwd) => cwd.join(path),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Collect the libraries linked by the link commands of the compile database and of the link
+/// commands file, plus the ones given on the command line. Build steps that can't be read are
+/// reported, and only the libraries given on the command line are linked then.
+pub fn get_link_info(tcfg: &TranspilerConfig, compile_commands: &Path) -> LinkInfo {
+    let mut link_info = LinkInfo::default();
+    match read_build_cmds(tcfg, compile_commands) {
+        Ok(cmds) => {
+            let args: Vec<Vec<String>> = cmds.iter().map(BuildCmd::args).collect();
+            let produced = produced_libs(&cmds, &args);
+            for (cmd, args) in cmds.iter().zip(&args) {
+                if let Some(ref command) = cmd.command {
+                    link_info.add_pkg_config(command);
+                }
+                if !is_compile_only(args) {
+                    link_info.add_link_args(&cmd.directory, args, &produced);
+                }
+            }
+        }
+        Err(e) => warn!(
+            "Could not read the link commands of the build, only linking the libraries given \
+             on the command line: {}",
+            e
+        ),
+    }
+    link_info.add_cli_args(tcfg);
+    link_info
+}
+
+/// Read the steps of the original build from the compile database and the link commands file
+fn read_build_cmds(
+    tcfg: &TranspilerConfig,
+    compile_commands: &Path,
+) -> Result<Vec<BuildCmd>, Error> {
+    let mut cmds: Vec<BuildCmd> = serde_json::from_reader(File::open(compile_commands)?)?;
+    if let Some(ref link_commands) = tcfg.link_commands {
+        let link_cmds: Vec<BuildCmd> = serde_json::from_reader(File::open(link_commands)?)?;
+        cmds.extend(link_cmds);
+    }
+    Ok(cmds)
+}
+
+/// The names of the libraries that the build produces itself
+fn produced_libs(cmds: &[BuildCmd], args: &[Vec<String>]) -> HashSet<String> {
+    cmds.iter()
+        .zip(args)
+        .flat_map(|(cmd, args)| outputs(cmd, args))
+        .filter_map(|output| library_name(output.to_str()?).map(String::from))
+        .collect()
 }
 
 ///GNU GCC treats all of the following extensions as C++
 const CPP_EXTS: [&str; 7] = ["C", "cc", "cpp", "CPP", "c++", "cp", "cxx"];
 
-fn filter_likely_cpp(cmds: Vec<CompileCmd>) -> Vec<CompileCmd> {
+/// Is a file a C or C++ source file that we translate?
+fn is_source(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .map_or(false, |ext| ext == "c" || CPP_EXTS.contains(&ext))
+}
+
+/// C++ translation units are translated too, but only the subset of C++ that corresponds to C
+/// is supported. The exporter warns about the constructs outside of it.
+fn note_likely_cpp(cmds: &[CompileCmd]) {
     let mut cpp_exts: HashSet<&OsStr> = HashSet::new();
     cpp_exts.extend(CPP_EXTS.iter().map(OsStr::new));
 
+    for cmd in cmds {
+        let is_cpp = cmd
+            .file
+            .extension()
+            .map_or(false, |ext| cpp_exts.contains(ext));
+        if is_cpp {
+            diag!(
+                Diagnostic::Cpp,
+                "Translating C++ file {}, only its C-like subset is supported",
+                cmd.file.display(),
+            );
+        }
+    }
+}
+
+/// Link commands in the compile database name the linked objects or libraries as their files
+/// and have nothing to translate. Files without an extension are only link outputs if their
+/// command links, since sources don't need an extension.
+fn filter_link_only(cmds: Vec<CompileCmd>) -> Vec<CompileCmd> {
     cmds.into_iter()
-        .filter(|c| {
-            let key = c.file.extension().unwrap();
-            !cpp_exts.contains(key)
+        .filter(|c| match c.file.extension().and_then(OsStr::to_str) {
+            Some(ext) => !["o", "obj", "a", "so", "dylib", "lib", "dll"].contains(&ext),
+            None => is_compile_only(&c.args()),
         })
         .collect::<Vec<CompileCmd>>()
 }
@@ -77,6 +352,20 @@ fn filter_duplicate_cmds(v: Vec<CompileCmd>) -> Vec<CompileCmd> {
     cmds
 }
 
+/// Write compile commands as a compile database. The files of the commands are made absolute
+/// so that the database can be moved.
+pub fn save_compile_commands(cmds: &[CompileCmd], compile_commands: &Path) -> Result<(), Error> {
+    let cmds: Vec<CompileCmd> = cmds
+        .iter()
+        .map(|cmd| CompileCmd {
+            file: cmd.abs_file(),
+            ..cmd.clone()
+        })
+        .collect();
+    serde_json::to_writer_pretty(File::create(compile_commands)?, &cmds)?;
+    Ok(())
+}
+
 /// Read `compile_commands` file and optionally ignore any entries not matching `filter`.
 pub fn get_compile_commands(
     compile_commands: &Path,
@@ -96,9 +385,54 @@ pub fn get_compile_commands(
         v
     };
 
-    let v = filter_likely_cpp(v);
+    let v = filter_link_only(v);
+
+    note_likely_cpp(&v);
 
     let v = filter_duplicate_cmds(v);
 
     Ok(v)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_plain_command() {
+        assert_eq!(
+            split_command("cc  -c -o foo.o\tfoo.c "),
+            vec!["cc", "-c", "-o", "foo.o", "foo.c"]
+        );
+    }
+
+    #[test]
+    fn split_quoted_command() {
+        assert_eq!(
+            split_command(r#"cc -DNAME="a b" "" -I"dir with spaces"/inc"#),
+            vec!["cc", "-DNAME=a b", "", "-Idir with spaces/inc"]
+        );
+        assert_eq!(
+            split_command(r#"cc -DQUOTE=\"x\" a\ b c\\d"#),
+            vec!["cc", "-DQUOTE=\"x\"", "a b", "c\\d"]
+        );
+    }
+
+    #[test]
+    fn library_names() {
+        assert_eq!(library_name("libz.so.1"), Some("z"));
+        assert_eq!(library_name("out/libfoo.a"), Some("foo"));
+        assert_eq!(library_name("libbar.dylib"), Some("bar"));
+        assert_eq!(library_name("baz.so"), Some("baz"));
+        // Only one `lib` prefix is stripped
+        assert_eq!(library_name("liblibx.a"), Some("libx"));
+    }
+
+    #[test]
+    fn non_library_names() {
+        assert_eq!(library_name("foo.o"), None);
+        assert_eq!(library_name("libfoo"), None);
+        assert_eq!(library_name("-Wl,libfoo.so"), None);
+        assert_eq!(library_name("libso.c"), None);
+    }
+}
@@ -0,0 +1,247 @@
+//! Grouping of translation units into the libraries and executables of the original build.
+//!
+//! The targets come either from a target map given by the user or from the link steps of the
+//! build: archives and shared libraries become library targets and everything else that is
+//! linked becomes an executable. The object files on the command lines are traced back to the
+//! translation units that were compiled into them.
+
+use std::collections::HashMap;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use super::*;
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetKind {
+    Lib,
+    Bin,
+}
+
+/// A library or executable of the original build
+#[derive(Deserialize, Debug)]
+pub struct Target {
+    pub name: String,
+    pub kind: TargetKind,
+    /// The translation units compiled into the target, except for those it gets from the
+    /// libraries it depends on
+    pub files: Vec<PathBuf>,
+    /// The library targets the target links against
+    #[serde(default)]
+    pub deps: Vec<String>,
+    /// The native libraries the target links against. Targets of a target map get the ones of
+    /// the link command producing a file of their name.
+    #[serde(skip)]
+    pub link_info: LinkInfo,
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Find the targets of the original build, from the target map if there is one and from the
+/// link steps of the build otherwise.
+pub fn get_targets(tcfg: &TranspilerConfig, compile_commands: &Path) -> Result<Vec<Target>, Error> {
+    let mut targets = match tcfg.target_map {
+        Some(ref target_map) => {
+            let mut targets = read_target_map(target_map)?;
+            add_link_info(&mut targets, tcfg, compile_commands);
+            targets
+        }
+        None => {
+            let cmds = read_build_cmds(tcfg, compile_commands)?;
+            targets_from_build_cmds(&cmds)
+        }
+    };
+
+    // Libraries provide their translation units to the targets depending on them
+    let lib_files: HashMap<String, Vec<PathBuf>> = targets
+        .iter()
+        .filter(|target| target.kind == TargetKind::Lib)
+        .map(|target| (target.name.clone(), target.files.clone()))
+        .collect();
+    for target in &mut targets {
+        let dep_files: HashSet<&PathBuf> = target
+            .deps
+            .iter()
+            .filter_map(|dep| lib_files.get(dep))
+            .flatten()
+            .collect();
+        target.files.retain(|file| !dep_files.contains(file));
+        target.link_info.add_cli_args(tcfg);
+    }
+    Ok(targets)
+}
+
+/// Read a target map, a JSON array of targets with paths relative to the map
+fn read_target_map(target_map: &Path) -> Result<Vec<Target>, Error> {
+    let mut targets: Vec<Target> = serde_json::from_reader(File::open(target_map)?)?;
+    let dir = target_map.parent().unwrap_or(Path::new(""));
+    for target in &mut targets {
+        for file in &mut target.files {
+            *file = normalize(&dir.join(&file));
+        }
+    }
+    Ok(targets)
+}
+
+/// Give the targets of a target map the native libraries linked by the link commands of the
+/// build that produce them. Targets without a link command only link the libraries given on the
+/// command line.
+fn add_link_info(targets: &mut [Target], tcfg: &TranspilerConfig, compile_commands: &Path) {
+    let cmds = match read_build_cmds(tcfg, compile_commands) {
+        Ok(cmds) => cmds,
+        Err(e) => {
+            warn!(
+                "Could not read the link commands of the build, only linking the libraries \
+                 given on the command line: {}",
+                e
+            );
+            return;
+        }
+    };
+    let mut build_targets = targets_from_build_cmds(&cmds);
+    for target in targets {
+        let build_target = build_targets
+            .iter_mut()
+            .find(|build| build.name == target.name && build.kind == target.kind);
+        if let Some(build_target) = build_target {
+            target.link_info = mem::replace(&mut build_target.link_info, LinkInfo::default());
+        }
+    }
+}
+
+fn targets_from_build_cmds(cmds: &[BuildCmd]) -> Vec<Target> {
+    let args: Vec<Vec<String>> = cmds.iter().map(BuildCmd::args).collect();
+    let produced = produced_libs(cmds, &args);
+
+    // The translation units of the object files
+    let mut sources = HashMap::new();
+    for (cmd, args) in cmds.iter().zip(&args) {
+        if !is_compile_only(args) {
+            continue;
+        }
+        if let Some(ref file) = cmd.file {
+            let source = cmd.directory.join(file);
+            let object = outputs(cmd, args)
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| source.with_extension("o"));
+            sources.insert(normalize(&object), normalize(&source));
+        }
+    }
+
+    let mut targets: Vec<Target> = vec![];
+    for (cmd, args) in cmds.iter().zip(&args) {
+        if is_compile_only(args) {
+            continue;
+        }
+        let output = match outputs(cmd, args).into_iter().next() {
+            Some(output) => output,
+            None => continue,
+        };
+        let output_name = match output.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => {
+                warn!(
+                    "Skipping link command of {}, which doesn't name an output file",
+                    output.display()
+                );
+                continue;
+            }
+        };
+        let (name, kind) = match library_name(&output_name) {
+            Some(name) => (name.to_string(), TargetKind::Lib),
+            None => (output_name.clone(), TargetKind::Bin),
+        };
+        if targets.iter().any(|target| target.name == name) {
+            continue;
+        }
+
+        let mut files = vec![];
+        let mut deps = vec![];
+        let mut inputs = args.iter().skip(1);
+        while let Some(arg) = inputs.next() {
+            let lib = if arg == "-o" || arg == "-L" {
+                inputs.next();
+                continue;
+            } else if arg == "-l" {
+                inputs.next().map(String::as_str)
+            } else if arg.starts_with("-l") {
+                Some(&arg[2..])
+            } else if arg.starts_with('-') {
+                continue;
+            } else {
+                library_name(arg)
+            };
+
+            match lib {
+                Some(lib) => {
+                    if lib != name && produced.contains(lib) && !deps.iter().any(|dep| dep == lib) {
+                        deps.push(lib.to_string());
+                    }
+                }
+                None => {
+                    let path = normalize(&cmd.directory.join(arg));
+                    if let Some(source) = sources.get(&path) {
+                        files.push(source.clone());
+                    } else if is_source(&path) {
+                        // Compiled and linked in a single step
+                        files.push(path);
+                    }
+                }
+            }
+        }
+
+        let mut link_info = LinkInfo::default();
+        if let Some(ref command) = cmd.command {
+            link_info.add_pkg_config(command);
+        }
+        link_info.add_link_args(&cmd.directory, args, &produced);
+        targets.push(Target {
+            name,
+            kind,
+            files,
+            deps,
+            link_info,
+        });
+    }
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link_cmd(args: &[&str]) -> BuildCmd {
+        BuildCmd {
+            directory: PathBuf::from("/build"),
+            file: None,
+            command: None,
+            arguments: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn compile_and_link_in_one_step() {
+        let cmds = vec![
+            link_cmd(&["c++", "-o", "app", "main.cpp", "util.cc", "-lm"]),
+            // Link outputs without a file name are skipped
+            link_cmd(&["cc", "-o", "..", "x.c"]),
+            link_cmd(&["cc", "-o", "/", "y.c"]),
+        ];
+        let targets = targets_from_build_cmds(&cmds);
+
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].name, "app");
+        assert_eq!(targets[0].kind, TargetKind::Bin);
+        assert_eq!(
+            targets[0].files,
+            vec![
+                PathBuf::from("/build/main.cpp"),
+                PathBuf::from("/build/util.cc"),
+            ]
+        );
+        assert!(targets[0].link_info.libs.contains("m"));
+    }
+}
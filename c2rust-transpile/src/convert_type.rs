@@ -3,17 +3,99 @@ use crate::c_ast::*;
 use crate::renamer::*;
 use crate::diagnostics::TranslationError;
 use c2rust_ast_builder::mk;
+use indexmap::IndexSet;
 use std::collections::{HashMap, HashSet};
 use std::ops::Index;
 use syntax::ast::*;
 use syntax::ptr::P;
+use syntax_pos::DUMMY_SP;
 
 pub struct TypeConverter {
     pub translate_valist: bool,
     renamer: Renamer<CDeclId>,
     fields: HashMap<CDeclId, Renamer<CFieldId>>,
+    field_case: Option<NameCase>,
     features: HashSet<&'static str>,
     emit_no_std: bool,
+    /// Data model to map C types to fixed-width Rust types with, if any
+    fixed_width: Option<DataModel>,
+    /// Keep the `libc` aliases of the C types even when using fixed-width types
+    c_aliases: bool,
+    /// C types with a target dependent width converted since the last call to
+    /// `take_platform_widths`
+    platform_widths: IndexSet<&'static str>,
+    /// Rust enums, which cross the boundary to C code as their integer types
+    rust_enums: IndexSet<CEnumId>,
+}
+
+/// Widths of the C types that differ between targets
+#[derive(Copy, Clone, Debug)]
+pub struct DataModel {
+    pub long_bits: u32,
+    pub pointer_bits: u32,
+    pub char_is_signed: bool,
+}
+
+impl DataModel {
+    /// The data model of a target triple
+    pub fn from_target(triple: &str) -> DataModel {
+        let mut parts = triple.split('-');
+        let arch = parts.next().unwrap_or("");
+        let is_windows = triple.contains("windows");
+        let is_apple = triple.contains("apple") || triple.contains("darwin");
+
+        let is_32_bit = match arch {
+            "i386" | "i586" | "i686" | "powerpc" | "sparc" | "wasm32" => true,
+            _ if arch.starts_with("arm") => !arch.starts_with("arm64"),
+            _ if arch.starts_with("thumb") || arch.starts_with("riscv32") => true,
+            _ if arch.starts_with("mips") => !arch.starts_with("mips64"),
+            _ => triple.ends_with("gnux32"),
+        };
+        let pointer_bits = if is_32_bit { 32 } else { 64 };
+        let long_bits = if is_windows { 32 } else { pointer_bits };
+
+        let char_is_unsigned = ["aarch64", "arm", "thumb", "powerpc", "s390x", "riscv"]
+            .iter()
+            .any(|prefix| arch.starts_with(prefix));
+        DataModel {
+            long_bits,
+            pointer_bits,
+            char_is_signed: !char_is_unsigned || is_windows || is_apple,
+        }
+    }
+
+    /// Is `kind` an integer type as wide as a pointer, as the types that `size_t` and
+    /// `ptrdiff_t` are defined as are?
+    pub fn is_pointer_sized(&self, kind: &CTypeKind) -> bool {
+        match *kind {
+            CTypeKind::Int | CTypeKind::UInt => self.pointer_bits == 32,
+            CTypeKind::Long | CTypeKind::ULong => self.pointer_bits == self.long_bits,
+            CTypeKind::LongLong | CTypeKind::ULongLong => self.pointer_bits == 64,
+            _ => false,
+        }
+    }
+
+    /// The data model of the host, which clang translates for by default
+    pub fn host() -> DataModel {
+        let pointer_bits = if cfg!(target_pointer_width = "32") {
+            32
+        } else {
+            64
+        };
+        DataModel {
+            long_bits: if cfg!(windows) { 32 } else { pointer_bits },
+            pointer_bits,
+            char_is_signed: (0 as std::os::raw::c_char).checked_sub(1).is_some(),
+        }
+    }
+}
+
+/// Is this the name of one of the typedefs of the standard library for pointer-sized integers?
+fn is_pointer_sized_typedef_name(name: &str) -> bool {
+    match name {
+        "size_t" | "uintptr_t" | "ssize_t" | "ptrdiff_t" | "intptr_t" => true,
+        _ => false,
+    }
 }
 
 pub const RESERVED_NAMES: [&str; 103] = [
@@ -131,11 +213,187 @@ impl TypeConverter {
             translate_valist: false,
             renamer: Renamer::new(&RESERVED_NAMES),
             fields: HashMap::new(),
+            field_case: None,
             features: HashSet::new(),
             emit_no_std,
+            fixed_width: None,
+            c_aliases: false,
+            platform_widths: IndexSet::new(),
+            rust_enums: IndexSet::new(),
+        }
+    }
+
+    /// Convert C types to the fixed-width Rust types of their widths in `data_model` instead
+    /// of the `libc` aliases
+    pub fn use_fixed_width_types(&mut self, data_model: DataModel) {
+        self.fixed_width = Some(data_model);
+    }
+
+    /// Set whether to keep the `libc` aliases of the C types, as is done at `extern`
+    /// boundaries. Returns the previous setting.
+    pub fn set_c_aliases(&mut self, c_aliases: bool) -> bool {
+        std::mem::replace(&mut self.c_aliases, c_aliases)
+    }
+
+    /// Pass these enums, which are translated into Rust enums, to and from functions as their
+    /// integer types
+    pub fn pass_rust_enums_as_integers(&mut self, rust_enums: IndexSet<CEnumId>) {
+        self.rust_enums = rust_enums;
+    }
+
+    /// The C types with a target dependent width that were converted to fixed-width types since
+    /// the last call
+    pub fn take_platform_widths(&mut self) -> IndexSet<&'static str> {
+        std::mem::replace(&mut self.platform_widths, IndexSet::new())
+    }
+
+    /// Convert a C type to a fixed-width Rust type, if fixed-width types are used and it is
+    /// one of the primitive C types or a typedef of the standard library for a pointer-sized
+    /// integer
+    fn convert_fixed_width(&mut self, ctxt: &TypedAstContext, ctype: CTypeId) -> Option<P<Ty>> {
+        match ctxt.index(ctype).kind {
+            CTypeKind::Typedef(decl_id) => match ctxt[decl_id].kind {
+                CDeclKind::Typedef { ref name, typ, .. } => {
+                    self.convert_pointer_sized_typedef(ctxt, name, typ.ctype)
+                }
+                _ => None,
+            },
+            ref kind if !self.c_aliases => self.fixed_width_primitive(kind),
+            _ => None,
         }
     }
 
+    /// Convert a typedef of the standard library for a pointer-sized integer, defined as
+    /// `ctype`, to `usize` or `isize` if fixed-width types are used. The typedefs are portable,
+    /// so they are not reported like the types they are defined as.
+    pub fn convert_pointer_sized_typedef(
+        &self,
+        ctxt: &TypedAstContext,
+        name: &str,
+        ctype: CTypeId,
+    ) -> Option<P<Ty>> {
+        let data_model = self.fixed_width?;
+        let kind = &ctxt.resolve_type(ctype).kind;
+        if !is_pointer_sized_typedef_name(name) || !data_model.is_pointer_sized(kind) {
+            return None;
+        }
+        // `libc` defines its aliases of the typedefs as `usize` and `isize` too
+        if self.c_aliases {
+            return Some(mk().path_ty(mk().path(vec!["libc", name])));
+        }
+        let ty = if kind.is_unsigned_integral_type() {
+            "usize"
+        } else {
+            "isize"
+        };
+        Some(mk().path_ty(mk().path(vec![ty])))
+    }
+
+    /// Is this type translated to `usize` or `isize`, as a typedef of the standard library for a
+    /// pointer-sized integer or a typedef of one? C converts between these typedefs and the
+    /// types they are defined as implicitly, but those are translated to fixed-width types.
+    pub fn is_pointer_sized_typedef(&self, ctxt: &TypedAstContext, ctype: CTypeId) -> bool {
+        let data_model = match self.fixed_width {
+            Some(data_model) => data_model,
+            None => return false,
+        };
+        match ctxt.index(ctype).kind {
+            CTypeKind::Typedef(decl_id) => match ctxt[decl_id].kind {
+                CDeclKind::Typedef { ref name, typ, .. } => {
+                    let is_pointer_sized = is_pointer_sized_typedef_name(name)
+                        && data_model.is_pointer_sized(&ctxt.resolve_type(typ.ctype).kind);
+                    is_pointer_sized || self.is_pointer_sized_typedef(ctxt, typ.ctype)
+                }
+                _ => false,
+            },
+            CTypeKind::Elaborated(ty)
+            | CTypeKind::Paren(ty)
+            | CTypeKind::TypeOf(ty)
+            | CTypeKind::Decayed(ty) => self.is_pointer_sized_typedef(ctxt, ty),
+            CTypeKind::Attributed(ty, _) => self.is_pointer_sized_typedef(ctxt, ty.ctype),
+            _ => false,
+        }
+    }
+
+    /// The fixed-width Rust type of a primitive C type, if fixed-width types are used
+    fn fixed_width_primitive(&mut self, kind: &CTypeKind) -> Option<P<Ty>> {
+        let data_model = self.fixed_width?;
+        let (long, ulong) = match data_model.long_bits {
+            32 => ("i32", "u32"),
+            _ => ("i64", "u64"),
+        };
+
+        let platform_width = match *kind {
+            CTypeKind::Long => Some("long"),
+            CTypeKind::ULong => Some("unsigned long"),
+            CTypeKind::Char => Some("char"),
+            _ => None,
+        };
+        if let Some(platform_width) = platform_width {
+            self.platform_widths.insert(platform_width);
+        }
+
+        let ty = match *kind {
+            CTypeKind::Short => "i16",
+            CTypeKind::Int => "i32",
+            CTypeKind::LongLong => "i64",
+            CTypeKind::UShort => "u16",
+            CTypeKind::UInt => "u32",
+            CTypeKind::ULongLong => "u64",
+            CTypeKind::SChar => "i8",
+            CTypeKind::UChar => "u8",
+            CTypeKind::Double => "f64",
+            CTypeKind::Float => "f32",
+            CTypeKind::Long => long,
+            CTypeKind::ULong => ulong,
+            CTypeKind::Char if data_model.char_is_signed => "i8",
+            CTypeKind::Char => "u8",
+            _ => return None,
+        };
+        Some(mk().path_ty(mk().path(vec![ty])))
+    }
+
+    /// Convert a primitive C type, for values the translator introduces itself that have no
+    /// type in the C AST
+    pub fn convert_primitive(&mut self, kind: &CTypeKind) -> P<Ty> {
+        if let Some(ty) = self.fixed_width_primitive(kind) {
+            return ty;
+        }
+        let name = match *kind {
+            CTypeKind::Short => "c_short",
+            CTypeKind::Int => "c_int",
+            CTypeKind::Long => "c_long",
+            CTypeKind::LongLong => "c_longlong",
+            CTypeKind::UShort => "c_ushort",
+            CTypeKind::UInt => "c_uint",
+            CTypeKind::ULong => "c_ulong",
+            CTypeKind::ULongLong => "c_ulonglong",
+            CTypeKind::SChar => "c_schar",
+            CTypeKind::UChar => "c_uchar",
+            CTypeKind::Char => "c_char",
+            CTypeKind::Double => "c_double",
+            CTypeKind::Float => "c_float",
+            ref kind => panic!("{:?} is not a primitive C type", kind),
+        };
+        mk().path_ty(mk().path(vec!["libc", name]))
+    }
+
+    /// `c_void`, from `core` or `std` instead of `libc` when using fixed-width types
+    fn c_void_ty(&self) -> P<Ty> {
+        if self.fixed_width.is_some() && !self.c_aliases {
+            let std_or_core = if self.emit_no_std { "core" } else { "std" };
+            mk().path_ty(vec!["", std_or_core, "ffi", "c_void"])
+        } else {
+            mk().path_ty(vec!["libc", "c_void"])
+        }
+    }
+
+    /// Name types and fields declared from now on following the Rust naming conventions
+    pub fn use_rust_naming_conventions(&mut self) {
+        self.renamer.set_case(NameCase::UpperCamel);
+        self.field_case = Some(NameCase::Snake);
+    }
+
     pub fn features_used(&self) -> &HashSet<&'static str> {
         &self.features
     }
@@ -146,6 +404,12 @@ impl TypeConverter {
             .expect("Name already assigned")
     }
 
+    /// Pick a fresh name for a type the translator introduces itself, which is unique among the
+    /// names of the translated types and follows their naming convention
+    pub fn pick_type_name(&mut self, basename: &str) -> String {
+        self.renamer.pick_name_root(basename)
+    }
+
     pub fn alias_decl_name(&mut self, new_decl_id: CDeclId, old_decl_id: CDeclId) {
         self.renamer.alias(new_decl_id, &old_decl_id)
     }
@@ -163,7 +427,11 @@ impl TypeConverter {
         let name = if name.is_empty() { "unnamed" } else { name };
 
         if !self.fields.contains_key(&record_id) {
-            self.fields.insert(record_id, Renamer::new(&RESERVED_NAMES));
+            let mut renamer = Renamer::new(&RESERVED_NAMES);
+            if let Some(case) = self.field_case {
+                renamer.set_case(case);
+            }
+            self.fields.insert(record_id, renamer);
         }
 
         self.fields
@@ -187,6 +455,28 @@ impl TypeConverter {
         }
     }
 
+    /// Convert the type of a value that crosses the boundary to C code, such as the parameters
+    /// of functions. C code can pass any integer in place of a Rust enum, so Rust enums cross it
+    /// as their integer types.
+    pub fn convert_boundary(
+        &mut self,
+        ctxt: &TypedAstContext,
+        ctype: CTypeId,
+    ) -> Result<P<Ty>, TranslationError> {
+        if let CTypeKind::Enum(enum_id) = ctxt.resolve_type(ctype).kind {
+            if self.rust_enums.contains(&enum_id) {
+                if let CDeclKind::Enum {
+                    integral_type: Some(integral_type),
+                    ..
+                } = ctxt[enum_id].kind
+                {
+                    return self.convert(ctxt, integral_type.ctype);
+                }
+            }
+        }
+        self.convert(ctxt, ctype)
+    }
+
     /// Helper function handling conversion of function types in `convert`.
     /// Optional return type excludes a ty when a function doesn't return.
     pub fn convert_function(
@@ -198,12 +488,17 @@ impl TypeConverter {
     ) -> Result<P<Ty>, TranslationError> {
         let mut inputs = params
             .iter()
-            .map(|x| mk().arg(self.convert(ctxt, x.ctype).unwrap(), mk().wild_pat()))
+            .map(|x| {
+                mk().arg(
+                    self.convert_boundary(ctxt, x.ctype).unwrap(),
+                    mk().wild_pat(),
+                )
+            })
             .collect::<Vec<_>>();
 
         let output = match ret {
             None => mk().never_ty(),
-            Some(ret) => self.convert(ctxt, ret.ctype)?,
+            Some(ret) => self.convert_boundary(ctxt, ret.ctype)?,
         };
 
         if is_variadic {
@@ -229,9 +524,7 @@ impl TypeConverter {
                 } else {
                     Mutability::Mutable
                 };
-                return Ok(mk()
-                    .set_mutbl(mutbl)
-                    .ptr_ty(mk().path_ty(vec!["libc", "c_void"])));
+                return Ok(mk().set_mutbl(mutbl).ptr_ty(self.c_void_ty()));
             }
 
             CTypeKind::VariableArray(mut elt, _len) => {
@@ -288,6 +581,45 @@ impl TypeConverter {
         Ok(mk().set_mutbl(mutbl).ptr_ty(child_ty))
     }
 
+    /// Convert the function type behind a block pointer to the type of a reference counted
+    /// Rust closure, `Rc<dyn Fn(..) -> ..>`. Blocks are copied by reference in C, so the
+    /// closure has to be shareable.
+    pub fn convert_block_closure(
+        &mut self,
+        ctxt: &TypedAstContext,
+        qtype: CQualTypeId,
+    ) -> Result<P<Ty>, TranslationError> {
+        let (ret, params) = match ctxt.resolve_type(qtype.ctype).kind {
+            CTypeKind::Function(ret, ref params, false, _, _) => (ret, params.clone()),
+            CTypeKind::Function(_, _, true, _, _) => {
+                return Err(format_err!("Variadic blocks are not supported").into())
+            }
+            ref t => return Err(format_err!("Block pointer to non-function type {:?}", t).into()),
+        };
+
+        let inputs = params
+            .iter()
+            .map(|x| self.convert(ctxt, x.ctype))
+            .collect::<Result<Vec<_>, _>>()?;
+        let output = match ctxt.resolve_type(ret.ctype).kind {
+            CTypeKind::Void => None,
+            _ => Some(self.convert(ctxt, ret.ctype)?),
+        };
+        let fn_args = ParenthesizedArgs {
+            span: DUMMY_SP,
+            inputs,
+            output,
+        };
+        let fn_trait = mk().dyn_trait_ty(vec![mk().path_segment_with_args("Fn", fn_args)]);
+        let rc = mk().path_segment_with_args("Rc", mk().angle_bracketed_args(vec![fn_trait]));
+        Ok(mk().path_ty(vec![
+            mk().path_segment(""),
+            mk().path_segment("std"),
+            mk().path_segment("rc"),
+            rc,
+        ]))
+    }
+
     pub fn is_inner_type_valist(ctxt: &TypedAstContext, qtype: CQualTypeId) -> bool {
         match ctxt.resolve_type(qtype.ctype).kind {
             CTypeKind::Struct(struct_id) => {
@@ -314,28 +646,40 @@ impl TypeConverter {
         ctxt: &TypedAstContext,
         ctype: CTypeId,
     ) -> Result<P<Ty>, TranslationError> {
+        if let Some(ty) = self.convert_fixed_width(ctxt, ctype) {
+            return Ok(ty);
+        }
+
         match ctxt.index(ctype).kind {
             CTypeKind::Void => Ok(mk().tuple_ty(vec![] as Vec<P<Ty>>)),
             CTypeKind::Bool => Ok(mk().path_ty(mk().path(vec!["bool"]))),
-            CTypeKind::Short => Ok(mk().path_ty(mk().path(vec!["libc", "c_short"]))),
-            CTypeKind::Int => Ok(mk().path_ty(mk().path(vec!["libc", "c_int"]))),
-            CTypeKind::Long => Ok(mk().path_ty(mk().path(vec!["libc", "c_long"]))),
-            CTypeKind::LongLong => Ok(mk().path_ty(mk().path(vec!["libc", "c_longlong"]))),
-            CTypeKind::UShort => Ok(mk().path_ty(mk().path(vec!["libc", "c_ushort"]))),
-            CTypeKind::UInt => Ok(mk().path_ty(mk().path(vec!["libc", "c_uint"]))),
-            CTypeKind::ULong => Ok(mk().path_ty(mk().path(vec!["libc", "c_ulong"]))),
-            CTypeKind::ULongLong => Ok(mk().path_ty(mk().path(vec!["libc", "c_ulonglong"]))),
-            CTypeKind::SChar => Ok(mk().path_ty(mk().path(vec!["libc", "c_schar"]))),
-            CTypeKind::UChar => Ok(mk().path_ty(mk().path(vec!["libc", "c_uchar"]))),
-            CTypeKind::Char => Ok(mk().path_ty(mk().path(vec!["libc", "c_char"]))),
-            CTypeKind::Double => Ok(mk().path_ty(mk().path(vec!["libc", "c_double"]))),
+            ref kind @ CTypeKind::Short
+            | ref kind @ CTypeKind::Int
+            | ref kind @ CTypeKind::Long
+            | ref kind @ CTypeKind::LongLong
+            | ref kind @ CTypeKind::UShort
+            | ref kind @ CTypeKind::UInt
+            | ref kind @ CTypeKind::ULong
+            | ref kind @ CTypeKind::ULongLong
+            | ref kind @ CTypeKind::SChar
+            | ref kind @ CTypeKind::UChar
+            | ref kind @ CTypeKind::Char
+            | ref kind @ CTypeKind::Double
+            | ref kind @ CTypeKind::Float => Ok(self.convert_primitive(kind)),
             CTypeKind::LongDouble => Ok(mk().path_ty(mk().path(vec!["f128", "f128"]))),
-            CTypeKind::Float => Ok(mk().path_ty(mk().path(vec!["libc", "c_float"]))),
             CTypeKind::Int128 => Ok(mk().path_ty(mk().path(vec!["i128"]))),
             CTypeKind::UInt128 => Ok(mk().path_ty(mk().path(vec!["u128"]))),
 
             CTypeKind::Pointer(qtype) => self.convert_pointer(ctxt, qtype),
 
+            // Block pointers are translated to Option applied to a shared closure in order to
+            // support NULL blocks natively
+            CTypeKind::BlockPointer(qtype) => {
+                let closure_ty = self.convert_block_closure(ctxt, qtype)?;
+                let param = mk().angle_bracketed_args(vec![closure_ty]);
+                Ok(mk().path_ty(vec![mk().path_segment_with_args("Option", param)]))
+            }
+
             CTypeKind::Elaborated(ref ctype) => self.convert(ctxt, *ctype),
             CTypeKind::Decayed(ref ctype) => self.convert(ctxt, *ctype),
             CTypeKind::Paren(ref ctype) => self.convert(ctxt, *ctype),
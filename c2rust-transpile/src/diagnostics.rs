@@ -11,12 +11,29 @@ use std::sync::Arc;
 use crate::c_ast::SrcLoc;
 use c2rust_ast_exporter::get_clang_major_version;
 
-const DEFAULT_WARNINGS: &[Diagnostic] = &[];
+const DEFAULT_WARNINGS: &[Diagnostic] = &[
+    Diagnostic::TaggedUnions,
+    Diagnostic::HeaderModules,
+    Diagnostic::Linking,
+    Diagnostic::PlatformWidths,
+    Diagnostic::ParallelLoops,
+    Diagnostic::Cpp,
+    Diagnostic::GotoElimination,
+];
 
 #[derive(PartialEq, Eq, Hash, Debug, Display, EnumString, Clone)]
 #[strum(serialize_all = "kebab_case")]
 pub enum Diagnostic {
     Comments,
+    LayoutConstants,
+    TaggedUnions,
+    HeaderModules,
+    Linking,
+    PlatformWidths,
+    ParallelLoops,
+    Cpp,
+    GotoElimination,
+    CfgStats,
 }
 
 macro_rules! diag {
@@ -0,0 +1,418 @@
+//! Deduplication of the declarations translated from C headers.
+//!
+//! Every translation unit has its own copy of the declarations in the headers it includes, so
+//! the types and externs of a header are normally translated once per translation unit. With
+//! `--header-modules`, the translations are collected by the header they come from instead.
+//! Each header becomes a single module of the crate, and the modules of the translation units
+//! import its items from there.
+//!
+//! Every translation unit names the anonymous types on its own, so their names differ between
+//! translation units. Before the items are compared, the anonymous types are renamed after the
+//! equal anonymous types of the module, and the translation units import them under their own
+//! names.
+
+use c2rust_ast_builder::mk;
+use indexmap::{IndexMap, IndexSet};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use syntax::ast::*;
+use syntax::mut_visit::{self, MutVisitor};
+use syntax::print::pprust;
+use syntax::ptr::P;
+use syntax::visit::{self, Visitor};
+
+use crate::diagnostics::Diagnostic;
+use crate::{get_output_path, TranspilerConfig};
+
+/// Stands in for the name of an anonymous type when it is compared with the ones of the module
+const ANONYMOUS: &str = "{anonymous}";
+
+/// An item of a header module
+struct HeaderItem {
+    /// The item printed without its comments, which the translation units have to agree on
+    shape: String,
+    /// The item printed with its comments
+    text: String,
+}
+
+/// The items translated from one header
+struct HeaderModule {
+    name: String,
+    /// Main files of the translation units importing from the module
+    users: IndexSet<PathBuf>,
+    uses: IndexSet<String>,
+    /// Items keyed by their names. Unnamed items such as `impl`s are keyed by their shape.
+    items: IndexMap<String, HeaderItem>,
+    /// Declarations of the `extern "C"` block, keyed by their names
+    foreign_items: IndexMap<String, HeaderItem>,
+    /// Names of the anonymous types, keyed by their shape with `ANONYMOUS` as their own name
+    anonymous_types: IndexMap<String, String>,
+}
+
+impl HeaderModule {
+    fn get(&self, name: &str) -> Option<&HeaderItem> {
+        self.items
+            .get(name)
+            .or_else(|| self.foreign_items.get(name))
+    }
+
+    fn is_used(&self, name: &str) -> bool {
+        self.get(name).is_some() || self.anonymous_types.values().any(|used| used == name)
+    }
+
+    fn contents(&self) -> String {
+        let mut contents = String::new();
+        for use_item in &self.uses {
+            contents.push_str(use_item);
+            contents.push('\n');
+        }
+        if !self.foreign_items.is_empty() {
+            contents.push_str("extern \"C\" {\n");
+            for foreign_item in self.foreign_items.values() {
+                for line in foreign_item.text.lines() {
+                    contents.push_str("    ");
+                    contents.push_str(line);
+                    contents.push('\n');
+                }
+            }
+            contents.push_str("}\n");
+        }
+        for item in self.items.values() {
+            contents.push_str(&item.text);
+            contents.push('\n');
+        }
+        contents
+    }
+}
+
+/// A header module written next to the translated files
+pub struct WrittenModule {
+    pub path: PathBuf,
+    /// Main files of the translation units importing from the module. Any header module the
+    /// module itself imports from has them as users as well.
+    pub users: Vec<PathBuf>,
+}
+
+/// The header modules of the translation units translated so far
+pub struct HeaderModules {
+    /// Module names of the headers. They are shared by all translation units so that they
+    /// agree on the module of every header.
+    pub mod_names: IndexMap<String, PathBuf>,
+    modules: IndexMap<PathBuf, HeaderModule>,
+    /// The header that first defined each name
+    definers: IndexMap<String, PathBuf>,
+}
+
+impl HeaderModules {
+    pub fn new() -> Self {
+        HeaderModules {
+            mod_names: IndexMap::new(),
+            modules: IndexMap::new(),
+            definers: IndexMap::new(),
+        }
+    }
+
+    fn module(&mut self, header: &Path, mod_name: &str) -> &mut HeaderModule {
+        self.modules
+            .entry(header.to_path_buf())
+            .or_insert_with(|| HeaderModule {
+                name: mod_name.to_string(),
+                users: IndexSet::new(),
+                uses: IndexSet::new(),
+                items: IndexMap::new(),
+                foreign_items: IndexMap::new(),
+                anonymous_types: IndexMap::new(),
+            })
+    }
+
+    /// Record that the translation unit of `main_file` imports from the module of a header
+    pub fn add_user(&mut self, header: &Path, mod_name: &str, main_file: &Path) {
+        self.module(header, mod_name)
+            .users
+            .insert(main_file.to_path_buf());
+    }
+
+    /// Add a `use` item needed by the items of a header
+    pub fn add_use(&mut self, header: &Path, mod_name: &str, use_item: String) {
+        self.module(header, mod_name).uses.insert(use_item);
+    }
+
+    /// Name the anonymous types among the items translated from a header after the equal
+    /// anonymous types of the header's module. Anonymous types the module doesn't have yet get
+    /// names that are fresh in the module. Returns the module names of the anonymous types,
+    /// keyed by the names the translation unit gave them.
+    pub fn name_anonymous_types(
+        &mut self,
+        header: &Path,
+        mod_name: &str,
+        items: &[P<Item>],
+    ) -> HashMap<String, String> {
+        let mut renames = HashMap::new();
+        let mut pending: Vec<&P<Item>> = items
+            .iter()
+            .filter(|item| is_anonymous(&item.ident.name.as_str()))
+            .collect();
+
+        // Anonymous types are compared with the module names of the anonymous types nested in
+        // them, so the nested ones are named first
+        while !pending.is_empty() {
+            let pending_names: HashSet<String> = pending
+                .iter()
+                .map(|item| item.ident.name.as_str().to_string())
+                .collect();
+            let (mut ready, waiting): (Vec<&P<Item>>, Vec<&P<Item>>) =
+                pending.into_iter().partition(|item| {
+                    let own_name = item.ident.name.as_str();
+                    path_names(item)
+                        .iter()
+                        .all(|name| *name == *own_name || !pending_names.contains(name))
+                });
+            pending = waiting;
+            if ready.is_empty() {
+                // Anonymous types can't refer back to the types they are nested in, but name the
+                // rest all the same should they do
+                ready = pending.drain(..).collect();
+            }
+
+            for item in ready {
+                let name = item.ident.name.as_str().to_string();
+                let mut shape_renames = renames.clone();
+                shape_renames.insert(name.clone(), ANONYMOUS.to_string());
+                let shape = print_item(&rename_types(&shape_renames, item.clone()));
+
+                let module = self.module(header, mod_name);
+                let module_name = match module.anonymous_types.get(&shape) {
+                    Some(module_name) => module_name.clone(),
+                    None => {
+                        let module_name = fresh_name(module, &name);
+                        module.anonymous_types.insert(shape, module_name.clone());
+                        module_name
+                    }
+                };
+                renames.insert(name, module_name);
+            }
+        }
+
+        renames
+    }
+
+    /// Add an item translated from a header to the header's module. The item has to be renamed
+    /// with the names from `name_anonymous_types` first. Returns `false` if the module already
+    /// holds a different item of the same name, in which case the translation unit has to keep
+    /// its own. Otherwise, `text` prints the item with its comments, and is called even if the
+    /// module already holds an equal item so that the comments of the item are used up.
+    pub fn add_item<F>(&mut self, header: &Path, mod_name: &str, item: &Item, text: F) -> bool
+    where
+        F: FnOnce() -> String,
+    {
+        let shape = print_item(item);
+        let name = item.ident.name.as_str();
+        self.add(header, mod_name, &name, shape, text, false)
+    }
+
+    /// Add a declaration of the `extern "C"` block of a header's module. Works like `add_item`.
+    pub fn add_foreign_item<F>(
+        &mut self,
+        header: &Path,
+        mod_name: &str,
+        foreign_item: &ForeignItem,
+        text: F,
+    ) -> bool
+    where
+        F: FnOnce() -> String,
+    {
+        let shape = pprust::to_string(|s| s.print_foreign_item(foreign_item));
+        let name = foreign_item.ident.name.as_str();
+        self.add(header, mod_name, &name, shape, text, true)
+    }
+
+    fn add<F>(
+        &mut self,
+        header: &Path,
+        mod_name: &str,
+        name: &str,
+        shape: String,
+        text: F,
+        foreign: bool,
+    ) -> bool
+    where
+        F: FnOnce() -> String,
+    {
+        let key = if name.is_empty() {
+            shape.clone()
+        } else {
+            name.to_string()
+        };
+
+        {
+            let module = self.module(header, mod_name);
+            let items = if foreign {
+                &mut module.foreign_items
+            } else {
+                &mut module.items
+            };
+            if let Some(existing) = items.get(&key) {
+                if existing.shape != shape {
+                    diag!(
+                        Diagnostic::HeaderModules,
+                        "Translation units disagree on the definition of `{}` from {}, keeping \
+                         the differing definitions in their own modules",
+                        name,
+                        header.display(),
+                    );
+                    return false;
+                }
+                text();
+                return true;
+            }
+            let text = text();
+            items.insert(
+                key,
+                HeaderItem {
+                    shape: shape.clone(),
+                    text,
+                },
+            );
+        }
+
+        // Anonymous types of different headers are different types
+        if name.is_empty() || is_anonymous(name) {
+            return true;
+        }
+        match self.definers.get(name) {
+            Some(definer) if definer != header => {
+                if self.modules[definer].get(name).map(|item| &item.shape) != Some(&shape) {
+                    diag!(
+                        Diagnostic::HeaderModules,
+                        "`{}` is defined differently in {} and {}",
+                        name,
+                        definer.display(),
+                        header.display(),
+                    );
+                }
+            }
+            Some(_) => {}
+            None => {
+                self.definers.insert(name.to_string(), header.to_path_buf());
+            }
+        }
+        true
+    }
+
+    /// Write the header modules next to the translated files
+    pub fn write(&self, tcfg: &TranspilerConfig, cc_db: &Path) -> Vec<WrittenModule> {
+        let cc_db_dir = cc_db.parent().unwrap();
+        let mut written = vec![];
+        for module in self.modules.values() {
+            let output_path = get_output_path(tcfg, &cc_db_dir.join(&module.name));
+            if output_path.exists() && !tcfg.overwrite_existing {
+                println!("Skipping existing file {}", output_path.display());
+            } else if let Err(e) = fs::write(&output_path, module.contents()) {
+                panic!("Unable to write {}: {}", output_path.display(), e);
+            }
+            written.push(WrittenModule {
+                path: output_path,
+                users: module.users.iter().cloned().collect(),
+            });
+        }
+        written
+    }
+}
+
+/// Is `name` one of the names the translator gives to anonymous types, such as `unnamed`,
+/// `unnamed_0` or `Unnamed0` with naming conventions?
+fn is_anonymous(name: &str) -> bool {
+    let number = match name {
+        "unnamed" | "Unnamed" => return true,
+        _ if name.starts_with("unnamed_") => &name["unnamed_".len()..],
+        _ if name.starts_with("Unnamed") => &name["Unnamed".len()..],
+        _ => return false,
+    };
+    !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Pick a name for an anonymous type that no item of `module` has yet, numbered like the names
+/// of the renamers
+fn fresh_name(module: &HeaderModule, name: &str) -> String {
+    let (basename, separator) = if name.starts_with('u') {
+        ("unnamed", "_")
+    } else {
+        ("Unnamed", "")
+    };
+    let mut fresh = name.to_string();
+    for i in 0.. {
+        if !module.is_used(&fresh) {
+            break;
+        }
+        fresh = format!("{}{}{}", basename, separator, i);
+    }
+    fresh
+}
+
+fn print_item(item: &Item) -> String {
+    pprust::to_string(|s| s.print_item(item))
+}
+
+/// Rename the anonymous types of an item with the names from `name_anonymous_types`
+pub fn rename_types(renames: &HashMap<String, String>, item: P<Item>) -> P<Item> {
+    let mut item = TypeRenamer(renames)
+        .flat_map_item(item)
+        .pop()
+        .expect("Renaming keeps the item");
+    if let Some(name) = renames.get(&*item.ident.name.as_str()) {
+        item = item.map(|mut item| {
+            item.ident = mk().ident(name.as_str());
+            item
+        });
+    }
+    item
+}
+
+/// Rename the anonymous types of a declaration of an `extern "C"` block with the names from
+/// `name_anonymous_types`
+pub fn rename_foreign_types(
+    renames: &HashMap<String, String>,
+    foreign_item: ForeignItem,
+) -> ForeignItem {
+    TypeRenamer(renames)
+        .flat_map_foreign_item(foreign_item)
+        .pop()
+        .expect("Renaming keeps the declaration")
+}
+
+/// Renames the types in paths, leaving the names of fields alone
+struct TypeRenamer<'a>(&'a HashMap<String, String>);
+
+impl<'a> MutVisitor for TypeRenamer<'a> {
+    fn visit_path(&mut self, path: &mut Path) {
+        for segment in &mut path.segments {
+            if let Some(name) = self.0.get(&*segment.ident.name.as_str()) {
+                segment.ident = mk().ident(name.as_str());
+            }
+        }
+        mut_visit::noop_visit_path(path, self);
+    }
+
+    fn visit_mac(&mut self, _mac: &mut Mac) {}
+}
+
+/// The names in the paths of an item
+fn path_names(item: &Item) -> HashSet<String> {
+    struct PathNames(HashSet<String>);
+
+    impl<'ast> Visitor<'ast> for PathNames {
+        fn visit_path(&mut self, path: &'ast Path, _id: NodeId) {
+            for segment in &path.segments {
+                self.0.insert(segment.ident.name.as_str().to_string());
+            }
+            visit::walk_path(self, path);
+        }
+
+        fn visit_mac(&mut self, _mac: &'ast Mac) {}
+    }
+
+    let mut names = PathNames(HashSet::new());
+    names.visit_item(item);
+    names.0
+}
@@ -37,6 +37,7 @@ pub mod c_ast;
 pub mod cfg;
 mod compile_cmds;
 pub mod convert_type;
+mod header_modules;
 pub mod renamer;
 pub mod rust_ast;
 pub mod translator;
@@ -46,7 +47,7 @@ use std::collections::HashSet;
 use std::fs::{self, File};
 use std::io;
 use std::io::prelude::*;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::process;
 
 use failure::Error;
@@ -57,15 +58,26 @@ use crate::c_ast::*;
 pub use crate::diagnostics::Diagnostic;
 use c2rust_ast_exporter as ast_exporter;
 
-use crate::build_files::{emit_build_files, get_build_dir};
-use crate::compile_cmds::get_compile_commands;
+use crate::build_files::{
+    emit_build_files, emit_workspace, get_build_dir, TranslatedModule, HEADERS_CRATE,
+};
+use crate::compile_cmds::{
+    get_compile_commands, get_link_info, get_targets, save_compile_commands,
+};
+use crate::convert_type::DataModel;
+use crate::header_modules::HeaderModules;
+use crate::translator::{LinkTable, Translation};
 pub use crate::translator::ReplaceMode;
 use std::prelude::v1::Vec;
 
 type PragmaVec = Vec<(&'static str, Vec<&'static str>)>;
 type PragmaSet = indexmap::IndexSet<(&'static str, &'static str)>;
 type CrateSet = indexmap::IndexSet<&'static str>;
-type TranspileResult = (PathBuf, Option<PragmaVec>, Option<CrateSet>);
+/// Extensions and contents of files written next to a translated file
+type SidecarVec = Vec<(&'static str, String)>;
+/// The translated file, its pragmas and crates unless it was skipped, and whether it defines
+/// `main`
+type TranspileResult = (PathBuf, Option<PragmaVec>, Option<CrateSet>, bool);
 
 /// Configuration settings for the translation process
 #[derive(Debug)]
@@ -78,6 +90,7 @@ pub struct TranspilerConfig {
     pub json_function_cfgs: bool,
     pub dump_cfg_liveness: bool,
     pub dump_structures: bool,
+    pub cfg_stats: bool,
     pub verbose: bool,
     pub debug_ast_exporter: bool,
 
@@ -107,44 +120,192 @@ pub struct TranspilerConfig {
     pub output_dir: Option<PathBuf>,
     pub translate_const_macros: bool,
     pub disable_refactoring: bool,
+    /// Translate variable-length arrays and `alloca` into stack buffers with at most this many
+    /// inline elements instead of heap allocated vectors
+    pub stack_vla_capacity: Option<usize>,
+    /// Translate enums that only ever hold their declared enumerators into Rust enums
+    pub translate_rust_enums: bool,
+    /// Translate structs holding an enum tag and a union whose fields are only accessed under a
+    /// matching tag check into Rust enums
+    pub translate_tagged_unions: bool,
+    /// Rename declarations to follow the Rust naming conventions, keeping the C names of
+    /// exported symbols, and write a map of the renamed declarations next to the output
+    pub rust_naming_conventions: bool,
+    /// Write a JSON source map from the translated items and statements to the C source
+    /// locations they came from next to the output
+    pub emit_source_map: bool,
+    /// Write the typed AST of every translation unit as versioned JSON next to its translation
+    pub emit_ast_json: bool,
+    /// Emit runtime checks for undefined behavior that panic with the C source location
+    pub checked: bool,
+    /// Emit one module per header holding the declarations from it shared by all translation
+    /// units, and import them from there instead of translating them into every module
+    pub header_modules: bool,
+    /// With header modules, emit the `static inline` functions of headers once in the module of
+    /// their header with `pub(crate)` visibility, instead of in every translation unit
+    pub share_inline_functions: bool,
+    /// Import functions defined by other translation units from their modules instead of
+    /// declaring them `extern`, and report declarations that don't match their definitions
+    pub link_functions: bool,
+    /// Only emit bindings for the declarations of these headers: types, constants, const macros
+    /// and `extern` declarations of functions and variables
+    pub bindings_headers: Vec<PathBuf>,
+    /// Translate C types to the fixed-width Rust types of their widths on the target instead of
+    /// the `libc` aliases, which are kept in `extern` declarations only. The typedefs of
+    /// pointer-sized integers become `usize` and `isize`.
+    pub fixed_width_types: bool,
+    /// Target triple to parse and translate for instead of the host
+    pub target: Option<String>,
+    /// Structure the control flow of functions whose names match with loops and labelled blocks
+    /// instead of `current_block` state variables where possible
+    pub goto_elimination: Option<Regex>,
+    /// Save the Clang AST of every translation unit to this directory, along with a compile
+    /// database of the saved translation units
+    pub save_ast: Option<PathBuf>,
+    /// Load the Clang ASTs of the translation units from this directory instead of running
+    /// clang. Needs `output_dir`, since the original sources may not exist where the ASTs are
+    /// loaded.
+    pub from_ast: Option<PathBuf>,
+    /// Translate variadic functions that only Rust code calls into functions taking their
+    /// variadic arguments as a slice of a generated `VarArg` enum, which works on stable Rust
+    pub stable_variadics: bool,
+    /// Translate calls to the `printf` family of functions with literal format strings into
+    /// `print!` and `write!`, formatting the conversions Rust can't through `c2rust-printf`
+    pub translate_printf: bool,
 
     // Options that control build files
     /// Emit `Cargo.toml` and one of `main.rs`, `lib.rs`
     pub emit_build_files: bool,
     /// Names the translation unit containing the main function
     pub main: Option<String>,
+    /// Link commands of the original build, in the format of a compile database
+    pub link_commands: Option<PathBuf>,
+    /// Native libraries to link, as with `-l`
+    pub link_libs: Vec<String>,
+    /// Directories to search for native libraries, as with `-L`
+    pub link_search_paths: Vec<PathBuf>,
+    /// Emit a Cargo workspace with a crate for each library and executable of the original build
+    pub workspace: bool,
+    /// Targets of the original build to emit crates for, instead of the ones found in its link
+    /// commands
+    pub target_map: Option<PathBuf>,
+}
+
+impl TranspilerConfig {
+    /// Are the declarations from headers put into modules of their own?
+    pub fn splits_headers(&self) -> bool {
+        self.reorganize_definitions || self.header_modules
+    }
+
+    /// The crate that the header modules live in. A workspace keeps them in a crate of their
+    /// own, so that all of its crates share them.
+    pub fn header_modules_crate(&self) -> &str {
+        if self.workspace {
+            HEADERS_CRATE
+        } else {
+            "crate"
+        }
+    }
+
+    /// Are only bindings for headers emitted instead of a translation?
+    pub fn bindings_only(&self) -> bool {
+        !self.bindings_headers.is_empty()
+    }
+
+    /// The data model of the target we translate for
+    pub fn data_model(&self) -> DataModel {
+        match self.target {
+            Some(ref target) => DataModel::from_target(target),
+            None => DataModel::host(),
+        }
+    }
 }
 
 /// Main entry point to transpiler. Called from CLI tools with the result of
 /// clap::App::get_matches().
 pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]) {
-    diagnostics::init(tcfg.enabled_warnings.clone());
+    let mut enabled_warnings = tcfg.enabled_warnings.clone();
+    // Constants computed by clang are only valid for the target we translate for
+    if tcfg.target.is_some() {
+        enabled_warnings.insert(Diagnostic::LayoutConstants);
+    }
+    if tcfg.cfg_stats {
+        enabled_warnings.insert(Diagnostic::CfgStats);
+    }
+    diagnostics::init(enabled_warnings);
+
+    if tcfg.from_ast.is_some() && tcfg.output_dir.is_none() {
+        eprintln!("Error: translating saved ASTs needs an output directory");
+        process::exit(1);
+    }
 
     let cmds = get_compile_commands(cc_db, &tcfg.filter).expect(&format!(
         "Could not parse compile commands from {}",
         cc_db.to_string_lossy()
     ));
 
+    if let Some(ref dir) = tcfg.save_ast {
+        fs::create_dir_all(dir).expect(&format!("Could not create {}", dir.display()));
+        save_compile_commands(&cmds, &dir.join("compile_commands.json"))
+            .expect("Could not save the compile commands of the saved ASTs");
+    }
+
     // we may need to specify path to system include dir on macOS
     let clang_args: Vec<String> = get_isystem_args();
     let mut clang_args: Vec<&str> = clang_args.iter().map(AsRef::as_ref).collect();
     clang_args.extend_from_slice(extra_clang_args);
 
+    // A workspace gets a crate for every target, and modules can only import from modules of
+    // the same crate
+    let targets = if tcfg.emit_build_files && tcfg.workspace {
+        get_targets(&tcfg, cc_db).expect("Could not find the targets of the build")
+    } else {
+        vec![]
+    };
+
+    // Linking needs the functions defined by all translation units before translating any.
+    // The prepared translation units are kept so that clang only parses every one of them once.
+    let mut link_table = LinkTable::new(&targets);
+    let mut translations: Vec<Option<Translation>> = cmds.iter().map(|_| None).collect();
+    if tcfg.link_functions {
+        for (cmd, translation) in cmds.iter().zip(&mut translations) {
+            let input_path = cmd.abs_file();
+            let parsed = parse_single(&tcfg, &input_path, cc_db, &clang_args);
+            let t = Translation::prepare(parsed, &tcfg, input_path.clone());
+            let module = get_module_name(&tcfg, &input_path);
+            link_table.add_unit(&t, module);
+            *translation = Some(t);
+        }
+    }
+
+    let mut header_modules = HeaderModules::new();
     let results = cmds
         .iter()
-        .map(|cmd| transpile_single(&tcfg, cmd.abs_file().as_path(), cc_db, extra_clang_args))
+        .zip(translations)
+        .map(|(cmd, translation)| {
+            transpile_single(
+                &tcfg,
+                cmd.abs_file().as_path(),
+                cc_db,
+                &clang_args,
+                translation,
+                &mut header_modules,
+                &link_table,
+            )
+        })
         .collect::<Vec<TranspileResult>>();
     let mut modules = vec![];
     let mut modules_skipped = false;
     let mut pragmas = PragmaSet::new();
     let mut crates = CrateSet::new();
-    for res in results {
-        let (module, pragma_vec, crate_set) = res;
-        modules.push(module);
-
-        if let Some(pv) = pragma_vec {
-            for (key, vals) in pv {
-                for val in vals {
+    let mut translated_modules = vec![];
+    for (res, cmd) in results.into_iter().zip(&cmds) {
+        let (module, pragma_vec, crate_set, has_main) = res;
+        modules.push(module.clone());
+
+        if let Some(ref pv) = pragma_vec {
+            for &(key, ref vals) in pv {
+                for &val in vals {
                     pragmas.insert((key, val));
                 }
             }
@@ -152,13 +313,34 @@ pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]
             modules_skipped = true;
         }
 
-        if let Some(cs) = crate_set {
-            crates.extend(cs);
+        if let Some(ref cs) = crate_set {
+            crates.extend(cs.iter().cloned());
+        }
+
+        if let (Some(pragmas), Some(crates)) = (pragma_vec, crate_set) {
+            translated_modules.push(TranslatedModule {
+                path: module,
+                source: cmd.abs_file(),
+                pragmas,
+                crates,
+                has_main,
+            });
         }
     }
     pragmas.sort();
     crates.sort();
 
+    let written_header_modules = if tcfg.header_modules {
+        header_modules.write(&tcfg, cc_db)
+    } else {
+        vec![]
+    };
+    modules.extend(
+        written_header_modules
+            .iter()
+            .map(|module| module.path.clone()),
+    );
+
     if tcfg.emit_build_files {
         if modules_skipped {
             // If we skipped a file, we may not have collected all required pragmas
@@ -166,10 +348,28 @@ pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]
             return;
         }
         let build_dir = get_build_dir(&tcfg, cc_db);
-        let crate_file = emit_build_files(&tcfg, &build_dir, modules, pragmas, crates);
+        if tcfg.workspace {
+            emit_workspace(
+                &tcfg,
+                &build_dir,
+                &targets,
+                &translated_modules,
+                &written_header_modules,
+            );
+            return;
+        }
+        let link_info = get_link_info(&tcfg, cc_db);
+        let crate_file = emit_build_files(&tcfg, &build_dir, modules, pragmas, crates, &link_info);
         // We only run the reorganization refactoring if we emitted a fresh crate file
         if crate_file.is_some() && !tcfg.disable_refactoring {
-            if tcfg.reorganize_definitions {
+            if tcfg.reorganize_definitions && tcfg.emit_source_map {
+                // The refactored files would no longer match their source maps
+                warn!(
+                    "Not reorganizing definitions, since it would invalidate the source maps. \
+                     Run `c2rust refactor reorganize_definitions` in {} to do so anyway.",
+                    build_dir.display()
+                );
+            } else if tcfg.reorganize_definitions {
                 reorganize_definitions(&build_dir).unwrap_or_else(|e| {
                     warn!("Failed to reorganize definitions. {}", e.as_fail());
                 })
@@ -263,11 +463,14 @@ fn transpile_single(
     input_path: &Path,
     cc_db: &Path,
     extra_clang_args: &[&str],
+    translation: Option<Translation>,
+    header_modules: &mut HeaderModules,
+    link_table: &LinkTable,
 ) -> TranspileResult {
     let output_path = get_output_path(tcfg, input_path);
     if output_path.exists() && !tcfg.overwrite_existing {
         println!("Skipping existing file {}", output_path.display());
-        return (output_path, None, None);
+        return (output_path, None, None, false);
     }
 
     let file = input_path.file_name().unwrap().to_str().unwrap();
@@ -283,13 +486,46 @@ fn transpile_single(
         println!("Additional Clang arguments: {}", extra_clang_args.join(" "));
     }
 
+    let t = translation.unwrap_or_else(|| {
+        let typed_context = parse_single(tcfg, input_path, cc_db, extra_clang_args);
+        Translation::prepare(typed_context, tcfg, input_path.to_path_buf())
+    });
+
+    let has_main = t.ast_context.c_main.is_some();
+
+    // Perform the translation
+    let (translated_string, pragmas, crates, sidecars) =
+        translator::translate(t, header_modules, link_table);
+
+    let mut file = match File::create(&output_path) {
+        Ok(file) => file,
+        Err(e) => panic!("Unable to open file for writing: {}", e),
+    };
+
+    match file.write_all(translated_string.as_bytes()) {
+        Ok(()) => (),
+        Err(e) => panic!("Unable to write translation to file: {}", e),
+    };
+
+    for (extension, contents) in sidecars {
+        let sidecar_path = output_path.with_extension(extension);
+        if let Err(e) = fs::write(&sidecar_path, contents) {
+            panic!("Unable to write {}: {}", sidecar_path.display(), e);
+        }
+    }
+
+    (output_path, Some(pragmas), Some(crates), has_main)
+}
+
+/// Parse a translation unit into a typed AST
+fn parse_single(
+    tcfg: &TranspilerConfig,
+    input_path: &Path,
+    cc_db: &Path,
+    extra_clang_args: &[&str],
+) -> TypedAstContext {
     // Extract the untyped AST from the CBOR file
-    let untyped_context = match ast_exporter::get_untyped_ast(
-        input_path,
-        cc_db,
-        extra_clang_args,
-        tcfg.debug_ast_exporter,
-    ) {
+    let untyped_context = match get_untyped_ast(tcfg, input_path, cc_db, extra_clang_args) {
         Err(e) => {
             eprintln!("Error: {:}", e);
             process::exit(1);
@@ -303,11 +539,9 @@ fn transpile_single(
     }
 
     // Convert this into a typed AST
-    let typed_context = {
-        let mut conv = ConversionContext::new(&untyped_context);
-        conv.convert(&untyped_context);
-        conv.typed_context
-    };
+    let mut conv = ConversionContext::new(&untyped_context);
+    conv.convert(&untyped_context);
+    let mut typed_context = conv.typed_context;
 
     if tcfg.dump_typed_context {
         println!("Clang AST");
@@ -319,21 +553,70 @@ fn transpile_single(
         println!("{:#?}", Printer::new(io::stdout()).print(&typed_context));
     }
 
-    // Perform the translation
-    let (translated_string, pragmas, crates) =
-        translator::translate(typed_context, &tcfg, input_path.to_path_buf());
+    // The comments are exported before the translation takes them out of the typed AST
+    if tcfg.emit_ast_json {
+        let json_path = get_output_path(tcfg, input_path).with_extension("ast.json");
+        if let Err(e) = fs::write(&json_path, c_ast::json::export_json(&mut typed_context)) {
+            panic!("Unable to write {}: {}", json_path.display(), e);
+        }
+    }
 
-    let mut file = match File::create(&output_path) {
-        Ok(file) => file,
-        Err(e) => panic!("Unable to open file for writing: {}", e),
-    };
+    typed_context
+}
 
-    match file.write_all(translated_string.as_bytes()) {
-        Ok(()) => (),
-        Err(e) => panic!("Unable to write translation to file: {}", e),
+/// Export the Clang AST of a translation unit, or load it if it was saved before
+fn get_untyped_ast(
+    tcfg: &TranspilerConfig,
+    input_path: &Path,
+    cc_db: &Path,
+    extra_clang_args: &[&str],
+) -> io::Result<ast_exporter::clang_ast::AstContext> {
+    let target_arg = tcfg
+        .target
+        .as_ref()
+        .map(|target| format!("--target={}", target));
+    let mut clang_args = extra_clang_args.to_vec();
+    clang_args.extend(target_arg.as_ref().map(String::as_str));
+
+    let cbor = match tcfg.from_ast {
+        Some(ref dir) => fs::read(get_saved_ast_path(dir, input_path))?,
+        None => {
+            ast_exporter::get_ast_cbor(input_path, cc_db, &clang_args, tcfg.debug_ast_exporter)?
+        }
     };
 
-    (output_path, Some(pragmas), Some(crates))
+    if let Some(ref dir) = tcfg.save_ast {
+        let saved_ast_path = get_saved_ast_path(dir, input_path);
+        fs::create_dir_all(saved_ast_path.parent().unwrap())?;
+        fs::write(&saved_ast_path, &cbor)?;
+    }
+
+    ast_exporter::untyped_ast_from_cbor(&cbor)
+}
+
+/// The saved AST of a translation unit mirrors the absolute path of its main file in `dir`, so
+/// that translation units of the same name in different directories don't collide
+fn get_saved_ast_path(dir: &Path, input_path: &Path) -> PathBuf {
+    let mut path = dir.to_path_buf();
+    path.extend(
+        input_path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name),
+                _ => None,
+            }),
+    );
+    let mut file_name = path.file_name().unwrap().to_os_string();
+    file_name.push(".cbor");
+    path.set_file_name(file_name);
+    path
+}
+
+/// Name of the module a translation unit is translated into
+fn get_module_name(tcfg: &TranspilerConfig, input_path: &Path) -> String {
+    let output_path = get_output_path(tcfg, input_path);
+    let file_stem = output_path.file_stem().unwrap().to_str().map(String::from);
+    build_files::get_module_name(&file_stem).unwrap()
 }
 
 fn get_output_path(tcfg: &TranspilerConfig, input_path: &Path) -> PathBuf {
@@ -37,9 +37,102 @@ impl<T: Clone + Eq + Hash> Scope<T> {
     }
 }
 
+/// Rust identifier naming conventions that names can be converted to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NameCase {
+    /// `snake_case`, used for functions, locals and fields
+    Snake,
+    /// `UpperCamelCase`, used for types and enum variants
+    UpperCamel,
+    /// `SCREAMING_SNAKE_CASE`, used for constants and statics
+    ScreamingSnake,
+}
+
+impl NameCase {
+    /// Converts a C identifier to this naming convention. Leading underscores are kept and
+    /// identifiers reserved for the implementation (`__foo`, `_Foo`) are returned unchanged,
+    /// since they come from system headers and are looked up by their C names.
+    pub fn convert(self, name: &str) -> String {
+        let trimmed = name.trim_start_matches('_');
+        let prefix = &name[..name.len() - trimmed.len()];
+        let is_reserved = prefix.len() > 1 || (prefix.len() == 1 && starts_uppercase(trimmed));
+        let words = split_words(trimmed);
+        if is_reserved || words.is_empty() {
+            return name.to_string();
+        }
+
+        let body = match self {
+            NameCase::Snake => words
+                .iter()
+                .map(|word| word.to_lowercase())
+                .collect::<Vec<_>>()
+                .join("_"),
+            NameCase::ScreamingSnake => words
+                .iter()
+                .map(|word| word.to_uppercase())
+                .collect::<Vec<_>>()
+                .join("_"),
+            NameCase::UpperCamel => words.iter().map(|word| capitalize(word)).collect(),
+        };
+        format!("{}{}", prefix, body)
+    }
+
+    /// Separator between a name and the number appended to make it unique
+    fn suffix_separator(self) -> &'static str {
+        match self {
+            NameCase::UpperCamel => "",
+            NameCase::Snake | NameCase::ScreamingSnake => "_",
+        }
+    }
+}
+
+fn starts_uppercase(s: &str) -> bool {
+    s.chars().next().map_or(false, char::is_uppercase)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+/// Splits an identifier into its words at underscores and case changes, so that `fooBar`,
+/// `foo_bar` and `FOO_BAR` all consist of the words `foo` and `bar`. A run of capitals followed
+/// by a lowercase letter is split before its last capital, as in `HTTP` `Server`.
+fn split_words(name: &str) -> Vec<&str> {
+    let mut words = vec![];
+    for part in name.split('_').filter(|part| !part.is_empty()) {
+        let chars: Vec<(usize, char)> = part.char_indices().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (idx, c) = chars[i];
+            let prev = chars[i - 1].1;
+            let next_is_lower = chars
+                .get(i + 1)
+                .map_or(false, |&(_, next)| next.is_lowercase());
+            let is_boundary = c.is_uppercase()
+                && (prev.is_lowercase()
+                    || prev.is_numeric()
+                    || (prev.is_uppercase() && next_is_lower));
+            if is_boundary {
+                words.push(&part[start..idx]);
+                start = idx;
+            }
+        }
+        words.push(&part[start..]);
+    }
+    words
+}
+
 pub struct Renamer<T> {
     scopes: Vec<Scope<T>>,
     next_fresh: u64,
+    case: Option<NameCase>,
 }
 
 impl<T: Clone + Eq + Hash> Renamer<T> {
@@ -51,9 +144,15 @@ impl<T: Clone + Eq + Hash> Renamer<T> {
         Renamer {
             scopes: vec![Scope::new_with_reserved(set)],
             next_fresh: 0,
+            case: None,
         }
     }
 
+    /// Converts all names picked from now on to the given naming convention
+    pub fn set_case(&mut self, case: NameCase) {
+        self.case = Some(case);
+    }
+
     /// Introduces a new name binding scope
     pub fn add_scope(&mut self) {
         self.scopes.push(Scope::new())
@@ -85,12 +184,22 @@ impl<T: Clone + Eq + Hash> Renamer<T> {
 
     /// Assigns a name that doesn't collide with anything in the context of a particular
     /// scope, defaulting to the current scope if None is provided
-    fn pick_name_in_scope(&mut self, basename: &str, scope: Option<usize>) -> String {
-        let mut target = basename.to_string();
+    fn pick_name_in_scope(
+        &mut self,
+        basename: &str,
+        scope: Option<usize>,
+        case: Option<NameCase>,
+    ) -> String {
+        let basename = match case {
+            Some(case) => case.convert(basename),
+            None => basename.to_string(),
+        };
+        let separator = case.map_or("_", NameCase::suffix_separator);
+        let mut target = basename.clone();
 
         for i in 0.. {
             if self.is_target_used(&target) {
-                target = format!("{}_{}", basename, i);
+                target = format!("{}{}{}", basename, separator, i);
             } else {
                 break;
             }
@@ -105,19 +214,25 @@ impl<T: Clone + Eq + Hash> Renamer<T> {
     }
 
     pub fn pick_name(&mut self, basename: &str) -> String {
-        self.pick_name_in_scope(basename, None)
+        self.pick_name_in_scope(basename, None, self.case)
     }
 
     /// Permanently assign a name that doesn't collide with anything
     /// currently in scope, and also never goes out of scope
     pub fn pick_name_root(&mut self, basename: &str) -> String {
-        self.pick_name_in_scope(basename, Some(0))
+        self.pick_name_in_scope(basename, Some(0), self.case)
     }
 
     /// Introduce a new name binding into a particular scope or the current one if None is provided.
     /// If the key is unbound in the scope then Some of the resulting mangled name is returned,
     /// otherwise None.
-    fn insert_in_scope(&mut self, key: T, basename: &str, scope: Option<usize>) -> Option<String> {
+    fn insert_in_scope(
+        &mut self,
+        key: T,
+        basename: &str,
+        scope: Option<usize>,
+        case: Option<NameCase>,
+    ) -> Option<String> {
         let contains_key = match scope {
             Some(scope_index) => self.scopes[scope_index].contains_key(&key),
             None => self.current_scope().contains_key(&key),
@@ -127,7 +242,7 @@ impl<T: Clone + Eq + Hash> Renamer<T> {
             return None;
         }
 
-        let target = self.pick_name_in_scope(basename, scope);
+        let target = self.pick_name_in_scope(basename, scope, case);
 
         match scope {
             Some(scope_index) => self.scopes[scope_index].insert(key, target.clone()),
@@ -141,14 +256,33 @@ impl<T: Clone + Eq + Hash> Renamer<T> {
     /// the current scope then Some of the resulting mangled name is returned, otherwise
     /// None.
     pub fn insert(&mut self, key: T, basename: &str) -> Option<String> {
-        self.insert_in_scope(key, basename, None)
+        self.insert_in_scope(key, basename, None, self.case)
+    }
+
+    /// Like `insert`, but converts the name to `case` instead of the renamer's naming
+    /// convention. Names are left as they are if the renamer has no naming convention.
+    pub fn insert_with_case(&mut self, key: T, basename: &str, case: NameCase) -> Option<String> {
+        let case = self.case.map(|_| case);
+        self.insert_in_scope(key, basename, None, case)
     }
 
     /// Introduce a new name binding into the root scope. If the key is unbound in
     /// the root scope then Some of the resulting mangled name is returned, otherwise
     /// None.
     pub fn insert_root(&mut self, key: T, basename: &str) -> Option<String> {
-        self.insert_in_scope(key, basename, Some(0))
+        self.insert_in_scope(key, basename, Some(0), self.case)
+    }
+
+    /// Like `insert_root`, but converts the name to `case` instead of the renamer's naming
+    /// convention. Names are left as they are if the renamer has no naming convention.
+    pub fn insert_root_with_case(
+        &mut self,
+        key: T,
+        basename: &str,
+        case: NameCase,
+    ) -> Option<String> {
+        let case = self.case.map(|_| case);
+        self.insert_in_scope(key, basename, Some(0), case)
     }
 
     /// Assign a name in the current scope without reservation or checking for overlap.
@@ -226,4 +360,49 @@ mod tests {
         renamer.drop_scope();
         assert_eq!(renamer.get(&1), None);
     }
+
+    #[test]
+    fn name_cases() {
+        assert_eq!(NameCase::Snake.convert("DoThing"), "do_thing");
+        assert_eq!(
+            NameCase::Snake.convert("parseHTTPHeader"),
+            "parse_http_header"
+        );
+        assert_eq!(NameCase::Snake.convert("MAX_LEN"), "max_len");
+        assert_eq!(NameCase::Snake.convert("_private"), "_private");
+        assert_eq!(NameCase::Snake.convert("utf8Decode"), "utf8_decode");
+        assert_eq!(NameCase::UpperCamel.convert("point_t"), "PointT");
+        assert_eq!(NameCase::UpperCamel.convert("HTTPServer"), "HttpServer");
+        assert_eq!(NameCase::UpperCamel.convert("vec3f"), "Vec3f");
+        assert_eq!(NameCase::ScreamingSnake.convert("maxLen"), "MAX_LEN");
+        assert_eq!(NameCase::ScreamingSnake.convert("Red"), "RED");
+
+        // Reserved identifiers keep their names
+        assert_eq!(NameCase::UpperCamel.convert("__m128i"), "__m128i");
+        assert_eq!(NameCase::Snake.convert("_Bool"), "_Bool");
+    }
+
+    #[test]
+    fn conventions() {
+        let mut renamer = Renamer::new(&["Option"]);
+        renamer.set_case(NameCase::UpperCamel);
+
+        assert_eq!(renamer.insert(1, "option").unwrap(), "Option0");
+        assert_eq!(renamer.insert(2, "my_struct").unwrap(), "MyStruct");
+        assert_eq!(renamer.insert(3, "MyStruct").unwrap(), "MyStruct0");
+        assert_eq!(
+            renamer
+                .insert_with_case(4, "maxLen", NameCase::ScreamingSnake)
+                .unwrap(),
+            "MAX_LEN"
+        );
+
+        let mut plain = Renamer::new(&[]);
+        assert_eq!(
+            plain
+                .insert_with_case(1, "maxLen", NameCase::ScreamingSnake)
+                .unwrap(),
+            "maxLen"
+        );
+    }
 }
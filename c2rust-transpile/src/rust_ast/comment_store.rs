@@ -14,6 +14,9 @@
 //!   ...
 //! ```
 //!
+//! Comments that should come after everything inside of an AST node, such as after the last
+//! statement of a block, are added with `add_trailing_comment_lines` instead.
+//!
 //! Right before printing the output, it is a good idea to use the `CommentTraverser` to make sure
 //! that the comment vector is in the right order. That just means doing something like this:
 //!
@@ -25,7 +28,8 @@
 
 use crate::rust_ast::traverse;
 use itertools::Itertools;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
 use syntax::ast::*;
 use syntax::parse::lexer::comments;
 use syntax_pos::hygiene::SyntaxContext;
@@ -36,6 +40,12 @@ pub struct CommentStore {
     /// `Span` of whatever is associated with the comment.
     output_comments: BTreeMap<Span, comments::Comment>,
 
+    /// Comments that come after everything inside of the AST node with the `Span` key.
+    trailing_comments: BTreeMap<Span, comments::Comment>,
+
+    /// Comments that could not be attached to any AST node.
+    unplaced_comments: Vec<String>,
+
     /// Monotonically increasing source of new byte positions.
     span_source: u32,
 }
@@ -44,6 +54,8 @@ impl CommentStore {
     pub fn new() -> Self {
         CommentStore {
             output_comments: BTreeMap::new(),
+            trailing_comments: BTreeMap::new(),
+            unplaced_comments: vec![],
             span_source: 0,
         }
     }
@@ -51,13 +63,35 @@ impl CommentStore {
     pub fn into_comment_traverser(self) -> CommentTraverser {
         CommentTraverser {
             old_comments: self.output_comments,
-            store: CommentStore::new(),
+            old_trailing_comments: self.trailing_comments,
+            markers: HashMap::new(),
+            store: CommentStore {
+                unplaced_comments: self.unplaced_comments,
+                ..CommentStore::new()
+            },
         }
     }
 
     /// Convert the comment context into the accumulated (and ordered) `libsyntax` comments.
     pub fn into_comments(self) -> Vec<comments::Comment> {
-        self.output_comments.into_iter().map(|(_, v)| v).collect()
+        let mut comments: Vec<comments::Comment> = self
+            .output_comments
+            .into_iter()
+            .chain(self.trailing_comments)
+            .map(|(_, v)| v)
+            .collect();
+        comments.sort_by_key(|cmmt| cmmt.pos);
+        comments
+    }
+
+    /// Comments that were given up on, because there was no AST node to attach them to.
+    pub fn unplaced_comments(&self) -> &[String] {
+        &self.unplaced_comments
+    }
+
+    /// Record comments that could not be attached to any AST node.
+    pub fn add_unplaced_comments(&mut self, lines: Vec<String>) {
+        self.unplaced_comments.extend(lines)
     }
 
     /// Add a `Comment` at the current position, then return the `Span` that should be given to
@@ -83,25 +117,59 @@ impl CommentStore {
     /// Add a comment at the current position, then return the `Span` that should be given to
     /// something we want associated with this comment.
     pub fn add_comment_lines(&mut self, lines: Vec<String>) -> Span {
-        fn translate_comment(comment: String) -> String {
-            comment
-                .lines()
-                .map(|line: &str| {
-                    let mut line = line.to_owned();
-                    if line.starts_with("//!")
-                        || line.starts_with("///")
-                        || line.starts_with("/**")
-                        || line.starts_with("/*!")
-                    {
-                        line.insert(2, ' ');
-                    };
-                    line
-                })
-                .join("\n")
-        }
+        let lines: Vec<String> = lines.into_iter().map(translate_comment).collect();
+        self.add_isolated_comment(lines)
+    }
 
+    /// Like `add_comment_lines`, but Doxygen-style doc comments (`/** */`, `/*! */`, `///`, `//!`
+    /// and their `<` variants for members) become `///` doc comments of the AST node.
+    pub fn add_doc_comment_lines(&mut self, lines: Vec<String>) -> Span {
+        let lines: Vec<String> = lines
+            .into_iter()
+            .flat_map(|comment| match doc_comment_lines(&comment) {
+                Some(doc_lines) => doc_lines,
+                None => vec![translate_comment(comment)],
+            })
+            .collect();
+        self.add_isolated_comment(lines)
+    }
+
+    /// Add comments that should come after everything inside of the AST node with `Span` `sp`,
+    /// such as after the last statement of a block, then return the `Span` that should be given
+    /// to that node instead.
+    pub fn add_trailing_comment_lines(&mut self, sp: Span, lines: Vec<String>) -> Span {
+        if lines.is_empty() {
+            return sp;
+        }
         let lines: Vec<String> = lines.into_iter().map(translate_comment).collect();
 
+        // Nodes without leading comments all share `DUMMY_SP`, so they need a `Span` of their own
+        let sp = if sp == DUMMY_SP {
+            self.span_source += 1;
+            Span::new(
+                BytePos(self.span_source),
+                BytePos(self.span_source),
+                SyntaxContext::empty(),
+            )
+        } else {
+            sp
+        };
+
+        self.span_source += 1;
+        let pos = BytePos(self.span_source);
+        self.trailing_comments
+            .entry(sp)
+            .or_insert(comments::Comment {
+                style: comments::CommentStyle::Isolated,
+                lines: vec![],
+                pos,
+            })
+            .lines
+            .extend(lines);
+        sp
+    }
+
+    fn add_isolated_comment(&mut self, lines: Vec<String>) -> Span {
         if lines.is_empty() {
             DUMMY_SP
         } else {
@@ -114,8 +182,92 @@ impl CommentStore {
     }
 }
 
+/// Turn C comment text into Rust comment text. Comments that look like doc comments in Rust are
+/// disarmed, so that they don't end up attached to the wrong thing.
+fn translate_comment(comment: String) -> String {
+    comment
+        .lines()
+        .map(|line: &str| {
+            let mut line = line.to_owned();
+            if line.starts_with("//!")
+                || line.starts_with("///")
+                || line.starts_with("/**")
+                || line.starts_with("/*!")
+            {
+                line.insert(2, ' ');
+            };
+            line
+        })
+        .join("\n")
+}
+
+/// Turn a Doxygen-style doc comment into the lines of a `///` doc comment. Returns `None` if the
+/// comment is not a doc comment.
+fn doc_comment_lines(comment: &str) -> Option<Vec<String>> {
+    fn strip_space(line: &str) -> &str {
+        if line.starts_with(' ') {
+            &line[1..]
+        } else {
+            line
+        }
+    }
+
+    let comment = comment.trim();
+    let is_block_doc = (comment.starts_with("/**") && !comment.starts_with("/***")
+        || comment.starts_with("/*!"))
+        && comment.len() >= 5
+        && comment.ends_with("*/");
+
+    let mut doc_lines: Vec<&str> = vec![];
+    if is_block_doc {
+        let body = comment[3..comment.len() - 2].trim_start_matches('<');
+        for (i, line) in body.lines().enumerate() {
+            let line = line.trim_end();
+            let line = match line.trim_start() {
+                // Most block doc comments start every line with a `*`
+                stripped if i > 0 && stripped.starts_with('*') => strip_space(&stripped[1..]),
+                _ if i == 0 => strip_space(line),
+                stripped => stripped,
+            };
+            doc_lines.push(line);
+        }
+    } else {
+        for line in comment.lines() {
+            let line = line.trim();
+            if !(line.starts_with("///") && !line.starts_with("////") || line.starts_with("//!")) {
+                return None;
+            }
+            doc_lines.push(strip_space(line[3..].trim_start_matches('<')));
+        }
+    }
+
+    // Leave out empty lines at the start and at the end
+    while doc_lines.first().map_or(false, |line| line.is_empty()) {
+        doc_lines.remove(0);
+    }
+    while doc_lines.last().map_or(false, |line| line.is_empty()) {
+        doc_lines.pop();
+    }
+
+    Some(
+        doc_lines
+            .into_iter()
+            .map(|line| {
+                if line.is_empty() {
+                    "///".to_string()
+                } else {
+                    format!("/// {}", line)
+                }
+            })
+            .collect(),
+    )
+}
+
 pub struct CommentTraverser {
     old_comments: BTreeMap<Span, comments::Comment>,
+    old_trailing_comments: BTreeMap<Span, comments::Comment>,
+    /// Comments that go right in front of the AST nodes with the `NodeId` keys.
+    markers: HashMap<NodeId, String>,
     store: CommentStore,
 }
 impl CommentTraverser {
@@ -127,17 +279,71 @@ impl CommentTraverser {
         }
     }
 
-    /// Turn the traverser back into a `CommentStore`.
-    pub fn into_comment_store(self) -> CommentStore {
-        //        assert!(old_comments.is_empty());
+    /// Reinsert the comments that come after everything inside of the AST node with `Span` `sp`.
+    /// This has to happen after traversing the inside of the node.
+    fn reinsert_trailing_comment_at(&mut self, sp: Span) {
+        if let Some(cmmt) = self.old_trailing_comments.remove(&sp) {
+            self.store.add_comment(cmmt);
+        }
+    }
+
+    /// Put the marker of the AST node with `NodeId` `id`, if any, after the other comments of the
+    /// node, which are at `sp`.
+    fn insert_marker(&mut self, id: NodeId, sp: Span) -> Span {
+        match self.markers.get(&id) {
+            Some(marker) => match self.store.output_comments.get_mut(&sp) {
+                Some(cmmt) => {
+                    cmmt.lines.push(marker.clone());
+                    sp
+                }
+                None => self.store.add_comment_lines(vec![marker.clone()]),
+            },
+            None => sp,
+        }
+    }
+
+    /// Add comments that go right in front of the AST nodes with the `NodeId` keys, such as the
+    /// markers of the source map.
+    pub fn add_markers(&mut self, markers: HashMap<NodeId, String>) {
+        self.markers.extend(markers)
+    }
+
+    /// Traverse an AST node that gets printed on its own, apart from the rest of the output.
+    /// Returns the node together with its comments, in the order they have to be printed in.
+    pub fn traverse_separately<T, F>(&mut self, node: T, traverse: F) -> (T, Vec<comments::Comment>)
+    where
+        F: FnOnce(&mut Self, T) -> T,
+    {
+        // Markers belong to the output the rest of the nodes are printed into
+        let markers = mem::replace(&mut self.markers, HashMap::new());
+        let store = mem::replace(&mut self.store, CommentStore::new());
+        let node = traverse(self, node);
+        let separate_store = mem::replace(&mut self.store, store);
+        self.markers = markers;
+        (node, separate_store.into_comments())
+    }
+
+    /// Turn the traverser back into a `CommentStore`. Comments whose AST node was not traversed
+    /// did not make it into the output, so they become unplaced comments.
+    pub fn into_comment_store(mut self) -> CommentStore {
+        let lost = self
+            .old_comments
+            .into_iter()
+            .chain(self.old_trailing_comments)
+            .flat_map(|(_, cmmt)| cmmt.lines);
+        self.store.unplaced_comments.extend(lost);
         self.store
     }
 }
 
 impl traverse::Traversal for CommentTraverser {
     fn traverse_stmt(&mut self, mut s: Stmt) -> Stmt {
+        let old_span = s.span;
         s.span = self.reinsert_comment_at(s.span);
-        traverse::traverse_stmt_def(self, s)
+        s.span = self.insert_marker(s.id, s.span);
+        let s = traverse::traverse_stmt_def(self, s);
+        self.reinsert_trailing_comment_at(old_span);
+        s
     }
 
     fn traverse_expr(&mut self, mut e: Expr) -> Expr {
@@ -156,8 +362,15 @@ impl traverse::Traversal for CommentTraverser {
     }
 
     fn traverse_block(&mut self, mut b: Block) -> Block {
+        let old_span = b.span;
         b.span = self.reinsert_comment_at(b.span);
-        traverse::traverse_block_def(self, b)
+        let mut b = traverse::traverse_block_def(self, b);
+        self.reinsert_trailing_comment_at(old_span);
+
+        // The pretty-printer dumps out the comments before the closing brace whose position is less
+        // than the end of the block, so make the block end after all comments inside of it
+        b.span = b.span.with_hi(BytePos(self.store.span_source + 1));
+        b
     }
 
     fn traverse_local(&mut self, mut l: Local) -> Local {
@@ -170,13 +383,25 @@ impl traverse::Traversal for CommentTraverser {
         traverse::traverse_field_def(self, f)
     }
 
+    fn traverse_struct_field(&mut self, mut f: StructField) -> StructField {
+        f.span = self.reinsert_comment_at(f.span);
+        f
+    }
+
+    fn traverse_variant(&mut self, mut v: Variant) -> Variant {
+        v.span = self.reinsert_comment_at(v.span);
+        traverse::traverse_variant_def(self, v)
+    }
+
     fn traverse_item(&mut self, mut i: Item) -> Item {
         i.span = self.reinsert_comment_at(i.span);
+        i.span = self.insert_marker(i.id, i.span);
         traverse::traverse_item_def(self, i)
     }
 
     fn traverse_foreign_item(&mut self, mut i: ForeignItem) -> ForeignItem {
         i.span = self.reinsert_comment_at(i.span);
+        i.span = self.insert_marker(i.id, i.span);
         i
     }
 }
@@ -52,6 +52,14 @@ pub trait Traversal: Sized {
         traverse_item_def(self, i)
     }
 
+    fn traverse_struct_field(&mut self, f: StructField) -> StructField {
+        f
+    }
+
+    fn traverse_variant(&mut self, v: Variant) -> Variant {
+        traverse_variant_def(self, v)
+    }
+
     fn traverse_foreign_item(&mut self, i: ForeignItem) -> ForeignItem {
         i
     }
@@ -85,6 +93,8 @@ traversable_impl!(Mod, traverse_mod);
 traversable_impl!(ForeignMod, traverse_foreign_mod);
 traversable_impl!(Item, traverse_item);
 traversable_impl!(ForeignItem, traverse_foreign_item);
+traversable_impl!(StructField, traverse_struct_field);
+traversable_impl!(Variant, traverse_variant);
 
 impl<A: Traversable> Traversable for Vec<A> {
     fn traverse<T: Traversal>(self, t: &mut T) -> Self {
@@ -255,9 +265,14 @@ pub fn traverse_item_def<W: Traversal>(walk: &mut W, mut i: Item) -> Item {
         ItemKind::ExternCrate(u) => ItemKind::ExternCrate(u),
         ItemKind::GlobalAsm(u) => ItemKind::GlobalAsm(u),
         ItemKind::Ty(l, r) => ItemKind::Ty(l, r),
-        ItemKind::Enum(l, r) => ItemKind::Enum(l, r),
-        ItemKind::Struct(l, r) => ItemKind::Struct(l, r),
-        ItemKind::Union(l, r) => ItemKind::Union(l, r),
+        ItemKind::Enum(def, gen) => ItemKind::Enum(
+            EnumDef {
+                variants: def.variants.traverse(walk),
+            },
+            gen,
+        ),
+        ItemKind::Struct(data, gen) => ItemKind::Struct(traverse_variant_data_def(walk, data), gen),
+        ItemKind::Union(data, gen) => ItemKind::Union(traverse_variant_data_def(walk, data), gen),
         ItemKind::TraitAlias(l, r) => ItemKind::TraitAlias(l, r),
         ItemKind::Mac(m) => ItemKind::Mac(m),
         ItemKind::MacroDef(m) => ItemKind::MacroDef(m),
@@ -265,3 +280,18 @@ pub fn traverse_item_def<W: Traversal>(walk: &mut W, mut i: Item) -> Item {
     };
     i
 }
+
+pub fn traverse_variant_def<W: Traversal>(walk: &mut W, mut v: Variant) -> Variant {
+    v.node.data = traverse_variant_data_def(walk, v.node.data);
+    v
+}
+
+pub fn traverse_variant_data_def<W: Traversal>(walk: &mut W, data: VariantData) -> VariantData {
+    match data {
+        VariantData::Struct(fields, recovered) => {
+            VariantData::Struct(fields.traverse(walk), recovered)
+        }
+        VariantData::Tuple(fields, id) => VariantData::Tuple(fields.traverse(walk), id),
+        VariantData::Unit(id) => VariantData::Unit(id),
+    }
+}
@@ -0,0 +1,57 @@
+//! Bindings to the declarations of C headers.
+//!
+//! With `--bindings-for`, only the given headers are translated, and only as far as needed to
+//! call into the C code from Rust: types, enum constants, const macros and `extern "C"`
+//! declarations of the functions and variables, plus the types these depend on. Function bodies
+//! are left out, as are functions and variables with internal linkage since they have no symbol
+//! to link against. The types and names come from the same `TypeConverter` and `Renamer` as in a
+//! full translation, so bindings agree with translated code on every type.
+
+use super::*;
+
+impl<'c> Translation<'c> {
+    /// Is the declaration located in one of the headers to emit bindings for?
+    fn in_bindings_headers(&self, decl: &CDecl) -> bool {
+        let file_path = decl.loc.as_ref().and_then(|loc| loc.file_path.as_ref());
+        match file_path {
+            Some(file_path) => self
+                .tcfg
+                .bindings_headers
+                .iter()
+                .any(|header| file_path.ends_with(header)),
+            None => false,
+        }
+    }
+
+    /// Can a binding be emitted for the declaration? Functions and variables need a symbol that
+    /// is visible to the linker.
+    pub fn is_bindable(&self, decl_kind: &CDeclKind) -> bool {
+        match *decl_kind {
+            CDeclKind::Function {
+                is_global,
+                is_implicit,
+                ..
+            } => is_global && !is_implicit,
+            CDeclKind::Variable {
+                is_externally_visible,
+                ..
+            } => is_externally_visible,
+            _ => true,
+        }
+    }
+
+    /// Prune all declarations that the bindings of the headers don't depend on
+    pub fn prune_to_bindings(&mut self) {
+        let roots = self
+            .ast_context
+            .c_decls_top
+            .iter()
+            .cloned()
+            .filter(|&decl_id| {
+                let decl = &self.ast_context[decl_id];
+                self.in_bindings_headers(decl) && self.is_bindable(&decl.kind)
+            })
+            .collect();
+        self.ast_context.prune_unreachable_decls(roots);
+    }
+}
@@ -87,7 +87,12 @@ impl<'a> Translation<'a> {
 
         for (field_name, ty, bitfield_width, bit_index, platform_ty_bitwidth) in field_info {
             let ctype = ty.ctype;
-            let ty = self.convert_type(ctype)?;
+            // Bitfields hold Rust enums as integers
+            let ty = if bitfield_width.is_some() {
+                self.convert_boundary_type(ctype)?
+            } else {
+                self.convert_type(ctype)?
+            };
             let bitfield_width = match bitfield_width {
                 // Bitfield widths of 0 should just be markers for clang,
                 // we shouldn't need to explicitly handle it ourselves
@@ -403,8 +408,8 @@ impl<'a> Translation<'a> {
                     let field = init.map(|init| mk().field(field_name, init));
                     fields.push(field);
                 }
-                Both(field_id, (field_name, _, bitfield_width, _, _)) => {
-                    let expr = self.convert_expr(ctx.used(), *field_id)?;
+                Both(field_id, (field_name, ty, bitfield_width, _, _)) => {
+                    let expr = self.convert_expr_as(ctx.used(), *field_id, ty.ctype)?;
 
                     if !expr.is_pure() {
                         return Err(TranslationError::generic(
@@ -413,6 +418,9 @@ impl<'a> Translation<'a> {
                     }
 
                     if bitfield_width.is_some() {
+                        // Bitfields hold Rust enums as integers
+                        let expr =
+                            expr.result_map(|expr| self.rust_enum_to_boundary(ty.ctype, expr))?;
                         bitfield_inits.push((field_name, expr));
 
                         continue;
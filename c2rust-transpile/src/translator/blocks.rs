@@ -0,0 +1,804 @@
+//! Translation of Apple blocks.
+//!
+//! Block literals become reference counted Rust closures (`Option<Rc<dyn Fn(..) -> ..>>`) and
+//! `__block` variables become shared cells that the closures capture by reference. Functions
+//! that are visible to C take the block ABI struct (`*mut __BlockLiteral`) instead of a closure
+//! for their block parameters, and we convert between the two at the function boundary.
+//!
+//! Closures passed to C become heap blocks that the blocks runtime reference counts: the caller
+//! holds a reference for the duration of the call, C code keeps the block alive with
+//! `Block_copy`, and the last `Block_release` drops the closure. Blocks received from C are
+//! copied for as long as the closure wrapping them lives.
+
+use super::*;
+
+/// `BLOCK_NEEDS_FREE | BLOCK_HAS_COPY_DISPOSE` from the blocks runtime: the block lives on the
+/// heap, and `Block_release` calls the dispose helper of its descriptor before freeing it
+const BLOCK_MALLOC_FLAGS: u128 = (1 << 24) | (1 << 25);
+
+const BLOCK_LITERAL: &str = "__BlockLiteral";
+const BLOCK_DESCRIPTOR: &str = "__BlockDescriptor";
+const BLOCK_REF: &str = "__BlockRef";
+const BLOCK_REFCOUNT_ONE: &str = "__BLOCK_REFCOUNT_ONE";
+const NS_CONCRETE_MALLOC_BLOCK: &str = "_NSConcreteMallocBlock";
+
+/// Names of the generated helpers that convert between one particular block signature and the
+/// block ABI struct.
+#[derive(Clone, Debug)]
+pub struct BlockAbiHelpers {
+    pub to_abi: String,
+    pub from_abi: String,
+}
+
+fn c_void_ty() -> P<Ty> {
+    mk().path_ty(vec!["libc", "c_void"])
+}
+
+/// The type of block parameters of functions that use the block ABI
+pub fn block_literal_ptr_ty() -> P<Ty> {
+    mk().mutbl().ptr_ty(mk().path_ty(vec![BLOCK_LITERAL]))
+}
+
+impl<'c> Translation<'c> {
+    /// Is this variable declared with the `__block` storage qualifier?
+    pub fn is_byref_block_var(&self, decl_id: CDeclId) -> bool {
+        match self.ast_context[decl_id].kind {
+            CDeclKind::Variable { ref attrs, .. } => attrs.contains(&c_ast::Attribute::Blocks),
+            _ => false,
+        }
+    }
+
+    /// Do block parameters of this function use the C block ABI rather than Rust closures?
+    /// This is the case for every function that C code can call or that is defined in C.
+    pub fn uses_block_abi(&self, decl_id: CDeclId) -> bool {
+        match self.ast_context[decl_id].kind {
+            CDeclKind::Function {
+                is_global, body, ..
+            } => is_global || body.is_none(),
+            _ => false,
+        }
+    }
+
+    /// Convert a block literal into a shared closure. Captured `__block` variables and captured
+    /// blocks are cloned into the closure so that they stay shared with the enclosing function.
+    pub fn convert_block_literal(
+        &self,
+        ctx: ExprContext,
+        ty: CQualTypeId,
+        params: &[CDeclId],
+        body: CStmtId,
+        captures: &[BlockCapture],
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let fn_ty = match self.ast_context.resolve_type(ty.ctype).kind {
+            CTypeKind::BlockPointer(fn_ty) => fn_ty,
+            _ => {
+                return Err(TranslationError::generic(
+                    "Block literal must have block type",
+                ))
+            }
+        };
+        let ret_ty = match self.ast_context.resolve_type(fn_ty.ctype).kind {
+            CTypeKind::Function(ret, _, _, _, _) => ret,
+            _ => {
+                return Err(TranslationError::generic(
+                    "Block literal must have function type",
+                ))
+            }
+        };
+        let is_void_ret = self.ast_context.resolve_type(ret_ty.ctype).kind == CTypeKind::Void;
+
+        let mut prelude = vec![];
+        for capture in captures {
+            let is_shared = capture.by_ref
+                || match self.ast_context[capture.variable].kind {
+                    CDeclKind::Variable { typ, .. } => self.ast_context.is_block_pointer(typ.ctype),
+                    _ => false,
+                };
+            if !is_shared {
+                continue;
+            }
+
+            let name = self
+                .renamer
+                .borrow()
+                .get(&capture.variable)
+                .ok_or_else(|| format_err!("Captured variable was not declared"))?;
+            let clone =
+                mk().method_call_expr(mk().ident_expr(&name), "clone", vec![] as Vec<P<Expr>>);
+            let local = mk().local(mk().ident_pat(&name), None as Option<P<Ty>>, Some(clone));
+            prelude.push(mk().local_stmt(P(local)));
+        }
+
+        // Returns in the body of the block return from the block
+        let outer_return_type = std::mem::replace(
+            &mut self.function_context.borrow_mut().return_type,
+            Some(ret_ty),
+        );
+        let closure =
+            self.with_scope(|| -> Result<P<Expr>, TranslationError> {
+                let mut args = vec![];
+                let mut body_stmts = vec![];
+                for &param in params {
+                    let (ident, typ) = match self.ast_context[param].kind {
+                        CDeclKind::Variable { ref ident, typ, .. } => (ident, typ),
+                        _ => {
+                            return Err(TranslationError::generic(
+                                "Block parameter must be a variable",
+                            ))
+                        }
+                    };
+                    let (ty, mutbl, _) = self.convert_variable(ctx, None, typ)?;
+                    let pat =
+                        if ident.is_empty() {
+                            mk().wild_pat()
+                        } else {
+                            let name = self.renamer.borrow_mut().insert(param, ident).ok_or_else(
+                                || format_err!("Failed to insert block parameter '{}'", ident),
+                            )?;
+                            mk().set_mutbl(mutbl).ident_pat(name)
+                        };
+                    args.push(mk().arg(ty, pat));
+                    body_stmts.append(&mut self.compute_variable_array_sizes(ctx, typ.ctype)?);
+                }
+
+                let (ret, implicit_ret) = if is_void_ret {
+                    (
+                        FunctionRetTy::Default(DUMMY_SP),
+                        cfg::ImplicitReturnType::Void,
+                    )
+                } else {
+                    let ret = self.convert_type(ret_ty.ctype)?;
+                    (
+                        FunctionRetTy::Ty(ret),
+                        cfg::ImplicitReturnType::NoImplicitReturnType,
+                    )
+                };
+
+                let body_ids = match self.ast_context[body].kind {
+                    CStmtKind::Compound(ref stmts) => stmts,
+                    _ => {
+                        return Err(TranslationError::generic(
+                            "Block body must be a compound statement",
+                        ))
+                    }
+                };
+                let name = format!("<block_{:?}>", body);
+                body_stmts.append(&mut self.convert_function_body(
+                    ctx,
+                    &name,
+                    body_ids,
+                    implicit_ret,
+                )?);
+
+                let body = mk().block_expr(mk().unsafe_().block(body_stmts));
+                let decl = mk().fn_decl(args, ret, false);
+                Ok(mk().closure_expr(CaptureBy::Value, Movability::Movable, decl, body))
+            });
+        self.function_context.borrow_mut().return_type = outer_return_type;
+        let closure = closure?;
+
+        let closure_ty = self
+            .type_converter
+            .borrow_mut()
+            .convert_block_closure(&self.ast_context, fn_ty)?;
+        let rc = mk().call_expr(
+            mk().path_expr(vec!["", "std", "rc", "Rc", "new"]),
+            vec![closure],
+        );
+        let block = mk().call_expr(
+            mk().ident_expr("Some"),
+            vec![mk().cast_expr(rc, closure_ty)],
+        );
+
+        if prelude.is_empty() {
+            Ok(WithStmts::new_val(block))
+        } else {
+            prelude.push(mk().expr_stmt(block));
+            Ok(WithStmts::new_val(mk().block_expr(mk().block(prelude))))
+        }
+    }
+
+    /// Convert a call through a block pointer. The block is borrowed for the call rather than
+    /// cloned.
+    pub fn convert_block_call(
+        &self,
+        ctx: ExprContext,
+        func: CExprId,
+        args: &[CExprId],
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let func = match self.ast_context[func].kind {
+            CExprKind::ImplicitCast(_, block, CastKind::LValueToRValue, _, _) => block,
+            _ => func,
+        };
+        let callee = self.convert_expr(ctx.used(), func)?;
+
+        let call = callee.and_then(|callee| {
+            let callee = mk().method_call_expr(callee, "as_ref", vec![] as Vec<P<Expr>>);
+            let err_msg = mk().lit_expr(mk().str_lit("non-null block pointer"));
+            let callee = mk().method_call_expr(callee, "expect", vec![err_msg]);
+
+            let args = self.convert_exprs(ctx.used(), args)?;
+            let res: Result<_, TranslationError> =
+                Ok(args.map(|args| mk().call_expr(callee, args)));
+            res
+        })?;
+
+        self.convert_side_effects_expr(
+            ctx,
+            call,
+            "Block call expression is not supposed to be used",
+        )
+    }
+
+    /// Convert the declaration of a `__block` variable. These live in a shared cell so that
+    /// blocks capturing the variable observe and perform updates to it.
+    pub fn convert_byref_block_var(
+        &self,
+        ctx: ExprContext,
+        decl_id: CDeclId,
+        rust_name: &str,
+        initializer: Option<CExprId>,
+        typ: CQualTypeId,
+    ) -> Result<cfg::DeclStmtInfo, TranslationError> {
+        let has_self_reference = if let Some(expr_id) = initializer {
+            self.has_decl_reference(decl_id, expr_id)
+        } else {
+            false
+        };
+
+        let (ty, _, init) = self.convert_variable(ctx, initializer, typ)?;
+        let mut init = init?;
+        let mut stmts = init.stmts_mut().drain(..).collect::<Vec<_>>();
+        let init = init.into_value();
+
+        let zeroed = self
+            .implicit_default_expr(typ.ctype, false)?
+            .to_pure_expr()
+            .expect("Expected decl initializer to not have any statements");
+
+        let cell_ty = mk().path_ty(vec![
+            mk().path_segment(""),
+            mk().path_segment("std"),
+            mk().path_segment("rc"),
+            mk().path_segment_with_args(
+                "Rc",
+                mk().angle_bracketed_args(vec![mk().path_ty(vec![
+                    mk().path_segment(""),
+                    mk().path_segment("std"),
+                    mk().path_segment("cell"),
+                    mk().path_segment_with_args("Cell", mk().angle_bracketed_args(vec![ty])),
+                ])]),
+            ),
+        ]);
+        let new_cell = |val: P<Expr>| {
+            let cell = mk().call_expr(
+                mk().path_expr(vec!["", "std", "cell", "Cell", "new"]),
+                vec![val],
+            );
+            mk().call_expr(
+                mk().path_expr(vec!["", "std", "rc", "Rc", "new"]),
+                vec![cell],
+            )
+        };
+
+        let decl = mk().local(
+            mk().ident_pat(rust_name),
+            Some(cell_ty.clone()),
+            Some(new_cell(zeroed)),
+        );
+        let decl = vec![mk().local_stmt(P(decl))];
+
+        let place = mk().unary_expr(
+            ast::UnOp::Deref,
+            mk().method_call_expr(mk().ident_expr(rust_name), "as_ptr", vec![] as Vec<P<Expr>>),
+        );
+        let mut assign = stmts.clone();
+        assign.push(mk().semi_stmt(mk().assign_expr(place, init.clone())));
+
+        let decl_and_assign = if has_self_reference {
+            let mut decl_and_assign = decl.clone();
+            decl_and_assign.extend(assign.iter().cloned());
+            decl_and_assign
+        } else {
+            let local = mk().local(
+                mk().ident_pat(rust_name),
+                Some(cell_ty),
+                Some(new_cell(init)),
+            );
+            stmts.push(mk().local_stmt(P(local)));
+            stmts
+        };
+
+        Ok(cfg::DeclStmtInfo::new(decl, assign, decl_and_assign))
+    }
+
+    fn emit_block_abi_items(&self) {
+        *self.emitted_block_abi.borrow_mut() = true;
+        let mut item_store = self.item_store.borrow_mut();
+
+        let ulong = self.convert_primitive(CTypeKind::ULong);
+        let int = self.convert_primitive(CTypeKind::Int);
+        let literal = mk()
+            .pub_()
+            .call_attr("derive", vec!["Copy", "Clone"])
+            .call_attr("repr", vec!["C"])
+            .struct_item(
+                BLOCK_LITERAL,
+                vec![
+                    mk().pub_()
+                        .struct_field("isa", mk().mutbl().ptr_ty(c_void_ty())),
+                    mk().pub_().struct_field("flags", int.clone()),
+                    mk().pub_().struct_field("reserved", int.clone()),
+                    mk().pub_()
+                        .struct_field("invoke", mk().mutbl().ptr_ty(c_void_ty())),
+                    mk().pub_().struct_field(
+                        "descriptor",
+                        mk().mutbl().ptr_ty(mk().path_ty(vec![BLOCK_DESCRIPTOR])),
+                    ),
+                    // Not part of the ABI, but C code never looks past the descriptor
+                    mk().pub_()
+                        .struct_field("closure", mk().mutbl().ptr_ty(c_void_ty())),
+                ],
+            );
+
+        let helper_ty = |args: Vec<P<Ty>>| {
+            let args = args
+                .into_iter()
+                .map(|ty| mk().arg(ty, mk().wild_pat()))
+                .collect();
+            let fn_ty = mk().unsafe_().abi("C").barefn_ty(mk().fn_decl(
+                args,
+                FunctionRetTy::Default(DUMMY_SP),
+                false,
+            ));
+            mk().path_ty(vec![mk().path_segment_with_args(
+                "Option",
+                mk().angle_bracketed_args(vec![fn_ty]),
+            )])
+        };
+        let descriptor = mk()
+            .pub_()
+            .call_attr("derive", vec!["Copy", "Clone"])
+            .call_attr("repr", vec!["C"])
+            .struct_item(
+                BLOCK_DESCRIPTOR,
+                vec![
+                    mk().pub_().struct_field("reserved", ulong.clone()),
+                    mk().pub_().struct_field("size", ulong.clone()),
+                    mk().pub_().struct_field(
+                        "copy",
+                        helper_ty(vec![
+                            mk().mutbl().ptr_ty(c_void_ty()),
+                            mk().ptr_ty(c_void_ty()),
+                        ]),
+                    ),
+                    mk().pub_()
+                        .struct_field("dispose", helper_ty(vec![mk().ptr_ty(c_void_ty())])),
+                ],
+            );
+
+        // A reference to a heap block that `Block_release`s it when dropped
+        let block_ref = mk().pub_().struct_item(
+            BLOCK_REF,
+            vec![mk().pub_().struct_field("block", block_literal_ptr_ty())],
+        );
+        let release = mk().call_expr(
+            mk().path_expr(vec!["_Block_release"]),
+            vec![mk().cast_expr(
+                mk().field_expr(mk().ident_expr("self"), "block"),
+                mk().ptr_ty(c_void_ty()),
+            )],
+        );
+        let drop = mk().method_impl_item(
+            "drop",
+            mk().fn_decl(
+                vec![mk().arg(
+                    mk().set_mutbl(Mutability::Mutable)
+                        .ref_ty(mk().path_ty(vec!["Self"])),
+                    mk().ident_pat("self"),
+                )],
+                FunctionRetTy::Default(DUMMY_SP),
+                false,
+            ),
+            mk().block(vec![mk().expr_stmt(
+                mk().block_expr(mk().unsafe_().block(vec![mk().semi_stmt(release)])),
+            )]),
+        );
+        let drop_impl = mk().trait_impl_item(
+            vec!["", "std", "ops", "Drop"],
+            mk().path_ty(vec![BLOCK_REF]),
+            vec![drop],
+        );
+
+        // Apple's runtime counts references in steps of two, the LLVM one in steps of one
+        let refcount_one = |cfg: &str, count: u128| {
+            mk().call_attr("cfg", vec![cfg]).const_item(
+                BLOCK_REFCOUNT_ONE,
+                int.clone(),
+                mk().lit_expr(mk().int_lit(count, "")),
+            )
+        };
+        let apple = "any(target_os = \"macos\", target_os = \"ios\")";
+
+        let isa = mk().mutbl().static_foreign_item(
+            NS_CONCRETE_MALLOC_BLOCK,
+            mk().array_ty(
+                mk().mutbl().ptr_ty(c_void_ty()),
+                mk().lit_expr(mk().int_lit(32, "")),
+            ),
+        );
+        let block_copy = mk().fn_foreign_item(
+            "_Block_copy",
+            mk().fn_decl(
+                vec![mk().arg(mk().ptr_ty(c_void_ty()), mk().wild_pat())],
+                FunctionRetTy::Ty(mk().mutbl().ptr_ty(c_void_ty())),
+                false,
+            ),
+        );
+        let block_release = mk().fn_foreign_item(
+            "_Block_release",
+            mk().fn_decl(
+                vec![mk().arg(mk().ptr_ty(c_void_ty()), mk().wild_pat())],
+                FunctionRetTy::Default(DUMMY_SP),
+                false,
+            ),
+        );
+
+        item_store.items.push(literal);
+        item_store.items.push(descriptor);
+        item_store.items.push(block_ref);
+        item_store.items.push(drop_impl);
+        item_store.items.push(refcount_one(apple, 2));
+        item_store
+            .items
+            .push(refcount_one(&format!("not({})", apple), 1));
+        item_store.foreign_items.push(isa);
+        item_store.foreign_items.push(block_copy);
+        item_store.foreign_items.push(block_release);
+    }
+
+    /// Get the helpers converting between the block ABI struct and shared closures of the
+    /// given block type, generating them if needed.
+    pub fn block_abi_helpers(
+        &self,
+        block_ty: CTypeId,
+    ) -> Result<BlockAbiHelpers, TranslationError> {
+        let block_ty = self.ast_context.resolve_type_id(block_ty);
+        if let Some(helpers) = self.block_abi_helpers.borrow().get(&block_ty) {
+            return Ok(helpers.clone());
+        }
+        if !*self.emitted_block_abi.borrow() {
+            self.emit_block_abi_items();
+        }
+
+        let fn_ty = match self.ast_context[block_ty].kind {
+            CTypeKind::BlockPointer(fn_ty) => fn_ty,
+            _ => return Err(TranslationError::generic("Expected a block pointer type")),
+        };
+        let (ret, params) = match self.ast_context.resolve_type(fn_ty.ctype).kind {
+            CTypeKind::Function(ret, ref params, _, _, _) => (ret, params.clone()),
+            _ => {
+                return Err(TranslationError::generic(
+                    "Block pointer to non-function type",
+                ))
+            }
+        };
+        let closure_ty = self
+            .type_converter
+            .borrow_mut()
+            .convert_block_closure(&self.ast_context, fn_ty)?;
+        let opt_closure_ty = mk().path_ty(vec![mk().path_segment_with_args(
+            "Option",
+            mk().angle_bracketed_args(vec![closure_ty.clone()]),
+        )]);
+        let ret = if self.ast_context.resolve_type(ret.ctype).kind == CTypeKind::Void {
+            FunctionRetTy::Default(DUMMY_SP)
+        } else {
+            FunctionRetTy::Ty(self.convert_type(ret.ctype)?)
+        };
+        let param_tys = params
+            .iter()
+            .map(|param| self.convert_type(param.ctype))
+            .collect::<Result<Vec<_>, _>>()?;
+        let arg_names: Vec<String> = (0..param_tys.len()).map(|i| format!("arg{}", i)).collect();
+        let arg_exprs = || {
+            arg_names
+                .iter()
+                .map(|name| mk().ident_expr(name))
+                .collect::<Vec<_>>()
+        };
+        let typed_args = || {
+            param_tys
+                .iter()
+                .zip(&arg_names)
+                .map(|(ty, name)| mk().arg(ty.clone(), mk().ident_pat(name)))
+                .collect::<Vec<_>>()
+        };
+        let block_expr = || mk().ident_expr("block");
+        let block_field = |field: &str| {
+            mk().field_expr(
+                mk().paren_expr(mk().unary_expr(ast::UnOp::Deref, block_expr())),
+                field,
+            )
+        };
+
+        let n = self.block_abi_helpers.borrow().len();
+        let invoke_name = format!("__block_invoke_{}", n);
+        let helpers = BlockAbiHelpers {
+            to_abi: format!("__block_to_abi_{}", n),
+            from_abi: format!("__block_from_abi_{}", n),
+        };
+
+        // unsafe extern "C" fn __block_invoke_N(block: *mut __BlockLiteral, args..) -> ret {
+        //     let closure = &*((*block).closure as *const Rc<dyn Fn(..) -> ..>);
+        //     closure(args..)
+        // }
+        let mut invoke_args = vec![mk().arg(block_literal_ptr_ty(), mk().ident_pat("block"))];
+        invoke_args.extend(typed_args());
+        let closure_ptr = mk().cast_expr(block_field("closure"), mk().ptr_ty(closure_ty.clone()));
+        let closure_ref = mk().addr_of_expr(mk().unary_expr(ast::UnOp::Deref, closure_ptr));
+        let invoke = mk().unsafe_().abi("C").fn_item(
+            &invoke_name,
+            mk().fn_decl(invoke_args, ret.clone(), false),
+            mk().block(vec![
+                mk().local_stmt(P(mk().local(
+                    mk().ident_pat("closure"),
+                    None as Option<P<Ty>>,
+                    Some(closure_ref),
+                ))),
+                mk().expr_stmt(mk().call_expr(mk().ident_expr("closure"), arg_exprs())),
+            ]),
+        );
+
+        // unsafe extern "C" fn __block_dispose_N(block: *const c_void) {
+        //     drop(Box::from_raw((*(block as *mut __BlockLiteral)).closure as *mut Rc<..>));
+        // }
+        let dispose_name = format!("__block_dispose_{}", n);
+        let literal_ptr = mk().cast_expr(
+            mk().ident_expr("block"),
+            mk().mutbl().ptr_ty(mk().path_ty(vec![BLOCK_LITERAL])),
+        );
+        let closure_box = mk().call_expr(
+            mk().path_expr(vec!["Box", "from_raw"]),
+            vec![mk().cast_expr(
+                mk().field_expr(
+                    mk().paren_expr(mk().unary_expr(ast::UnOp::Deref, literal_ptr)),
+                    "closure",
+                ),
+                mk().mutbl().ptr_ty(closure_ty.clone()),
+            )],
+        );
+        let dispose = mk().unsafe_().abi("C").fn_item(
+            &dispose_name,
+            mk().fn_decl(
+                vec![mk().arg(mk().ptr_ty(c_void_ty()), mk().ident_pat("block"))],
+                FunctionRetTy::Default(DUMMY_SP),
+                false,
+            ),
+            mk().block(vec![mk().semi_stmt(
+                mk().call_expr(mk().ident_expr("drop"), vec![closure_box]),
+            )]),
+        );
+
+        // static mut __BLOCK_DESCRIPTOR_N: __BlockDescriptor = __BlockDescriptor { .. };
+        let descriptor_name = format!("__BLOCK_DESCRIPTOR_{}", n);
+        let size_of = mk().call_expr(
+            mk().path_expr(vec![
+                mk().path_segment(""),
+                mk().path_segment("std"),
+                mk().path_segment("mem"),
+                mk().path_segment_with_args(
+                    "size_of",
+                    mk().angle_bracketed_args(vec![mk().path_ty(vec![BLOCK_LITERAL])]),
+                ),
+            ]),
+            vec![] as Vec<P<Expr>>,
+        );
+        let descriptor_init = mk().struct_expr(
+            vec![BLOCK_DESCRIPTOR],
+            vec![
+                mk().field("reserved", mk().lit_expr(mk().int_lit(0, ""))),
+                mk().field(
+                    "size",
+                    mk().cast_expr(size_of.clone(), self.convert_primitive(CTypeKind::ULong)),
+                ),
+                // Only blocks on the stack are copied with the copy helper
+                mk().field("copy", mk().ident_expr("None")),
+                mk().field(
+                    "dispose",
+                    mk().call_expr(
+                        mk().ident_expr("Some"),
+                        vec![mk().ident_expr(&dispose_name)],
+                    ),
+                ),
+            ],
+        );
+        let descriptor = mk().mutbl().static_item(
+            &descriptor_name,
+            mk().path_ty(vec![BLOCK_DESCRIPTOR]),
+            descriptor_init,
+        );
+
+        // unsafe fn __block_to_abi_N(block: Option<Rc<dyn Fn(..) -> ..>>) -> __BlockRef {
+        //     __BlockRef {
+        //         block: block.map_or(::std::ptr::null_mut(), |closure| {
+        //             let literal = libc::malloc(size_of::<__BlockLiteral>()) as *mut __BlockLiteral;
+        //             *literal = __BlockLiteral { .. };
+        //             literal
+        //         }),
+        //     }
+        // }
+        let void_ptr = |e: P<Expr>| mk().cast_expr(e, mk().mutbl().ptr_ty(c_void_ty()));
+        let boxed = |e: P<Expr>| {
+            let boxed = mk().call_expr(mk().path_expr(vec!["Box", "new"]), vec![e]);
+            mk().call_expr(mk().path_expr(vec!["Box", "into_raw"]), vec![boxed])
+        };
+        let isa = mk().method_call_expr(
+            mk().ident_expr(NS_CONCRETE_MALLOC_BLOCK),
+            "as_mut_ptr",
+            vec![] as Vec<P<Expr>>,
+        );
+        let flags = mk().binary_expr(
+            BinOpKind::BitOr,
+            mk().lit_expr(mk().int_lit(BLOCK_MALLOC_FLAGS, "")),
+            mk().ident_expr(BLOCK_REFCOUNT_ONE),
+        );
+        let literal = mk().struct_expr(
+            vec![BLOCK_LITERAL],
+            vec![
+                mk().field("isa", void_ptr(isa)),
+                mk().field("flags", flags),
+                mk().field("reserved", mk().lit_expr(mk().int_lit(0, ""))),
+                mk().field("invoke", void_ptr(mk().ident_expr(&invoke_name))),
+                mk().field(
+                    "descriptor",
+                    mk().set_mutbl(Mutability::Mutable)
+                        .addr_of_expr(mk().ident_expr(&descriptor_name)),
+                ),
+                mk().field("closure", void_ptr(boxed(mk().ident_expr("closure")))),
+            ],
+        );
+        // The blocks runtime frees heap blocks with `free`
+        let malloc = mk().cast_expr(
+            mk().call_expr(mk().path_expr(vec!["libc", "malloc"]), vec![size_of]),
+            mk().mutbl().ptr_ty(mk().path_ty(vec![BLOCK_LITERAL])),
+        );
+        let to_abi_closure = mk().closure_expr(
+            CaptureBy::Ref,
+            Movability::Movable,
+            mk().fn_decl(
+                vec![mk().arg(mk().infer_ty(), mk().ident_pat("closure"))],
+                FunctionRetTy::Default(DUMMY_SP),
+                false,
+            ),
+            mk().block_expr(mk().block(vec![
+                mk().local_stmt(P(mk().local(
+                    mk().ident_pat("literal"),
+                    None as Option<P<Ty>>,
+                    Some(malloc),
+                ))),
+                mk().semi_stmt(mk().assign_expr(
+                    mk().unary_expr(ast::UnOp::Deref, mk().ident_expr("literal")),
+                    literal,
+                )),
+                mk().expr_stmt(mk().ident_expr("literal")),
+            ])),
+        );
+        let null = mk().call_expr(
+            mk().path_expr(vec!["", "std", "ptr", "null_mut"]),
+            vec![] as Vec<P<Expr>>,
+        );
+        let block_ref = mk().struct_expr(
+            vec![BLOCK_REF],
+            vec![mk().field(
+                "block",
+                mk().method_call_expr(block_expr(), "map_or", vec![null, to_abi_closure]),
+            )],
+        );
+        let to_abi = mk().unsafe_().fn_item(
+            &helpers.to_abi,
+            mk().fn_decl(
+                vec![mk().arg(opt_closure_ty.clone(), mk().ident_pat("block"))],
+                FunctionRetTy::Ty(mk().path_ty(vec![BLOCK_REF])),
+                false,
+            ),
+            mk().block(vec![mk().expr_stmt(block_ref)]),
+        );
+
+        // unsafe fn __block_from_abi_N(block: *mut __BlockLiteral) -> Option<Rc<dyn Fn(..) -> ..>> {
+        //     if block.is_null() { return None; }
+        //     let block = __BlockRef { block: _Block_copy(block as *const c_void) as *mut _ };
+        //     let invoke: unsafe extern "C" fn(*mut __BlockLiteral, ..) -> .. =
+        //         ::std::mem::transmute((*block.block).invoke);
+        //     Some(Rc::new(move |args..| unsafe { invoke(block.block, args..) }) as Rc<dyn Fn(..) -> ..>)
+        // }
+        let mut invoke_ty_args = vec![mk().arg(block_literal_ptr_ty(), mk().wild_pat())];
+        invoke_ty_args.extend(
+            param_tys
+                .iter()
+                .map(|ty| mk().arg(ty.clone(), mk().wild_pat())),
+        );
+        let invoke_ty =
+            mk().unsafe_()
+                .abi("C")
+                .barefn_ty(mk().fn_decl(invoke_ty_args, ret.clone(), false));
+        let early_return = mk().ifte_expr(
+            mk().method_call_expr(block_expr(), "is_null", vec![] as Vec<P<Expr>>),
+            mk().block(vec![
+                mk().semi_stmt(mk().return_expr(Some(mk().ident_expr("None"))))
+            ]),
+            None as Option<P<Expr>>,
+        );
+        // The closure keeps a copy of the block, which may be on the stack of its caller
+        let copy = mk().call_expr(
+            mk().path_expr(vec!["_Block_copy"]),
+            vec![mk().cast_expr(block_expr(), mk().ptr_ty(c_void_ty()))],
+        );
+        let block_copy = mk().struct_expr(
+            vec![BLOCK_REF],
+            vec![mk().field("block", mk().cast_expr(copy, block_literal_ptr_ty()))],
+        );
+        let copied_block = || mk().field_expr(block_expr(), "block");
+        let invoke_fn = transmute_expr(
+            mk().infer_ty(),
+            mk().infer_ty(),
+            mk().field_expr(
+                mk().paren_expr(mk().unary_expr(ast::UnOp::Deref, copied_block())),
+                "invoke",
+            ),
+            self.tcfg.emit_no_std,
+        );
+        let mut call_args = vec![copied_block()];
+        call_args.extend(arg_exprs());
+        let forward = mk().closure_expr(
+            CaptureBy::Value,
+            Movability::Movable,
+            mk().fn_decl(typed_args(), FunctionRetTy::Default(DUMMY_SP), false),
+            mk().block_expr(mk().unsafe_().block(vec![
+                mk().expr_stmt(mk().call_expr(mk().ident_expr("invoke"), call_args)),
+            ])),
+        );
+        let rc = mk().call_expr(
+            mk().path_expr(vec!["", "std", "rc", "Rc", "new"]),
+            vec![forward],
+        );
+        let from_abi = mk().unsafe_().fn_item(
+            &helpers.from_abi,
+            mk().fn_decl(
+                vec![mk().arg(block_literal_ptr_ty(), mk().ident_pat("block"))],
+                FunctionRetTy::Ty(opt_closure_ty),
+                false,
+            ),
+            mk().block(vec![
+                mk().expr_stmt(early_return),
+                mk().local_stmt(P(mk().local(
+                    mk().ident_pat("block"),
+                    None as Option<P<Ty>>,
+                    Some(block_copy),
+                ))),
+                mk().local_stmt(P(mk().local(
+                    mk().ident_pat("invoke"),
+                    Some(invoke_ty),
+                    Some(invoke_fn),
+                ))),
+                mk().expr_stmt(mk().call_expr(
+                    mk().ident_expr("Some"),
+                    vec![mk().cast_expr(rc, closure_ty)],
+                )),
+            ]),
+        );
+
+        {
+            let mut item_store = self.item_store.borrow_mut();
+            item_store.items.push(invoke);
+            item_store.items.push(dispose);
+            item_store.items.push(descriptor);
+            item_store.items.push(to_abi);
+            item_store.items.push(from_abi);
+        }
+        self.block_abi_helpers
+            .borrow_mut()
+            .insert(block_ty, helpers.clone());
+        Ok(helpers)
+    }
+}
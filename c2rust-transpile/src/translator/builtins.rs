@@ -184,6 +184,11 @@ impl<'c> Translation<'c> {
                 })
             }
 
+            "__builtin_va_start" | "__builtin_va_copy" | "__builtin_va_end"
+                if self.stable_va_args().is_some() =>
+            {
+                self.convert_stable_va_part(ctx, builtin_name, args)
+            }
             "__builtin_va_start" => {
                 if ctx.is_unused() && args.len() == 2 {
                     if let Some(va_id) = self.match_vastart(args[0]) {
@@ -261,15 +266,24 @@ impl<'c> Translation<'c> {
             }
 
             "__builtin_alloca" => {
+                let capacity = self.alloca_inline_capacity(args[0]);
                 let count = self.convert_expr(ctx.used(), args[0])?;
                 count.and_then(|count| {
                     let alloca_name = self.renamer.borrow_mut().fresh();
                     let zero_elem = mk().lit_expr(mk().int_lit(0, LitIntType::Unsuffixed));
+                    let count = cast_int(count, "usize");
+                    let (ty, init) = match capacity {
+                        Some(capacity) => (
+                            Some(small_vec_ty(mk().path_ty(vec!["u8"]), capacity)),
+                            small_vec_expr(zero_elem, count),
+                        ),
+                        None => (None, vec_expr(zero_elem, count)),
+                    };
                     Ok(WithStmts::new(
                         vec![mk().local_stmt(P(mk().local(
                             mk().mutbl().ident_pat(&alloca_name),
-                            None as Option<P<Ty>>,
-                            Some(vec_expr(zero_elem, cast_int(count, "usize"))),
+                            ty,
+                            Some(init),
                         )))],
                         mk().method_call_expr(
                             mk().ident_expr(&alloca_name),
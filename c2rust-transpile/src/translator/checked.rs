@@ -0,0 +1,150 @@
+//! Runtime checks for undefined behavior in the translated code.
+//!
+//! In `--checked` mode, the translation panics where the C program would have undefined
+//! behavior instead of silently carrying it over: on null pointer dereferences, on out of
+//! bounds subscripts of constant-size arrays, on signed arithmetic overflow (including negation,
+//! increments and decrements), on out of range shift amounts and on left shifts of negative
+//! values or out of the range of a signed type. The panic messages name the C source location of
+//! the offending expression. Static and const initializers can't run the checks and are
+//! translated unchecked.
+
+use super::*;
+
+impl<'c> Translation<'c> {
+    /// Are runtime checks emitted for expressions translated in this context?
+    pub fn emits_checks(&self, ctx: ExprContext) -> bool {
+        self.tcfg.checked && !ctx.is_static && !ctx.is_const
+    }
+
+    /// Panic message of a failed check of the expression being translated
+    fn check_message(&self, ctx: ExprContext, problem: &str) -> String {
+        let loc = ctx
+            .checked_expr
+            .and_then(|expr_id| self.ast_context[expr_id].loc.as_ref());
+        match loc {
+            Some(loc) => format!("{}: {}", loc, problem),
+            None => problem.to_string(),
+        }
+    }
+
+    /// `{ let name = val; if cond(name) { panic!(msg) } name }`
+    fn checked_block<F>(&self, val: P<Expr>, cond: F, msg: &str) -> P<Expr>
+    where
+        F: FnOnce(P<Expr>) -> P<Expr>,
+    {
+        let name = self.renamer.borrow_mut().fresh();
+        let local = mk().local(mk().ident_pat(&name), None as Option<P<Ty>>, Some(val));
+        let check = mk().semi_stmt(mk().ifte_expr(
+            cond(mk().ident_expr(&name)),
+            mk().block(vec![mk().semi_stmt(self.panic(msg))]),
+            None as Option<P<Expr>>,
+        ));
+        mk().block_expr(mk().block(vec![
+            mk().local_stmt(P(local)),
+            check,
+            mk().expr_stmt(mk().ident_expr(&name)),
+        ]))
+    }
+
+    /// Check that a raw pointer about to be dereferenced is not null
+    pub fn null_checked(&self, ctx: ExprContext, ptr: P<Expr>) -> P<Expr> {
+        if !self.emits_checks(ctx) {
+            return ptr;
+        }
+        let msg = self.check_message(ctx, "null pointer dereference");
+        self.checked_block(
+            ptr,
+            |ptr| mk().method_call_expr(ptr, "is_null", vec![] as Vec<P<Expr>>),
+            &msg,
+        )
+    }
+
+    /// Check a `usize` index into a constant-size array of `len` elements. With `one_past_end`,
+    /// the index `len` is allowed too, for subscripts that only compute an element's address.
+    pub fn bounds_checked(
+        &self,
+        ctx: ExprContext,
+        index: P<Expr>,
+        len: usize,
+        one_past_end: bool,
+    ) -> P<Expr> {
+        if !self.emits_checks(ctx) {
+            return index;
+        }
+        let msg = self.check_message(
+            ctx,
+            &format!("array index out of bounds for array of length {}", len),
+        );
+        let op = if one_past_end {
+            BinOpKind::Gt
+        } else {
+            BinOpKind::Ge
+        };
+        let len = mk().lit_expr(mk().int_lit(len as u128, LitIntType::Unsuffixed));
+        self.checked_block(index, |index| mk().binary_expr(op, index, len), &msg)
+    }
+
+    /// `lhs.method(rhs).expect(msg)` for one of the `checked_*` integer methods
+    pub fn checked_int_op(
+        &self,
+        ctx: ExprContext,
+        method: &str,
+        lhs: P<Expr>,
+        rhs: P<Expr>,
+        problem: &str,
+    ) -> P<Expr> {
+        let msg = self.check_message(ctx, problem);
+        let checked = mk().method_call_expr(lhs, method, vec![rhs]);
+        mk().method_call_expr(checked, "expect", vec![mk().lit_expr(mk().str_lit(&msg))])
+    }
+
+    /// `arg.method().expect(msg)` for one of the unary `checked_*` integer methods
+    pub fn checked_int_unop(
+        &self,
+        ctx: ExprContext,
+        method: &str,
+        arg: P<Expr>,
+        problem: &str,
+    ) -> P<Expr> {
+        let msg = self.check_message(ctx, problem);
+        let checked = mk().method_call_expr(arg, method, vec![] as Vec<P<Expr>>);
+        mk().method_call_expr(checked, "expect", vec![mk().lit_expr(mk().str_lit(&msg))])
+    }
+
+    /// Left shift of a signed integer by a `u32` amount. On top of the shift amount being in
+    /// range, C requires the shifted value to be non-negative and the result to be representable,
+    /// so shifting set bits out or into the sign bit is checked too.
+    pub fn checked_signed_shl(&self, ctx: ExprContext, lhs: P<Expr>, rhs: P<Expr>) -> P<Expr> {
+        let lhs_name = self.renamer.borrow_mut().fresh();
+        let rhs_name = self.renamer.borrow_mut().fresh();
+        let shifted = self.checked_int_op(
+            ctx,
+            "checked_shl",
+            mk().ident_expr(&lhs_name),
+            mk().ident_expr(&rhs_name),
+            "shift amount out of range",
+        );
+        let msg = self.check_message(ctx, "left shift of a negative value or signed overflow");
+        let checked = self.checked_block(
+            shifted,
+            |shifted| {
+                let zero = mk().lit_expr(mk().int_lit(0, LitIntType::Unsuffixed));
+                let negative = mk().binary_expr(BinOpKind::Lt, mk().ident_expr(&lhs_name), zero);
+                let unshifted =
+                    mk().binary_expr(BinOpKind::Shr, shifted, mk().ident_expr(&rhs_name));
+                let lost_bits =
+                    mk().binary_expr(BinOpKind::Ne, unshifted, mk().ident_expr(&lhs_name));
+                mk().binary_expr(BinOpKind::Or, negative, lost_bits)
+            },
+            &msg,
+        );
+
+        let lhs_local = mk().local(mk().ident_pat(&lhs_name), None as Option<P<Ty>>, Some(lhs));
+        let rhs_local = mk().local(mk().ident_pat(&rhs_name), None as Option<P<Ty>>, Some(rhs));
+        mk().block_expr(mk().block(vec![
+            mk().local_stmt(P(lhs_local)),
+            mk().local_stmt(P(rhs_local)),
+            mk().expr_stmt(checked),
+        ]))
+    }
+}
@@ -0,0 +1,659 @@
+//! Translation of C enums into Rust enums.
+//!
+//! By default, C enums become a type alias of their underlying integral type and a constant per
+//! enumerator. When enabled, enums whose values are only ever built from their declared
+//! enumerators are instead translated into `#[repr]` Rust enums, and `switch` statements on them
+//! match their variants. The enums also get a `TryFrom` implementation that rejects undeclared
+//! values.
+//!
+//! A Rust enum holding an undeclared value is undefined behavior, so Rust enums cross the boundary
+//! to C code we don't translate as their integer types: functions take and return them as
+//! integers, as do the bitfields and the variables that C code can access. Translated code
+//! converts the integers it receives with `TryFrom`, panicking on undeclared values. Enums that C
+//! code can store into memory that translated code reads, such as through pointers or struct
+//! fields, keep the integer translation.
+
+use super::*;
+
+use std::collections::HashSet;
+
+fn enum_of(ast_context: &TypedAstContext, ty: CTypeId) -> Option<CEnumId> {
+    match ast_context.resolve_type(ty).kind {
+        CTypeKind::Enum(enum_id) => Some(enum_id),
+        _ => None,
+    }
+}
+
+fn enumerator_value(ast_context: &TypedAstContext, variant: CEnumConstantId) -> i128 {
+    match ast_context[variant].kind {
+        CDeclKind::EnumConstant {
+            value: ConstIntExpr::I(value),
+            ..
+        } => value as i128,
+        CDeclKind::EnumConstant {
+            value: ConstIntExpr::U(value),
+            ..
+        } => value as i128,
+        _ => panic!("{:?} does not point to an enum variant", variant),
+    }
+}
+
+/// The value of the enumerator of the given enum that this expression converts, if any
+fn enumerator_expr_value(
+    ast_context: &TypedAstContext,
+    enum_id: CEnumId,
+    expr_id: CExprId,
+) -> Option<i128> {
+    let variants = match ast_context[enum_id].kind {
+        CDeclKind::Enum { ref variants, .. } => variants,
+        _ => return None,
+    };
+    let declared = |value: i128| {
+        if variants
+            .iter()
+            .any(|&variant| enumerator_value(ast_context, variant) == value)
+        {
+            Some(value)
+        } else {
+            None
+        }
+    };
+
+    match ast_context[expr_id].kind {
+        CExprKind::DeclRef(_, decl_id, _) if variants.contains(&decl_id) => {
+            Some(enumerator_value(ast_context, decl_id))
+        }
+        CExprKind::Literal(_, CLiteral::Integer(value, _)) => declared(value as i128),
+        CExprKind::Unary(_, c_ast::UnOp::Negate, subexpr_id, _) => {
+            match ast_context[subexpr_id].kind {
+                CExprKind::Literal(_, CLiteral::Integer(value, _)) => declared(-(value as i128)),
+                _ => None,
+            }
+        }
+        CExprKind::Paren(_, subexpr_id)
+        | CExprKind::ImplicitCast(_, subexpr_id, CastKind::IntegralCast, _, _) => {
+            enumerator_expr_value(ast_context, enum_id, subexpr_id)
+        }
+        _ => None,
+    }
+}
+
+/// Add the types whose values C code can reach through a value of this type: the type itself,
+/// what it points to, its elements and fields, and the parameters of function pointers.
+fn add_reachable_types(ast_context: &TypedAstContext, ty: CTypeId, seen: &mut HashSet<CTypeId>) {
+    let ty = ast_context.resolve_type_id(ty);
+    if !seen.insert(ty) {
+        return;
+    }
+    match ast_context[ty].kind {
+        CTypeKind::Pointer(pointee) | CTypeKind::BlockPointer(pointee) => {
+            add_reachable_types(ast_context, pointee.ctype, seen)
+        }
+        CTypeKind::ConstantArray(elt, _)
+        | CTypeKind::IncompleteArray(elt)
+        | CTypeKind::VariableArray(elt, _) => add_reachable_types(ast_context, elt, seen),
+        CTypeKind::Function(ret, ref params, ..) => {
+            add_reachable_types(ast_context, ret.ctype, seen);
+            for param in params {
+                add_reachable_types(ast_context, param.ctype, seen);
+            }
+        }
+        CTypeKind::Struct(record_id) | CTypeKind::Union(record_id) => {
+            let fields = match ast_context[record_id].kind {
+                CDeclKind::Struct {
+                    fields: Some(ref fields),
+                    ..
+                }
+                | CDeclKind::Union {
+                    fields: Some(ref fields),
+                    ..
+                } => fields,
+                _ => return,
+            };
+            for &field in fields {
+                if let CDeclKind::Field { typ, .. } = ast_context[field].kind {
+                    add_reachable_types(ast_context, typ.ctype, seen);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The resolved types of values that C code we don't translate can produce or inspect. C code
+/// can call the functions and access the variables that are visible to it, and read and store
+/// values through their pointers and fields. Bitfields are included since their accessors
+/// convert values from integers.
+pub(super) fn foreign_types(ast_context: &TypedAstContext) -> HashSet<CTypeId> {
+    let mut seen = HashSet::new();
+    for (_, decl) in ast_context.iter_decls() {
+        let foreign_ty = match decl.kind {
+            CDeclKind::Function {
+                is_global,
+                body,
+                typ,
+                ..
+            } if is_global || body.is_none() => Some(typ),
+            CDeclKind::Variable {
+                is_defn,
+                is_externally_visible,
+                typ,
+                ..
+            } if is_externally_visible || !is_defn => Some(typ.ctype),
+            CDeclKind::Field {
+                bitfield_width: Some(_),
+                typ,
+                ..
+            } => Some(typ.ctype),
+            _ => None,
+        };
+        if let Some(ty) = foreign_ty {
+            add_reachable_types(ast_context, ty, &mut seen);
+        }
+    }
+    seen
+}
+
+/// The type of a variable that C code we don't translate can access, since it is visible to it or
+/// defined by it
+fn c_visible_variable_type(ast_context: &TypedAstContext, decl_id: CDeclId) -> Option<CTypeId> {
+    match ast_context[decl_id].kind {
+        CDeclKind::Variable {
+            is_defn,
+            is_externally_visible,
+            typ,
+            ..
+        } if is_externally_visible || !is_defn => Some(typ.ctype),
+        _ => None,
+    }
+}
+
+/// Add the enums that C code can store into memory that translated code reads through a value of
+/// this type. Enums that are passed by value, as parameters and return values of functions, are
+/// converted where they cross the boundary instead, as are bitfields.
+fn add_enums_in_memory(
+    ast_context: &TypedAstContext,
+    ty: CTypeId,
+    in_memory: bool,
+    seen: &mut HashSet<(CTypeId, bool)>,
+    enums: &mut HashSet<CEnumId>,
+) {
+    let ty = ast_context.resolve_type_id(ty);
+    if !seen.insert((ty, in_memory)) {
+        return;
+    }
+    match ast_context[ty].kind {
+        CTypeKind::Enum(enum_id) if in_memory => {
+            enums.insert(enum_id);
+        }
+        CTypeKind::Pointer(pointee) => {
+            add_enums_in_memory(ast_context, pointee.ctype, true, seen, enums)
+        }
+        // The closures behind blocks take their parameters without converting them
+        CTypeKind::BlockPointer(pointee) => {
+            if let CTypeKind::Function(ret, ref params, ..) =
+                ast_context.resolve_type(pointee.ctype).kind
+            {
+                add_enums_in_memory(ast_context, ret.ctype, true, seen, enums);
+                for param in params {
+                    add_enums_in_memory(ast_context, param.ctype, true, seen, enums);
+                }
+            }
+        }
+        CTypeKind::ConstantArray(elt, _)
+        | CTypeKind::IncompleteArray(elt)
+        | CTypeKind::VariableArray(elt, _) => {
+            add_enums_in_memory(ast_context, elt, true, seen, enums)
+        }
+        CTypeKind::Function(ret, ref params, ..) => {
+            add_enums_in_memory(ast_context, ret.ctype, false, seen, enums);
+            for param in params {
+                add_enums_in_memory(ast_context, param.ctype, false, seen, enums);
+            }
+        }
+        CTypeKind::Struct(record_id) | CTypeKind::Union(record_id) => {
+            let fields = match ast_context[record_id].kind {
+                CDeclKind::Struct {
+                    fields: Some(ref fields),
+                    ..
+                }
+                | CDeclKind::Union {
+                    fields: Some(ref fields),
+                    ..
+                } => fields,
+                _ => return,
+            };
+            for &field in fields {
+                if let CDeclKind::Field {
+                    typ,
+                    bitfield_width,
+                    ..
+                } = ast_context[field].kind
+                {
+                    let in_memory = bitfield_width.is_none();
+                    add_enums_in_memory(ast_context, typ.ctype, in_memory, seen, enums);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The enums that C code we don't translate can store into memory that translated code reads,
+/// through the functions and variables that are visible to it
+fn foreign_enums_in_memory(ast_context: &TypedAstContext) -> HashSet<CEnumId> {
+    let mut seen = HashSet::new();
+    let mut enums = HashSet::new();
+    for (&decl_id, decl) in ast_context.iter_decls() {
+        let foreign_ty = match decl.kind {
+            CDeclKind::Function {
+                is_global,
+                body,
+                typ,
+                ..
+            } if is_global || body.is_none() => Some(typ),
+            CDeclKind::Variable { .. } => c_visible_variable_type(ast_context, decl_id),
+            _ => None,
+        };
+        if let Some(ty) = foreign_ty {
+            add_enums_in_memory(ast_context, ty, false, &mut seen, &mut enums);
+        }
+    }
+    enums
+}
+
+/// Skip the parentheses around an expression
+fn skip_parens(ast_context: &TypedAstContext, mut expr_id: CExprId) -> CExprId {
+    while let CExprKind::Paren(_, subexpr_id) = ast_context[expr_id].kind {
+        expr_id = subexpr_id;
+    }
+    expr_id
+}
+
+/// Find the enums that can be translated into Rust enums. This excludes enums with duplicate
+/// values or no zero value, enums that integers other than their enumerators are converted into,
+/// and enums whose values C code that we do not translate may store into memory.
+pub fn rust_enum_candidates(ast_context: &TypedAstContext) -> IndexSet<CEnumId> {
+    let mut candidates = IndexSet::new();
+
+    for (&decl_id, decl) in ast_context.iter_decls() {
+        if let CDeclKind::Enum {
+            integral_type: Some(_),
+            ref variants,
+            ..
+        } = decl.kind
+        {
+            let mut values = HashSet::new();
+            let unique = variants
+                .iter()
+                .all(|&variant| values.insert(enumerator_value(ast_context, variant)));
+            if unique && values.contains(&0) {
+                candidates.insert(decl_id);
+            }
+        }
+    }
+
+    // References to variables that C code can access whose values are converted
+    let mut converted_refs = HashSet::new();
+
+    for (_, expr) in ast_context.iter_exprs() {
+        match expr.kind {
+            CExprKind::ImplicitCast(_, src, CastKind::LValueToRValue, _, _) => {
+                converted_refs.insert(skip_parens(ast_context, src));
+            }
+            CExprKind::Binary(_, c_ast::BinOp::Assign, lhs, _, _, _) => {
+                converted_refs.insert(skip_parens(ast_context, lhs));
+            }
+            _ => {}
+        }
+
+        match expr.kind {
+            CExprKind::ImplicitCast(ty, src, kind, _, _)
+            | CExprKind::ExplicitCast(ty, src, kind, _, _) => {
+                let target_enum = enum_of(ast_context, ty.ctype);
+                let source_enum = ast_context[src]
+                    .kind
+                    .get_type()
+                    .and_then(|ty| enum_of(ast_context, ty));
+
+                // Integers converted into the enum
+                if let Some(enum_id) = target_enum {
+                    if source_enum != Some(enum_id)
+                        && enumerator_expr_value(ast_context, enum_id, src).is_none()
+                    {
+                        candidates.remove(&enum_id);
+                    }
+                }
+
+                // Enum values used as anything but an integer
+                if let Some(enum_id) = source_enum {
+                    let is_integral = match kind {
+                        CastKind::IntegralCast | CastKind::ToVoid => true,
+                        _ => false,
+                    };
+                    if target_enum != Some(enum_id) && !is_integral {
+                        candidates.remove(&enum_id);
+                    }
+                }
+            }
+
+            CExprKind::Unary(ty, op, _, _) => match op {
+                c_ast::UnOp::PreIncrement
+                | c_ast::UnOp::PostIncrement
+                | c_ast::UnOp::PreDecrement
+                | c_ast::UnOp::PostDecrement => {
+                    if let Some(enum_id) = enum_of(ast_context, ty.ctype) {
+                        candidates.remove(&enum_id);
+                    }
+                }
+                _ => {}
+            },
+
+            CExprKind::Binary(ty, op, _, _, _, _) if op.underlying_assignment().is_some() => {
+                if let Some(enum_id) = enum_of(ast_context, ty.ctype) {
+                    candidates.remove(&enum_id);
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    // Variables that C code can access hold integers, which are converted where they are read
+    // and assigned. Other references, such as taking their address, would need a Rust enum.
+    for (expr_id, expr) in ast_context.iter_exprs() {
+        if let CExprKind::DeclRef(_, decl_id, _) = expr.kind {
+            if converted_refs.contains(expr_id) {
+                continue;
+            }
+            let enum_id = c_visible_variable_type(ast_context, decl_id)
+                .and_then(|ty| enum_of(ast_context, ty));
+            if let Some(enum_id) = enum_id {
+                candidates.remove(&enum_id);
+            }
+        }
+    }
+
+    // Values that C code we don't translate stores into memory can't be checked
+    let mut foreign_enums = foreign_enums_in_memory(ast_context);
+
+    // Blocks are translated into closures that take and return their values unconverted
+    let mut seen = HashSet::new();
+    for (_, expr) in ast_context.iter_exprs() {
+        if let Some(ty) = expr.kind.get_type() {
+            if ast_context.is_block_pointer(ty) {
+                add_enums_in_memory(ast_context, ty, false, &mut seen, &mut foreign_enums);
+            }
+        }
+    }
+    candidates.retain(|enum_id| !foreign_enums.contains(enum_id));
+
+    candidates
+}
+
+impl<'c> Translation<'c> {
+    /// Is this enum translated into a Rust enum?
+    pub fn is_rust_enum(&self, enum_id: CEnumId) -> bool {
+        self.rust_enums.contains(&enum_id)
+    }
+
+    /// The Rust enum that this type resolves to, if any
+    fn rust_enum_of(&self, ty: CTypeId) -> Option<CEnumId> {
+        enum_of(&self.ast_context, ty).filter(|&enum_id| self.is_rust_enum(enum_id))
+    }
+
+    /// Does this type resolve to a Rust enum?
+    pub fn is_rust_enum_type(&self, ty: CTypeId) -> bool {
+        self.rust_enum_of(ty).is_some()
+    }
+
+    /// Is this a reference to a bitfield or to a variable that C code can access, which hold Rust
+    /// enums as their integer types?
+    pub fn holds_rust_enum_as_integer(&self, expr_id: CExprId) -> bool {
+        let ty = match self.ast_context[skip_parens(&self.ast_context, expr_id)].kind {
+            CExprKind::DeclRef(_, decl_id, _) => {
+                c_visible_variable_type(&self.ast_context, decl_id)
+            }
+            CExprKind::Member(_, _, field_id, _, _) => match self.ast_context[field_id].kind {
+                CDeclKind::Field {
+                    bitfield_width: Some(_),
+                    typ,
+                    ..
+                } => Some(typ.ctype),
+                _ => None,
+            },
+            _ => None,
+        };
+        ty.map_or(false, |ty| self.is_rust_enum_type(ty))
+    }
+
+    /// `::std::convert::TryFrom<int>`
+    fn try_from_path(&self, int_ty: P<Ty>) -> Path {
+        let std_or_core = if self.tcfg.emit_no_std { "core" } else { "std" };
+        mk().path(vec![
+            mk().path_segment(""),
+            mk().path_segment(std_or_core),
+            mk().path_segment("convert"),
+            mk().path_segment_with_args("TryFrom", mk().angle_bracketed_args(vec![int_ty])),
+        ])
+    }
+
+    /// Convert the type of a value that crosses the boundary to C code, see
+    /// `TypeConverter::convert_boundary`
+    pub fn convert_boundary_type(&self, ty: CTypeId) -> Result<P<Ty>, TranslationError> {
+        match self.rust_enum_of(ty) {
+            Some(_) => self
+                .type_converter
+                .borrow_mut()
+                .convert_boundary(&self.ast_context, ty),
+            None => self.convert_type(ty),
+        }
+    }
+
+    /// Convert a value of this type that is passed to C code, turning Rust enums into integers
+    pub fn rust_enum_to_boundary(
+        &self,
+        ty: CTypeId,
+        val: P<Expr>,
+    ) -> Result<P<Expr>, TranslationError> {
+        if self.rust_enum_of(ty).is_none() {
+            return Ok(val);
+        }
+        Ok(mk().cast_expr(val, self.convert_boundary_type(ty)?))
+    }
+
+    /// Convert a value of this type that is received from C code, turning integers into Rust
+    /// enums. Undeclared values panic with the C source location they are received at.
+    pub fn rust_enum_from_boundary(
+        &self,
+        ty: CTypeId,
+        val: P<Expr>,
+        loc: Option<&SrcLoc>,
+    ) -> Result<P<Expr>, TranslationError> {
+        let enum_id = match self.rust_enum_of(ty) {
+            Some(enum_id) => enum_id,
+            None => return Ok(val),
+        };
+        let enum_name = self
+            .type_converter
+            .borrow()
+            .resolve_decl_name(enum_id)
+            .expect("Enums should already be renamed");
+        if let Some(cur_file) = self.cur_file.borrow().as_ref() {
+            self.add_import(cur_file, enum_id, &enum_name);
+        }
+
+        // <Enum as TryFrom<int>>::try_from(val).expect(msg)
+        let mut path = self.try_from_path(self.convert_boundary_type(ty)?);
+        let qself = ast::QSelf {
+            ty: mk().path_ty(vec![enum_name.as_str()]),
+            path_span: DUMMY_SP,
+            position: path.segments.len(),
+        };
+        path.segments.push(mk().path_segment("try_from"));
+        let try_from = mk().call_expr(mk().qpath_expr(Some(qself), path), vec![val]);
+
+        let problem = format!("invalid value of enum {}", enum_name);
+        let msg = match loc {
+            Some(loc) => format!("{}: {}", loc, problem),
+            None => problem,
+        };
+        Ok(mk().method_call_expr(try_from, "expect", vec![mk().lit_expr(mk().str_lit(&msg))]))
+    }
+
+    /// Convert an enum into a `#[repr]` Rust enum along with its `TryFrom` implementation.
+    pub fn convert_rust_enum(
+        &self,
+        span: Span,
+        enum_name: &str,
+        integral_type: CQualTypeId,
+        variants: &[CEnumConstantId],
+    ) -> Result<ConvertedDecl, TranslationError> {
+        // The widths of `char` and `long` depend on the target we translate for
+        let data_model = self.tcfg.data_model();
+        let repr = match self.ast_context.resolve_type(integral_type.ctype).kind {
+            CTypeKind::Char if data_model.char_is_signed => "i8",
+            CTypeKind::Char => "u8",
+            CTypeKind::SChar => "i8",
+            CTypeKind::UChar => "u8",
+            CTypeKind::Short => "i16",
+            CTypeKind::UShort => "u16",
+            CTypeKind::Int => "i32",
+            CTypeKind::UInt => "u32",
+            CTypeKind::Long if data_model.long_bits == 32 => "i32",
+            CTypeKind::ULong if data_model.long_bits == 32 => "u32",
+            CTypeKind::Long | CTypeKind::LongLong => "i64",
+            CTypeKind::ULong | CTypeKind::ULongLong => "u64",
+            ref kind => {
+                return Err(format_err!("Unsupported underlying type for enum: {:?}", kind).into())
+            }
+        };
+        let int_ty = self.convert_type(integral_type.ctype)?;
+        let enum_ty = mk().path_ty(vec![enum_name]);
+
+        let mut enum_variants = vec![];
+        let mut arms = vec![];
+        for &variant_id in variants {
+            let name = self.enum_variant_name(variant_id);
+            let value = match enumerator_value(&self.ast_context, variant_id) {
+                value if value < 0 => signed_int_expr(value as i64),
+                value => mk().lit_expr(mk().int_lit(value as u128, LitIntType::Unsuffixed)),
+            };
+
+            let variant = mk().path_expr(vec![enum_name, name.as_str()]);
+            let ok = mk().call_expr(mk().ident_expr("Ok"), vec![variant]);
+            arms.push(mk().arm(vec![mk().lit_pat(value.clone())], None, ok));
+            // The constant of the variant only gets its comments if it is translated first
+            let variant_span = self.decl_doc_span(variant_id);
+            enum_variants.push(mk().span(variant_span).unit_variant(&name, Some(value)));
+        }
+        let err = mk().call_expr(mk().ident_expr("Err"), vec![mk().ident_expr("value")]);
+        arms.push(mk().arm(vec![mk().wild_pat()], None, err));
+
+        let enum_item = mk()
+            .span(span)
+            .pub_()
+            .call_attr("derive", vec!["Copy", "Clone", "PartialEq", "Eq"])
+            .call_attr("repr", vec![repr])
+            .enum_item(enum_name, enum_variants);
+
+        // impl TryFrom<int> for Enum {
+        //     type Error = int;
+        //     fn try_from(value: int) -> Result<Enum, int> {
+        //         match value { 0 => Ok(Enum::A), .., _ => Err(value) }
+        //     }
+        // }
+        let trait_path = self.try_from_path(int_ty.clone());
+        let result_ty = mk().path_ty(vec![mk().path_segment_with_args(
+            "Result",
+            mk().angle_bracketed_args(vec![enum_ty.clone(), int_ty.clone()]),
+        )]);
+        let try_from = mk().method_impl_item(
+            "try_from",
+            mk().fn_decl(
+                vec![mk().arg(int_ty.clone(), mk().ident_pat("value"))],
+                FunctionRetTy::Ty(result_ty),
+                false,
+            ),
+            mk().block(vec![
+                mk().expr_stmt(mk().match_expr(mk().ident_expr("value"), arms))
+            ]),
+        );
+        let impl_item = mk().trait_impl_item(
+            trait_path,
+            enum_ty,
+            vec![mk().ty_impl_item("Error", int_ty), try_from],
+        );
+
+        Ok(ConvertedDecl::Items(vec![enum_item, impl_item]))
+    }
+
+    /// Convert an integer into a Rust enum. Only enumerators and values of the enum itself are
+    /// ever converted into Rust enums, see `rust_enum_candidates`.
+    pub fn rust_enum_cast(
+        &self,
+        enum_type: CTypeId,
+        enum_id: CEnumId,
+        expr_id: CExprId,
+        val: WithStmts<P<Expr>>,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let source_enum = self.ast_context[expr_id]
+            .kind
+            .get_type()
+            .and_then(|ty| enum_of(&self.ast_context, ty));
+        if source_enum == Some(enum_id) {
+            return Ok(val);
+        }
+        let value =
+            enumerator_expr_value(&self.ast_context, enum_id, expr_id).ok_or_else(|| {
+                format_err!("Integer converted into a Rust enum is not an enumerator")
+            })?;
+        Ok(val.map(|_| self.enum_for_i64(enum_type, value as i64)))
+    }
+
+    /// The enum that a `switch` statement on this scrutinee matches the variants of, if it is a
+    /// Rust enum, along with the scrutinee before its integer promotion
+    pub fn switch_rust_enum(&self, scrutinee: CExprId) -> Option<(CExprId, CEnumId)> {
+        let mut expr_id = scrutinee;
+        while let CExprKind::ImplicitCast(_, subexpr_id, CastKind::IntegralCast, _, _)
+        | CExprKind::Paren(_, subexpr_id) = self.ast_context[expr_id].kind
+        {
+            expr_id = subexpr_id;
+        }
+        let enum_id = self.ast_context[expr_id]
+            .kind
+            .get_type()
+            .and_then(|ty| enum_of(&self.ast_context, ty))?;
+        if self.is_rust_enum(enum_id) {
+            Some((expr_id, enum_id))
+        } else {
+            None
+        }
+    }
+
+    /// The pattern matching the variant of a Rust enum with the value of a `case` label, or
+    /// `None` if the enum has no such variant and can't hold the value
+    pub fn rust_enum_case_pat(&self, enum_id: CEnumId, value: ConstIntExpr) -> Option<P<Pat>> {
+        let value = match value {
+            ConstIntExpr::I(value) => value as i128,
+            ConstIntExpr::U(value) => value as i128,
+        };
+        let variant_id = match self.ast_context[enum_id].kind {
+            CDeclKind::Enum { ref variants, .. } => *variants
+                .iter()
+                .find(|&&variant| enumerator_value(&self.ast_context, variant) == value)?,
+            _ => return None,
+        };
+
+        let enum_name = self
+            .type_converter
+            .borrow()
+            .resolve_decl_name(enum_id)
+            .expect("Enums should already be renamed");
+        if let Some(cur_file) = self.cur_file.borrow().as_ref() {
+            self.add_import(cur_file, enum_id, &enum_name);
+        }
+        let variant_name = self.enum_variant_name(variant_id);
+        Some(mk().qpath_pat(None, vec![enum_name, variant_name]))
+    }
+}
@@ -0,0 +1,286 @@
+//! Linking of functions declared in one translation unit and defined in another.
+//!
+//! Before anything is translated, every translation unit is parsed and prepared for translation
+//! to collect the functions it defines under the names they get in its module, and the prepared
+//! units are translated afterwards. Translation units that only declare
+//! one of those functions then import it from the module of the defining translation unit
+//! instead of declaring it in an `extern "C"` block, as long as the declaration and the
+//! definition agree on the signature, down to the fields of the records it uses. In a
+//! workspace, functions are only imported from translation units of the same crate, and calls
+//! into other crates keep going through the symbols of their `extern "C"` declarations. So do
+//! the functions declared in headers with `--header-modules`, since the header modules of a
+//! workspace are shared by all of its crates.
+
+use super::*;
+use crate::compile_cmds::Target;
+
+/// A function definition that other translation units can link against
+#[derive(Clone)]
+pub struct LinkedFunction {
+    /// Module of the defining translation unit
+    module: String,
+    /// Main file of the defining translation unit
+    main_file: PathBuf,
+    /// Name of the function in that module
+    rust_name: String,
+    signature: String,
+}
+
+/// The functions defined by all translation units, by their symbol names
+pub struct LinkTable {
+    functions: IndexMap<String, LinkedFunction>,
+    /// Modules of the translation units, by the paths of their main files
+    modules: IndexMap<PathBuf, String>,
+    /// Targets of the translation units when translating into a workspace, by the canonical
+    /// paths of their main files
+    targets: IndexMap<PathBuf, IndexSet<String>>,
+}
+
+impl LinkTable {
+    /// Make a table for translating into a workspace with crates for `targets`, or into a
+    /// single crate if there are none
+    pub fn new(targets: &[Target]) -> Self {
+        let mut target_names = IndexMap::new();
+        for target in targets {
+            for file in &target.files {
+                target_names
+                    .entry(file.clone())
+                    .or_insert_with(IndexSet::new)
+                    .insert(target.name.clone());
+            }
+        }
+        LinkTable {
+            functions: IndexMap::new(),
+            modules: IndexMap::new(),
+            targets: target_names,
+        }
+    }
+
+    /// Can the translation unit of `main_file` import from the one of `other`? Only if every
+    /// crate including the former includes the latter as well.
+    fn can_import(&self, main_file: &path::Path, other: &path::Path) -> bool {
+        let targets = |file: &path::Path| {
+            let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+            self.targets.get(&file).cloned().unwrap_or_default()
+        };
+        targets(main_file).is_subset(&targets(other))
+    }
+
+    /// Add the functions defined by a translation unit that gets translated into `module`
+    pub fn add_unit(&mut self, t: &Translation, module: String) {
+        for (&decl_id, decl) in t.ast_context.iter_decls() {
+            if let CDeclKind::Function {
+                is_global: true,
+                is_inline: false,
+                body: Some(_),
+                typ,
+                ref name,
+                ..
+            } = decl.kind
+            {
+                let symbol = t.ast_context.symbol_name(decl_id, name);
+                if let Some(other) = self.functions.get(symbol) {
+                    diag!(
+                        Diagnostic::Linking,
+                        "`{}` is defined in both {} and {}, linking against the former",
+                        name,
+                        other.module,
+                        module,
+                    );
+                    continue;
+                }
+                let rust_name = t
+                    .renamer
+                    .borrow()
+                    .get(&decl_id)
+                    .expect("Functions should already be renamed");
+                let function = LinkedFunction {
+                    module: module.clone(),
+                    main_file: t.main_file.clone(),
+                    rust_name,
+                    signature: type_signature(&t.ast_context, typ),
+                };
+                self.functions.insert(symbol.to_string(), function);
+            }
+        }
+
+        self.modules.insert(t.main_file.clone(), module);
+    }
+}
+
+/// A string describing a type, equal for the same type in different translation units
+fn type_signature(ast_context: &TypedAstContext, ctype: CTypeId) -> String {
+    type_signature_in(ast_context, ctype, &mut vec![])
+}
+
+/// `type_signature` of a type nested in the records of `enclosing`. Records refer to the
+/// enclosing ones by name, which ends the recursion through self-referential records.
+fn type_signature_in(
+    ast_context: &TypedAstContext,
+    ctype: CTypeId,
+    enclosing: &mut Vec<CDeclId>,
+) -> String {
+    let signature =
+        |ctype, enclosing: &mut Vec<CDeclId>| type_signature_in(ast_context, ctype, enclosing);
+    match ast_context.resolve_type(ctype).kind {
+        CTypeKind::Pointer(pointee) => {
+            let mutbl = if pointee.qualifiers.is_const {
+                "const"
+            } else {
+                "mut"
+            };
+            format!("*{} {}", mutbl, signature(pointee.ctype, enclosing))
+        }
+        CTypeKind::ConstantArray(elt, len) => format!("[{}; {}]", signature(elt, enclosing), len),
+        CTypeKind::IncompleteArray(elt) | CTypeKind::VariableArray(elt, _) => {
+            format!("[{}]", signature(elt, enclosing))
+        }
+        CTypeKind::Function(ret, ref params, is_variadic, is_noreturn, _) => {
+            let mut params: Vec<String> = params
+                .iter()
+                .map(|param| signature(param.ctype, enclosing))
+                .collect();
+            if is_variadic {
+                params.push("...".to_string());
+            }
+            let ret = if is_noreturn {
+                "!".to_string()
+            } else {
+                signature(ret.ctype, enclosing)
+            };
+            format!("fn({}) -> {}", params.join(", "), ret)
+        }
+        CTypeKind::Struct(decl_id) | CTypeKind::Union(decl_id) | CTypeKind::Enum(decl_id) => {
+            let decl_kind = &ast_context[decl_id].kind;
+            let (tag, fields) = match *decl_kind {
+                CDeclKind::Struct { ref fields, .. } => ("struct", fields.as_ref()),
+                CDeclKind::Union { ref fields, .. } => ("union", fields.as_ref()),
+                _ => ("enum", None),
+            };
+            let name = match decl_kind.get_name() {
+                Some(name) => format!("{} {}", tag, name),
+                None => format!("{} <anonymous>", tag),
+            };
+            let fields = match fields {
+                Some(fields) if !enclosing.contains(&decl_id) => fields,
+                _ => return name,
+            };
+
+            // Records of the same name may still differ in their fields
+            enclosing.push(decl_id);
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|&field_id| match ast_context[field_id].kind {
+                    CDeclKind::Field {
+                        ref name,
+                        typ,
+                        bitfield_width,
+                        ..
+                    } => {
+                        let width = bitfield_width.map_or(String::new(), |w| format!(": {}", w));
+                        format!("{}: {}{}", name, signature(typ.ctype, enclosing), width)
+                    }
+                    _ => panic!("Record fields should be field declarations"),
+                })
+                .collect();
+            enclosing.pop();
+            format!("{} {{ {} }}", name, fields.join(", "))
+        }
+        ref kind => format!("{:?}", kind),
+    }
+}
+
+impl<'c> Translation<'c> {
+    /// Find the function declarations of this translation unit that can be linked against a
+    /// definition in another one. Declarations whose signature differs from the definition are
+    /// reported and stay in the `extern "C"` block.
+    pub fn link_functions(&mut self, link_table: &LinkTable) {
+        let module = link_table.modules.get(&self.main_file);
+        let mut linked_functions = IndexMap::new();
+
+        for (&decl_id, decl) in self.ast_context.iter_decls() {
+            let (name, typ) = match decl.kind {
+                CDeclKind::Function {
+                    is_global: true,
+                    body: None,
+                    typ,
+                    ref name,
+                    ..
+                } => (name, typ),
+                _ => continue,
+            };
+            let symbol = self.ast_context.symbol_name(decl_id, name);
+            let function = match link_table.functions.get(symbol) {
+                Some(function)
+                    if Some(&function.module) != module
+                        && link_table.can_import(&self.main_file, &function.main_file) =>
+                {
+                    function
+                }
+                _ => continue,
+            };
+
+            let signature = type_signature(&self.ast_context, typ);
+            if signature == function.signature {
+                linked_functions.insert(decl_id, function.clone());
+            } else {
+                let loc = decl
+                    .loc
+                    .as_ref()
+                    .map_or(String::new(), |loc| format!(" at {}", loc));
+                diag!(
+                    Diagnostic::Linking,
+                    "Declaration of `{}`{} does not match its definition in {}, keeping it \
+                     extern: `{}` is not `{}`",
+                    name,
+                    loc,
+                    function.module,
+                    signature,
+                    function.signature,
+                );
+            }
+        }
+
+        self.linked_functions = linked_functions;
+    }
+
+    /// Import the definition of a function declared as `local_name` that is linked against
+    /// another translation unit. Returns `false` if the function is not linked.
+    pub fn import_linked_function(&self, decl_id: CDeclId, local_name: &str) -> bool {
+        let function = match self.linked_functions.get(&decl_id) {
+            Some(function) => function,
+            None => return false,
+        };
+        let in_header = match self.cur_file.borrow().as_ref() {
+            Some(file) => self.tcfg.splits_headers() && *file != self.main_file,
+            None => false,
+        };
+        // The header modules of a workspace are shared by all of its crates, so they can't
+        // import from the modules of any one of them
+        if in_header && self.tcfg.header_modules && self.tcfg.workspace {
+            return false;
+        }
+
+        let path = vec!["crate".to_string(), function.module.clone()];
+        let import = |item_store: &mut ItemStore| {
+            if function.rust_name == local_name {
+                item_store.uses.get_mut(path.clone()).insert(local_name);
+            } else {
+                let mut rename_path = path.clone();
+                rename_path.push(function.rust_name.clone());
+                let use_item = mk().use_item(rename_path, Some(local_name));
+                item_store.items.push(use_item);
+            }
+        };
+
+        import(&mut self.item_store.borrow_mut());
+        // Code in the module of a header may call the function as well
+        if let Some(file) = self.cur_file.borrow().as_ref() {
+            if in_header {
+                let mut mod_blocks = self.mod_blocks.borrow_mut();
+                import(mod_blocks.entry(file.clone()).or_insert(ItemStore::new()));
+            }
+        }
+        true
+    }
+}
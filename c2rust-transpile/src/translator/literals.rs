@@ -10,6 +10,8 @@ impl<'c> Translation<'c> {
     pub fn mk_int_lit(&self, ty: CQualTypeId, val: u64, base: IntBase) -> P<Expr> {
         // Note that C doesn't have anything smaller than integer literals
         let (intty, suffix) = match self.ast_context.resolve_type(ty.ctype).kind {
+            // C++ `true` and `false`
+            CTypeKind::Bool => return mk().lit_expr(mk().bool_lit(val != 0)),
             CTypeKind::Int => (LitIntType::Signed(IntTy::I32), "i32"),
             CTypeKind::Long => (LitIntType::Signed(IntTy::I64), "i64"),
             CTypeKind::LongLong => (LitIntType::Signed(IntTy::I64), "i64"),
@@ -215,7 +217,7 @@ impl<'c> Translation<'c> {
                     Ok(ids
                         .iter()
                         .map(|id| {
-                            self.convert_expr(ctx.used(), *id)?
+                            self.convert_expr_as(ctx.used(), *id, ty)?
                                 .result_map(|x| {
                                     // Array literals require all of their elements to be
                                     // the correct type; they will not use implicit casts to
@@ -286,7 +288,7 @@ impl<'c> Translation<'c> {
                         let val = if ids.is_empty() {
                             self.implicit_default_expr(field_ty.ctype, ctx.is_static)?
                         } else {
-                            self.convert_expr(ctx.used(), ids[0])?
+                            self.convert_expr_as(ctx.used(), ids[0], field_ty.ctype)?
                         };
 
                         Ok(val.map(|v| {
@@ -389,7 +391,7 @@ impl<'c> Translation<'c> {
            .map(|(decl, maybe_id)| {
                let &(ref field_name, ty, _, _, _) = decl;
                let field_init = match maybe_id {
-                   Some(id) => self.convert_expr(ctx.used(), *id)?,
+                   Some(id) => self.convert_expr_as(ctx.used(), *id, ty.ctype)?,
                    None => self.implicit_default_expr(ty.ctype, ctx.is_static)?,
                };
                Ok(field_init.map(|expr| mk().field(field_name, expr)))
@@ -10,6 +10,7 @@ use indexmap::{IndexMap, IndexSet};
 
 use rustc_data_structures::sync::Lrc;
 use syntax::ast::*;
+use syntax::parse::lexer::comments;
 use syntax::parse::token::{DelimToken, Nonterminal, Token};
 use syntax::print::pprust::*;
 use syntax::ptr::*;
@@ -17,7 +18,7 @@ use syntax::tokenstream::{TokenStream, TokenTree};
 use syntax::{ast, with_globals};
 use syntax_pos::{Span, DUMMY_SP};
 
-use crate::rust_ast::comment_store::CommentStore;
+use crate::rust_ast::comment_store::{CommentStore, CommentTraverser};
 use crate::rust_ast::item_store::ItemStore;
 use crate::rust_ast::traverse::Traversal;
 use c2rust_ast_builder::{mk, Builder};
@@ -27,24 +28,39 @@ use crate::c_ast::iterators::{DFExpr, SomeId};
 use crate::c_ast::*;
 use crate::cfg;
 use crate::convert_type::TypeConverter;
-use crate::renamer::Renamer;
+use crate::diagnostics::Diagnostic;
+use crate::header_modules::{rename_foreign_types, rename_types, HeaderModules};
+use crate::renamer::{NameCase, Renamer};
 use crate::with_stmts::WithStmts;
 use crate::TranspilerConfig;
 use c2rust_ast_exporter::clang_ast::LRValue;
 
 mod assembly;
+mod bindings;
 mod bitfields;
+mod blocks;
 mod builtins;
+mod checked;
+mod enums;
+mod linking;
 mod literals;
 mod main_function;
 mod named_references;
+mod naming;
+mod openmp;
 mod operators;
+mod printf;
+mod shared_inlines;
 mod simd;
+mod source_map;
+mod tagged_unions;
 mod variadic;
 
+pub use self::linking::LinkTable;
 pub use crate::diagnostics::{TranslationError, TranslationErrorKind};
 use crate::CrateSet;
 use crate::PragmaVec;
+use crate::SidecarVec;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum DecayRef {
@@ -107,6 +123,10 @@ pub struct ExprContext {
 
     ternary_needs_parens: bool,
     expanding_macro: Option<CDeclId>,
+
+    // The C expression being translated, whose location the runtime checks
+    // of `--checked` mode report.
+    checked_expr: Option<CExprId>,
 }
 
 impl ExprContext {
@@ -191,6 +211,10 @@ pub struct FunContext {
     promoted_va_decl: Option<CDeclId>,
     /// The va_list decls that we did not promote because they were `va_copy`ed.
     copied_va_decls: Option<IndexSet<CDeclId>>,
+    /// The slice of `VarArg`s that the function takes its variadic arguments as, if it does
+    va_args: Option<String>,
+    /// The return type of the function or block we're currently translating
+    return_type: Option<CQualTypeId>,
 }
 
 impl FunContext {
@@ -199,13 +223,17 @@ impl FunContext {
             name: None,
             promoted_va_decl: None,
             copied_va_decls: None,
+            va_args: None,
+            return_type: None,
         }
     }
 
-    pub fn enter_new(&mut self, fn_name: &str) {
+    pub fn enter_new(&mut self, fn_name: &str, return_type: Option<CQualTypeId>) {
         self.name = Some(fn_name.to_string());
         self.promoted_va_decl = None;
         self.copied_va_decls = None;
+        self.va_args = None;
+        self.return_type = return_type;
     }
 
     pub fn get_name<'a>(&'a self) -> &'a str {
@@ -231,6 +259,22 @@ pub struct Translation<'c> {
     function_context: RefCell<FunContext>,
     potential_flexible_array_members: RefCell<IndexSet<CDeclId>>,
     macro_types: RefCell<IndexMap<CDeclId, CTypeId>>,
+    rust_enums: IndexSet<CEnumId>,
+    tagged_unions: IndexMap<CRecordId, tagged_unions::TaggedUnion>,
+    /// Union fields bound by the arms of the tag `match`es being translated
+    payload_bindings: RefCell<Vec<tagged_unions::PayloadBinding>>,
+    emitted_block_abi: RefCell<bool>,
+    block_abi_helpers: RefCell<IndexMap<CTypeId, blocks::BlockAbiHelpers>>,
+    /// Variadic functions taking their variadic arguments as a slice of `VarArg`s
+    stable_variadics: IndexSet<CDeclId>,
+    /// Name of the `VarArg` enum, once it is emitted
+    var_arg_name: RefCell<Option<String>>,
+    /// `static inline` functions of headers that go into the modules of their headers
+    shared_inline_functions: IndexSet<CDeclId>,
+    /// Control-flow quality metrics, per function
+    cfg_stats: RefCell<IndexMap<String, cfg::stats::CfgStats>>,
+    /// C locations of the translated nodes, when emitting a source map
+    source_map: RefCell<Option<source_map::SourceMapBuilder>>,
 
     // Comment support
     pub comment_context: RefCell<CommentContext>, // Incoming comments
@@ -242,6 +286,9 @@ pub struct Translation<'c> {
     // Mod names to try to stop collisions from happening
     mod_names: RefCell<IndexMap<String, PathBuf>>,
 
+    // Items of declarations in C++ namespaces, by the names of their namespaces
+    namespace_blocks: RefCell<IndexMap<Vec<String>, ItemStore>>,
+
     // The file that the translator is operating on
     main_file: PathBuf,
 
@@ -249,6 +296,9 @@ pub struct Translation<'c> {
     // expanded from. This is needed in order to note imports in mod_blocks when
     // encountering DeclRefs.
     cur_file: RefCell<Option<PathBuf>>,
+
+    // Function declarations linked against the definitions of other translation units
+    linked_functions: IndexMap<CDeclId, linking::LinkedFunction>,
 }
 
 fn simple_metaitem(name: &str) -> NestedMetaItem {
@@ -324,6 +374,64 @@ fn vec_expr(val: P<Expr>, count: P<Expr>) -> P<Expr> {
     mk().call_expr(from_elem, vec![val, count])
 }
 
+fn small_vec_expr(val: P<Expr>, count: P<Expr>) -> P<Expr> {
+    let from_elem = mk().path_expr(vec!["", "smallvec", "SmallVec", "from_elem"]);
+    mk().call_expr(from_elem, vec![val, count])
+}
+
+/// Can `smallvec` store `n` elements inline? Version 0.6 only implements its `Array` trait for
+/// arrays of up to 16 elements, of 20, 24, 32 and 36 elements, and of powers of two up to 2^20.
+pub fn is_small_vec_capacity(n: usize) -> bool {
+    match n {
+        0..=16 | 20 | 24 | 32 | 36 => true,
+        _ => n.is_power_of_two() && n <= 0x10_0000,
+    }
+}
+
+/// The smallest inline capacity of a `SmallVec` holding at least `n` elements
+fn small_vec_capacity(n: usize) -> usize {
+    match n {
+        0..=16 => n,
+        17..=20 => 20,
+        21..=24 => 24,
+        25..=32 => 32,
+        33..=36 => 36,
+        _ => n.next_power_of_two(),
+    }
+}
+
+fn small_vec_ty(elt: P<Ty>, capacity: usize) -> P<Ty> {
+    let capacity = mk().lit_expr(mk().int_lit(capacity as u128, LitIntType::Unsuffixed));
+    mk().path_ty(vec![
+        mk().path_segment(""),
+        mk().path_segment("smallvec"),
+        mk().path_segment_with_args(
+            "SmallVec",
+            mk().angle_bracketed_args(vec![mk().array_ty(elt, capacity)]),
+        ),
+    ])
+}
+
+/// Recognize `break 'lbl val;`, returning `val`
+fn as_semi_break_stmt(stmt: &ast::Stmt, lbl: &cfg::Label) -> Option<Option<P<ast::Expr>>> {
+    if let ast::Stmt {
+        node: ast::StmtKind::Semi(ref expr),
+        ..
+    } = *stmt
+    {
+        if let ast::Expr {
+            node: ast::ExprKind::Break(Some(ref blbl), ref ret_val),
+            ..
+        } = **expr
+        {
+            if blbl.ident == mk().label(lbl.pretty_print()).ident {
+                return Some(ret_val.clone());
+            }
+        }
+    }
+    None
+}
+
 pub fn stmts_block(mut stmts: Vec<Stmt>) -> P<Block> {
     if stmts.len() == 1 {
         if let StmtKind::Expr(ref e) = stmts[0].node {
@@ -367,6 +475,7 @@ pub fn signed_int_expr(value: i64) -> P<Expr> {
 
 // This should only be used for tests
 fn prefix_names(translation: &mut Translation, prefix: &str) {
+    let mut defined_functions = vec![];
     for (&decl_id, ref mut decl) in translation.ast_context.iter_mut_decls() {
         match decl.kind {
             CDeclKind::Function {
@@ -382,6 +491,7 @@ fn prefix_names(translation: &mut Translation, prefix: &str) {
                 name.insert_str(0, prefix);
 
                 translation.renamer.borrow_mut().insert(decl_id, &name);
+                defined_functions.push(decl_id);
             }
             CDeclKind::Variable {
                 ref mut ident,
@@ -392,6 +502,13 @@ fn prefix_names(translation: &mut Translation, prefix: &str) {
             _ => (),
         }
     }
+
+    // The mangled symbols of C++ functions are prefixed as well
+    for decl_id in defined_functions {
+        if let Some(symbol) = translation.ast_context.symbol_names.get_mut(&decl_id) {
+            symbol.insert_str(0, prefix);
+        }
+    }
 }
 
 // This function is meant to create module names, for modules being created with the
@@ -442,12 +559,16 @@ pub fn translate_failure(tcfg: &TranspilerConfig, msg: &str) {
     }
 }
 
+/// Translate a translation unit prepared with `Translation::prepare`
 pub fn translate(
-    ast_context: TypedAstContext,
-    tcfg: &TranspilerConfig,
-    main_file: PathBuf,
-) -> (String, PragmaVec, CrateSet) {
-    let mut t = Translation::new(ast_context, tcfg, main_file);
+    mut t: Translation,
+    header_modules: &mut HeaderModules,
+    link_table: &LinkTable,
+) -> (String, PragmaVec, CrateSet, SidecarVec) {
+    let tcfg = t.tcfg;
+    if tcfg.header_modules {
+        *t.mod_names.borrow_mut() = header_modules.mod_names.clone();
+    }
     let ctx = ExprContext {
         used: true,
         is_static: false,
@@ -457,6 +578,7 @@ pub fn translate(
         needs_address: false,
         ternary_needs_parens: false,
         expanding_macro: None,
+        checked_expr: None,
     };
 
     if t.tcfg.reorganize_definitions {
@@ -469,16 +591,17 @@ pub fn translate(
 
     t.extern_crates.borrow_mut().insert("libc");
 
-    // Headers often pull in declarations that are unused;
-    // we simplify the translator output by omitting those.
-    t.ast_context.prune_unused_decls();
-
-    // Sort the top-level declarations by file and source location so that we
-    // preserve the ordering of all declarations in each file.
-    t.ast_context.sort_top_decls();
+    if tcfg.link_functions {
+        t.link_functions(link_table);
+    }
+    if tcfg.share_inline_functions {
+        t.shared_inline_functions = t.find_shared_inline_functions();
+    }
+    if tcfg.stable_variadics {
+        t.stable_variadics = t.find_stable_variadics();
+    }
 
     enum Name<'a> {
-        VarName(&'a str),
         TypeName(&'a str),
         AnonymousType,
         NoName,
@@ -491,11 +614,6 @@ pub fn translate(
         }
     }
 
-    // Used for testing; so that we don't overlap with C function names
-    if let Some(ref prefix) = t.tcfg.prefix_function_names {
-        prefix_names(&mut t, prefix);
-    }
-
     // `with_globals` sets up a thread-local variable required by the syntax crate.
     with_globals(|| {
         // Identify typedefs that name unnamed types and collapse the two declarations
@@ -561,7 +679,8 @@ pub fn translate(
                 || prenamed_decls.values().find(|id| *id == decl_id).is_some())
         }
 
-        // Populate renamer with top-level names
+        // Populate the type renamer with top-level names. The values were named when the
+        // translation unit was prepared.
         for (&decl_id, decl) in t.ast_context.iter_decls() {
             let decl_name = match decl.kind {
                 _ if contains(&t.ast_context.prenamed_decls, &decl_id) => Name::NoName,
@@ -575,14 +694,6 @@ pub fn translate(
                     some_type_name(name.as_ref().map(String::as_str))
                 }
                 CDeclKind::Typedef { ref name, .. } => Name::TypeName(name),
-                CDeclKind::Function { ref name, .. } => Name::VarName(name),
-                CDeclKind::EnumConstant { ref name, .. } => Name::VarName(name),
-                CDeclKind::Variable { ref ident, .. }
-                    if t.ast_context.c_decls_top.contains(&decl_id) =>
-                {
-                    Name::VarName(ident)
-                }
-                CDeclKind::MacroObject { ref name, .. } => Name::VarName(name),
                 _ => Name::NoName,
             };
             match decl_name {
@@ -597,9 +708,6 @@ pub fn translate(
                         .borrow_mut()
                         .declare_decl_name(decl_id, name);
                 }
-                Name::VarName(name) => {
-                    t.renamer.borrow_mut().insert(decl_id, &name);
-                }
             }
         }
 
@@ -613,12 +721,20 @@ pub fn translate(
                     .flatten()
                     .next();
 
-                if t.tcfg.reorganize_definitions {
+                if t.tcfg.splits_headers() {
                     *t.cur_file.borrow_mut() = decl_file_path.cloned();
                 }
-                match t.convert_decl(ctx, decl_id) {
+                match t
+                    .convert_decl(ctx, decl_id)
+                    .map(|converted| t.tag_converted_decl(decl_id, converted))
+                {
                     Ok(ConvertedDecl::Item(item)) => {
-                        t.insert_item(item, decl_file_path);
+                        t.insert_item(decl_id, item, decl_file_path);
+                    }
+                    Ok(ConvertedDecl::Items(items)) => {
+                        for item in items {
+                            t.insert_item(decl_id, item, decl_file_path);
+                        }
                     }
                     Ok(ConvertedDecl::ForeignItem(item)) => {
                         t.insert_foreign_item(item, decl_file_path);
@@ -631,8 +747,9 @@ pub fn translate(
                     }
                 }
                 t.cur_file.borrow_mut().take();
+                t.warn_platform_widths(decl_id);
 
-                if t.tcfg.reorganize_definitions && decl_file_path != Some(&t.main_file) {
+                if t.tcfg.splits_headers() && decl_file_path != Some(&t.main_file) {
                     t.generate_submodule_imports(decl_id, decl_file_path);
                 }
             };
@@ -660,7 +777,9 @@ pub fn translate(
 
         // Export top-level value declarations
         for top_id in &t.ast_context.c_decls_top {
-            let needs_export = match t.ast_context[*top_id].kind {
+            let decl_kind = &t.ast_context[*top_id].kind;
+            let needs_export = match *decl_kind {
+                _ if tcfg.bindings_only() && !t.is_bindable(decl_kind) => false,
                 CDeclKind::Function { is_implicit, .. } => !is_implicit,
                 CDeclKind::Variable { .. } => true,
                 CDeclKind::MacroObject { .. } => tcfg.translate_const_macros,
@@ -673,13 +792,22 @@ pub fn translate(
                     Some(Some(s)) => Some(s),
                     _ => None,
                 };
+                let decl_file_path = t.module_file_path(*top_id, decl_file_path);
 
-                if t.tcfg.reorganize_definitions && decl_file_path != Some(&t.main_file) {
+                if t.tcfg.splits_headers() && decl_file_path != Some(&t.main_file) {
                     *t.cur_file.borrow_mut() = decl_file_path.cloned();
                 }
-                match t.convert_decl(ctx, *top_id) {
+                match t
+                    .convert_decl(ctx, *top_id)
+                    .map(|converted| t.tag_converted_decl(*top_id, converted))
+                {
                     Ok(ConvertedDecl::Item(item)) => {
-                        t.insert_item(item, decl_file_path);
+                        t.insert_item(*top_id, item, decl_file_path);
+                    }
+                    Ok(ConvertedDecl::Items(items)) => {
+                        for item in items {
+                            t.insert_item(*top_id, item, decl_file_path);
+                        }
                     }
                     Ok(ConvertedDecl::ForeignItem(item)) => {
                         t.insert_foreign_item(item, decl_file_path);
@@ -705,8 +833,9 @@ pub fn translate(
                     }
                 }
                 t.cur_file.borrow_mut().take();
+                t.warn_platform_widths(*top_id);
 
-                if t.tcfg.reorganize_definitions && decl_file_path != Some(&t.main_file) {
+                if t.tcfg.splits_headers() && decl_file_path != Some(&t.main_file) {
                     t.generate_submodule_imports(*top_id, decl_file_path);
                 }
             }
@@ -723,6 +852,52 @@ pub fn translate(
             }
         }
 
+        if let Some(ref functions) = tcfg.goto_elimination {
+            for (name, stats) in t.cfg_stats.borrow().iter() {
+                if functions.is_match(name) && stats.current_blocks > 0 {
+                    diag!(
+                        Diagnostic::GotoElimination,
+                        "Goto elimination left {} `current_block` state variable(s) in {}",
+                        stats.current_blocks,
+                        name
+                    );
+                }
+            }
+        }
+
+        if tcfg.cfg_stats {
+            for (name, stats) in t.cfg_stats.borrow().iter() {
+                diag!(Diagnostic::CfgStats, "Control flow of {}: {}", name, stats);
+            }
+        }
+
+        // Report the comments that didn't make it into the output. Comments of declarations
+        // outside of the main file are left out, since those declarations are mostly unused.
+        // Comments that were extracted but then lost with the Rust code they were attached to are
+        // reported when printing.
+        {
+            let comment_context = t.comment_context.borrow();
+            let unplaced = comment_context.unplaced_comments(|decl_id| {
+                let decl_file_path = t.ast_context[decl_id]
+                    .loc
+                    .as_ref()
+                    .and_then(|loc| loc.file_path.as_ref());
+                decl_file_path == Some(&t.main_file)
+            });
+            for comment in unplaced {
+                let text = comment.kind.lines().next().unwrap_or("");
+                match comment.loc {
+                    Some(ref loc) => diag!(
+                        Diagnostic::Comments,
+                        "Could not place comment at {}: {}",
+                        loc,
+                        text
+                    ),
+                    None => diag!(Diagnostic::Comments, "Could not place comment: {}", text),
+                }
+            }
+        }
+
         // Initialize global statics when necessary
         if !t.sectioned_static_initializers.borrow().is_empty() {
             let (initializer_fn, initializer_static) = t.generate_global_static_init();
@@ -734,24 +909,52 @@ pub fn translate(
 
         let pragmas = t.get_pragmas();
         let crates = t.extern_crates.borrow().clone();
+        let mut sidecars = vec![];
+        if tcfg.rust_naming_conventions {
+            sidecars.push(("renames.json", t.rename_map()));
+        }
+        let source_map = t.source_map.borrow_mut().take();
+        let source_markers = source_map.as_ref().map(|source_map| source_map.markers());
         // pass all converted items to the Rust pretty printer
         let translation = to_string(|s| {
             print_header(s, &t)?;
 
             // Re-order comments
             let mut traverser = t.comment_store.into_inner().into_comment_traverser();
+            if let Some(markers) = source_markers {
+                traverser.add_markers(markers);
+            }
             let mut mod_items: Vec<P<Item>> = Vec::new();
 
             // Header Reorganization: Submodule Item Stores
             for (file_path, ref mut mod_item_store) in t.mod_blocks.borrow_mut().iter_mut() {
-                mod_items.push(make_submodule(
-                    mod_item_store,
-                    file_path,
-                    &t.item_store,
-                    &t.mod_names,
-                ));
+                if tcfg.header_modules {
+                    export_header_module(
+                        tcfg,
+                        mod_item_store,
+                        file_path,
+                        &t.main_file,
+                        &t.item_store,
+                        &t.mod_names,
+                        header_modules,
+                        &mut traverser,
+                    );
+                } else {
+                    mod_items.push(make_submodule(
+                        mod_item_store,
+                        file_path,
+                        &t.item_store,
+                        &t.mod_names,
+                    ));
+                }
             }
 
+            // C++ namespaces
+            mod_items.extend(make_namespace_modules(
+                &mut t.namespace_blocks.borrow_mut(),
+                &t.item_store,
+            ));
+
             // Global Item Store
             let (items, foreign_items, uses) = t.item_store.borrow_mut().drain();
 
@@ -768,9 +971,14 @@ pub fn translate(
                 .map(|p_i| p_i.map(|i| traverser.traverse_item(i)))
                 .collect();
 
+            let comment_store = traverser.into_comment_store();
+            for comment in comment_store.unplaced_comments() {
+                let text = comment.lines().next().unwrap_or("");
+                diag!(Diagnostic::Comments, "Could not place comment: {}", text);
+            }
             s.comments()
                 .get_or_insert(vec![])
-                .extend(traverser.into_comment_store().into_comments());
+                .extend(comment_store.into_comments());
 
             for mod_item in mod_items {
                 s.print_item(&*mod_item)?;
@@ -795,7 +1003,18 @@ pub fn translate(
 
             Ok(())
         });
-        (translation, pragmas, crates)
+        if tcfg.header_modules {
+            header_modules.mod_names = t.mod_names.borrow().clone();
+        }
+        let translation = match source_map {
+            Some(source_map) => {
+                let (translation, json) = source_map.extract(&translation);
+                sidecars.push(("srcmap.json", json));
+                translation
+            }
+            None => translation,
+        };
+        (translation, pragmas, crates, sidecars)
     })
 }
 
@@ -848,6 +1067,160 @@ fn make_submodule(
         .mod_item(mod_name, mk().mod_(items))
 }
 
+/// Make the modules of C++ namespaces. Their items are imported into the main module, so that
+/// everything can still refer to them by their (unique) names.
+fn make_namespace_modules(
+    namespace_blocks: &mut IndexMap<Vec<String>, ItemStore>,
+    global_item_store: &RefCell<ItemStore>,
+) -> Vec<P<Item>> {
+    let mut global_item_store = global_item_store.borrow_mut();
+    let outermost: IndexSet<String> = namespace_blocks
+        .keys()
+        .map(|path| path[0].clone())
+        .collect();
+    outermost
+        .into_iter()
+        .map(|name| make_namespace_module(&[name], namespace_blocks, &mut global_item_store))
+        .collect()
+}
+
+fn make_namespace_module(
+    path: &[String],
+    namespace_blocks: &mut IndexMap<Vec<String>, ItemStore>,
+    global_item_store: &mut ItemStore,
+) -> P<Item> {
+    let mut items = vec![mk().use_glob_item(vec!["super"])];
+
+    if let Some(mut namespace_item_store) = namespace_blocks.swap_remove(path) {
+        let (namespace_items, _, uses) = namespace_item_store.drain();
+        items.extend(uses.into_items());
+
+        let mut use_path = vec!["self".to_string()];
+        use_path.extend(path.iter().cloned());
+        for mut item in namespace_items {
+            let ident_name = item.ident.name.as_str();
+            if !ident_name.is_empty() {
+                // Private items are used by the rest of the translation unit as well
+                let vis = match item.vis.node {
+                    VisibilityKind::Public => mk().pub_(),
+                    VisibilityKind::Inherited => {
+                        item.vis.node = VisibilityKind::Crate(CrateSugar::PubCrate);
+                        mk()
+                    }
+                    _ => mk(),
+                };
+
+                global_item_store
+                    .uses
+                    .get_mut(use_path.clone())
+                    .insert_with_attr(&*ident_name, vis);
+            }
+            items.push(item);
+        }
+    }
+
+    let nested: IndexSet<String> = namespace_blocks
+        .keys()
+        .filter(|nested_path| nested_path.len() > path.len() && nested_path.starts_with(path))
+        .map(|nested_path| nested_path[path.len()].clone())
+        .collect();
+    for name in nested {
+        let mut nested_path = path.to_vec();
+        nested_path.push(name);
+        items.push(make_namespace_module(
+            &nested_path,
+            namespace_blocks,
+            global_item_store,
+        ));
+    }
+
+    let name = path.last().expect("Namespaces have names");
+    mk().vis("pub").mod_item(name.as_str(), mk().mod_(items))
+}
+
+/// Hand the items translated from a header over to the module shared by all translation units,
+/// and import them from there. Items that differ from the ones already in the shared module
+/// stay in the module of this translation unit.
+fn export_header_module(
+    tcfg: &TranspilerConfig,
+    submodule_item_store: &mut ItemStore,
+    file_path: &path::Path,
+    main_file: &path::Path,
+    global_item_store: &RefCell<ItemStore>,
+    mod_names: &RefCell<IndexMap<String, PathBuf>>,
+    header_modules: &mut HeaderModules,
+    traverser: &mut CommentTraverser,
+) {
+    let (items, foreign_items, uses) = submodule_item_store.drain();
+    let mod_name = clean_path(mod_names, file_path);
+    header_modules.add_user(file_path, &mod_name, main_file);
+    let mut global_item_store = global_item_store.borrow_mut();
+    let crate_name = tcfg.header_modules_crate();
+    let use_path = |name: &str| vec![crate_name.into(), mod_name.clone(), name.to_string()];
+
+    for item in uses.into_items() {
+        header_modules.add_use(file_path, &mod_name, to_string(|s| s.print_item(&item)));
+    }
+
+    let renames = header_modules.name_anonymous_types(file_path, &mod_name, &items);
+
+    for item in items {
+        let exported = rename_types(&renames, item.clone());
+        let module_name = exported.ident.name.as_str().to_string();
+        let added = header_modules.add_item(file_path, &mod_name, &exported, || {
+            let (exported, comments) = traverser
+                .traverse_separately(exported.clone(), |t, i| i.map(|i| t.traverse_item(i)));
+            print_with_comments(comments, |s| s.print_item(&exported))
+        });
+        if !added {
+            global_item_store.items.push(item);
+        } else if !module_name.is_empty() {
+            import_from_header_module(
+                &mut global_item_store,
+                use_path(&module_name),
+                &item.ident.name.as_str(),
+            );
+        }
+    }
+
+    for foreign_item in foreign_items {
+        let exported = rename_foreign_types(&renames, foreign_item.clone());
+        let name = exported.ident.name.as_str().to_string();
+        let added = header_modules.add_foreign_item(file_path, &mod_name, &exported, || {
+            let (exported, comments) =
+                traverser.traverse_separately(exported.clone(), |t, i| t.traverse_foreign_item(i));
+            print_with_comments(comments, |s| s.print_foreign_item(&exported))
+        });
+        if !added {
+            global_item_store.foreign_items.push(foreign_item);
+        } else {
+            import_from_header_module(&mut global_item_store, use_path(&name), &name);
+        }
+    }
+}
+
+/// Import an item of a header module under the name the translation unit knows it by, which
+/// differs from its name in the module for anonymous types
+fn import_from_header_module(item_store: &mut ItemStore, mut path: Vec<String>, name: &str) {
+    if path.last().map(String::as_str) == Some(name) {
+        let leaf = path.pop().expect("Imported items have names");
+        item_store.uses.get_mut(path).insert(leaf);
+    } else {
+        item_store.items.push(mk().use_item(path, Some(name)));
+    }
+}
+
+/// Pretty-print an AST node apart from the rest of the output, together with its comments
+fn print_with_comments<F>(comments: Vec<comments::Comment>, print: F) -> String
+where
+    F: FnOnce(&mut State) -> io::Result<()>,
+{
+    to_string(|s| {
+        s.comments().get_or_insert(vec![]).extend(comments);
+        print(s)
+    })
+}
+
 /// Pretty-print the leading pragmas and extern crate declarations
 fn print_header(s: &mut State, t: &Translation) -> io::Result<()> {
     if t.tcfg.emit_modules {
@@ -919,9 +1292,9 @@ fn print_header(s: &mut State, t: &Translation) -> io::Result<()> {
     Ok(())
 }
 
-/// Convert a boolean expression to a c_int
-fn bool_to_int(val: P<Expr>) -> P<Expr> {
-    mk().cast_expr(val, mk().path_ty(vec!["libc", "c_int"]))
+/// Convert a boolean expression to a c_int, translated as `int_ty`
+fn bool_to_int(val: P<Expr>, int_ty: P<Ty>) -> P<Expr> {
+    mk().cast_expr(val, int_ty)
 }
 
 /// This represents all of the ways a C expression can be used in a C program. Making this
@@ -956,6 +1329,7 @@ pub enum ExprUse {
 pub enum ConvertedDecl {
     ForeignItem(ForeignItem),
     Item(P<Item>),
+    Items(Vec<P<Item>>),
     NoItem,
 }
 
@@ -972,37 +1346,133 @@ impl<'c> Translation<'c> {
             type_converter.translate_valist = true
         }
 
+        if tcfg.fixed_width_types {
+            type_converter.use_fixed_width_types(tcfg.data_model());
+        }
+
+        let mut renamer = Renamer::new(&[
+            // Keywords currently in use
+            "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn",
+            "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+            "return", "Self", "self", "static", "struct", "super", "trait", "true", "type",
+            "unsafe", "use", "where", "while", "dyn",
+            // Keywords reserved for future use
+            "abstract", "alignof", "become", "box", "do", "final", "macro", "offsetof", "override",
+            "priv", "proc", "pure", "sizeof", "typeof", "unsized", "virtual", "async", "try",
+            "yield", // Prevent use for other reasons
+            "main",  // prelude names
+            "drop", "Some", "None", "Ok", "Err",
+        ]);
+
+        if tcfg.rust_naming_conventions {
+            renamer.set_case(NameCase::Snake);
+            type_converter.use_rust_naming_conventions();
+        }
+
+        let rust_enums = if tcfg.translate_rust_enums {
+            enums::rust_enum_candidates(&ast_context)
+        } else {
+            IndexSet::new()
+        };
+        type_converter.pass_rust_enums_as_integers(rust_enums.clone());
+
+        let tagged_unions = if tcfg.translate_tagged_unions {
+            tagged_unions::find_tagged_unions(&ast_context)
+        } else {
+            IndexMap::new()
+        };
+
         Translation {
             features: RefCell::new(IndexSet::new()),
             item_store: RefCell::new(ItemStore::new()),
             type_converter: RefCell::new(type_converter),
             ast_context,
             tcfg,
-            renamer: RefCell::new(Renamer::new(&[
-                // Keywords currently in use
-                "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false",
-                "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
-                "pub", "ref", "return", "Self", "self", "static", "struct", "super", "trait",
-                "true", "type", "unsafe", "use", "where", "while", "dyn",
-                // Keywords reserved for future use
-                "abstract", "alignof", "become", "box", "do", "final", "macro", "offsetof",
-                "override", "priv", "proc", "pure", "sizeof", "typeof", "unsized", "virtual",
-                "async", "try", "yield", // Prevent use for other reasons
-                "main",  // prelude names
-                "drop", "Some", "None", "Ok", "Err",
-            ])),
+            renamer: RefCell::new(renamer),
             zero_inits: RefCell::new(IndexMap::new()),
             function_context: RefCell::new(FunContext::new()),
             potential_flexible_array_members: RefCell::new(IndexSet::new()),
             macro_types: RefCell::new(IndexMap::new()),
+            rust_enums,
+            tagged_unions,
+            payload_bindings: RefCell::new(Vec::new()),
+            emitted_block_abi: RefCell::new(false),
+            block_abi_helpers: RefCell::new(IndexMap::new()),
+            stable_variadics: IndexSet::new(),
+            var_arg_name: RefCell::new(None),
+            shared_inline_functions: IndexSet::new(),
+            cfg_stats: RefCell::new(IndexMap::new()),
+            source_map: RefCell::new(if tcfg.emit_source_map {
+                Some(source_map::SourceMapBuilder::new())
+            } else {
+                None
+            }),
             comment_context,
             comment_store: RefCell::new(CommentStore::new()),
             sectioned_static_initializers: RefCell::new(Vec::new()),
             mod_blocks: RefCell::new(IndexMap::new()),
             mod_names: RefCell::new(IndexMap::new()),
+            namespace_blocks: RefCell::new(IndexMap::new()),
             main_file,
             extern_crates: RefCell::new(IndexSet::new()),
             cur_file: RefCell::new(None),
+            linked_functions: IndexMap::new(),
+        }
+    }
+
+    /// Start the translation of a translation unit by preparing its declarations and naming its
+    /// top-level values. Linking uses the names of prepared translation units before any of them
+    /// is translated.
+    pub fn prepare(
+        ast_context: TypedAstContext,
+        tcfg: &'c TranspilerConfig,
+        main_file: PathBuf,
+    ) -> Self {
+        let mut t = Translation::new(ast_context, tcfg, main_file);
+        t.prepare_decls();
+        t.name_top_level_values();
+        t
+    }
+
+    /// Prune the unused declarations, order the top-level ones and prefix the function names
+    fn prepare_decls(&mut self) {
+        // Headers often pull in declarations that are unused;
+        // we simplify the translator output by omitting those.
+        if self.tcfg.bindings_only() {
+            self.prune_to_bindings();
+        } else {
+            self.ast_context.prune_unused_decls();
+        }
+
+        // Sort the top-level declarations by file and source location so that we
+        // preserve the ordering of all declarations in each file.
+        self.ast_context.sort_top_decls();
+
+        // Used for testing; so that we don't overlap with C function names
+        let tcfg = self.tcfg;
+        if let Some(ref prefix) = tcfg.prefix_function_names {
+            prefix_names(self, prefix);
+        }
+    }
+
+    /// Name the functions, enum constants, const macros and top-level variables
+    fn name_top_level_values(&self) {
+        for (&decl_id, decl) in self.ast_context.iter_decls() {
+            let name = match decl.kind {
+                CDeclKind::Function { ref name, .. }
+                | CDeclKind::EnumConstant { ref name, .. }
+                | CDeclKind::MacroObject { ref name, .. } => name,
+                CDeclKind::Variable { ref ident, .. }
+                    if self.ast_context.c_decls_top.contains(&decl_id) =>
+                {
+                    ident
+                }
+                _ => continue,
+            };
+            let case = self.value_name_case(&decl.kind);
+            self.renamer
+                .borrow_mut()
+                .insert_with_case(decl_id, name, case);
         }
     }
 
@@ -1038,6 +1508,46 @@ impl<'c> Translation<'c> {
         pragmas
     }
 
+    /// Warn about a constant that clang computed from the sizes or layouts of types, which only
+    /// holds on the target we translate for.
+    pub fn warn_layout_dependent(&self, loc: &Option<SrcLoc>, what: &str) {
+        let loc = loc
+            .as_ref()
+            .map_or_else(|| "<unknown location>".to_string(), |loc| loc.to_string());
+        diag!(
+            Diagnostic::LayoutConstants,
+            "{}: {} depends on the layout of types on the target",
+            loc,
+            what
+        );
+    }
+
+    /// Warn about the C types with a target dependent width or signedness that the translation
+    /// of a declaration converted to fixed-width types.
+    pub fn warn_platform_widths(&self, decl_id: CDeclId) {
+        let platform_widths = self.type_converter.borrow_mut().take_platform_widths();
+        if platform_widths.is_empty() {
+            return;
+        }
+        let decl = &self.ast_context[decl_id];
+        let loc = decl
+            .loc
+            .as_ref()
+            .map_or_else(|| "<unknown location>".to_string(), |loc| loc.to_string());
+        let name = decl.kind.get_name().map_or("declaration", String::as_str);
+        let types: Vec<String> = platform_widths
+            .iter()
+            .map(|ty| format!("`{}`", ty))
+            .collect();
+        diag!(
+            Diagnostic::PlatformWidths,
+            "{}: `{}` relies on the target's definition of {}, which varies between targets",
+            loc,
+            name,
+            types.join(", ")
+        );
+    }
+
     // This node should _never_ show up in the final generated code. This is an easy way to notice
     // if it does.
     pub fn panic_or_err(&self, msg: &str) -> P<Expr> {
@@ -1280,18 +1790,24 @@ impl<'c> Translation<'c> {
         (fn_item, static_item)
     }
 
+    /// Extract the comments of a declaration, with Doxygen-style comments as doc comments, and
+    /// return the `Span` to give to what it gets translated into
+    fn decl_doc_span(&self, decl_id: CDeclId) -> Span {
+        let decl_cmt = self
+            .comment_context
+            .borrow_mut()
+            .remove_decl_comment(decl_id);
+        self.comment_store
+            .borrow_mut()
+            .add_doc_comment_lines(decl_cmt)
+    }
+
     fn convert_decl(
         &self,
         ctx: ExprContext,
         decl_id: CDeclId,
     ) -> Result<ConvertedDecl, TranslationError> {
-        let mut s = {
-            let decl_cmt = self
-                .comment_context
-                .borrow_mut()
-                .remove_decl_comment(decl_id);
-            self.comment_store.borrow_mut().add_comment_lines(decl_cmt)
-        };
+        let mut s = self.decl_doc_span(decl_id);
 
         let decl = self
             .ast_context
@@ -1330,6 +1846,9 @@ impl<'c> Translation<'c> {
                     .borrow()
                     .resolve_decl_name(decl_id)
                     .unwrap();
+                if self.tagged_unions.contains_key(&decl_id) {
+                    return self.convert_tagged_union(s, decl_id, &name);
+                }
                 let mut has_bitfields = false;
 
                 // Check if the last field might be a flexible array member
@@ -1373,8 +1892,10 @@ impl<'c> Translation<'c> {
                             ));
 
                             let typ = self.convert_type(typ.ctype)?;
+                            let field_span = self.decl_doc_span(x);
 
-                            field_entries.push(mk().span(s).pub_().struct_field(name, typ));
+                            field_entries
+                                .push(mk().span(field_span).pub_().struct_field(name, typ));
                         }
                         _ => {
                             return Err(TranslationError::generic(
@@ -1442,7 +1963,8 @@ impl<'c> Translation<'c> {
                                 .borrow_mut()
                                 .declare_field_name(decl_id, x, name);
                             let typ = self.convert_type(typ.ctype)?;
-                            field_syns.push(mk().span(s).pub_().struct_field(name, typ))
+                            let field_span = self.decl_doc_span(x);
+                            field_syns.push(mk().span(field_span).pub_().struct_field(name, typ))
                         }
                         _ => {
                             return Err(TranslationError::generic(
@@ -1478,6 +2000,7 @@ impl<'c> Translation<'c> {
 
             CDeclKind::Enum {
                 integral_type: Some(integral_type),
+                ref variants,
                 ..
             } => {
                 let enum_name = &self
@@ -1485,26 +2008,40 @@ impl<'c> Translation<'c> {
                     .borrow()
                     .resolve_decl_name(decl_id)
                     .expect("Enums should already be renamed");
+                if self.is_rust_enum(decl_id) {
+                    return self.convert_rust_enum(s, enum_name, integral_type, variants);
+                }
                 let ty = self.convert_type(integral_type.ctype)?;
                 Ok(ConvertedDecl::Item(
                     mk().span(s).pub_().type_item(enum_name, ty),
                 ))
             }
 
-            CDeclKind::EnumConstant { value, .. } => {
+            CDeclKind::EnumConstant {
+                value,
+                is_layout_dependent,
+                ..
+            } => {
                 let name = self
                     .renamer
                     .borrow_mut()
                     .get(&decl_id)
                     .expect("Enum constant not named");
+                if is_layout_dependent {
+                    self.warn_layout_dependent(&decl.loc, &format!("The value of {}", name));
+                }
                 let enum_id = self.ast_context.parents[&decl_id];
                 let enum_name = self
                     .type_converter
                     .borrow()
                     .resolve_decl_name(enum_id)
                     .expect("Enums should already be renamed");
-                let ty = mk().path_ty(mk().path(vec![enum_name]));
+                let ty = mk().path_ty(mk().path(vec![enum_name.as_str()]));
                 let val = match value {
+                    _ if self.is_rust_enum(enum_id) => {
+                        let variant_name = self.enum_variant_name(decl_id);
+                        mk().path_expr(vec![enum_name.as_str(), variant_name.as_str()])
+                    }
                     ConstIntExpr::I(value) => signed_int_expr(value),
                     ConstIntExpr::U(value) => {
                         mk().lit_expr(mk().int_lit(value as u128, LitIntType::Unsuffixed))
@@ -1541,6 +2078,10 @@ impl<'c> Translation<'c> {
                     return Ok(ConvertedDecl::NoItem);
                 }
 
+                if self.import_linked_function(decl_id, new_name) {
+                    return Ok(ConvertedDecl::NoItem);
+                }
+
                 let (ret, is_var): (Option<CQualTypeId>, bool) =
                     match self.ast_context.resolve_type(typ).kind {
                         CTypeKind::Function(ret, _, is_var, is_noreturn, _) => {
@@ -1571,21 +2112,34 @@ impl<'c> Translation<'c> {
 
                 let is_main = self.ast_context.c_main == Some(decl_id);
 
+                // Bindings only declare the functions defined in C
+                let (is_inline, body) = if self.tcfg.bindings_only() {
+                    (false, None)
+                } else {
+                    (is_inline, body)
+                };
+                let is_stable_var = body.is_some() && self.is_stable_variadic(decl_id);
+
+                // C++ functions are linked by their mangled names
+                let symbol = self.ast_context.symbol_name(decl_id, name);
+
                 let converted_function = self.convert_function(
-                    ctx, s, is_global, is_inline, is_main, is_var, is_extern, new_name, name,
-                    &args, ret, body, attrs,
+                    ctx, s, decl_id, is_global, is_inline, is_main, is_var, is_stable_var,
+                    is_extern, new_name, symbol, &args, ret, body, attrs,
                 );
 
                 converted_function.or_else(|e| match self.tcfg.replace_unsupported_decls {
                     ReplaceMode::Extern if body.is_none() => self.convert_function(
-                        ctx, s, is_global, false, is_main, is_var, is_extern, new_name, name,
-                        &args, ret, None, attrs,
+                        ctx, s, decl_id, is_global, false, is_main, is_var, false, is_extern,
+                        new_name, symbol, &args, ret, None, attrs,
                     ),
                     _ => Err(e),
                 })
             }
 
-            CDeclKind::Typedef { ref typ, .. } => {
+            CDeclKind::Typedef {
+                ref name, ref typ, ..
+            } => {
                 let new_name = &self
                     .type_converter
                     .borrow()
@@ -1596,30 +2150,39 @@ impl<'c> Translation<'c> {
                     return Ok(ConvertedDecl::NoItem);
                 }
 
-                let ty = self.convert_type(typ.ctype)?;
+                let pointer_sized = self.type_converter.borrow().convert_pointer_sized_typedef(
+                    &self.ast_context,
+                    name,
+                    typ.ctype,
+                );
+                let ty = match pointer_sized {
+                    Some(ty) => ty,
+                    None => self.convert_type(typ.ctype)?,
+                };
                 Ok(ConvertedDecl::Item(
                     mk().span(s).pub_().type_item(new_name, ty),
                 ))
             }
 
-            // Externally-visible variable without initializer (definition elsewhere)
+            // Externally-visible variable without initializer (definition elsewhere). Bindings
+            // declare the variables defined in C as well.
             CDeclKind::Variable {
                 is_externally_visible: true,
                 has_static_duration,
                 has_thread_duration,
-                is_defn: false,
+                is_defn,
                 ref ident,
                 initializer,
                 typ,
                 ref attrs,
                 ..
-            } => {
+            } if !is_defn || self.tcfg.bindings_only() => {
                 assert!(
                     has_static_duration || has_thread_duration,
                     "An extern variable must be static or thread-local"
                 );
                 assert!(
-                    initializer.is_none(),
+                    is_defn || initializer.is_none(),
                     "An extern variable that isn't a definition can't have an initializer"
                 );
 
@@ -1632,9 +2195,18 @@ impl<'c> Translation<'c> {
                     .borrow()
                     .get(&decl_id)
                     .expect("Variables should already be renamed");
-                let (ty, mutbl, _) = self.convert_variable(ctx.static_(), None, typ)?;
+                let c_aliases = self.type_converter.borrow_mut().set_c_aliases(true);
+                let converted = self.convert_variable(ctx.static_(), None, typ);
+                self.type_converter.borrow_mut().set_c_aliases(c_aliases);
+                let (ty, mutbl, _) = converted?;
+                // Variables that C code can access hold Rust enums as integers
+                let ty = if self.is_rust_enum_type(typ.ctype) {
+                    self.convert_boundary_type(typ.ctype)?
+                } else {
+                    ty
+                };
                 // When putting extern statics into submodules, they need to be public to be accessible
-                let visibility = if self.tcfg.reorganize_definitions {
+                let visibility = if self.tcfg.splits_headers() {
                     "pub"
                 } else {
                     ""
@@ -1691,6 +2263,9 @@ impl<'c> Translation<'c> {
                         self.convert_variable(ctx.not_static(), initializer, typ)?;
 
                     let mut init = init?.to_expr();
+                    if is_externally_visible {
+                        init = self.rust_enum_to_boundary(typ.ctype, init)?;
+                    }
 
                     let comment = String::from("// Initialized in run_static_initializers");
                     // REVIEW: We might want to add the comment to the original span comments
@@ -1718,6 +2293,14 @@ impl<'c> Translation<'c> {
                     (ty, init)
                 };
 
+                // Variables that C code can access hold Rust enums as integers
+                let (ty, init) = if is_externally_visible && self.is_rust_enum_type(typ.ctype) {
+                    let init = self.rust_enum_to_boundary(typ.ctype, init)?;
+                    (self.convert_boundary_type(typ.ctype)?, init)
+                } else {
+                    (ty, init)
+                };
+
                 let static_def = if is_externally_visible {
                     mk_linkage(false, new_name, ident).pub_().abi("C")
                 } else if self.cur_file.borrow().is_some() {
@@ -1798,6 +2381,22 @@ impl<'c> Translation<'c> {
         ctx: ExprContext,
         replacements: &[CExprId],
     ) -> Result<(P<Expr>, CTypeId), TranslationError> {
+        // `_Generic` selects by the types at each expansion site, so the expansions can only
+        // share a constant if they all select the same associations. Otherwise every expansion
+        // is translated in place. Function-like macros are not exported, so they never become
+        // functions and need no trait-based dispatch over the associations.
+        let mut selections = replacements
+            .iter()
+            .map(|&id| self.ast_context.generic_selections(id));
+        if let Some(first) = selections.next() {
+            if selections.any(|selection| selection != first) {
+                return Err(format_err!(
+                    "Macro expansions select different `_Generic` associations"
+                )
+                .into());
+            }
+        }
+
         let (val, ty) = replacements
             .iter()
             .try_fold::<Option<(WithStmts<P<Expr>>, CTypeId)>, _, _>(
@@ -1865,10 +2464,12 @@ impl<'c> Translation<'c> {
         &self,
         ctx: ExprContext,
         span: Span,
+        decl_id: CDeclId,
         is_global: bool,
         is_inline: bool,
         is_main: bool,
         is_variadic: bool,
+        is_stable_variadic: bool,
         is_extern: bool,
         new_name: &str,
         name: &str,
@@ -1877,12 +2478,14 @@ impl<'c> Translation<'c> {
         body: Option<CStmtId>,
         attrs: &IndexSet<c_ast::Attribute>,
     ) -> Result<ConvertedDecl, TranslationError> {
-        self.function_context.borrow_mut().enter_new(name);
+        self.function_context
+            .borrow_mut()
+            .enter_new(name, return_type);
 
         let is_valist: bool = arguments
             .iter()
             .any(|&(_, _, typ)| Self::is_inner_type_valist(&self.ast_context, typ));
-        if is_variadic || is_valist {
+        if (is_variadic || is_valist) && !is_stable_variadic {
             if let Some(body_id) = body {
                 if !self.is_well_formed_variadic(body_id) {
                     return Err(format_err!("Variadic function definition is not well-formed.").into());
@@ -1890,12 +2493,37 @@ impl<'c> Translation<'c> {
             }
         }
 
-        self.with_scope(|| {
+        // Blocks cross the boundary of functions visible to C in their ABI representation
+        let uses_block_abi = is_global || body.is_none();
+
+        // Declarations of functions defined in C keep the C types in their signatures
+        let c_aliases = self
+            .type_converter
+            .borrow_mut()
+            .set_c_aliases(body.is_none());
+
+        let converted = self.with_scope(|| {
             let mut args: Vec<Arg> = vec![];
+            let mut abi_args: Vec<Stmt> = vec![];
 
             // handle regular (non-variadic) arguments
             for &(decl_id, ref var, typ) in arguments {
                 let (ty, mutbl, _) = self.convert_variable(ctx, None, typ)?;
+                let is_block_abi_arg = uses_block_abi && self.ast_context.is_block_pointer(typ.ctype);
+                let from_abi = if is_block_abi_arg {
+                    Some(self.block_abi_helpers(typ.ctype)?.from_abi)
+                } else {
+                    None
+                };
+                // Rust enums are passed as integers
+                let is_rust_enum_arg = self.is_rust_enum_type(typ.ctype);
+                let ty = if is_block_abi_arg {
+                    blocks::block_literal_ptr_ty()
+                } else if is_rust_enum_arg {
+                    self.convert_boundary_type(typ.ctype)?
+                } else {
+                    ty
+                };
 
                 let pat = if var.is_empty() {
                     mk().wild_pat()
@@ -1916,14 +2544,39 @@ impl<'c> Translation<'c> {
                             var, name
                         ));
 
-                    mk().set_mutbl(mutbl).ident_pat(new_var)
+                    let converted = match from_abi {
+                        Some(from_abi) => Some(mk().call_expr(
+                            mk().path_expr(vec![from_abi]),
+                            vec![mk().ident_expr(&new_var)],
+                        )),
+                        None if is_rust_enum_arg && body.is_some() => {
+                            let loc = self.ast_context[decl_id].loc.as_ref();
+                            let arg = mk().ident_expr(&new_var);
+                            Some(self.rust_enum_from_boundary(typ.ctype, arg, loc)?)
+                        }
+                        None => None,
+                    };
+                    match converted {
+                        Some(converted) => {
+                            let pat = mk().set_mutbl(mutbl).ident_pat(&new_var);
+                            let local = mk().local(pat, None as Option<P<Ty>>, Some(converted));
+                            abi_args.push(mk().local_stmt(P(local)));
+                            mk().ident_pat(new_var)
+                        }
+                        None => mk().set_mutbl(mutbl).ident_pat(new_var),
+                    }
                 };
 
                 args.push(mk().arg(ty, pat))
             }
 
             // handle variadic arguments
-            if is_variadic {
+            if is_stable_variadic {
+                args.push(self.stable_va_args_arg())
+            } else if is_variadic {
+                if body.is_some() {
+                    self.use_feature("c_variadic");
+                }
                 if let Some(va_decl_id) = self.get_promoted_va_decl() {
                     // `register_va_arg` succeeded
                     let var = self
@@ -1942,7 +2595,7 @@ impl<'c> Translation<'c> {
 
             // handle return type
             let ret = match return_type {
-                Some(return_type) => self.convert_type(return_type.ctype)?,
+                Some(return_type) => self.convert_boundary_type(return_type.ctype)?,
                 None => mk().never_ty(),
             };
             let is_void_ret = return_type
@@ -1957,7 +2610,7 @@ impl<'c> Translation<'c> {
                 FunctionRetTy::Ty(ret)
             };
 
-            let decl = mk().fn_decl(args, ret, is_variadic);
+            let decl = mk().fn_decl(args, ret, is_variadic && !is_stable_variadic);
 
             if let Some(body) = body {
                 // Translating an actual function
@@ -1977,7 +2630,7 @@ impl<'c> Translation<'c> {
                     _ => cfg::ImplicitReturnType::Void,
                 };
 
-                let mut body_stmts = vec![];
+                let mut body_stmts = abi_args;
                 for &(_, _, typ) in arguments {
                     body_stmts.append(&mut self.compute_variable_array_sizes(ctx, typ.ctype)?);
                 }
@@ -1987,7 +2640,17 @@ impl<'c> Translation<'c> {
                     _ => panic!("function body expects to be a compound statement"),
                 };
                 body_stmts.append(&mut self.convert_function_body(ctx, name, body_ids, ret)?);
-                let block = stmts_block(body_stmts);
+                let mut block = stmts_block(body_stmts);
+
+                // Comments after the last statement of the function
+                let end_cmmt = self
+                    .comment_context
+                    .borrow_mut()
+                    .remove_block_end_comment(body);
+                block.span = self
+                    .comment_store
+                    .borrow_mut()
+                    .add_trailing_comment_lines(block.span, end_cmmt);
 
                 // Only add linkage attributes if the function is `extern`
                 let mut mk_ = if is_main {
@@ -2002,6 +2665,12 @@ impl<'c> Translation<'c> {
                     // extern inlines, which become subject to their gnu89 visibility (private)
 
                     mk_linkage(false, new_name, name).abi("C").pub_()
+                } else if is_stable_variadic {
+                    // Only Rust code calls the function
+                    mk()
+                } else if self.shared_inline_functions.contains(&decl_id) {
+                    // Only the modules of the translation units import shared `static inline`s
+                    mk().abi("C").vis("pub(crate)")
                 } else if self.cur_file.borrow().is_some() {
                     mk().abi("C").pub_()
                 } else {
@@ -2029,7 +2698,7 @@ impl<'c> Translation<'c> {
                 // Translating an extern function declaration
 
                 // When putting extern fns into submodules, they need to be public to be accessible
-                let visibility = if self.tcfg.reorganize_definitions {
+                let visibility = if self.tcfg.splits_headers() {
                     "pub"
                 } else {
                     ""
@@ -2048,7 +2717,10 @@ impl<'c> Translation<'c> {
 
                 Ok(ConvertedDecl::ForeignItem(function_decl))
             }
-        })
+        });
+
+        self.type_converter.borrow_mut().set_c_aliases(c_aliases);
+        converted
     }
 
     pub fn convert_cfg(
@@ -2076,6 +2748,49 @@ impl<'c> Translation<'c> {
                 .expect("Failed to write CFG .json file");
         }
 
+        let current_block_ident = self.renamer.borrow_mut().pick_name("current_block");
+        let current_block = mk().ident_expr(&current_block_ident);
+
+        // The incremental relooper calls us on sub-statements too, so select on the name of the
+        // enclosing function
+        let fn_name = self
+            .function_context
+            .borrow()
+            .name
+            .clone()
+            .unwrap_or_else(|| name.to_string());
+        let eliminate_gotos = self
+            .tcfg
+            .goto_elimination
+            .as_ref()
+            .map_or(false, |functions| functions.is_match(&fn_name));
+        // Statistics are only needed for `--cfg-stats` and for reporting on goto elimination
+        let collect_stats = self.tcfg.cfg_stats || eliminate_gotos;
+        let mut stats = cfg::stats::CfgStats::new(&graph);
+        let mut store = store;
+        if eliminate_gotos {
+            if let Some((lifted_stmts, structured)) =
+                cfg::labeled_blocks::eliminate_gotos(&graph, &mut store, live_in.clone())
+            {
+                if collect_stats {
+                    self.add_cfg_stats(fn_name, &stats);
+                }
+                if cfg::labeled_blocks::has_block(&structured) {
+                    self.use_feature("label_break_value");
+                }
+
+                let mut stmts: Vec<Stmt> = lifted_stmts;
+                stmts.extend(cfg::structures::structured_ast_into_stmts(
+                    structured,
+                    &mut self.comment_store.borrow_mut(),
+                    current_block,
+                    self.tcfg.debug_relooper_labels,
+                    cut_out_trailing_ret,
+                ));
+                return Ok(stmts);
+            }
+        }
+
         let (lifted_stmts, relooped) = cfg::relooper::reloop(
             graph,
             store,
@@ -2083,7 +2798,11 @@ impl<'c> Translation<'c> {
             self.tcfg.use_c_loop_info,
             self.tcfg.use_c_multiple_info,
             live_in,
+            &mut stats,
         );
+        if collect_stats {
+            stats.multiples = cfg::stats::count_multiples(&relooped);
+        }
 
         if self.tcfg.dump_structures {
             eprintln!("Relooped structures:");
@@ -2092,14 +2811,13 @@ impl<'c> Translation<'c> {
             }
         }
 
-        let current_block_ident = self.renamer.borrow_mut().pick_name("current_block");
-        let current_block = mk().ident_expr(&current_block_ident);
         let mut stmts: Vec<Stmt> = lifted_stmts;
         if cfg::structures::has_multiple(&relooped) {
             if self.tcfg.fail_on_multiple {
                 panic!("Uses of `current_block' are illegal with `--fail-on-multiple'.");
             }
 
+            stats.current_blocks += 1;
             let current_block_ty = if self.tcfg.debug_relooper_labels {
                 mk().ref_lt_ty("'static", mk().path_ty(vec!["str"]))
             } else {
@@ -2113,6 +2831,9 @@ impl<'c> Translation<'c> {
             );
             stmts.push(mk().local_stmt(P(local)))
         }
+        if collect_stats {
+            self.add_cfg_stats(fn_name, &stats);
+        }
 
         stmts.extend(cfg::structures::structured_cfg(
             &relooped,
@@ -2124,6 +2845,15 @@ impl<'c> Translation<'c> {
         Ok(stmts)
     }
 
+    /// Record control-flow metrics of one of the CFGs of a function
+    fn add_cfg_stats(&self, fn_name: String, stats: &cfg::stats::CfgStats) {
+        self.cfg_stats
+            .borrow_mut()
+            .entry(fn_name)
+            .or_insert_with(Default::default)
+            .add(stats)
+    }
+
     fn convert_function_body(
         &self,
         ctx: ExprContext,
@@ -2134,7 +2864,18 @@ impl<'c> Translation<'c> {
         // Function body scope
         self.with_scope(|| {
             let (graph, store) = cfg::Cfg::from_stmts(self, ctx, body_ids, ret)?;
-            self.convert_cfg(name, graph, store, IndexSet::new(), true)
+            let stmts = self.convert_cfg(name, graph, store, IndexSet::new(), true)?;
+
+            if self.tcfg.cfg_stats {
+                let nesting_depth = cfg::stats::nesting_depth(&stmts);
+                let mut cfg_stats = self.cfg_stats.borrow_mut();
+                let stats = cfg_stats
+                    .entry(name.to_string())
+                    .or_insert_with(Default::default);
+                stats.nesting_depth = nesting_depth;
+            }
+
+            Ok(stmts)
         })
     }
 
@@ -2158,7 +2899,9 @@ impl<'c> Translation<'c> {
                     .get_type()
                     .ok_or_else(|| format_err!("bad pointer type for condition"))?;
                 Ok(val.map(|e| {
-                    if self.ast_context.is_function_pointer(ptr_type) {
+                    if self.ast_context.is_function_pointer(ptr_type)
+                        || self.ast_context.is_block_pointer(ptr_type)
+                    {
                         if negated {
                             mk().method_call_expr(e, "is_some", vec![] as Vec<P<Expr>>)
                         } else {
@@ -2264,7 +3007,7 @@ impl<'c> Translation<'c> {
                     let ident2 = self
                         .renamer
                         .borrow_mut()
-                        .insert_root(decl_id, ident)
+                        .insert_root_with_case(decl_id, ident, NameCase::ScreamingSnake)
                         .ok_or_else(|| {
                             TranslationError::generic(
                                 "Unable to rename function scoped static initializer",
@@ -2316,6 +3059,12 @@ impl<'c> Translation<'c> {
                     .insert(decl_id, &ident)
                     .expect(&format!("Failed to insert variable '{}'", ident));
 
+                if self.stable_va_args().is_some()
+                    && Self::is_inner_type_valist(&self.ast_context, typ)
+                {
+                    return Ok(self.convert_stable_va_list_decl(&rust_name));
+                }
+
                 if self.is_copied_va_decl(decl_id) {
                     // translate `va_list` declarations not promoted to an arg
                     // to `VaList` and do not emit an initializer.
@@ -2334,6 +3083,16 @@ impl<'c> Translation<'c> {
                     ));
                 }
 
+                if self.is_byref_block_var(decl_id) {
+                    return self.convert_byref_block_var(
+                        ctx,
+                        decl_id,
+                        &rust_name,
+                        initializer,
+                        typ,
+                    );
+                }
+
                 let has_self_reference = if let Some(expr_id) = initializer {
                     self.has_decl_reference(decl_id, expr_id)
                 } else {
@@ -2401,7 +3160,11 @@ impl<'c> Translation<'c> {
 
             ref decl => {
                 let inserted = if let Some(ident) = decl.get_name() {
-                    self.renamer.borrow_mut().insert(decl_id, &ident).is_some()
+                    let case = self.value_name_case(decl);
+                    self.renamer
+                        .borrow_mut()
+                        .insert_with_case(decl_id, &ident, case)
+                        .is_some()
                 } else {
                     false
                 };
@@ -2421,17 +3184,18 @@ impl<'c> Translation<'c> {
                 if skip {
                     Ok(cfg::DeclStmtInfo::new(vec![], vec![], vec![]))
                 } else {
-                    let item = match self.convert_decl(ctx, decl_id)? {
-                        ConvertedDecl::Item(item) => item,
-                        ConvertedDecl::ForeignItem(item) => mk().abi("C").foreign_items(vec![item]),
+                    let items = match self.convert_decl(ctx, decl_id)? {
+                        ConvertedDecl::Item(item) => vec![item],
+                        ConvertedDecl::Items(items) => items,
+                        ConvertedDecl::ForeignItem(item) => {
+                            vec![mk().abi("C").foreign_items(vec![item])]
+                        }
                         ConvertedDecl::NoItem => return Ok(cfg::DeclStmtInfo::empty()),
                     };
+                    let stmts: Vec<Stmt> =
+                        items.into_iter().map(|item| mk().item_stmt(item)).collect();
 
-                    Ok(cfg::DeclStmtInfo::new(
-                        vec![mk().item_stmt(item.clone())],
-                        vec![],
-                        vec![mk().item_stmt(item)],
-                    ))
+                    Ok(cfg::DeclStmtInfo::new(stmts.clone(), vec![], stmts))
                 }
             }
         }
@@ -2541,7 +3305,7 @@ impl<'c> Translation<'c> {
         TranslationError,
     > {
         let init = match initializer {
-            Some(x) => self.convert_expr(ctx.used(), x),
+            Some(x) => self.convert_expr_as(ctx.used(), x, typ.ctype),
             None => self.implicit_default_expr(typ.ctype, ctx.is_static),
         };
 
@@ -2552,9 +3316,12 @@ impl<'c> Translation<'c> {
         {
             elt = self.variable_array_base_type(elt);
             let ty = self.convert_type(elt)?;
-            mk().path_ty(vec![
-                mk().path_segment_with_args("Vec", mk().angle_bracketed_args(vec![ty]))
-            ])
+            match self.variable_array_inline_capacity(typ.ctype) {
+                Some(capacity) => small_vec_ty(ty, capacity),
+                None => mk().path_ty(vec![
+                    mk().path_segment_with_args("Vec", mk().angle_bracketed_args(vec![ty]))
+                ]),
+            }
         } else {
             self.convert_type(typ.ctype)?
         };
@@ -2577,10 +3344,60 @@ impl<'c> Translation<'c> {
             .convert(&self.ast_context, type_id)
     }
 
+    /// Cast a value of C type `from` that C uses as a value of type `to` without converting it.
+    /// C converts between a typedef and the type it is defined as implicitly, but when using
+    /// fixed-width types, the typedefs of pointer-sized integers are translated to `usize` and
+    /// `isize` whereas the types they are defined as are not.
+    pub fn cast_pointer_sized(
+        &self,
+        from: CTypeId,
+        to: CTypeId,
+        val: P<Expr>,
+    ) -> Result<P<Expr>, TranslationError> {
+        let needs_cast = {
+            let type_converter = self.type_converter.borrow();
+            self.ast_context.resolve_type(from).kind == self.ast_context.resolve_type(to).kind
+                && type_converter.is_pointer_sized_typedef(&self.ast_context, from)
+                    != type_converter.is_pointer_sized_typedef(&self.ast_context, to)
+        };
+        if needs_cast {
+            Ok(mk().cast_expr(val, self.convert_type(to)?))
+        } else {
+            Ok(val)
+        }
+    }
+
+    /// Convert an expression that C uses as a value of type `ty` without converting it, see
+    /// `cast_pointer_sized`
+    pub fn convert_expr_as(
+        &self,
+        ctx: ExprContext,
+        expr_id: CExprId,
+        ty: CTypeId,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let val = self.convert_expr(ctx, expr_id)?;
+        match self.ast_context[expr_id].kind.get_type() {
+            Some(expr_ty) => val.result_map(|val| self.cast_pointer_sized(expr_ty, ty, val)),
+            None => Ok(val),
+        }
+    }
+
+    /// The return type of the function or block being translated
+    pub fn return_type(&self) -> Option<CQualTypeId> {
+        self.function_context.borrow().return_type
+    }
+
+    /// Convert a primitive C type the same way as the types of the C AST
+    pub fn convert_primitive(&self, kind: CTypeKind) -> P<Ty> {
+        self.type_converter.borrow_mut().convert_primitive(&kind)
+    }
+
     /// Construct an expression for a NULL at any type, including forward declarations,
     /// function pointers, and normal pointers.
     fn null_ptr(&self, type_id: CTypeId, is_static: bool) -> Result<P<Expr>, TranslationError> {
-        if self.ast_context.is_function_pointer(type_id) {
+        if self.ast_context.is_function_pointer(type_id)
+            || self.ast_context.is_block_pointer(type_id)
+        {
             return Ok(mk().path_expr(vec!["None"]));
         }
 
@@ -2715,6 +3532,92 @@ impl<'c> Translation<'c> {
         elt
     }
 
+    /// Inline capacity of the stack buffer backing a variable-length array, if VLAs are stack
+    /// allocated. When the length of the array has a constant upper bound below the configured
+    /// capacity, the buffer is sized to that bound so that it never spills onto the heap.
+    fn variable_array_inline_capacity(&self, type_id: CTypeId) -> Option<usize> {
+        let capacity = self.tcfg.stack_vla_capacity?;
+        self.extern_crates.borrow_mut().insert("smallvec");
+
+        let mut bound = Some(1u64);
+        let mut type_id = type_id;
+        while let CTypeKind::VariableArray(elt, count) = self.ast_context.resolve_type(type_id).kind {
+            bound = match (bound, count.and_then(|count| self.expr_upper_bound(count))) {
+                (Some(bound), Some(count)) => bound.checked_mul(count),
+                _ => None,
+            };
+            type_id = elt;
+        }
+
+        Some(self.inline_capacity(bound, capacity))
+    }
+
+    /// Inline capacity of the stack buffer backing an `alloca` of the given number of bytes
+    fn alloca_inline_capacity(&self, size: CExprId) -> Option<usize> {
+        let capacity = self.tcfg.stack_vla_capacity?;
+        self.extern_crates.borrow_mut().insert("smallvec");
+        Some(self.inline_capacity(self.expr_upper_bound(size), capacity))
+    }
+
+    /// The bound rounded up to a capacity `smallvec` supports, which is at most the configured
+    /// capacity when that is supported as well
+    fn inline_capacity(&self, bound: Option<u64>, capacity: usize) -> usize {
+        match bound {
+            Some(bound) if bound < capacity as u64 => small_vec_capacity(bound.max(1) as usize),
+            _ => small_vec_capacity(capacity),
+        }
+    }
+
+    /// Conservatively compute a constant upper bound on the value of an integer expression,
+    /// e.g. `n < 16 ? n : 16` or a variable of type `unsigned char`.
+    fn expr_upper_bound(&self, expr_id: CExprId) -> Option<u64> {
+        let bound = match self.ast_context[expr_id].kind {
+            CExprKind::Literal(_, CLiteral::Integer(val, _))
+            | CExprKind::Literal(_, CLiteral::Character(val)) => Some(val),
+
+            CExprKind::Paren(_, e)
+            | CExprKind::ImplicitCast(_, e, CastKind::IntegralCast, _, _)
+            | CExprKind::ImplicitCast(_, e, CastKind::LValueToRValue, _, _)
+            | CExprKind::ExplicitCast(_, e, CastKind::IntegralCast, _, _) => {
+                self.expr_upper_bound(e)
+            }
+
+            CExprKind::Conditional(_, _, lhs, rhs) => {
+                match (self.expr_upper_bound(lhs), self.expr_upper_bound(rhs)) {
+                    (Some(lhs), Some(rhs)) => Some(lhs.max(rhs)),
+                    _ => None,
+                }
+            }
+
+            CExprKind::Binary(_, op, lhs, rhs, _, _) => {
+                match (op, self.expr_upper_bound(lhs), self.expr_upper_bound(rhs)) {
+                    (c_ast::BinOp::Add, Some(lhs), Some(rhs)) => lhs.checked_add(rhs),
+                    (c_ast::BinOp::Multiply, Some(lhs), Some(rhs)) => lhs.checked_mul(rhs),
+                    (c_ast::BinOp::Divide, Some(lhs), _) => Some(lhs),
+                    (c_ast::BinOp::Modulus, _, Some(rhs)) => Some(rhs.saturating_sub(1)),
+                    (c_ast::BinOp::BitAnd, Some(lhs), Some(rhs)) => Some(lhs.min(rhs)),
+                    (c_ast::BinOp::BitAnd, bound, None) | (c_ast::BinOp::BitAnd, None, bound) => {
+                        bound
+                    }
+                    _ => None,
+                }
+            }
+
+            _ => None,
+        };
+
+        // Fall back to the range of small integer types
+        bound.or_else(|| {
+            let ty = self.ast_context[expr_id].kind.get_type()?;
+            match self.ast_context.resolve_type(ty).kind {
+                CTypeKind::Bool => Some(1),
+                CTypeKind::Char | CTypeKind::SChar | CTypeKind::UChar => Some(u8::max_value() as u64),
+                CTypeKind::Short | CTypeKind::UShort => Some(u16::max_value() as u64),
+                _ => None,
+            }
+        })
+    }
+
     /// This generates variables that store the computed sizes of the variable-length arrays in
     /// the given type.
     pub fn compute_variable_array_sizes(
@@ -2842,6 +3745,35 @@ impl<'c> Translation<'c> {
     /// `stmts` field of the output and it is expected that the `val` field of the output will be
     /// ignored.
     pub fn convert_expr(
+        &self,
+        ctx: ExprContext,
+        expr_id: CExprId,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let cmmts = self
+            .comment_context
+            .borrow_mut()
+            .remove_expr_comment(expr_id);
+        let mut converted = self.convert_expr_node(ctx, expr_id)?;
+        if cmmts.is_empty() {
+            return Ok(converted);
+        }
+
+        // Comments in front of the expression go with the expression, unless its value is
+        // thrown away, in which case they go with its first statement
+        let span = self.comment_store.borrow_mut().add_comment_lines(cmmts);
+        let first_stmt = converted
+            .stmts_mut()
+            .first_mut()
+            .filter(|stmt| ctx.is_unused() && stmt.span == DUMMY_SP);
+        if let Some(stmt) = first_stmt {
+            stmt.span = span;
+            return Ok(converted);
+        }
+        Ok(converted.map(|val| val.map(|val| Expr { span, ..val })))
+    }
+
+    /// Translate a C expression, without the comments in front of it
+    fn convert_expr_node(
         &self,
         mut ctx: ExprContext,
         expr_id: CExprId,
@@ -2849,10 +3781,15 @@ impl<'c> Translation<'c> {
         let Located {
             loc: src_loc,
             kind: expr_kind,
+            ..
         } = &self.ast_context[expr_id];
 
         trace!("Converting expr {:?}: {:?}", expr_id, self.ast_context[expr_id]);
 
+        if self.tcfg.checked {
+            ctx.checked_expr = Some(expr_id);
+        }
+
         if self.tcfg.translate_const_macros {
             if let Some(converted) = self.convert_macro_expansion(ctx, expr_id)? {
                 return Ok(converted);
@@ -2875,7 +3812,7 @@ impl<'c> Translation<'c> {
                 Err(TranslationError::generic("convert vector not supported"))
             }
 
-            CExprKind::UnaryType(_ty, kind, opt_expr, arg_ty) => {
+            CExprKind::UnaryType(ty, kind, opt_expr, arg_ty) => {
                 let result = match kind {
                     UnTypeOp::SizeOf => match opt_expr {
                         None => self.compute_size_of_type(ctx, arg_ty.ctype)?,
@@ -2895,7 +3832,8 @@ impl<'c> Translation<'c> {
                     UnTypeOp::PreferredAlignOf => self.compute_align_of_type(arg_ty.ctype, true)?,
                 };
 
-                Ok(result.map(|x| mk().cast_expr(x, mk().path_ty(vec!["libc", "c_ulong"]))))
+                let ty = self.convert_type(ty.ctype)?;
+                Ok(result.map(|x| mk().cast_expr(x, ty)))
             }
 
             CExprKind::DeclRef(qual_ty, decl_id, lrvalue) => {
@@ -2921,7 +3859,7 @@ impl<'c> Translation<'c> {
                     .ok_or_else(|| format_err!("name not declared: '{}'", varname))?;
 
                 // Import the referenced global decl into our submodule
-                if self.tcfg.reorganize_definitions {
+                if self.tcfg.splits_headers() {
                     if let Some(cur_file) = self.cur_file.borrow().as_ref() {
                         self.add_import(cur_file, decl_id, &rustname);
                         // match decl {
@@ -2933,6 +3871,12 @@ impl<'c> Translation<'c> {
 
                 let mut val = mk().path_expr(vec![rustname]);
 
+                // `__block` variables live in a cell shared with the blocks that capture them
+                if self.is_byref_block_var(decl_id) {
+                    let ptr = mk().method_call_expr(val, "as_ptr", vec![] as Vec<P<Expr>>);
+                    val = mk().paren_expr(mk().unary_expr(ast::UnOp::Deref, ptr));
+                }
+
                 // If the variable is volatile and used as something that isn't an LValue, this
                 // constitutes a volatile read.
                 if lrvalue.is_rvalue() && qual_ty.qualifiers.is_volatile {
@@ -2967,6 +3911,7 @@ impl<'c> Translation<'c> {
 
             CExprKind::OffsetOf(ty, ref kind) => match kind {
                 OffsetOfKind::Constant(val) => {
+                    self.warn_layout_dependent(src_loc, "The value of offsetof");
                     Ok(WithStmts::new_val(self.mk_int_lit(ty, *val, IntBase::Dec)))
                 }
                 OffsetOfKind::Variable(qty, field_id, expr_id) => {
@@ -3061,6 +4006,15 @@ impl<'c> Translation<'c> {
                 } else {
                     self.convert_expr(ctx, expr)?
                 };
+                // Bitfields and variables that C code can access hold Rust enums as integers
+                let val = match kind {
+                    CastKind::LValueToRValue if self.holds_rust_enum_as_integer(expr) => {
+                        val.result_map(|val| {
+                            self.rust_enum_from_boundary(source_ty.ctype, val, src_loc.as_ref())
+                        })?
+                    }
+                    _ => val,
+                };
                 // Shuffle Vector "function" builtins will add a cast to the output of the
                 // builtin call which is unnecessary for translation purposes
                 if self.casting_simd_builtin_call(expr, is_explicit, kind) {
@@ -3073,7 +4027,7 @@ impl<'c> Translation<'c> {
                 self.convert_unary_operator(ctx, op, type_id, arg, lrvalue)
             }
 
-            CExprKind::Conditional(_, cond, lhs, rhs) => {
+            CExprKind::Conditional(ty, cond, lhs, rhs) => {
                 if ctx.is_const {
                     return Err(format_translation_err!(
                         src_loc,
@@ -3082,8 +4036,8 @@ impl<'c> Translation<'c> {
                 }
                 let cond = self.convert_condition(ctx, true, cond)?;
 
-                let lhs = self.convert_expr(ctx, lhs)?;
-                let rhs = self.convert_expr(ctx, rhs)?;
+                let lhs = self.convert_expr_as(ctx, lhs, ty.ctype)?;
+                let rhs = self.convert_expr_as(ctx, rhs, ty.ctype)?;
 
                 if ctx.is_unused() {
                     let is_unsafe = lhs.is_unsafe() || rhs.is_unsafe();
@@ -3183,6 +4137,33 @@ impl<'c> Translation<'c> {
                     ));
                 }
 
+                // Length of the constant-size array the LHS decayed from, to check the index
+                // against in `--checked` mode
+                let array_len = match lhs_node {
+                    &CExprKind::ImplicitCast(_, arr, CastKind::ArrayToPointerDecay, _, _) => {
+                        match self.ast_context[arr].kind {
+                            CExprKind::Member(_, _, field_decl, _, _)
+                                if self
+                                    .potential_flexible_array_members
+                                    .borrow()
+                                    .contains(&field_decl) =>
+                            {
+                                None
+                            }
+                            ref kind => match kind.get_type() {
+                                Some(arr_type) => {
+                                    match self.ast_context.resolve_type(arr_type).kind {
+                                        CTypeKind::ConstantArray(_, len) => Some(len),
+                                        _ => None,
+                                    }
+                                }
+                                None => None,
+                            },
+                        }
+                    }
+                    _ => None,
+                };
+
                 let rhs = self.convert_expr(ctx.used(), *rhs)?;
                 rhs.and_then(|rhs| {
                     let simple_index_array = if ctx.needs_address() {
@@ -3243,10 +4224,22 @@ impl<'c> Translation<'c> {
                                     ),
                                 }
                             } else {
-                                mk().index_expr(lhs, cast_int(rhs, "usize"))
+                                let index = cast_int(rhs, "usize");
+                                let index = match array_len {
+                                    Some(len) => self.bounds_checked(ctx, index, len, false),
+                                    None => index,
+                                };
+                                mk().index_expr(lhs, index)
                             }
                         }))
                     } else {
+                        // Taking the address of the element one past the end is fine
+                        let rhs = match array_len {
+                            Some(len) => {
+                                self.bounds_checked(ctx, cast_int(rhs, "usize"), len, true)
+                            }
+                            None => rhs,
+                        };
                         let lhs = self.convert_expr(ctx.used(), *lhs)?;
                         lhs.result_map(|lhs| {
                             // stmts.extend(lhs.stmts_mut());
@@ -3273,6 +4266,7 @@ impl<'c> Translation<'c> {
                                 Ok(pointer_offset(lhs, offset))
                             } else {
                                 // Otherwise, use the pointer and make a deref of a pointer offset expression
+                                let lhs = self.null_checked(ctx, lhs);
                                 Ok(mk().unary_expr(ast::UnOp::Deref, pointer_offset(lhs, rhs)))
                             }
                         })
@@ -3281,6 +4275,21 @@ impl<'c> Translation<'c> {
             }
 
             CExprKind::Call(call_expr_ty, func, ref args) => {
+                let callee_ty = self.ast_context[func].kind.get_type()
+                    .ok_or_else(|| format_err!("Invalid callee expression {:?}", func))?;
+                if self.ast_context.is_block_pointer(callee_ty) {
+                    return self.convert_block_call(ctx, func, args);
+                }
+                if self.tcfg.translate_printf {
+                    if let Some(call) = self.convert_printf_call(ctx, func, args)? {
+                        return self.convert_side_effects_expr(
+                            ctx,
+                            call,
+                            "Function call expression is not supposed to be used",
+                        );
+                    }
+                }
+
                 let fn_ty = self.ast_context.get_pointee_qual_type(
                     self.ast_context[func].kind.get_type()
                         .ok_or_else(|| format_err!("Invalid callee expression {:?}", func))?
@@ -3289,6 +4298,28 @@ impl<'c> Translation<'c> {
                     Some(CTypeKind::Function(_, _, is_variadic, _, _)) => *is_variadic,
                     _ => false,
                 };
+                // Functions visible to C take blocks in their ABI representation
+                let block_abi_callee = match self.ast_context[func].kind {
+                    CExprKind::ImplicitCast(_, fexp, CastKind::FunctionToPointerDecay, _, _) => {
+                        match self.ast_context[fexp].kind {
+                            CExprKind::DeclRef(_, decl_id, _) => self.uses_block_abi(decl_id),
+                            _ => false,
+                        }
+                    }
+                    _ => false,
+                };
+                // Functions only called from Rust may take their variadic arguments as a slice
+                let stable_var_args = match self.direct_callee(func) {
+                    Some((_, decl_id)) if self.is_stable_variadic(decl_id) => {
+                        match self.ast_context[decl_id].kind {
+                            CDeclKind::Function { ref parameters, .. } => {
+                                args.get(parameters.len()..)
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
                 let func = match self.ast_context[func].kind {
                     // Direct function call
                     CExprKind::ImplicitCast(_, fexp, CastKind::FunctionToPointerDecay, _, _) => {
@@ -3325,7 +4356,7 @@ impl<'c> Translation<'c> {
                         match fn_ty {
                             Some(CTypeKind::Function(ret_ty, _, _, _, false)) => {
                                 // K&R function pointer without arguments
-                                let ret_ty = self.convert_type(ret_ty.ctype)?;
+                                let ret_ty = self.convert_boundary_type(ret_ty.ctype)?;
                                 let target_ty = make_fn_ty(ret_ty);
                                 callee.map(|fn_ptr| {
                                     let fn_ptr = unwrap_function_pointer(fn_ptr);
@@ -3334,7 +4365,7 @@ impl<'c> Translation<'c> {
                             }
                             None => {
                                 // We have to infer the return type from our expression type
-                                let ret_ty = self.convert_type(call_expr_ty.ctype)?;
+                                let ret_ty = self.convert_boundary_type(call_expr_ty.ctype)?;
                                 let target_ty = make_fn_ty(ret_ty);
                                 callee.map(|fn_ptr| {
                                     transmute_expr(mk().infer_ty(), target_ty, fn_ptr, self.tcfg.emit_no_std)
@@ -3352,13 +4383,68 @@ impl<'c> Translation<'c> {
                     // We want to decay refs only when function is variadic
                     ctx.decay_ref = DecayRef::from(is_variadic);
 
-                    let args = self.convert_exprs(ctx.used(), args)?;
+                    let param_tys = match fn_ty {
+                        Some(CTypeKind::Function(_, params, ..)) => params.as_slice(),
+                        _ => &[],
+                    };
+                    let mut to_abi = vec![];
+                    let mut arg_tys = vec![];
+                    for (i, &arg_id) in args.iter().enumerate() {
+                        let arg_ty = self.ast_context[arg_id].kind.get_type()
+                            .ok_or_else(|| format_err!("bad argument type"))?;
+                        let param_ty = param_tys.get(i).map_or(arg_ty, |param| param.ctype);
+                        arg_tys.push((arg_ty, param_ty));
+                        if block_abi_callee && self.ast_context.is_block_pointer(arg_ty) {
+                            to_abi.push(Some(self.block_abi_helpers(arg_ty)?.to_abi));
+                        } else {
+                            to_abi.push(None);
+                        }
+                    }
+
+                    let args = self.convert_exprs(ctx.used(), args)?.map(|args| {
+                        args.into_iter()
+                            .zip(to_abi)
+                            .map(|(arg, to_abi)| match to_abi {
+                                // The block stays alive until the end of the statement
+                                Some(to_abi) => mk().field_expr(
+                                    mk().call_expr(mk().path_expr(vec![to_abi]), vec![arg]),
+                                    "block",
+                                ),
+                                None => arg,
+                            })
+                            .collect::<Vec<_>>()
+                    });
+                    // Arguments are used as values of the types of the parameters, and Rust
+                    // enums are passed as integers
+                    let args = args.result_map(|args| {
+                        args.into_iter()
+                            .zip(arg_tys)
+                            .map(|(arg, (arg_ty, param_ty))| {
+                                let arg = self.cast_pointer_sized(arg_ty, param_ty, arg)?;
+                                self.rust_enum_to_boundary(arg_ty, arg)
+                            })
+                            .collect::<Result<Vec<_>, _>>()
+                    })?;
+                    let args = match stable_var_args {
+                        Some(var_c_args) => args.result_map(|mut args| {
+                            let var_args = args.split_off(args.len() - var_c_args.len());
+                            self.convert_var_args(var_c_args, var_args).map(|slice| {
+                                args.push(slice);
+                                args
+                            })
+                        })?,
+                        None => args,
+                    };
 
                     let res: Result<_, TranslationError> = Ok(
                         args.map(|args| mk().call_expr(func, args))
                     );
                     res
                 })?;
+                // Rust enums are returned as integers
+                let call = call.result_map(|call| {
+                    self.rust_enum_from_boundary(call_expr_ty.ctype, call, src_loc.as_ref())
+                })?;
 
                 self.convert_side_effects_expr(
                     ctx,
@@ -3367,6 +4453,14 @@ impl<'c> Translation<'c> {
                 )
             }
 
+            CExprKind::Member(..) if self.is_tagged_union_tag(expr_id) => {
+                self.convert_tagged_union_tag(ctx, expr_id)
+            }
+
+            CExprKind::Member(..) if self.is_tagged_union_field(expr_id) => {
+                self.convert_tagged_union_field(ctx, expr_id)
+            }
+
             CExprKind::Member(_, expr, decl, kind, _) => {
                 let is_bitfield = match &self.ast_context[decl].kind {
                     CDeclKind::Field { bitfield_width, .. } => bitfield_width.is_some(),
@@ -3403,6 +4497,7 @@ impl<'c> Translation<'c> {
                             } else {
                                 let val = self.convert_expr(ctx, expr)?;
                                 Ok(val.map(|v| {
+                                    let v = self.null_checked(ctx, v);
                                     mk().field_expr(
                                         mk().unary_expr(ast::UnOp::Deref, v),
                                         field_name,
@@ -3434,6 +4529,10 @@ impl<'c> Translation<'c> {
 
             CExprKind::VAArg(ty, val_id) => self.convert_vaarg(ctx, ty, val_id),
 
+            CExprKind::Block(ty, ref params, body, ref captures) => {
+                self.convert_block_literal(ctx, ty, params, body, captures)
+            }
+
             CExprKind::Choose(_, _cond, lhs, rhs, is_cond_true) => {
                 let chosen_expr = if is_cond_true {
                     self.convert_expr(ctx, lhs)?
@@ -3457,6 +4556,11 @@ impl<'c> Translation<'c> {
                 Ok(chosen_expr)
             }
 
+            // Clang resolves the selection at every use, so only the selected association is
+            // translated. The controlling expression is never evaluated.
+            CExprKind::GenericSelection(_, _, ref associations, selected) => {
+                self.convert_expr(ctx, associations[selected].expr)
+            }
         }
     }
 
@@ -3531,25 +4635,6 @@ impl<'c> Translation<'c> {
         ctx: ExprContext,
         compound_stmt_id: CStmtId,
     ) -> Result<WithStmts<P<Expr>>, TranslationError> {
-        fn as_semi_break_stmt(stmt: &ast::Stmt, lbl: &cfg::Label) -> Option<Option<P<ast::Expr>>> {
-            if let ast::Stmt {
-                node: ast::StmtKind::Semi(ref expr),
-                ..
-            } = *stmt
-            {
-                if let ast::Expr {
-                    node: ast::ExprKind::Break(Some(ref blbl), ref ret_val),
-                    ..
-                } = **expr
-                {
-                    if blbl.ident == mk().label(lbl.pretty_print()).ident {
-                        return Some(ret_val.clone());
-                    }
-                }
-            }
-            None
-        }
-
         match self.ast_context[compound_stmt_id].kind {
             CStmtKind::Compound(ref substmt_ids) if !substmt_ids.is_empty() => {
                 let n = substmt_ids.len();
@@ -3782,7 +4867,7 @@ impl<'c> Translation<'c> {
                 } else if let &CTypeKind::Enum(enum_decl_id) = target_ty_ctype {
                     // Casts targeting `enum` types...
                     let expr = expr.ok_or_else(|| format_err!("Casts to enums require a C ExprId"))?;
-                    Ok(self.enum_cast(ty.ctype, enum_decl_id, expr, val, source_ty, target_ty))
+                    self.enum_cast(ty.ctype, enum_decl_id, expr, val, source_ty, target_ty)
                 } else {
                     // Other numeric casts translate to Rust `as` casts,
                     // unless the cast is to a function pointer then use `transmute`.
@@ -3799,6 +4884,11 @@ impl<'c> Translation<'c> {
                 }
             }
 
+            // Reading a block shares it rather than moving it out of its variable
+            CastKind::LValueToRValue if self.ast_context.is_block_pointer(ty.ctype) => {
+                Ok(val.map(|x| mk().method_call_expr(x, "clone", vec![] as Vec<P<Expr>>)))
+            }
+
             CastKind::LValueToRValue | CastKind::ToVoid | CastKind::ConstCast => Ok(val),
 
             CastKind::FunctionToPointerDecay | CastKind::BuiltinFnToFnPtr => {
@@ -3948,7 +5038,7 @@ impl<'c> Translation<'c> {
         val: WithStmts<P<Expr>>, // translated Rust argument to cast
         _source_ty: P<Ty>,  // source type of cast
         target_ty: P<Ty>,   // target type of cast
-    ) -> WithStmts<P<Expr>> {
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
         // Extract the IDs of the `EnumConstant` decls underlying the enum.
         let variants = match self.ast_context.index(enum_decl).kind {
             CDeclKind::Enum { ref variants, .. } => variants,
@@ -3960,24 +5050,24 @@ impl<'c> Translation<'c> {
             // we are casting to. Here, we can just remove the extraneous cast instead of generating
             // a new one.
             CExprKind::DeclRef(_, decl_id, _) if variants.contains(&decl_id) => {
-                return val.map(|x| match x.node {
+                return Ok(val.map(|x| match x.node {
                     ast::ExprKind::Cast(ref e, _) => e.clone(),
                     _ => panic!(format!(
                         "DeclRef {:?} of enum {:?} is not cast",
                         expr, enum_decl
                     )),
-                })
+                }))
             }
 
             CExprKind::Literal(_, CLiteral::Integer(i, _)) => {
-                return val.map(|_| self.enum_for_i64(enum_type, i as i64));
+                return Ok(val.map(|_| self.enum_for_i64(enum_type, i as i64)));
             }
 
             CExprKind::Unary(_, c_ast::UnOp::Negate, subexpr_id, _) => {
                 if let &CExprKind::Literal(_, CLiteral::Integer(i, _)) =
                     &self.ast_context[subexpr_id].kind
                 {
-                    return val.map(|_| self.enum_for_i64(enum_type, -(i as i64)));
+                    return Ok(val.map(|_| self.enum_for_i64(enum_type, -(i as i64))));
                 }
             }
 
@@ -3986,7 +5076,11 @@ impl<'c> Translation<'c> {
             _ => {}
         }
 
-        val.map(|x| mk().cast_expr(x, target_ty))
+        if self.is_rust_enum(enum_decl) {
+            return self.rust_enum_cast(enum_type, enum_decl, expr, val);
+        }
+
+        Ok(val.map(|x| mk().cast_expr(x, target_ty)))
     }
 
     pub fn implicit_default_expr(
@@ -4006,7 +5100,7 @@ impl<'c> Translation<'c> {
                 CTypeKind::LongDouble => Ok(WithStmts::new_val(mk().path_expr(vec!["f128", "f128", "ZERO"]))),
                 _ => Ok(WithStmts::new_val(mk().lit_expr(mk().float_unsuffixed_lit("0.")))),
             }
-        } else if let &CTypeKind::Pointer(_) = resolved_ty {
+        } else if let &CTypeKind::Pointer(_) | &CTypeKind::BlockPointer(_) = resolved_ty {
             self.null_ptr(resolved_ty_id, is_static)
                 .map(WithStmts::new_val)
         } else if let &CTypeKind::ConstantArray(elt, sz) = resolved_ty {
@@ -4025,8 +5119,13 @@ impl<'c> Translation<'c> {
             // Find base element type of potentially nested arrays
             let inner = self.variable_array_base_type(elt);
             let count = self.compute_size_of_expr(ty_id).unwrap();
+            let stack_allocated = self.tcfg.stack_vla_capacity.is_some();
             Ok(self.implicit_default_expr(inner, is_static)?
-               .map(|val| vec_expr(val, count)))
+               .map(|val| if stack_allocated {
+                   small_vec_expr(val, count)
+               } else {
+                   vec_expr(val, count)
+               }))
         } else if let &CTypeKind::Vector(CQualTypeId { ctype, .. }, len) = resolved_ty {
             self.implicit_vector_default(ctype, len, is_static)
         } else {
@@ -4082,7 +5181,9 @@ impl<'c> Translation<'c> {
                     })
                     .any(|x| x);
 
-                if has_bitfields {
+                if self.tagged_unions.contains_key(&decl_id) {
+                    self.tagged_union_zero_initializer(decl_id, is_static)?
+                } else if has_bitfields {
                     self.bitfield_zero_initializer(name, fields, platform_byte_size, is_static)?
                 } else {
                     let fields: WithStmts<Vec<Field>> = fields
@@ -4173,7 +5274,7 @@ impl<'c> Translation<'c> {
     fn match_bool(&self, target: bool, ty_id: CTypeId, val: P<Expr>) -> P<Expr> {
         let ty = &self.ast_context.resolve_type(ty_id).kind;
 
-        if self.ast_context.is_function_pointer(ty_id) {
+        if self.ast_context.is_function_pointer(ty_id) || self.ast_context.is_block_pointer(ty_id) {
             if target {
                 mk().method_call_expr(val, "is_some", vec![] as Vec<P<Expr>>)
             } else {
@@ -4250,14 +5351,42 @@ impl<'c> Translation<'c> {
         result
     }
 
+    /// The file whose module a top-level declaration goes into. With `--header-modules`,
+    /// declarations from headers that have internal linkage stay in the module of the
+    /// translation unit, since every translation unit has its own copy of them, unless they are
+    /// shared `static inline` functions.
+    fn module_file_path<'a>(
+        &'a self,
+        decl_id: CDeclId,
+        decl_file_path: Option<&'a PathBuf>,
+    ) -> Option<&'a PathBuf> {
+        if self.shared_inline_functions.contains(&decl_id) {
+            return decl_file_path;
+        }
+        let has_internal_linkage = match self.ast_context[decl_id].kind {
+            CDeclKind::Function {
+                is_global: false, ..
+            } => true,
+            CDeclKind::Variable {
+                has_static_duration,
+                has_thread_duration,
+                is_externally_visible: false,
+                ..
+            } => has_static_duration || has_thread_duration,
+            _ => false,
+        };
+        if self.tcfg.header_modules && has_internal_linkage {
+            Some(&self.main_file)
+        } else {
+            decl_file_path
+        }
+    }
+
     /// If we're trying to organize item definitions into submodules, add them to a module
-    /// scoped "namespace" if we have a path available, otherwise add it to the global "namespace"
-    fn insert_item(
-        &self,
-        item: P<Item>,
-        decl_file_path: Option<&PathBuf>,
-    ) {
-        if self.tcfg.reorganize_definitions
+    /// scoped "namespace" if we have a path available, otherwise add it to the global "namespace".
+    /// Items of declarations in C++ namespaces go into the modules of their namespaces.
+    fn insert_item(&self, decl_id: CDeclId, item: P<Item>, decl_file_path: Option<&PathBuf>) {
+        if self.tcfg.splits_headers()
             && decl_file_path.expect("There should be a decl file path.") != &self.main_file
         {
             let mut mod_blocks = self.mod_blocks.borrow_mut();
@@ -4266,6 +5395,13 @@ impl<'c> Translation<'c> {
                 .or_insert(ItemStore::new());
 
             mod_block_items.items.push(item);
+        } else if let Some(namespace) = self.ast_context.namespaces.get(&decl_id) {
+            let mut namespace_blocks = self.namespace_blocks.borrow_mut();
+            let namespace_items = namespace_blocks
+                .entry(namespace.clone())
+                .or_insert(ItemStore::new());
+
+            namespace_items.items.push(item);
         } else {
             self.item_store.borrow_mut().items.push(item)
         }
@@ -4278,7 +5414,7 @@ impl<'c> Translation<'c> {
         item: ForeignItem,
         decl_file_path: Option<&PathBuf>,
     ) {
-        if self.tcfg.reorganize_definitions && decl_file_path.unwrap() != &self.main_file {
+        if self.tcfg.splits_headers() && decl_file_path.unwrap() != &self.main_file {
             let mut mod_blocks = self.mod_blocks.borrow_mut();
             let mod_block_items = mod_blocks
                 .entry(decl_file_path.unwrap().clone())
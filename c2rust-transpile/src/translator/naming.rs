@@ -0,0 +1,151 @@
+//! Naming of translated declarations after the Rust naming conventions.
+//!
+//! When enabled, functions, locals and fields become `snake_case`, types and enum variants
+//! `UpperCamelCase`, and constants and statics `SCREAMING_SNAKE_CASE`. Declarations visible to C
+//! keep their symbol names through `export_name` and `link_name` attributes, and the C names of
+//! all renamed declarations are recorded in a rename map written next to the translation.
+
+use super::*;
+
+use crate::renamer::NameCase;
+
+/// Entry of the rename map for a declaration whose Rust name differs from its C name
+#[derive(Serialize)]
+struct RenamedDecl {
+    kind: &'static str,
+    /// Name of the struct, union or enum the declaration is a member of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent: Option<String>,
+    c_name: String,
+    rust_name: String,
+}
+
+impl<'c> Translation<'c> {
+    /// The naming convention of a value declaration. It only takes effect when the translation
+    /// follows the Rust naming conventions.
+    pub fn value_name_case(&self, kind: &CDeclKind) -> NameCase {
+        match *kind {
+            CDeclKind::EnumConstant { .. } | CDeclKind::MacroObject { .. } => {
+                NameCase::ScreamingSnake
+            }
+            CDeclKind::Variable {
+                has_static_duration,
+                has_thread_duration,
+                ..
+            } if has_static_duration || has_thread_duration => NameCase::ScreamingSnake,
+            _ => NameCase::Snake,
+        }
+    }
+
+    /// The name of the Rust enum variant translated from an enum constant
+    pub fn enum_variant_name(&self, variant_id: CEnumConstantId) -> String {
+        let name = self
+            .renamer
+            .borrow()
+            .get(&variant_id)
+            .expect("Enum constant not named");
+        if self.tcfg.rust_naming_conventions {
+            NameCase::UpperCamel.convert(&name)
+        } else {
+            name
+        }
+    }
+
+    /// Build the JSON rename map of the top-level declarations, fields and enum variants whose
+    /// Rust names differ from their C names
+    pub fn rename_map(&self) -> String {
+        let renamer = self.renamer.borrow();
+        let type_converter = self.type_converter.borrow();
+        let mut renamed = vec![];
+        let mut add = |kind, parent: Option<&String>, c_name: &String, rust_name| {
+            if let Some(rust_name) = rust_name {
+                if !c_name.is_empty() && *c_name != rust_name {
+                    renamed.push(RenamedDecl {
+                        kind,
+                        parent: parent.cloned(),
+                        c_name: c_name.clone(),
+                        rust_name,
+                    });
+                }
+            }
+        };
+
+        for (&decl_id, decl) in self.ast_context.iter_decls() {
+            match decl.kind {
+                CDeclKind::Function { ref name, .. } => {
+                    add("function", None, name, renamer.get(&decl_id))
+                }
+                CDeclKind::Variable { ref ident, .. }
+                    if self.ast_context.c_decls_top.contains(&decl_id) =>
+                {
+                    add("variable", None, ident, renamer.get(&decl_id))
+                }
+                CDeclKind::EnumConstant { ref name, .. } => {
+                    add("constant", None, name, renamer.get(&decl_id))
+                }
+                CDeclKind::MacroObject { ref name, .. } => {
+                    add("macro", None, name, renamer.get(&decl_id))
+                }
+                CDeclKind::Typedef { ref name, .. } => add(
+                    "type",
+                    None,
+                    name,
+                    type_converter.resolve_decl_name(decl_id),
+                ),
+                CDeclKind::Enum {
+                    ref name,
+                    ref variants,
+                    ..
+                } => {
+                    if let Some(name) = name {
+                        add(
+                            "enum",
+                            None,
+                            name,
+                            type_converter.resolve_decl_name(decl_id),
+                        );
+                    }
+                    if self.is_rust_enum(decl_id) {
+                        let parent = name.clone().or(type_converter.resolve_decl_name(decl_id));
+                        for &variant_id in variants {
+                            if let CDeclKind::EnumConstant { ref name, .. } =
+                                self.ast_context[variant_id].kind
+                            {
+                                let variant_name = self.enum_variant_name(variant_id);
+                                add("variant", parent.as_ref(), name, Some(variant_name));
+                            }
+                        }
+                    }
+                }
+                CDeclKind::Struct {
+                    ref name,
+                    ref fields,
+                    ..
+                }
+                | CDeclKind::Union {
+                    ref name,
+                    ref fields,
+                } => {
+                    let kind = match decl.kind {
+                        CDeclKind::Struct { .. } => "struct",
+                        _ => "union",
+                    };
+                    if let Some(name) = name {
+                        add(kind, None, name, type_converter.resolve_decl_name(decl_id));
+                    }
+                    let parent = name.clone().or(type_converter.resolve_decl_name(decl_id));
+                    for &field_id in fields.iter().flatten() {
+                        if let CDeclKind::Field { ref name, .. } = self.ast_context[field_id].kind {
+                            let field_name =
+                                type_converter.resolve_field_name(Some(decl_id), field_id);
+                            add("field", parent.as_ref(), name, field_name);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        serde_json::to_string_pretty(&renamed).expect("Could not serialize the rename map")
+    }
+}
@@ -0,0 +1,739 @@
+//! Translation of OpenMP `parallel for` loops.
+//!
+//! Canonical `for` loops under `#pragma omp parallel for` become rayon parallel iterators over
+//! the range of their loop variable. Variables of the enclosing function that the loop body
+//! reads are copied into the iterations, `private` variables are declared afresh in every
+//! iteration, and `reduction` variables are combined from the iterations with `reduce`. A
+//! `continue` ends the closure of its iteration. Loops outside of this subset are translated
+//! sequentially, with a warning.
+
+use super::tagged_unions::breaks_to;
+use super::*;
+
+/// The reduction identifiers of `reduction` clauses that we translate
+#[derive(Copy, Clone, Debug)]
+enum ReductionOp {
+    Add,
+    Subtract,
+    Multiply,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Max,
+    Min,
+}
+
+impl ReductionOp {
+    fn from_identifier(identifier: &str) -> Option<ReductionOp> {
+        match identifier {
+            "+" => Some(ReductionOp::Add),
+            "-" => Some(ReductionOp::Subtract),
+            "*" => Some(ReductionOp::Multiply),
+            "&" => Some(ReductionOp::BitAnd),
+            "|" => Some(ReductionOp::BitOr),
+            "^" => Some(ReductionOp::BitXor),
+            "max" => Some(ReductionOp::Max),
+            "min" => Some(ReductionOp::Min),
+            _ => None,
+        }
+    }
+
+    fn is_bitwise(self) -> bool {
+        match self {
+            ReductionOp::BitAnd | ReductionOp::BitOr | ReductionOp::BitXor => true,
+            _ => false,
+        }
+    }
+
+    /// Do the iterations start from the value of the variable before the loop? The others start
+    /// from zero, or one for `*`.
+    fn starts_from_original(self) -> bool {
+        match self {
+            ReductionOp::BitAnd | ReductionOp::Max | ReductionOp::Min => true,
+            _ => false,
+        }
+    }
+
+    /// Combine two partial results. Partial results of `-` reductions are negated sums, so they
+    /// are added up like those of `+` reductions.
+    fn combine(self, lhs: P<Expr>, rhs: P<Expr>, wrapping: bool) -> P<Expr> {
+        let method =
+            |name: &str, lhs: P<Expr>, rhs: P<Expr>| mk().method_call_expr(lhs, name, vec![rhs]);
+        match self {
+            ReductionOp::Add | ReductionOp::Subtract if wrapping => {
+                method("wrapping_add", lhs, rhs)
+            }
+            ReductionOp::Add | ReductionOp::Subtract => mk().binary_expr(BinOpKind::Add, lhs, rhs),
+            ReductionOp::Multiply if wrapping => method("wrapping_mul", lhs, rhs),
+            ReductionOp::Multiply => mk().binary_expr(BinOpKind::Mul, lhs, rhs),
+            ReductionOp::BitAnd => mk().binary_expr(BinOpKind::BitAnd, lhs, rhs),
+            ReductionOp::BitOr => mk().binary_expr(BinOpKind::BitOr, lhs, rhs),
+            ReductionOp::BitXor => mk().binary_expr(BinOpKind::BitXor, lhs, rhs),
+            ReductionOp::Max => method("max", lhs, rhs),
+            ReductionOp::Min => method("min", lhs, rhs),
+        }
+    }
+}
+
+/// A variable of a `reduction` clause
+struct Reduction {
+    var: CDeclId,
+    op: ReductionOp,
+    typ: CQualTypeId,
+}
+
+/// A `for` loop in the canonical form that OpenMP requires of the loops it distributes
+/// between threads: `for (var = start; var < end; var++)`, or with `<=`, `++var` or `var += 1`.
+struct CanonicalLoop {
+    var: CDeclId,
+    typ: CQualTypeId,
+    /// Is the loop variable declared by the loop rather than assigned?
+    declared: bool,
+    start: CExprId,
+    end: CExprId,
+    inclusive: bool,
+    body: CStmtId,
+}
+
+/// A `parallel for` loop that we can translate into a parallel iterator
+struct ParallelFor {
+    canonical: CanonicalLoop,
+    reductions: Vec<Reduction>,
+    private: Vec<CDeclId>,
+    /// Local variables declared before the loop that its body reads
+    shared: IndexSet<CDeclId>,
+}
+
+/// A single value, or a tuple of several
+fn tuple_or_single(mut exprs: Vec<P<Expr>>) -> P<Expr> {
+    if exprs.len() == 1 {
+        exprs.pop().unwrap()
+    } else {
+        mk().tuple_expr(exprs)
+    }
+}
+
+/// Component `index` of the value built by `tuple_or_single` from `count` values
+fn component(name: &str, index: usize, count: usize) -> P<Expr> {
+    let value = mk().ident_expr(name);
+    if count == 1 {
+        value
+    } else {
+        mk().field_expr(value, index.to_string())
+    }
+}
+
+impl<'c> Translation<'c> {
+    /// Translate a `parallel for` loop into a rayon parallel iterator. Returns `None` if the
+    /// loop is outside of the subset we translate, in which case the caller translates it
+    /// sequentially.
+    pub fn convert_parallel_for(
+        &self,
+        ctx: ExprContext,
+        stmt_id: CStmtId,
+        for_stmt: CStmtId,
+        clauses: &[OmpClause],
+    ) -> Result<Option<Vec<Stmt>>, TranslationError> {
+        let parallel_for = match self.parallel_for(for_stmt, clauses) {
+            Ok(parallel_for) => parallel_for,
+            Err(reason) => {
+                let loc = self.ast_context[stmt_id]
+                    .loc
+                    .as_ref()
+                    .map_or_else(|| "<unknown location>".to_string(), |loc| loc.to_string());
+                diag!(
+                    Diagnostic::ParallelLoops,
+                    "{}: translating `parallel for` loop sequentially because {}",
+                    loc,
+                    reason
+                );
+                return Ok(None);
+            }
+        };
+        let ParallelFor {
+            canonical,
+            reductions,
+            private,
+            shared,
+        } = parallel_for;
+
+        self.extern_crates.borrow_mut().insert("rayon");
+        {
+            let mut item_store = self.item_store.borrow_mut();
+            let uses = item_store.uses.get_mut(vec!["rayon".into(), "iter".into()]);
+            uses.insert("IntoParallelIterator");
+            uses.insert("ParallelIterator");
+        }
+
+        let mut stmts = vec![];
+
+        // The range of the loop variable
+        let var_ty = self.convert_type(canonical.typ.ctype)?;
+        let (mut start_stmts, start) = self
+            .convert_expr(ctx.used(), canonical.start)?
+            .discard_unsafe();
+        stmts.append(&mut start_stmts);
+        let (mut end_stmts, mut end) = self
+            .convert_expr(ctx.used(), canonical.end)?
+            .discard_unsafe();
+        stmts.append(&mut end_stmts);
+        let end_ty = self.ast_context[canonical.end]
+            .kind
+            .get_type()
+            .ok_or_else(|| format_err!("bad loop bound type"))?;
+        if self.ast_context.resolve_type_id(end_ty)
+            != self.ast_context.resolve_type_id(canonical.typ.ctype)
+        {
+            end = mk().cast_expr(end, var_ty.clone());
+        }
+        // `end + 1` could overflow, so `<=` loops iterate over an inclusive range
+        let range = if canonical.inclusive {
+            mk().range_inclusive_expr(start, end)
+        } else {
+            mk().range_expr(Some(start), Some(end))
+        };
+        let iter = mk().method_call_expr(range, "into_par_iter", vec![] as Vec<P<Expr>>);
+
+        // Copy the shared variables, and the original values of reduction variables that the
+        // iterations start from, into a struct that can be sent to other threads
+        let mut captured: Vec<CDeclId> = shared.iter().cloned().collect();
+        captured.extend(
+            reductions
+                .iter()
+                .filter(|reduction| reduction.op.starts_from_original())
+                .map(|reduction| reduction.var),
+        );
+        let shared_local = if captured.is_empty() {
+            None
+        } else {
+            let struct_name = self
+                .type_converter
+                .borrow_mut()
+                .pick_type_name("ParallelForShared");
+            let local_name = self.renamer.borrow_mut().pick_name("shared");
+
+            let mut fields = vec![];
+            let mut inits = vec![];
+            for &var in &captured {
+                let name = self.variable_name(var)?;
+                let ty = self.convert_type(self.variable_type(var)?.ctype)?;
+                fields.push(mk().struct_field(&name, ty));
+                inits.push(mk().field(&name, mk().ident_expr(&name)));
+            }
+
+            let mut item_store = self.item_store.borrow_mut();
+            item_store.items.push(
+                mk().call_attr("derive", vec!["Copy", "Clone"])
+                    .struct_item(&struct_name, fields),
+            );
+            for trait_name in &["Send", "Sync"] {
+                item_store.items.push(mk().unsafe_().trait_impl_item(
+                    vec![*trait_name],
+                    mk().path_ty(vec![&struct_name]),
+                    vec![],
+                ));
+            }
+
+            let init = mk().struct_expr(vec![&struct_name], inits);
+            let local = mk().local(
+                mk().ident_pat(&local_name),
+                None as Option<P<Ty>>,
+                Some(init),
+            );
+            stmts.push(mk().local_stmt(P(local)));
+            Some(local_name)
+        };
+        let shared_field = |name: &str| {
+            let shared_local = shared_local.as_ref().expect("Missing shared variables");
+            mk().field_expr(mk().ident_expr(shared_local), name)
+        };
+
+        let mut reduction_names = vec![];
+        let mut reduction_identities = vec![];
+        for reduction in &reductions {
+            let name = self.variable_name(reduction.var)?;
+            let identity = if reduction.op.starts_from_original() {
+                mk().ident_expr(&name)
+            } else {
+                let is_float = self
+                    .ast_context
+                    .resolve_type(reduction.typ.ctype)
+                    .kind
+                    .is_floating_type();
+                let lit = match (reduction.op, is_float) {
+                    (ReductionOp::Multiply, true) => mk().float_unsuffixed_lit("1."),
+                    (ReductionOp::Multiply, false) => mk().int_lit(1, LitIntType::Unsuffixed),
+                    (_, true) => mk().float_unsuffixed_lit("0."),
+                    (_, false) => mk().int_lit(0, LitIntType::Unsuffixed),
+                };
+                mk().lit_expr(lit)
+            };
+            reduction_names.push(name);
+            reduction_identities.push(identity);
+        }
+
+        let closure = self.with_scope(|| -> Result<P<Expr>, TranslationError> {
+            let var_name = if canonical.declared {
+                let ident = match self.ast_context[canonical.var].kind {
+                    CDeclKind::Variable { ref ident, .. } => ident,
+                    _ => {
+                        return Err(TranslationError::generic(
+                            "Loop variable must be a variable",
+                        ))
+                    }
+                };
+                self.renamer
+                    .borrow_mut()
+                    .insert(canonical.var, ident)
+                    .ok_or_else(|| format_err!("Failed to insert loop variable '{}'", ident))?
+            } else {
+                self.variable_name(canonical.var)?
+            };
+
+            let mut body_stmts = vec![];
+            for &var in &shared {
+                let name = self.variable_name(var)?;
+                let local = mk().local(
+                    mk().ident_pat(&name),
+                    None as Option<P<Ty>>,
+                    Some(shared_field(&name)),
+                );
+                body_stmts.push(mk().local_stmt(P(local)));
+            }
+            for &var in &private {
+                let name = self.variable_name(var)?;
+                let typ = self.variable_type(var)?;
+                let ty = self.convert_type(typ.ctype)?;
+                let init = self.implicit_default_expr(typ.ctype, false)?.to_expr();
+                let local = mk().local(mk().mutbl().ident_pat(&name), Some(ty), Some(init));
+                body_stmts.push(mk().local_stmt(P(local)));
+            }
+            for (i, reduction) in reductions.iter().enumerate() {
+                let name = &reduction_names[i];
+                let ty = self.convert_type(reduction.typ.ctype)?;
+                let init = if reduction.op.starts_from_original() {
+                    shared_field(name)
+                } else {
+                    reduction_identities[i].clone()
+                };
+                let local = mk().local(mk().mutbl().ident_pat(name), Some(ty), Some(init));
+                body_stmts.push(mk().local_stmt(P(local)));
+            }
+
+            // The iteration breaks out of a block around its body at its end and on `continue`,
+            // so that the closure still returns the partial results of the reductions
+            let name = format!("<parallel_for_{:?}>", canonical.body);
+            let lbl = cfg::Label::FromC(canonical.body);
+            let mut iteration = self.convert_function_body(
+                ctx,
+                &name,
+                &[canonical.body],
+                cfg::ImplicitReturnType::Iteration(lbl),
+            )?;
+            let ends_iteration = iteration
+                .last()
+                .and_then(|stmt| as_semi_break_stmt(stmt, &lbl))
+                .is_some();
+            if ends_iteration {
+                iteration.pop();
+            }
+            if breaks_to(&iteration, &lbl) {
+                self.use_feature("label_break_value");
+                let block = mk().labelled_block_expr(mk().block(iteration), lbl.pretty_print());
+                body_stmts.push(mk().semi_stmt(block));
+            } else {
+                body_stmts.append(&mut iteration);
+            }
+            if !reduction_names.is_empty() {
+                let partials = reduction_names
+                    .iter()
+                    .map(|name| mk().ident_expr(name))
+                    .collect();
+                body_stmts.push(mk().expr_stmt(tuple_or_single(partials)));
+            }
+
+            let body = mk().block_expr(mk().unsafe_().block(body_stmts));
+            let decl = mk().fn_decl(
+                vec![mk().arg(var_ty, mk().ident_pat(var_name))],
+                FunctionRetTy::Default(DUMMY_SP),
+                false,
+            );
+            Ok(mk().closure_expr(CaptureBy::Value, Movability::Movable, decl, body))
+        })?;
+
+        if reductions.is_empty() {
+            let for_each = mk().method_call_expr(iter, "for_each", vec![closure]);
+            stmts.push(mk().semi_stmt(for_each));
+            return Ok(Some(stmts));
+        }
+
+        // Reduce the partial results of the iterations and combine them with the original
+        // values of the reduction variables
+        let count = reductions.len();
+        let is_wrapping = |reduction: &Reduction| {
+            self.ast_context
+                .resolve_type(reduction.typ.ctype)
+                .kind
+                .is_unsigned_integral_type()
+        };
+        let identity = mk().closure_expr(
+            CaptureBy::Ref,
+            Movability::Movable,
+            mk().fn_decl(vec![], FunctionRetTy::Default(DUMMY_SP), false),
+            tuple_or_single(reduction_identities),
+        );
+        let combined = reductions
+            .iter()
+            .enumerate()
+            .map(|(i, reduction)| {
+                reduction.op.combine(
+                    component("lhs", i, count),
+                    component("rhs", i, count),
+                    is_wrapping(reduction),
+                )
+            })
+            .collect();
+        let combine = mk().closure_expr(
+            CaptureBy::Ref,
+            Movability::Movable,
+            mk().fn_decl(
+                vec![
+                    mk().arg(mk().infer_ty(), mk().ident_pat("lhs")),
+                    mk().arg(mk().infer_ty(), mk().ident_pat("rhs")),
+                ],
+                FunctionRetTy::Default(DUMMY_SP),
+                false,
+            ),
+            tuple_or_single(combined),
+        );
+        let map = mk().method_call_expr(iter, "map", vec![closure]);
+        let reduce = mk().method_call_expr(map, "reduce", vec![identity, combine]);
+
+        let reduced_names: Vec<String> = reduction_names
+            .iter()
+            .map(|name| {
+                self.renamer
+                    .borrow_mut()
+                    .pick_name(&format!("{}_reduced", name))
+            })
+            .collect();
+        let pat = if count == 1 {
+            mk().ident_pat(&reduced_names[0])
+        } else {
+            mk().tuple_pat(
+                reduced_names
+                    .iter()
+                    .map(|name| mk().ident_pat(name))
+                    .collect(),
+            )
+        };
+        let local = mk().local(pat, None as Option<P<Ty>>, Some(reduce));
+        stmts.push(mk().local_stmt(P(local)));
+        for ((reduction, name), reduced) in
+            reductions.iter().zip(&reduction_names).zip(&reduced_names)
+        {
+            let value = reduction.op.combine(
+                mk().ident_expr(name),
+                mk().ident_expr(reduced),
+                is_wrapping(reduction),
+            );
+            stmts.push(mk().semi_stmt(mk().assign_expr(mk().ident_expr(name), value)));
+        }
+
+        Ok(Some(stmts))
+    }
+
+    /// Check that a `parallel for` loop is in the subset we translate, and find the variables
+    /// its iterations share. The error is the reason why the loop isn't.
+    fn parallel_for(
+        &self,
+        for_stmt: CStmtId,
+        clauses: &[OmpClause],
+    ) -> Result<ParallelFor, String> {
+        let canonical = self.canonical_loop(for_stmt)?;
+
+        let mut reductions = vec![];
+        let mut private = vec![];
+        for clause in clauses {
+            match *clause {
+                OmpClause::Reduction(ref identifier, ref vars) => {
+                    let op = ReductionOp::from_identifier(identifier)
+                        .ok_or_else(|| format!("`{}` reductions are not supported", identifier))?;
+                    for &var in vars {
+                        let (ident, typ) = self
+                            .local_variable(var)
+                            .ok_or("a reduction variable is not a local variable")?;
+                        let kind = &self.ast_context.resolve_type(typ.ctype).kind;
+                        let is_integral = kind.is_integral_type() && !kind.is_bool();
+                        let is_float = kind.is_floating_type() && *kind != CTypeKind::LongDouble;
+                        if !(is_integral || (is_float && !op.is_bitwise())) {
+                            return Err(format!(
+                                "`{}` can't be reduced with `{}`",
+                                ident, identifier
+                            ));
+                        }
+                        reductions.push(Reduction { var, op, typ });
+                    }
+                }
+                OmpClause::Private(ref vars) => {
+                    for &var in vars {
+                        self.local_variable(var)
+                            .ok_or("a private variable is not a local variable")?;
+                        private.push(var);
+                    }
+                }
+                OmpClause::Other(ref name) => {
+                    return Err(format!("its `{}` clause is not supported", name));
+                }
+            }
+        }
+
+        if self.breaks_out(canonical.body) {
+            return Err("its body breaks out of the loop".to_string());
+        }
+
+        let mut declared = IndexSet::new();
+        let mut referenced = IndexSet::new();
+        let mut written = IndexSet::new();
+        for node in DFExpr::new(&self.ast_context, canonical.body.into()) {
+            match node {
+                SomeId::Stmt(stmt) => match self.ast_context[stmt].kind {
+                    CStmtKind::Return(_) | CStmtKind::Goto(_) | CStmtKind::Label(_) => {
+                        return Err("its body returns or jumps out of the loop".to_string());
+                    }
+                    _ => {}
+                },
+                SomeId::Expr(expr) => match self.ast_context[expr].kind {
+                    CExprKind::Binary(_, op, lhs, _, _, _) if op.is_assignment() => {
+                        written.extend(self.lvalue_variable(lhs));
+                    }
+                    CExprKind::Unary(_, op, operand, _) => match op {
+                        c_ast::UnOp::PreIncrement
+                        | c_ast::UnOp::PostIncrement
+                        | c_ast::UnOp::PreDecrement
+                        | c_ast::UnOp::PostDecrement
+                        | c_ast::UnOp::AddressOf => {
+                            written.extend(self.lvalue_variable(operand));
+                        }
+                        _ => {}
+                    },
+                    CExprKind::DeclRef(_, decl, _) if self.local_variable(decl).is_some() => {
+                        referenced.insert(decl);
+                    }
+                    _ => {}
+                },
+                SomeId::Decl(decl) => {
+                    declared.insert(decl);
+                }
+                SomeId::Type(_) => {}
+            }
+        }
+
+        if written.contains(&canonical.var) {
+            return Err("its body assigns the loop variable".to_string());
+        }
+
+        let mut shared = IndexSet::new();
+        for var in referenced {
+            if var == canonical.var
+                || declared.contains(&var)
+                || private.contains(&var)
+                || reductions.iter().any(|reduction| reduction.var == var)
+            {
+                continue;
+            }
+            let (ident, typ) = self.local_variable(var).unwrap();
+            if written.contains(&var) {
+                return Err(format!(
+                    "its body assigns or takes the address of `{}`, which the iterations share",
+                    ident
+                ));
+            }
+            match self.ast_context.resolve_type(typ.ctype).kind {
+                CTypeKind::ConstantArray(..)
+                | CTypeKind::IncompleteArray(..)
+                | CTypeKind::VariableArray(..) => {
+                    return Err(format!("the iterations share the array `{}`", ident));
+                }
+                _ => {}
+            }
+            shared.insert(var);
+        }
+
+        Ok(ParallelFor {
+            canonical,
+            reductions,
+            private,
+            shared,
+        })
+    }
+
+    /// Match a loop of the form `for (var = start; var < end; var++)`
+    fn canonical_loop(&self, for_stmt: CStmtId) -> Result<CanonicalLoop, String> {
+        let (init, condition, increment, body) = match self.ast_context[for_stmt].kind {
+            CStmtKind::ForLoop {
+                init: Some(init),
+                condition: Some(condition),
+                increment: Some(increment),
+                body,
+            } => (init, condition, increment, body),
+            _ => {
+                return Err("the loop lacks an initialization, condition or increment".to_string());
+            }
+        };
+
+        let (var, start, declared) = match self.ast_context[init].kind {
+            CStmtKind::Decls(ref decls) if decls.len() == 1 => {
+                match self.ast_context[decls[0]].kind {
+                    CDeclKind::Variable {
+                        initializer: Some(start),
+                        ..
+                    } => (decls[0], start, true),
+                    _ => return Err("the loop variable is not initialized".to_string()),
+                }
+            }
+            CStmtKind::Expr(expr) => match self.ast_context[expr].kind {
+                CExprKind::Binary(_, c_ast::BinOp::Assign, lhs, rhs, _, _) => {
+                    match self.variable_ref(lhs) {
+                        Some(var) => (var, rhs, false),
+                        None => return Err("the loop does not assign a variable".to_string()),
+                    }
+                }
+                _ => return Err("the loop initialization is not an assignment".to_string()),
+            },
+            _ => return Err("the loop does not initialize a single variable".to_string()),
+        };
+        let typ = match self.local_variable(var) {
+            Some((_, typ)) => typ,
+            None => return Err("the loop variable is not a local variable".to_string()),
+        };
+        match self.ast_context.resolve_type(typ.ctype).kind {
+            CTypeKind::Bool | CTypeKind::Int128 | CTypeKind::UInt128 => {
+                return Err("the loop variable has an unsupported type".to_string());
+            }
+            ref kind if !kind.is_integral_type() => {
+                return Err("the loop variable is not an integer".to_string());
+            }
+            _ => {}
+        }
+
+        let (end, inclusive) = match self.ast_context[condition].kind {
+            CExprKind::Binary(_, c_ast::BinOp::Less, lhs, rhs, _, _)
+                if self.variable_ref(lhs) == Some(var) =>
+            {
+                (rhs, false)
+            }
+            CExprKind::Binary(_, c_ast::BinOp::LessEqual, lhs, rhs, _, _)
+                if self.variable_ref(lhs) == Some(var) =>
+            {
+                (rhs, true)
+            }
+            _ => return Err("the loop condition is not an upper bound".to_string()),
+        };
+
+        let is_one = |expr: CExprId| match self.ast_context[expr].kind {
+            CExprKind::Literal(_, CLiteral::Integer(1, _)) => true,
+            CExprKind::ImplicitCast(_, expr, _, _, _) => match self.ast_context[expr].kind {
+                CExprKind::Literal(_, CLiteral::Integer(1, _)) => true,
+                _ => false,
+            },
+            _ => false,
+        };
+        let increments = match self.ast_context[increment].kind {
+            CExprKind::Unary(_, c_ast::UnOp::PreIncrement, operand, _)
+            | CExprKind::Unary(_, c_ast::UnOp::PostIncrement, operand, _) => {
+                self.variable_ref(operand) == Some(var)
+            }
+            CExprKind::Binary(_, c_ast::BinOp::AssignAdd, lhs, rhs, _, _) => {
+                self.variable_ref(lhs) == Some(var) && is_one(rhs)
+            }
+            _ => false,
+        };
+        if !increments {
+            return Err("the loop does not increment its variable by one".to_string());
+        }
+
+        Ok(CanonicalLoop {
+            var,
+            typ,
+            declared,
+            start,
+            end,
+            inclusive,
+            body,
+        })
+    }
+
+    /// Does this statement break out of the loop it is in, rather than a loop or `switch` nested
+    /// in it?
+    fn breaks_out(&self, stmt_id: CStmtId) -> bool {
+        match self.ast_context[stmt_id].kind {
+            CStmtKind::Break => true,
+            CStmtKind::While { .. }
+            | CStmtKind::DoWhile { .. }
+            | CStmtKind::ForLoop { .. }
+            | CStmtKind::Switch { .. } => false,
+            _ => c_ast::iterators::immediate_children(&self.ast_context, stmt_id.into())
+                .into_iter()
+                .filter_map(SomeId::stmt)
+                .any(|stmt| self.breaks_out(stmt)),
+        }
+    }
+
+    /// The variable an expression reads, if it is just a variable
+    fn variable_ref(&self, mut expr: CExprId) -> Option<CDeclId> {
+        loop {
+            match self.ast_context[expr].kind {
+                CExprKind::Paren(_, inner) | CExprKind::ImplicitCast(_, inner, _, _, _) => {
+                    expr = inner
+                }
+                CExprKind::DeclRef(_, decl, _) => return Some(decl),
+                _ => return None,
+            }
+        }
+    }
+
+    /// The variable that contains an lvalue, if the lvalue is stored in a variable rather than
+    /// behind a pointer
+    fn lvalue_variable(&self, mut expr: CExprId) -> Option<CDeclId> {
+        loop {
+            match self.ast_context[expr].kind {
+                CExprKind::Paren(_, inner)
+                | CExprKind::ImplicitCast(_, inner, _, _, _)
+                | CExprKind::Member(_, inner, _, MemberKind::Dot, _) => expr = inner,
+                CExprKind::DeclRef(_, decl, _) => return Some(decl),
+                _ => return None,
+            }
+        }
+    }
+
+    /// The name and type of a variable with automatic storage duration
+    fn local_variable(&self, decl_id: CDeclId) -> Option<(&str, CQualTypeId)> {
+        match self.ast_context[decl_id].kind {
+            CDeclKind::Variable {
+                has_static_duration: false,
+                has_thread_duration: false,
+                ref ident,
+                typ,
+                ..
+            } => Some((ident.as_str(), typ)),
+            _ => None,
+        }
+    }
+
+    fn variable_name(&self, decl_id: CDeclId) -> Result<String, TranslationError> {
+        let name = self
+            .renamer
+            .borrow()
+            .get(&decl_id)
+            .ok_or_else(|| format_err!("Loop uses an undeclared variable"))?;
+        Ok(name)
+    }
+
+    fn variable_type(&self, decl_id: CDeclId) -> Result<CQualTypeId, TranslationError> {
+        let (_, typ) = self
+            .local_variable(decl_id)
+            .ok_or_else(|| format_err!("Loop uses a non-local variable"))?;
+        Ok(typ)
+    }
+}
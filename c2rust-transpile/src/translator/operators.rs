@@ -58,6 +58,10 @@ impl<'c> Translation<'c> {
         let lhs_loc = &self.ast_context[lhs].loc;
         let rhs_loc = &self.ast_context[rhs].loc;
         match op {
+            c_ast::BinOp::Assign if self.is_tagged_union_tag(lhs) => {
+                self.convert_tagged_union_tag_assignment(ctx, lhs, rhs)
+            }
+
             c_ast::BinOp::Comma => {
                 // The value of the LHS of a comma expression is always discarded
                 self.convert_expr(ctx.unused(), lhs)?
@@ -67,18 +71,20 @@ impl<'c> Translation<'c> {
             c_ast::BinOp::And | c_ast::BinOp::Or => {
                 let lhs = self.convert_condition(ctx, true, lhs)?;
                 let rhs = self.convert_condition(ctx, true, rhs)?;
-                lhs
-                    .map(|x| bool_to_int(mk().binary_expr(BinOpKind::from(op), x, rhs.to_expr())))
-                    .and_then(|out| {
-                        if ctx.is_unused() {
-                            Ok(WithStmts::new(
-                                vec![mk().semi_stmt(out)],
-                                self.panic_or_err("Binary expression is not supposed to be used"),
-                            ))
-                        } else {
-                            Ok(WithStmts::new_val(out))
-                        }
-                    })
+                let ty = self.convert_type(type_id.ctype)?;
+                lhs.map(|x| {
+                    bool_to_int(mk().binary_expr(BinOpKind::from(op), x, rhs.to_expr()), ty)
+                })
+                .and_then(|out| {
+                    if ctx.is_unused() {
+                        Ok(WithStmts::new(
+                            vec![mk().semi_stmt(out)],
+                            self.panic_or_err("Binary expression is not supposed to be used"),
+                        ))
+                    } else {
+                        Ok(WithStmts::new_val(out))
+                    }
+                })
             }
 
             // No sequence-point cases
@@ -135,9 +141,23 @@ impl<'c> Translation<'c> {
                        .and_then(|_| self.convert_expr(ctx, rhs))?
                        .map(|_| self.panic_or_err("Binary expression is not supposed to be used")))
                 } else {
-                    self.convert_expr(ctx, lhs)?
+                    // The operands are used as values of the type of the operation, except that
+                    // comparisons use them at each other's and shifts their right operand as is
+                    let (lhs_target, rhs_target) = match op {
+                        c_ast::BinOp::Less
+                        | c_ast::BinOp::Greater
+                        | c_ast::BinOp::LessEqual
+                        | c_ast::BinOp::GreaterEqual
+                        | c_ast::BinOp::EqualEqual
+                        | c_ast::BinOp::NotEqual => (lhs_type.ctype, lhs_type.ctype),
+                        c_ast::BinOp::ShiftLeft | c_ast::BinOp::ShiftRight => {
+                            (type_id.ctype, rhs_type.ctype)
+                        }
+                        _ => (type_id.ctype, type_id.ctype),
+                    };
+                    self.convert_expr_as(ctx, lhs, lhs_target)?
                         .and_then(|lhs_val| {
-                            self.convert_expr(ctx, rhs)?
+                            self.convert_expr_as(ctx, rhs, rhs_target)?
                                .result_map(|rhs_val| {
                                    let expr_ids = Some((lhs, rhs));
                                    self.convert_binary_operator(
@@ -177,6 +197,7 @@ impl<'c> Translation<'c> {
         if self.ast_context.resolve_type_id(compute_lhs_ty.ctype)
             == self.ast_context.resolve_type_id(lhs_ty.ctype)
         {
+            let rhs = self.cast_pointer_sized(compute_lhs_ty.ctype, lhs_ty.ctype, rhs)?;
             Ok(WithStmts::new_val(mk().assign_op_expr(bin_op_kind, write, rhs)))
         } else {
             let lhs_type = self.convert_type(compute_lhs_ty.ctype)?;
@@ -223,7 +244,28 @@ impl<'c> Translation<'c> {
             .kind
             .get_qual_type()
             .ok_or_else(|| format_err!("bad assignment rhs type"))?;
-        let rhs_translation = self.convert_expr(ctx.used(), rhs)?;
+        let compute_rhs_type = compute_type.unwrap_or(qtype).ctype;
+        let rhs_translation = self.convert_expr_as(ctx.used(), rhs, compute_rhs_type)?;
+
+        // Bitfields and variables that C code can access hold Rust enums as integers
+        if self.holds_rust_enum_as_integer(lhs) {
+            let rhs_translation =
+                rhs_translation.result_map(|rhs| self.rust_enum_to_boundary(qtype.ctype, rhs))?;
+            let assignment = self.convert_assignment_operator_with_rhs(
+                ctx,
+                op,
+                qtype,
+                lhs,
+                rhs_type_id,
+                rhs_translation,
+                compute_type,
+                result_type,
+            )?;
+            let loc = self.ast_context[lhs].loc.as_ref();
+            return assignment
+                .result_map(|val| self.rust_enum_from_boundary(qtype.ctype, val, loc));
+        }
+
         self.convert_assignment_operator_with_rhs(
             ctx,
             op,
@@ -307,11 +349,27 @@ impl<'c> Translation<'c> {
             _ => false,
         };
 
+        // Checked arithmetic is desugared into explicit reads and writes just like wrapping
+        // arithmetic
+        let is_checked_arith = self.emits_checks(ctx)
+            && match op {
+                c_ast::BinOp::AssignAdd
+                | c_ast::BinOp::AssignSubtract
+                | c_ast::BinOp::AssignMultiply
+                | c_ast::BinOp::AssignDivide
+                | c_ast::BinOp::AssignModulus => compute_type_kind.is_signed_integral_type(),
+                c_ast::BinOp::AssignShiftLeft | c_ast::BinOp::AssignShiftRight => {
+                    compute_type_kind.is_integral_type()
+                }
+                _ => false,
+            };
+
         let lhs_translation = if initial_lhs_type_id.ctype != compute_lhs_type_id.ctype
             || ctx.is_used()
             || pointer_lhs.is_some()
             || is_volatile_compound_assign
             || is_unsigned_arith
+            || is_checked_arith
         {
             self.name_reference_write_read(ctx, lhs)?
         } else {
@@ -335,7 +393,7 @@ impl<'c> Translation<'c> {
                     }
 
                     // Anything volatile needs to be desugared into explicit reads and writes
-                    op if is_volatile || is_unsigned_arith => {
+                    op if is_volatile || is_unsigned_arith || is_checked_arith => {
                         let mut is_unsafe = false;
                         let op = op
                             .underlying_assignment()
@@ -579,6 +637,9 @@ impl<'c> Translation<'c> {
             .index(ctype)
             .kind
             .is_unsigned_integral_type();
+        let resolved_kind = &self.ast_context.resolve_type(ctype).kind;
+        let is_checked_signed = self.emits_checks(ctx) && resolved_kind.is_signed_integral_type();
+        let is_checked_shift = self.emits_checks(ctx) && resolved_kind.is_integral_type();
 
         match op {
             c_ast::BinOp::Add => self.convert_addition(ctx, lhs_type, rhs_type, lhs, rhs),
@@ -592,6 +653,13 @@ impl<'c> Translation<'c> {
                 }
                 Ok(mk().method_call_expr(lhs, mk().path_segment("wrapping_mul"), vec![rhs]))
             }
+            c_ast::BinOp::Multiply if is_checked_signed => Ok(self.checked_int_op(
+                ctx,
+                "checked_mul",
+                lhs,
+                rhs,
+                "signed multiplication overflow",
+            )),
             c_ast::BinOp::Multiply => Ok(mk().binary_expr(BinOpKind::Mul, lhs, rhs)),
 
             c_ast::BinOp::Divide if is_unsigned_integral_type => {
@@ -602,6 +670,13 @@ impl<'c> Translation<'c> {
                 }
                 Ok(mk().method_call_expr(lhs, mk().path_segment("wrapping_div"), vec![rhs]))
             }
+            c_ast::BinOp::Divide if is_checked_signed => Ok(self.checked_int_op(
+                ctx,
+                "checked_div",
+                lhs,
+                rhs,
+                "signed division overflow or division by zero",
+            )),
             c_ast::BinOp::Divide => Ok(mk().binary_expr(BinOpKind::Div, lhs, rhs)),
 
             c_ast::BinOp::Modulus if is_unsigned_integral_type => {
@@ -612,10 +687,34 @@ impl<'c> Translation<'c> {
                 }
                 Ok(mk().method_call_expr(lhs, mk().path_segment("wrapping_rem"), vec![rhs]))
             }
+            c_ast::BinOp::Modulus if is_checked_signed => Ok(self.checked_int_op(
+                ctx,
+                "checked_rem",
+                lhs,
+                rhs,
+                "signed remainder overflow or division by zero",
+            )),
             c_ast::BinOp::Modulus => Ok(mk().binary_expr(BinOpKind::Rem, lhs, rhs)),
 
             c_ast::BinOp::BitXor => Ok(mk().binary_expr(BinOpKind::BitXor, lhs, rhs)),
 
+            c_ast::BinOp::ShiftRight if is_checked_shift => Ok(self.checked_int_op(
+                ctx,
+                "checked_shr",
+                lhs,
+                cast_int(rhs, "u32"),
+                "shift amount out of range",
+            )),
+            c_ast::BinOp::ShiftLeft if is_checked_signed => {
+                Ok(self.checked_signed_shl(ctx, lhs, cast_int(rhs, "u32")))
+            }
+            c_ast::BinOp::ShiftLeft if is_checked_shift => Ok(self.checked_int_op(
+                ctx,
+                "checked_shl",
+                lhs,
+                cast_int(rhs, "u32"),
+                "shift amount out of range",
+            )),
             c_ast::BinOp::ShiftRight => Ok(mk().binary_expr(BinOpKind::Shr, lhs, rhs)),
             c_ast::BinOp::ShiftLeft => Ok(mk().binary_expr(BinOpKind::Shl, lhs, rhs)),
 
@@ -623,9 +722,11 @@ impl<'c> Translation<'c> {
                 // Using is_none method for null comparison means we don't have to
                 // rely on the PartialEq trait as much and is also more idiomatic
                 let expr = if let Some((lhs_expr_id, rhs_expr_id)) = lhs_rhs_ids {
-                    let fn_eq_null = self.ast_context.is_function_pointer(lhs_type.ctype)
+                    let fn_eq_null = (self.ast_context.is_function_pointer(lhs_type.ctype)
+                        || self.ast_context.is_block_pointer(lhs_type.ctype))
                         && self.ast_context.is_null_expr(rhs_expr_id);
-                    let null_eq_fn = self.ast_context.is_function_pointer(rhs_type.ctype)
+                    let null_eq_fn = (self.ast_context.is_function_pointer(rhs_type.ctype)
+                        || self.ast_context.is_block_pointer(rhs_type.ctype))
                         && self.ast_context.is_null_expr(lhs_expr_id);
 
                     if fn_eq_null {
@@ -639,15 +740,17 @@ impl<'c> Translation<'c> {
                     mk().binary_expr(BinOpKind::Eq, lhs, rhs)
                 };
 
-                Ok(bool_to_int(expr))
+                Ok(bool_to_int(expr, ty))
             }
             c_ast::BinOp::NotEqual => {
                 // Using is_some method for null comparison means we don't have to
                 // rely on the PartialEq trait as much and is also more idiomatic
                 let expr = if let Some((lhs_expr_id, rhs_expr_id)) = lhs_rhs_ids {
-                    let fn_eq_null = self.ast_context.is_function_pointer(lhs_type.ctype)
+                    let fn_eq_null = (self.ast_context.is_function_pointer(lhs_type.ctype)
+                        || self.ast_context.is_block_pointer(lhs_type.ctype))
                         && self.ast_context.is_null_expr(rhs_expr_id);
-                    let null_eq_fn = self.ast_context.is_function_pointer(rhs_type.ctype)
+                    let null_eq_fn = (self.ast_context.is_function_pointer(rhs_type.ctype)
+                        || self.ast_context.is_block_pointer(rhs_type.ctype))
                         && self.ast_context.is_null_expr(lhs_expr_id);
 
                     if fn_eq_null {
@@ -661,12 +764,16 @@ impl<'c> Translation<'c> {
                     mk().binary_expr(BinOpKind::Ne, lhs, rhs)
                 };
 
-                Ok(bool_to_int(expr))
+                Ok(bool_to_int(expr, ty))
+            }
+            c_ast::BinOp::Less => Ok(bool_to_int(mk().binary_expr(BinOpKind::Lt, lhs, rhs), ty)),
+            c_ast::BinOp::Greater => Ok(bool_to_int(mk().binary_expr(BinOpKind::Gt, lhs, rhs), ty)),
+            c_ast::BinOp::GreaterEqual => {
+                Ok(bool_to_int(mk().binary_expr(BinOpKind::Ge, lhs, rhs), ty))
+            }
+            c_ast::BinOp::LessEqual => {
+                Ok(bool_to_int(mk().binary_expr(BinOpKind::Le, lhs, rhs), ty))
             }
-            c_ast::BinOp::Less => Ok(bool_to_int(mk().binary_expr(BinOpKind::Lt, lhs, rhs))),
-            c_ast::BinOp::Greater => Ok(bool_to_int(mk().binary_expr(BinOpKind::Gt, lhs, rhs))),
-            c_ast::BinOp::GreaterEqual => Ok(bool_to_int(mk().binary_expr(BinOpKind::Ge, lhs, rhs))),
-            c_ast::BinOp::LessEqual => Ok(bool_to_int(mk().binary_expr(BinOpKind::Le, lhs, rhs))),
 
             c_ast::BinOp::BitAnd => Ok(mk().binary_expr(BinOpKind::BitAnd, lhs, rhs)),
             c_ast::BinOp::BitOr => Ok(mk().binary_expr(BinOpKind::BitOr, lhs, rhs)),
@@ -717,6 +824,8 @@ impl<'c> Translation<'c> {
                 ));
             }
             Ok(mk().method_call_expr(lhs, mk().path_segment("wrapping_add"), vec![rhs]))
+        } else if lhs_type.is_signed_integral_type() && self.emits_checks(ctx) {
+            Ok(self.checked_int_op(ctx, "checked_add", lhs, rhs, "signed addition overflow"))
         } else {
             Ok(mk().binary_expr(BinOpKind::Add, lhs, rhs))
         }
@@ -771,6 +880,8 @@ impl<'c> Translation<'c> {
                 ));
             }
             Ok(mk().method_call_expr(lhs, mk().path_segment("wrapping_sub"), vec![rhs]))
+        } else if lhs_type.is_signed_integral_type() && self.emits_checks(ctx) {
+            Ok(self.checked_int_op(ctx, "checked_sub", lhs, rhs, "signed subtraction overflow"))
         } else {
             Ok(mk().binary_expr(BinOpKind::Sub, lhs, rhs))
         }
@@ -888,6 +999,19 @@ impl<'c> Translation<'c> {
                             }
                             let m = if up { "wrapping_add" } else { "wrapping_sub" };
                             mk().method_call_expr(read.clone(), m, vec![one])
+                        } else if self.emits_checks(ctx)
+                            && self
+                                .ast_context
+                                .resolve_type(ty.ctype)
+                                .kind
+                                .is_signed_integral_type()
+                        {
+                            let (m, problem) = if up {
+                                ("checked_add", "signed increment overflow")
+                            } else {
+                                ("checked_sub", "signed decrement overflow")
+                            };
+                            self.checked_int_op(ctx, m, read.clone(), one, problem)
                         } else {
                             let k = if up { BinOpKind::Add } else { BinOpKind::Sub };
                             mk().binary_expr(k, read.clone(), one)
@@ -1006,6 +1130,7 @@ impl<'c> Translation<'c> {
                                 } else if let Some(_vla) = self.compute_size_of_expr(ctype) {
                                     Ok(val)
                                 } else {
+                                    let val = self.null_checked(ctx, val);
                                     let mut val = mk().unary_expr(ast::UnOp::Deref, val);
 
                                     // If the type on the other side of the pointer we are dereferencing is volatile and
@@ -1031,6 +1156,10 @@ impl<'c> Translation<'c> {
                         ));
                     }
                     Ok(val.map(wrapping_neg_expr))
+                } else if resolved_ctype.kind.is_signed_integral_type() && self.emits_checks(ctx) {
+                    Ok(val.map(|val| {
+                        self.checked_int_unop(ctx, "checked_neg", val, "signed negation overflow")
+                    }))
                 } else {
                     Ok(val.map(neg_expr))
                 }
@@ -1041,7 +1170,7 @@ impl<'c> Translation<'c> {
 
             c_ast::UnOp::Not => {
                 let val = self.convert_condition(ctx, false, arg)?;
-                Ok(val.map(|x| mk().cast_expr(x, mk().path_ty(vec!["libc", "c_int"]))))
+                Ok(val.map(|x| mk().cast_expr(x, ty)))
             }
             c_ast::UnOp::Extension => {
                 let arg = self.convert_expr(ctx, arg)?;
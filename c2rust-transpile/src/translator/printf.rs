@@ -0,0 +1,655 @@
+//! Translation of calls to the `printf` family of functions with literal format strings.
+//!
+//! Calls to `printf`, `fprintf`, `sprintf`, `snprintf`, `puts` and `fputs` become `write!` to one
+//! of the writers of the `c2rust-printf` crate, which write through C stdio like the original
+//! calls. Integer conversions that Rust formats like C are translated into Rust format specs, with
+//! the argument cast to the type of the conversion's length modifier. Every other conversion
+//! formats a `c2rust_printf::Conv` carrying its C conversion spec. Calls with conversions or
+//! arguments we can't check, such as `%n`, wide strings or positional arguments, are translated as
+//! ordinary calls.
+
+use super::*;
+
+/// Where the output of a call goes
+enum Output {
+    Stdout,
+    Stderr,
+    /// A `FILE *` stream other than `stdout` and `stderr`
+    File(CExprId),
+    /// The buffer of `sprintf` and `snprintf`, and the size of the latter
+    Buffer(CExprId, Option<CExprId>),
+}
+
+/// A part of a format string
+enum Piece {
+    /// Text to print as it is
+    Text(String),
+    Conv(ConvSpec),
+}
+
+/// A conversion specification of a format string
+struct ConvSpec {
+    /// The whole specification, starting with `%`
+    spec: String,
+    flags: String,
+    width: Option<Count>,
+    precision: Option<Count>,
+    length: String,
+    conv: char,
+}
+
+/// The width or precision of a conversion
+enum Count {
+    Literal(String),
+    /// A `*`, taken from an argument
+    Arg,
+}
+
+/// The ways of formatting the argument of a conversion with `c2rust_printf`
+#[derive(Copy, Clone)]
+enum ConvKind {
+    Signed,
+    Unsigned,
+    Double,
+    Char,
+    String,
+    Pointer,
+}
+
+impl ConvSpec {
+    fn kind(&self) -> Option<ConvKind> {
+        let length = self.length.as_str();
+        match (self.conv, length) {
+            ('d', _) | ('i', _) if length_type(length, true).is_some() => Some(ConvKind::Signed),
+            ('u', _) | ('o', _) | ('x', _) | ('X', _) if length_type(length, false).is_some() => {
+                Some(ConvKind::Unsigned)
+            }
+            (c, "") | (c, "l") if "fFeEgGaA".contains(c) => Some(ConvKind::Double),
+            ('c', "") => Some(ConvKind::Char),
+            ('s', "") => Some(ConvKind::String),
+            ('p', "") => Some(ConvKind::Pointer),
+            _ => None,
+        }
+    }
+
+    /// The Rust format spec of this conversion, if Rust formats its argument like C does
+    fn rust_spec(&self) -> Option<String> {
+        let signed = match self.kind()? {
+            ConvKind::Signed => true,
+            ConvKind::Unsigned => false,
+            _ => return None,
+        };
+        let width = match self.width {
+            Some(Count::Literal(ref width)) => width.as_str(),
+            Some(Count::Arg) => return None,
+            None => "",
+        };
+        if self.precision.is_some() || self.flags.chars().any(|c| !"-0+".contains(c)) {
+            return None;
+        }
+        // `+` has no effect on unsigned conversions in C
+        if self.flags.contains('+') && !signed {
+            return None;
+        }
+
+        let mut spec = String::new();
+        if self.flags.contains('-') {
+            spec.push('<');
+        }
+        if self.flags.contains('+') {
+            spec.push('+');
+        }
+        // `-` overrides `0` in C, whereas Rust ignores the alignment of zero padded values
+        if self.flags.contains('0') && !self.flags.contains('-') {
+            spec.push('0');
+        }
+        spec.push_str(width);
+        match self.conv {
+            'o' => spec.push('o'),
+            'x' => spec.push('x'),
+            'X' => spec.push('X'),
+            _ => {}
+        }
+        if spec.is_empty() {
+            Some("{}".to_string())
+        } else {
+            Some(format!("{{:{}}}", spec))
+        }
+    }
+}
+
+/// Parse a format string, if all of its conversions are ones we can translate
+fn parse_format(format: &str) -> Option<Vec<Piece>> {
+    let mut pieces = vec![];
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            text.push('%');
+            continue;
+        }
+
+        let mut spec = ConvSpec {
+            spec: "%".to_string(),
+            flags: String::new(),
+            width: None,
+            precision: None,
+            length: String::new(),
+            conv: '\0',
+        };
+        while let Some(&c) = chars.peek().filter(|c| "-+ #0".contains(**c)) {
+            spec.flags.push(c);
+            chars.next();
+        }
+        spec.width = parse_count(&mut chars);
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            spec.precision = Some(parse_count(&mut chars).unwrap_or(Count::Literal("0".into())));
+        }
+        while let Some(&c) = chars.peek().filter(|c| "hlLqjzt".contains(**c)) {
+            spec.length.push(c);
+            chars.next();
+        }
+        spec.conv = chars.next()?;
+
+        // Rebuild the spec, which normalizes an empty precision to `.0`
+        spec.spec.push_str(&spec.flags);
+        for (prefix, count) in &[("", &spec.width), (".", &spec.precision)] {
+            match count {
+                Some(Count::Literal(n)) => spec.spec.push_str(&format!("{}{}", prefix, n)),
+                Some(Count::Arg) => spec.spec.push_str(&format!("{}*", prefix)),
+                None => {}
+            }
+        }
+        spec.spec.push_str(&spec.length);
+        spec.spec.push(spec.conv);
+
+        // Positional arguments end up here as well, since `$` isn't a conversion
+        spec.kind()?;
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text.split_off(0)));
+        }
+        pieces.push(Piece::Conv(spec));
+    }
+
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Some(pieces)
+}
+
+fn parse_count<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) -> Option<Count> {
+    if chars.peek() == Some(&'*') {
+        chars.next();
+        return Some(Count::Arg);
+    }
+    let mut n = String::new();
+    while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+        n.push(c);
+        chars.next();
+    }
+    if n.is_empty() {
+        None
+    } else {
+        Some(Count::Literal(n))
+    }
+}
+
+/// Escape text for a Rust format string
+fn escape_text(text: &str) -> String {
+    text.replace('{', "{{").replace('}', "}}")
+}
+
+/// The C type and `libc` type of integer arguments of a length modifier. The typedefs of `j`,
+/// `z` and `t` have no C type kind of their own.
+fn length_type(length: &str, signed: bool) -> Option<(Option<CTypeKind>, &'static str)> {
+    Some(match (length, signed) {
+        ("", true) => (Some(CTypeKind::Int), "c_int"),
+        ("", false) => (Some(CTypeKind::UInt), "c_uint"),
+        ("hh", true) => (Some(CTypeKind::SChar), "c_schar"),
+        ("hh", false) => (Some(CTypeKind::UChar), "c_uchar"),
+        ("h", true) => (Some(CTypeKind::Short), "c_short"),
+        ("h", false) => (Some(CTypeKind::UShort), "c_ushort"),
+        ("l", true) => (Some(CTypeKind::Long), "c_long"),
+        ("l", false) => (Some(CTypeKind::ULong), "c_ulong"),
+        ("ll", true) | ("q", true) => (Some(CTypeKind::LongLong), "c_longlong"),
+        ("ll", false) | ("q", false) => (Some(CTypeKind::ULongLong), "c_ulonglong"),
+        ("j", true) => (None, "intmax_t"),
+        ("j", false) => (None, "uintmax_t"),
+        ("z", true) => (None, "ssize_t"),
+        ("z", false) | ("t", false) => (None, "size_t"),
+        ("t", true) => (None, "ptrdiff_t"),
+        _ => return None,
+    })
+}
+
+impl<'c> Translation<'c> {
+    /// Translate a call to a function of the `printf` family with a literal format string, if
+    /// it is one we can translate
+    pub fn convert_printf_call(
+        &self,
+        ctx: ExprContext,
+        func: CExprId,
+        args: &[CExprId],
+    ) -> Result<Option<WithStmts<P<Expr>>>, TranslationError> {
+        let name = match self.direct_callee(func) {
+            Some((_, decl_id)) => match self.ast_context[decl_id].kind {
+                CDeclKind::Function {
+                    ref name,
+                    is_global: true,
+                    body: None,
+                    ..
+                } => name.as_str(),
+                _ => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+
+        // The output, the position of the format string, and whether it is only printed
+        let (output, format_arg, is_format) = match (name, args.len()) {
+            ("printf", n) if n >= 1 => (Output::Stdout, 0, true),
+            ("fprintf", n) if n >= 2 => (self.convert_printf_stream(args[0]), 1, true),
+            ("sprintf", n) if n >= 2 => (Output::Buffer(args[0], None), 1, true),
+            ("snprintf", n) if n >= 3 => (Output::Buffer(args[0], Some(args[1])), 2, true),
+            ("puts", 1) => (Output::Stdout, 0, false),
+            ("fputs", 2) => (self.convert_printf_stream(args[1]), 0, false),
+            _ => return Ok(None),
+        };
+        let format = match self.printf_literal(args[format_arg]) {
+            Some(format) => format,
+            None => return Ok(None),
+        };
+        let pieces = if is_format {
+            match parse_format(&format) {
+                Some(pieces) => pieces,
+                None => return Ok(None),
+            }
+        } else if name == "puts" {
+            vec![Piece::Text(format + "\n")]
+        } else {
+            vec![Piece::Text(format)]
+        };
+
+        // Match the conversions with the arguments following the format string
+        let mut conv_args = if is_format {
+            args[format_arg + 1..].iter()
+        } else {
+            [].iter()
+        };
+        let mut convs = vec![];
+        for piece in &pieces {
+            if let Piece::Conv(ref spec) = *piece {
+                let mut next_arg = || conv_args.next().cloned();
+                let width = match spec.width {
+                    Some(Count::Arg) => Some(next_arg()),
+                    _ => None,
+                };
+                let precision = match spec.precision {
+                    Some(Count::Arg) => Some(next_arg()),
+                    _ => None,
+                };
+                let arg = next_arg();
+                match (width, precision, arg) {
+                    (Some(None), _, _) | (_, Some(None), _) | (_, _, None) => return Ok(None),
+                    (width, precision, Some(arg)) => {
+                        convs.push((spec, width.and_then(|w| w), precision.and_then(|p| p), arg))
+                    }
+                }
+            }
+        }
+        if conv_args.next().is_some() {
+            return Ok(None);
+        }
+        for &(spec, width, precision, arg) in &convs {
+            let counts_are_ints = width.iter().chain(precision.iter()).all(|&count| {
+                self.printf_arg_kind(count)
+                    .map_or(false, |k| k.is_integral_type())
+            });
+            if !counts_are_ints || !self.printf_arg_matches(spec, arg) {
+                return Ok(None);
+            }
+        }
+
+        self.extern_crates.borrow_mut().insert("c2rust_printf");
+
+        // Convert the arguments in the order of the C call
+        let output_args = match output {
+            Output::File(stream) => vec![stream],
+            Output::Buffer(buf, Some(size)) => vec![buf, size],
+            Output::Buffer(buf, None) => vec![buf],
+            Output::Stdout | Output::Stderr => vec![],
+        };
+        let mut c_args = output_args.clone();
+        for &(_, width, precision, arg) in &convs {
+            c_args.extend(width.iter().chain(precision.iter()));
+            c_args.push(arg);
+        }
+        let converted = self.convert_exprs(ctx.used().decay_ref(), &c_args)?;
+
+        converted
+            .result_map(|mut exprs| -> Result<P<Expr>, TranslationError> {
+                let conv_exprs = exprs.split_off(output_args.len());
+                let mut conv_exprs = conv_exprs.into_iter();
+
+                let mut format_str = String::new();
+                let mut format_args = vec![];
+                let mut convs = convs.iter();
+                for piece in &pieces {
+                    let spec = match *piece {
+                        Piece::Text(ref text) => {
+                            format_str.push_str(&escape_text(text));
+                            continue;
+                        }
+                        Piece::Conv(ref spec) => spec,
+                    };
+                    let &(_, width, precision, arg) = convs.next().unwrap();
+                    let width = width.map(|w| (w, conv_exprs.next().unwrap()));
+                    let precision = precision.map(|p| (p, conv_exprs.next().unwrap()));
+                    let arg = (arg, conv_exprs.next().unwrap());
+
+                    match spec.rust_spec() {
+                        Some(rust_spec) => {
+                            format_str.push_str(&rust_spec);
+                            format_args.push(self.convert_printf_int(spec, arg, None)?);
+                        }
+                        None => {
+                            format_str.push_str("{}");
+                            format_args
+                                .push(self.convert_printf_conv(spec, width, precision, arg)?);
+                        }
+                    }
+                }
+
+                let mut exprs = exprs.into_iter();
+                let dest = match output {
+                    Output::Stdout => mk().path_expr(vec!["", "c2rust_printf", "Stdout"]),
+                    Output::Stderr => mk().path_expr(vec!["", "c2rust_printf", "Stderr"]),
+                    Output::File(_) => {
+                        let void_ptr = mk().mutbl().ptr_ty(mk().path_ty(vec!["libc", "c_void"]));
+                        let stream = mk().cast_expr(exprs.next().unwrap(), void_ptr);
+                        mk().call_expr(
+                            mk().path_expr(vec!["", "c2rust_printf", "File"]),
+                            vec![stream],
+                        )
+                    }
+                    Output::Buffer(_, None) => mk().call_expr(
+                        mk().path_expr(vec!["", "c2rust_printf", "Buffer", "new"]),
+                        vec![exprs.next().unwrap()],
+                    ),
+                    Output::Buffer(_, Some(_)) => {
+                        let buf = exprs.next().unwrap();
+                        let size =
+                            mk().cast_expr(exprs.next().unwrap(), mk().path_ty(vec!["usize"]));
+                        mk().call_expr(
+                            mk().path_expr(vec!["", "c2rust_printf", "Buffer", "with_size"]),
+                            vec![buf, size],
+                        )
+                    }
+                };
+
+                fn push_expr(tokens: &mut Vec<Token>, expr: P<Expr>) {
+                    tokens.push(Token::Interpolated(Lrc::new(Nonterminal::NtExpr(expr))));
+                }
+
+                let mut tokens = vec![];
+                push_expr(&mut tokens, dest);
+                tokens.push(Token::Comma);
+                push_expr(&mut tokens, mk().lit_expr(mk().str_lit(format_str)));
+                for arg in format_args {
+                    tokens.push(Token::Comma);
+                    push_expr(&mut tokens, arg);
+                }
+                let tokens = tokens.into_iter().collect::<TokenStream>();
+                Ok(mk().mac_expr(mk().mac(vec!["write"], tokens, MacDelimiter::Parenthesis)))
+            })
+            .map(Some)
+    }
+
+    /// The output of `fprintf` and `fputs` to a stream
+    fn convert_printf_stream(&self, stream: CExprId) -> Output {
+        let mut expr = stream;
+        loop {
+            match self.ast_context[expr].kind {
+                CExprKind::ImplicitCast(_, e, CastKind::LValueToRValue, _, _)
+                | CExprKind::Paren(_, e) => expr = e,
+                CExprKind::DeclRef(_, decl_id, _) => {
+                    // macOS defines `stdout` and `stderr` as macros for these
+                    return match self.ast_context[decl_id].kind {
+                        CDeclKind::Variable { ref ident, .. } => match ident.as_str() {
+                            "stdout" | "__stdoutp" => Output::Stdout,
+                            "stderr" | "__stderrp" => Output::Stderr,
+                            _ => Output::File(stream),
+                        },
+                        _ => Output::File(stream),
+                    };
+                }
+                _ => return Output::File(stream),
+            }
+        }
+    }
+
+    /// The contents of a narrow string literal up to its first NUL, if it is valid UTF-8
+    fn printf_literal(&self, expr: CExprId) -> Option<String> {
+        let mut expr = expr;
+        loop {
+            match self.ast_context[expr].kind {
+                CExprKind::ImplicitCast(_, e, _, _, _) | CExprKind::Paren(_, e) => expr = e,
+                CExprKind::Literal(_, CLiteral::String(ref bytes, 1)) => {
+                    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                    return String::from_utf8(bytes[..len].to_vec()).ok();
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn printf_arg_kind(&self, arg: CExprId) -> Option<&CTypeKind> {
+        let ty = self.ast_context[arg].kind.get_type()?;
+        Some(&self.ast_context.resolve_type(ty).kind)
+    }
+
+    /// Can an argument be formatted by a conversion?
+    fn printf_arg_matches(&self, spec: &ConvSpec, arg: CExprId) -> bool {
+        let kind = match self.printf_arg_kind(arg) {
+            Some(kind) => kind,
+            None => return false,
+        };
+        let ty = self.ast_context[arg].kind.get_type().unwrap();
+        match spec.kind() {
+            Some(ConvKind::Signed) | Some(ConvKind::Unsigned) | Some(ConvKind::Char) => match *kind
+            {
+                CTypeKind::Int128 | CTypeKind::UInt128 => false,
+                ref kind => kind.is_integral_type(),
+            },
+            // Arguments have gone through the default argument promotions, so there are no floats
+            Some(ConvKind::Double) => *kind == CTypeKind::Double,
+            Some(ConvKind::String) | Some(ConvKind::Pointer) => {
+                kind.is_pointer() && !self.ast_context.is_function_pointer(ty)
+            }
+            None => false,
+        }
+    }
+
+    /// Cast an integer argument to the type of a conversion, and then to `as_ty` if given
+    fn convert_printf_int(
+        &self,
+        spec: &ConvSpec,
+        (arg_id, arg): (CExprId, P<Expr>),
+        as_ty: Option<&str>,
+    ) -> Result<P<Expr>, TranslationError> {
+        let signed = match spec.kind() {
+            Some(ConvKind::Signed) => true,
+            _ => false,
+        };
+        let (length_kind, length_ty) = length_type(&spec.length, signed)
+            .ok_or_else(|| format_err!("Unsupported length modifier in {}", spec.spec))?;
+        let arg = if length_kind.as_ref() == self.printf_arg_kind(arg_id) {
+            arg
+        } else {
+            mk().cast_expr(arg, mk().path_ty(vec!["libc", length_ty]))
+        };
+        Ok(match as_ty {
+            Some(ty) => mk().cast_expr(arg, mk().path_ty(vec![ty])),
+            None => arg,
+        })
+    }
+
+    /// Cast the width or precision argument of a conversion to `c_int`
+    fn convert_printf_count(&self, (arg_id, arg): (CExprId, P<Expr>)) -> P<Expr> {
+        if self.printf_arg_kind(arg_id) == Some(&CTypeKind::Int) {
+            arg
+        } else {
+            mk().cast_expr(arg, mk().path_ty(vec!["libc", "c_int"]))
+        }
+    }
+
+    /// Build the `c2rust_printf::Conv` formatting a conversion
+    fn convert_printf_conv(
+        &self,
+        spec: &ConvSpec,
+        width: Option<(CExprId, P<Expr>)>,
+        precision: Option<(CExprId, P<Expr>)>,
+        arg: (CExprId, P<Expr>),
+    ) -> Result<P<Expr>, TranslationError> {
+        let kind = spec
+            .kind()
+            .ok_or_else(|| format_err!("Unsupported conversion {}", spec.spec))?;
+        let (ctor, arg) = match kind {
+            ConvKind::Signed => ("int", self.convert_printf_int(spec, arg, Some("i64"))?),
+            ConvKind::Unsigned => ("uint", self.convert_printf_int(spec, arg, Some("u64"))?),
+            ConvKind::Double => ("double", arg.1),
+            ConvKind::Char => ("byte", self.convert_printf_count(arg)),
+            ConvKind::String => {
+                let ty = mk().ptr_ty(mk().path_ty(vec!["libc", "c_char"]));
+                ("string", mk().cast_expr(arg.1, ty))
+            }
+            ConvKind::Pointer => {
+                let ty = mk().ptr_ty(mk().path_ty(vec!["libc", "c_void"]));
+                ("pointer", mk().cast_expr(arg.1, ty))
+            }
+        };
+        let spec_lit = mk().lit_expr(mk().str_lit(&spec.spec));
+        let mut conv = mk().call_expr(
+            mk().path_expr(vec!["", "c2rust_printf", ctor]),
+            vec![spec_lit, arg],
+        );
+        if let Some(width) = width {
+            conv = mk().method_call_expr(conv, "width", vec![self.convert_printf_count(width)]);
+        }
+        if let Some(precision) = precision {
+            let precision = self.convert_printf_count(precision);
+            conv = mk().method_call_expr(conv, "precision", vec![precision]);
+        }
+        Ok(conv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The only conversion of a format string
+    fn conv(format: &str) -> ConvSpec {
+        let mut pieces = parse_format(format).expect("Untranslatable format");
+        match pieces.pop() {
+            Some(Piece::Conv(spec)) if pieces.is_empty() => spec,
+            _ => panic!("Expected a single conversion in {:?}", format),
+        }
+    }
+
+    fn rust_spec(format: &str) -> Option<String> {
+        conv(format).rust_spec()
+    }
+
+    #[test]
+    fn text_and_percent_signs() {
+        let pieces = parse_format("100%% of {%s}\n").unwrap();
+        match pieces.as_slice() {
+            [Piece::Text(before), Piece::Conv(spec), Piece::Text(after)] => {
+                assert_eq!(before, "100% of {");
+                assert_eq!(spec.spec, "%s");
+                assert_eq!(after, "}\n");
+                assert_eq!(escape_text(before), "100% of {{");
+            }
+            _ => panic!("Unexpected pieces"),
+        }
+    }
+
+    #[test]
+    fn flags() {
+        assert_eq!(rust_spec("%d").unwrap(), "{}");
+        assert_eq!(rust_spec("%5d").unwrap(), "{:5}");
+        assert_eq!(rust_spec("%+d").unwrap(), "{:+}");
+        assert_eq!(rust_spec("%05d").unwrap(), "{:05}");
+        assert_eq!(rust_spec("%-5d").unwrap(), "{:<5}");
+        // `-` overrides `0`
+        assert_eq!(rust_spec("%-05d").unwrap(), "{:<5}");
+        assert_eq!(rust_spec("%0-5d").unwrap(), "{:<5}");
+        assert_eq!(rust_spec("%08X").unwrap(), "{:08X}");
+        // `+` has no effect on unsigned conversions, which Rust would print with a sign
+        assert_eq!(rust_spec("%+u"), None);
+        assert_eq!(rust_spec("%+x"), None);
+        // Flags Rust formats differently are left to `c2rust_printf`
+        assert_eq!(rust_spec("% d"), None);
+        assert_eq!(rust_spec("%#x"), None);
+        assert_eq!(rust_spec("%.3d"), None);
+    }
+
+    #[test]
+    fn counts_from_arguments() {
+        let spec = conv("%-*.*d");
+        assert_eq!(spec.spec, "%-*.*d");
+        match (&spec.width, &spec.precision) {
+            (Some(Count::Arg), Some(Count::Arg)) => {}
+            _ => panic!("Expected the width and precision to be arguments"),
+        }
+        assert_eq!(spec.rust_spec(), None);
+
+        // An empty precision is a precision of zero
+        assert_eq!(conv("%.f").spec, "%.0f");
+        assert_eq!(conv("%*.e").spec, "%*.0e");
+    }
+
+    #[test]
+    fn length_modifiers() {
+        let spec = conv("%hhd");
+        assert_eq!(spec.length, "hh");
+        assert_eq!(rust_spec("%hhd").unwrap(), "{}");
+        assert_eq!(
+            length_type(&spec.length, true),
+            Some((Some(CTypeKind::SChar), "c_schar"))
+        );
+
+        // An `int` argument of `%lx` is cast to `c_ulong`
+        let spec = conv("%lx");
+        assert_eq!(spec.rust_spec().unwrap(), "{:x}");
+        assert_eq!(
+            length_type(&spec.length, false),
+            Some((Some(CTypeKind::ULong), "c_ulong"))
+        );
+
+        assert_eq!(length_type("z", false), Some((None, "size_t")));
+        assert_eq!(length_type("t", true), Some((None, "ptrdiff_t")));
+        assert_eq!(length_type("L", true), None);
+    }
+
+    #[test]
+    fn untranslatable_formats() {
+        // `%n` writes through its argument
+        assert!(parse_format("%d%n").is_none());
+        // Positional arguments
+        assert!(parse_format("%1$d").is_none());
+        assert!(parse_format("%2$s %1$s").is_none());
+        // Long doubles and wide characters
+        assert!(parse_format("%Lf").is_none());
+        assert!(parse_format("%ls").is_none());
+        // A format ending in the middle of a conversion
+        assert!(parse_format("%-5").is_none());
+    }
+}
@@ -0,0 +1,124 @@
+//! Sharing of the `static inline` functions of headers between translation units.
+//!
+//! Every translation unit including a header has its own copy of the header's `static inline`
+//! functions. With `--share-inline-functions`, these functions go into the module of their
+//! header along with the header's other declarations from `--header-modules`, with `pub(crate)`
+//! visibility. The header module keeps a single copy of each function, which the translation
+//! units import. Translation units whose copy is translated differently keep their own, like
+//! they do for the other declarations of headers.
+
+use super::*;
+use crate::c_ast::iterators::immediate_children_all_types;
+use std::collections::HashSet;
+
+impl<'c> Translation<'c> {
+    /// Find the `static inline` function definitions of headers that can go into the module of
+    /// their header. They may only refer to declarations the header module can import, so not to
+    /// declarations of the main file or to variables with internal linkage, and only to functions
+    /// with internal linkage that are shared as well. Variadic functions stay in the translation
+    /// unit for `--stable-variadics`.
+    pub fn find_shared_inline_functions(&self) -> IndexSet<CDeclId> {
+        let mut shared = IndexMap::new();
+        for (&decl_id, decl) in self.ast_context.iter_decls() {
+            if let CDeclKind::Function {
+                is_global: false,
+                is_inline: true,
+                typ,
+                body: Some(_),
+                ..
+            } = decl.kind
+            {
+                let is_variadic = match self.ast_context.resolve_type(typ).kind {
+                    CTypeKind::Function(_, _, is_variadic, _, _) => is_variadic,
+                    _ => true,
+                };
+                let in_header = match self.decl_file_path(decl_id) {
+                    Some(path) => path != &self.main_file,
+                    None => false,
+                };
+                if is_variadic || !in_header {
+                    continue;
+                }
+                if let Some(callees) = self.inline_function_callees(decl_id) {
+                    shared.insert(decl_id, callees);
+                }
+            }
+        }
+
+        // Functions referring to functions that stay in the translation unit stay there as well
+        loop {
+            let unshared: Vec<CDeclId> = shared
+                .iter()
+                .filter(|(_, callees)| callees.iter().any(|callee| !shared.contains_key(callee)))
+                .map(|(&decl_id, _)| decl_id)
+                .collect();
+            if unshared.is_empty() {
+                break;
+            }
+            for decl_id in unshared {
+                shared.remove(&decl_id);
+            }
+        }
+
+        shared.into_iter().map(|(decl_id, _)| decl_id).collect()
+    }
+
+    fn decl_file_path(&self, decl_id: CDeclId) -> Option<&PathBuf> {
+        self.ast_context[decl_id]
+            .loc
+            .as_ref()
+            .and_then(|loc| loc.file_path.as_ref())
+    }
+
+    /// The other functions with internal linkage that a function definition refers to, or `None`
+    /// if it refers to a declaration that the module of its header can't import. Static local
+    /// variables rule out sharing as well, since every translation unit has its own.
+    fn inline_function_callees(&self, decl_id: CDeclId) -> Option<IndexSet<CDeclId>> {
+        let mut callees = IndexSet::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![SomeId::Decl(decl_id)];
+
+        while let Some(node) = stack.pop() {
+            if !visited.insert(node) {
+                continue;
+            }
+            stack.extend(immediate_children_all_types(&self.ast_context, node));
+
+            let referenced = match node {
+                // Follow `struct` and `enum` types to their declarations
+                SomeId::Type(id) => match self.ast_context[id].kind {
+                    CTypeKind::Elaborated(ty) => {
+                        stack.push(SomeId::Type(ty));
+                        continue;
+                    }
+                    _ => continue,
+                },
+                SomeId::Decl(id) => id,
+                SomeId::Expr(id) => match self.ast_context[id].kind {
+                    CExprKind::DeclRef(_, id, _) => id,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            if self.decl_file_path(referenced) == Some(&self.main_file) {
+                return None;
+            }
+            match self.ast_context[referenced].kind {
+                CDeclKind::Function {
+                    is_global: false, ..
+                } if referenced != decl_id => {
+                    callees.insert(referenced);
+                }
+                CDeclKind::Variable {
+                    has_static_duration,
+                    has_thread_duration,
+                    is_externally_visible: false,
+                    ..
+                } if has_static_duration || has_thread_duration => return None,
+                _ => {}
+            }
+        }
+
+        Some(callees)
+    }
+}
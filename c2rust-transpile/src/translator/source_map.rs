@@ -0,0 +1,181 @@
+//! Source maps from the translated Rust code back to the C source.
+//!
+//! Translated items and statements are tagged with a `NodeId` standing for the location of the C
+//! node they came from. Statements the relooper builds out of a branch, such as `if` and `match`,
+//! take the tag of their condition. Right before pretty-printing, the comment traverser puts a
+//! marker comment in front of every tagged node. The markers are then taken back out of the
+//! printed output, and the lines they were on make up the source map.
+//!
+//! Only C statements get entries of their own. The statements generated for a C expression (for
+//! instance the temporaries of a `x++` in a condition) are covered by the entry of the C statement
+//! containing the expression, even when they end up inside of a Rust block expression. Statements
+//! inside of GNU statement expressions are C statements, so they do get entries.
+//!
+//! The source map describes the output of the translator. Rewriting that output afterwards, as
+//! `--reorganize-definitions` does with `c2rust-refactor` and `cargo fmt`, would make it stale, so
+//! that refactoring is skipped when emitting source maps.
+
+use super::*;
+
+use std::collections::HashMap;
+
+const MARKER_PREFIX: &str = "// c2rust-source-map: ";
+
+/// Mapping from the start of a translated item or statement to the C location it came from
+#[derive(Serialize)]
+struct Mapping {
+    rust_line: usize,
+    rust_column: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    c_file: Option<String>,
+    c_line: u64,
+    c_column: u64,
+}
+
+/// The C locations of the tagged nodes, indexed by their `NodeId`s
+pub struct SourceMapBuilder {
+    locs: Vec<SrcLoc>,
+}
+
+impl SourceMapBuilder {
+    pub fn new() -> Self {
+        SourceMapBuilder { locs: vec![] }
+    }
+
+    fn node_id(&mut self, loc: &SrcLoc) -> NodeId {
+        self.locs.push(loc.clone());
+        NodeId::from_u32(self.locs.len() as u32 - 1)
+    }
+
+    /// The marker comments to put in front of the tagged nodes
+    pub fn markers(&self) -> HashMap<NodeId, String> {
+        (0..self.locs.len() as u32)
+            .map(|id| (NodeId::from_u32(id), format!("{}{}", MARKER_PREFIX, id)))
+            .collect()
+    }
+
+    /// Take the marker comments out of the pretty-printed output. Returns the remaining output
+    /// and the JSON source map built from the positions of the markers.
+    pub fn extract(&self, output: &str) -> (String, String) {
+        let mut stripped = String::with_capacity(output.len());
+        let mut mappings = vec![];
+        let mut pending = vec![];
+        let mut rust_line = 0;
+
+        for line in output.lines() {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with(MARKER_PREFIX) {
+                if let Ok(id) = trimmed[MARKER_PREFIX.len()..].parse::<usize>() {
+                    pending.push(id);
+                    continue;
+                }
+            }
+
+            rust_line += 1;
+            let rust_column = line.len() - trimmed.len() + 1;
+            for id in pending.drain(..) {
+                let loc = &self.locs[id];
+                mappings.push(Mapping {
+                    rust_line,
+                    rust_column,
+                    c_file: loc
+                        .file_path
+                        .as_ref()
+                        .map(|path| path.display().to_string()),
+                    c_line: loc.line,
+                    c_column: loc.column,
+                });
+            }
+            stripped.push_str(line);
+            stripped.push('\n');
+        }
+
+        let json =
+            serde_json::to_string_pretty(&mappings).expect("Could not serialize the source map");
+        (stripped, json)
+    }
+}
+
+impl<'c> Translation<'c> {
+    /// The `NodeId` to give a translated node so that the source map points it to `loc`. This
+    /// is `DUMMY_NODE_ID` if no source map is being built.
+    pub fn src_loc_node_id(&self, loc: Option<&SrcLoc>) -> NodeId {
+        match (self.source_map.borrow_mut().as_mut(), loc) {
+            (Some(source_map), Some(loc)) => source_map.node_id(loc),
+            _ => DUMMY_NODE_ID,
+        }
+    }
+
+    /// Point the source map from the first item translated from a declaration to the
+    /// declaration.
+    pub fn tag_converted_decl(&self, decl_id: CDeclId, converted: ConvertedDecl) -> ConvertedDecl {
+        let id = self.src_loc_node_id(self.ast_context[decl_id].loc.as_ref());
+        match converted {
+            ConvertedDecl::Item(mut item) => {
+                item.id = id;
+                ConvertedDecl::Item(item)
+            }
+            ConvertedDecl::Items(mut items) => {
+                if let Some(item) = items.first_mut() {
+                    item.id = id;
+                }
+                ConvertedDecl::Items(items)
+            }
+            ConvertedDecl::ForeignItem(mut item) => {
+                item.id = id;
+                ConvertedDecl::ForeignItem(item)
+            }
+            ConvertedDecl::NoItem => ConvertedDecl::NoItem,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loc(line: u64, column: u64) -> SrcLoc {
+        SrcLoc {
+            fileid: 0,
+            line,
+            column,
+            file_path: Some(PathBuf::from("test.c")),
+        }
+    }
+
+    #[test]
+    fn markers_become_mappings() {
+        let mut builder = SourceMapBuilder::new();
+        let item = builder.node_id(&loc(1, 1));
+        let stmt = builder.node_id(&loc(2, 5));
+        let markers = builder.markers();
+
+        let output = format!(
+            "{}\nfn f() {{\n    {}\n    g();\n}}\n",
+            markers[&item], markers[&stmt]
+        );
+        let (stripped, json) = builder.extract(&output);
+        assert_eq!(stripped, "fn f() {\n    g();\n}\n");
+
+        let mappings: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            mappings,
+            serde_json::json!([
+                {
+                    "rust_line": 1,
+                    "rust_column": 1,
+                    "c_file": "test.c",
+                    "c_line": 1,
+                    "c_column": 1
+                },
+                {
+                    "rust_line": 2,
+                    "rust_column": 5,
+                    "c_file": "test.c",
+                    "c_line": 2,
+                    "c_column": 5
+                }
+            ])
+        );
+    }
+}
@@ -0,0 +1,1397 @@
+//! Translation of tagged unions into Rust enums.
+//!
+//! A tagged union is a struct of the form `struct S { enum kind tag; union { .. } u; }` in which
+//! every union field is only accessed when the tag is known to select that field: in a `case` of
+//! a `switch (x.tag)`, in the `then` branch of `if (x.tag == K)`, or after an `x.tag = K;`
+//! statement in the same block. Such structs become `#[repr(C)]` Rust enums with one variant
+//! per enumerator of the tag, carrying the field selected by that enumerator.
+//!
+//! A `switch (x.tag)` whose cases don't fall through becomes a `match x` whose arms bind the union
+//! field their case accesses. Other tag reads and writes turn into calls to the generated `tag`
+//! and `set_tag` methods, and other union field accesses into calls to accessors. Reading an
+//! inactive field gives its zero value and writing one activates it, as with a C union, instead
+//! of reinterpreting memory.
+//!
+//! The layout of the enums doesn't match the C struct, so structs that C code we don't translate
+//! can reach stay plain `#[repr(C)]` structs, as do structs that break the discipline, with a
+//! warning.
+
+use super::enums::foreign_types;
+use super::*;
+
+use crate::c_ast::iterators::immediate_children;
+use crate::convert_type::RESERVED_NAMES;
+use std::collections::HashMap;
+use syntax::visit::{self, Visitor};
+
+/// A struct translated into a Rust enum
+#[derive(Clone, Debug)]
+pub struct TaggedUnion {
+    pub tag_field: CFieldId,
+    pub union_field: CFieldId,
+    pub tag_enum: CEnumId,
+    /// The enumerators of the tag in order of their values, with the union field each selects
+    pub variants: Vec<(CEnumConstantId, Option<CFieldId>)>,
+    /// Names of the accessor methods of each union field
+    pub accessors: IndexMap<CFieldId, String>,
+}
+
+/// A union field bound by an arm of the `match` that a tag `switch` is translated into
+pub struct PayloadBinding {
+    base: CExprId,
+    field: CFieldId,
+    name: String,
+}
+
+/// A `switch` over the tag of `base` whose cases can become the arms of a `match`
+struct TagSwitch {
+    base: CExprId,
+    kind: MemberKind,
+    record: CRecordId,
+    arms: Vec<TagSwitchArm>,
+}
+
+struct TagSwitchArm {
+    /// The enumerators of the `case` labels, or `None` for `default`
+    variants: Option<Vec<CEnumConstantId>>,
+    /// The union field the statements access, and whether they access it through `const`
+    field: Option<(CFieldId, bool)>,
+    stmts: Vec<CStmtId>,
+}
+
+struct Candidate {
+    tag_field: CFieldId,
+    union_field: CFieldId,
+    tag_enum: CEnumId,
+    variants: Vec<CEnumConstantId>,
+    field_guards: IndexMap<CFieldId, IndexSet<CEnumConstantId>>,
+}
+
+/// A known value of the tag of some tagged union lvalue
+#[derive(Clone)]
+struct Guard {
+    base: CExprId,
+    record: CRecordId,
+    tags: IndexSet<CEnumConstantId>,
+}
+
+struct Analysis<'a> {
+    ast_context: &'a TypedAstContext,
+    candidates: IndexMap<CRecordId, Candidate>,
+    tag_fields: HashMap<CFieldId, CRecordId>,
+    union_fields: HashMap<CFieldId, CRecordId>,
+    violations: IndexMap<CRecordId, String>,
+}
+
+fn strip_implicit(ast_context: &TypedAstContext, mut expr_id: CExprId) -> CExprId {
+    loop {
+        match ast_context[expr_id].kind {
+            CExprKind::Paren(_, e) | CExprKind::ImplicitCast(_, e, _, _, _) => expr_id = e,
+            _ => return expr_id,
+        }
+    }
+}
+
+fn const_int_value(value: ConstIntExpr) -> i128 {
+    match value {
+        ConstIntExpr::I(value) => value as i128,
+        ConstIntExpr::U(value) => value as i128,
+    }
+}
+
+fn is_terminator(ast_context: &TypedAstContext, stmt_id: CStmtId) -> bool {
+    match ast_context[stmt_id].kind {
+        CStmtKind::Break | CStmtKind::Continue | CStmtKind::Goto(_) | CStmtKind::Return(_) => true,
+        CStmtKind::Compound(ref stmts) => stmts
+            .last()
+            .map_or(false, |&stmt_id| is_terminator(ast_context, stmt_id)),
+        _ => false,
+    }
+}
+
+/// Do these expressions refer to the same object?
+fn same_lvalue(ast_context: &TypedAstContext, lhs: CExprId, rhs: CExprId) -> bool {
+    let lhs = strip_implicit(ast_context, lhs);
+    let rhs = strip_implicit(ast_context, rhs);
+    match (&ast_context[lhs].kind, &ast_context[rhs].kind) {
+        (&CExprKind::DeclRef(_, lhs, _), &CExprKind::DeclRef(_, rhs, _)) => lhs == rhs,
+        (
+            &CExprKind::Member(_, lhs_base, lhs_field, lhs_kind, _),
+            &CExprKind::Member(_, rhs_base, rhs_field, rhs_kind, _),
+        ) => {
+            let same_kind = match (lhs_kind, rhs_kind) {
+                (MemberKind::Dot, MemberKind::Dot) | (MemberKind::Arrow, MemberKind::Arrow) => true,
+                _ => false,
+            };
+            same_kind && lhs_field == rhs_field && same_lvalue(ast_context, lhs_base, rhs_base)
+        }
+        (
+            &CExprKind::Unary(_, c_ast::UnOp::Deref, lhs, _),
+            &CExprKind::Unary(_, c_ast::UnOp::Deref, rhs, _),
+        ) => same_lvalue(ast_context, lhs, rhs),
+        (
+            &CExprKind::ArraySubscript(_, lhs_base, lhs_idx, _),
+            &CExprKind::ArraySubscript(_, rhs_base, rhs_idx, _),
+        ) => {
+            let same_idx = match (
+                &ast_context[strip_implicit(ast_context, lhs_idx)].kind,
+                &ast_context[strip_implicit(ast_context, rhs_idx)].kind,
+            ) {
+                (
+                    &CExprKind::Literal(_, CLiteral::Integer(lhs, _)),
+                    &CExprKind::Literal(_, CLiteral::Integer(rhs, _)),
+                ) => lhs == rhs,
+                _ => same_lvalue(ast_context, lhs_idx, rhs_idx),
+            };
+            same_idx && same_lvalue(ast_context, lhs_base, rhs_base)
+        }
+        _ => false,
+    }
+}
+
+/// The variable an lvalue accessed by `same_lvalue` is part of
+fn lvalue_root(ast_context: &TypedAstContext, expr_id: CExprId) -> Option<CDeclId> {
+    match ast_context[strip_implicit(ast_context, expr_id)].kind {
+        CExprKind::DeclRef(_, decl_id, _) => Some(decl_id),
+        CExprKind::Member(_, base, ..)
+        | CExprKind::Unary(_, c_ast::UnOp::Deref, base, _)
+        | CExprKind::ArraySubscript(_, base, _, _) => lvalue_root(ast_context, base),
+        _ => None,
+    }
+}
+
+/// Can control leave this statement other than by falling through or returning? `break` and
+/// `continue` may only target loops and switches inside of it.
+fn escapes(
+    ast_context: &TypedAstContext,
+    stmt_id: CStmtId,
+    in_loop: bool,
+    in_switch: bool,
+) -> bool {
+    match ast_context[stmt_id].kind {
+        CStmtKind::Break => !in_loop && !in_switch,
+        CStmtKind::Continue => !in_loop,
+        CStmtKind::Case(..) | CStmtKind::Default(_) => !in_switch,
+        CStmtKind::Label(_) | CStmtKind::Goto(_) => true,
+        CStmtKind::While { body, .. }
+        | CStmtKind::DoWhile { body, .. }
+        | CStmtKind::ForLoop { body, .. } => escapes(ast_context, body, true, false),
+        CStmtKind::Switch { body, .. } => escapes(ast_context, body, in_loop, true),
+        _ => immediate_children(ast_context, stmt_id.into())
+            .into_iter()
+            .filter_map(SomeId::stmt)
+            .any(|stmt_id| escapes(ast_context, stmt_id, in_loop, in_switch)),
+    }
+}
+
+/// Do these statements break out of the block labelled `lbl`?
+pub(super) fn breaks_to(stmts: &[Stmt], lbl: &cfg::Label) -> bool {
+    struct BreaksTo {
+        label: Ident,
+        found: bool,
+    }
+
+    impl<'ast> Visitor<'ast> for BreaksTo {
+        fn visit_expr(&mut self, expr: &'ast Expr) {
+            if let ExprKind::Break(Some(ref label), _) = expr.node {
+                self.found |= label.ident == self.label;
+            }
+            visit::walk_expr(self, expr);
+        }
+
+        fn visit_mac(&mut self, _mac: &'ast Mac) {}
+    }
+
+    let mut visitor = BreaksTo {
+        label: mk().label(lbl.pretty_print()).ident,
+        found: false,
+    };
+    for stmt in stmts {
+        visitor.visit_stmt(stmt);
+    }
+    visitor.found
+}
+
+impl<'a> Analysis<'a> {
+    fn new(ast_context: &'a TypedAstContext) -> Self {
+        let mut analysis = Analysis {
+            ast_context,
+            candidates: IndexMap::new(),
+            tag_fields: HashMap::new(),
+            union_fields: HashMap::new(),
+            violations: IndexMap::new(),
+        };
+
+        for (&decl_id, decl) in ast_context.iter_decls() {
+            if let Some(candidate) = analysis.candidate(decl) {
+                analysis.tag_fields.insert(candidate.tag_field, decl_id);
+                analysis.union_fields.insert(candidate.union_field, decl_id);
+                analysis.candidates.insert(decl_id, candidate);
+            }
+        }
+
+        analysis
+    }
+
+    /// Recognize `struct { enum kind tag; union { .. } u; }` where the enumerators of the tag
+    /// have the values `0, 1, ..`
+    fn candidate(&self, decl: &CDecl) -> Option<Candidate> {
+        let fields = match decl.kind {
+            CDeclKind::Struct {
+                fields: Some(ref fields),
+                is_packed: false,
+                manual_alignment: None,
+                ..
+            } if fields.len() == 2 => fields,
+            _ => return None,
+        };
+        let field_ty = |field_id: CFieldId| match self.ast_context[field_id].kind {
+            CDeclKind::Field {
+                typ,
+                bitfield_width: None,
+                ..
+            } => Some(&self.ast_context.resolve_type(typ.ctype).kind),
+            _ => None,
+        };
+
+        let tag_enum = match field_ty(fields[0])? {
+            &CTypeKind::Enum(enum_id) => enum_id,
+            _ => return None,
+        };
+        match field_ty(fields[1])? {
+            &CTypeKind::Union(union_id) => match self.ast_context[union_id].kind {
+                CDeclKind::Union {
+                    fields: Some(ref fields),
+                    ..
+                } if !fields.is_empty() => {}
+                _ => return None,
+            },
+            _ => return None,
+        }
+
+        let mut variants = match self.ast_context[tag_enum].kind {
+            CDeclKind::Enum {
+                ref variants,
+                integral_type: Some(_),
+                ..
+            } => variants.clone(),
+            _ => return None,
+        };
+        variants.sort_by_key(|&variant| self.enumerator_value(variant));
+        let contiguous = variants
+            .iter()
+            .enumerate()
+            .all(|(i, &variant)| self.enumerator_value(variant) == i as i128);
+        if variants.is_empty() || !contiguous {
+            return None;
+        }
+
+        Some(Candidate {
+            tag_field: fields[0],
+            union_field: fields[1],
+            tag_enum,
+            variants,
+            field_guards: IndexMap::new(),
+        })
+    }
+
+    fn enumerator_value(&self, variant: CEnumConstantId) -> i128 {
+        match self.ast_context[variant].kind {
+            CDeclKind::EnumConstant { value, .. } => const_int_value(value),
+            _ => panic!("{:?} does not point to an enum variant", variant),
+        }
+    }
+
+    fn violation(&mut self, record: CRecordId, reason: &str) {
+        self.violations
+            .entry(record)
+            .or_insert_with(|| reason.to_string());
+    }
+
+    /// Recognize a read of the tag of a candidate, returning the tagged union lvalue
+    fn tag_read(&self, expr_id: CExprId) -> Option<(CExprId, CRecordId)> {
+        match self.ast_context[strip_implicit(self.ast_context, expr_id)].kind {
+            CExprKind::Member(_, base, field, _, _) => {
+                self.tag_fields.get(&field).map(|&record| (base, record))
+            }
+            _ => None,
+        }
+    }
+
+    /// Recognize an access to a field of the union of a candidate, returning whether the union
+    /// is accessed through `const`
+    fn union_field_access(&self, expr_id: CExprId) -> Option<(CRecordId, bool)> {
+        let inner = match self.ast_context[strip_implicit(self.ast_context, expr_id)].kind {
+            CExprKind::Member(_, inner, ..) => inner,
+            _ => return None,
+        };
+        match self.ast_context[strip_implicit(self.ast_context, inner)].kind {
+            CExprKind::Member(qty, _, union_field, _, _) => self
+                .union_fields
+                .get(&union_field)
+                .map(|&record| (record, qty.qualifiers.is_const)),
+            _ => None,
+        }
+    }
+
+    fn enumerator(&self, record: CRecordId, expr_id: CExprId) -> Option<CEnumConstantId> {
+        let variants = &self.candidates[&record].variants;
+        match self.ast_context[strip_implicit(self.ast_context, expr_id)].kind {
+            CExprKind::DeclRef(_, decl_id, _) if variants.contains(&decl_id) => Some(decl_id),
+            CExprKind::Literal(_, CLiteral::Integer(value, _)) => variants
+                .iter()
+                .cloned()
+                .find(|&variant| self.enumerator_value(variant) == value as i128),
+            _ => None,
+        }
+    }
+
+    fn case_enumerator(&self, record: CRecordId, value: ConstIntExpr) -> Option<CEnumConstantId> {
+        self.candidates[&record]
+            .variants
+            .iter()
+            .cloned()
+            .find(|&variant| self.enumerator_value(variant) == const_int_value(value))
+    }
+
+    /// Recognize `x.tag == K`
+    fn tag_test(&self, expr_id: CExprId) -> Option<Guard> {
+        match self.ast_context[strip_implicit(self.ast_context, expr_id)].kind {
+            CExprKind::Binary(_, c_ast::BinOp::EqualEqual, lhs, rhs, _, _) => {
+                let (tag, value) = if self.tag_read(lhs).is_some() {
+                    (lhs, rhs)
+                } else {
+                    (rhs, lhs)
+                };
+                let (base, record) = self.tag_read(tag)?;
+                let enumerator = self.enumerator(record, value)?;
+                Some(Guard {
+                    base,
+                    record,
+                    tags: vec![enumerator].into_iter().collect(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Recognize `x.tag = K`
+    fn tag_assignment(&self, expr_id: CExprId) -> Option<Guard> {
+        match self.ast_context[strip_implicit(self.ast_context, expr_id)].kind {
+            CExprKind::Binary(_, c_ast::BinOp::Assign, lhs, rhs, _, _) => {
+                let (base, record) = self.tag_read(lhs)?;
+                let enumerator = self.enumerator(record, rhs)?;
+                Some(Guard {
+                    base,
+                    record,
+                    tags: vec![enumerator].into_iter().collect(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn visit_children(&mut self, id: SomeId, guards: &[Guard]) {
+        for child in immediate_children(self.ast_context, id) {
+            match child {
+                SomeId::Stmt(stmt_id) => self.visit_stmt(stmt_id, guards),
+                SomeId::Expr(expr_id) => self.visit_expr(expr_id, guards),
+                SomeId::Decl(decl_id) => self.visit_children(decl_id.into(), guards),
+                SomeId::Type(_) => {}
+            }
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt_id: CStmtId, guards: &[Guard]) {
+        let ast_context = self.ast_context;
+        match ast_context[stmt_id].kind {
+            CStmtKind::Compound(ref stmts) => {
+                let mut guards = guards.to_vec();
+                for &stmt_id in stmts {
+                    self.visit_stmt(stmt_id, &guards);
+
+                    if let CStmtKind::Expr(expr_id) = ast_context[stmt_id].kind {
+                        if let Some(guard) = self.tag_assignment(expr_id) {
+                            guards.retain(|g| !same_lvalue(self.ast_context, g.base, guard.base));
+                            guards.push(guard);
+                        }
+                    }
+                }
+            }
+
+            CStmtKind::If {
+                scrutinee,
+                true_variant,
+                false_variant,
+            } => {
+                self.visit_expr(scrutinee, guards);
+                match self.tag_test(scrutinee) {
+                    Some(guard) => {
+                        let mut true_guards = guards.to_vec();
+                        true_guards.push(guard);
+                        self.visit_stmt(true_variant, &true_guards);
+                    }
+                    None => self.visit_stmt(true_variant, guards),
+                }
+                if let Some(false_variant) = false_variant {
+                    self.visit_stmt(false_variant, guards);
+                }
+            }
+
+            CStmtKind::Switch { scrutinee, body } => {
+                self.visit_expr(scrutinee, guards);
+                match self.tag_read(scrutinee) {
+                    Some((base, record)) => self.visit_tag_switch(base, record, body, guards),
+                    None => self.visit_stmt(body, guards),
+                }
+            }
+
+            _ => self.visit_children(stmt_id.into(), guards),
+        }
+    }
+
+    /// Visit the body of a `switch` over the tag of `base`. Statements following a `case` label
+    /// know the value of the tag until control may fall through from another `case`.
+    fn visit_tag_switch(
+        &mut self,
+        base: CExprId,
+        record: CRecordId,
+        body: CStmtId,
+        guards: &[Guard],
+    ) {
+        let stmts = match self.ast_context[body].kind {
+            CStmtKind::Compound(ref stmts) => stmts.clone(),
+            _ => vec![body],
+        };
+
+        let mut tags: Option<IndexSet<CEnumConstantId>> = None;
+        let mut terminated = true;
+        for stmt_id in stmts {
+            // Collect the labels of `case A: case B: stmt`
+            let mut labels = IndexSet::new();
+            let mut is_default = false;
+            let mut stmt = stmt_id;
+            let mut is_labeled = false;
+            loop {
+                match self.ast_context[stmt].kind {
+                    CStmtKind::Case(_, sub_stmt, value) => {
+                        match self.case_enumerator(record, value) {
+                            Some(enumerator) => {
+                                labels.insert(enumerator);
+                            }
+                            None => is_default = true,
+                        }
+                        stmt = sub_stmt;
+                    }
+                    CStmtKind::Default(sub_stmt) => {
+                        is_default = true;
+                        stmt = sub_stmt;
+                    }
+                    _ => break,
+                }
+                is_labeled = true;
+            }
+
+            if is_labeled {
+                tags = match tags {
+                    _ if is_default => None,
+                    _ if terminated => Some(labels),
+                    Some(mut tags) => {
+                        tags.extend(labels);
+                        Some(tags)
+                    }
+                    None => None,
+                };
+            }
+
+            let mut stmt_guards = guards.to_vec();
+            if let Some(ref tags) = tags {
+                stmt_guards.push(Guard {
+                    base,
+                    record,
+                    tags: tags.clone(),
+                });
+            }
+            self.visit_stmt(stmt, &stmt_guards);
+            terminated = is_terminator(self.ast_context, stmt);
+        }
+    }
+
+    fn visit_expr(&mut self, expr_id: CExprId, guards: &[Guard]) {
+        match self.ast_context[expr_id].kind {
+            CExprKind::Member(_, inner, field, _, _) => {
+                if let Some(&record) = self.union_fields.get(&field) {
+                    self.violation(record, "its union is used without selecting a field");
+                }
+
+                let union_access =
+                    match self.ast_context[strip_implicit(self.ast_context, inner)].kind {
+                        CExprKind::Member(_, base, union_field, _, _) => self
+                            .union_fields
+                            .get(&union_field)
+                            .map(|&record| (base, record)),
+                        _ => None,
+                    };
+                if let Some((base, record)) = union_access {
+                    let guard = guards
+                        .iter()
+                        .rev()
+                        .find(|g| g.record == record && same_lvalue(self.ast_context, g.base, base))
+                        .cloned();
+                    match guard {
+                        Some(guard) => {
+                            self.candidates[&record]
+                                .field_guards
+                                .entry(field)
+                                .or_insert_with(IndexSet::new)
+                                .extend(guard.tags);
+                        }
+                        None => self.violation(
+                            record,
+                            "a union field is accessed without checking the tag",
+                        ),
+                    }
+                    self.visit_expr(base, guards);
+                    return;
+                }
+            }
+
+            CExprKind::Binary(_, op, lhs, rhs, _, _) if op.is_assignment() => {
+                if let Some((_, record)) = self.tag_read(lhs) {
+                    if op != c_ast::BinOp::Assign {
+                        self.violation(record, "its tag is updated arithmetically");
+                    } else if self.enumerator(record, rhs).is_none() {
+                        self.violation(record, "its tag is assigned a computed value");
+                    }
+                }
+            }
+
+            CExprKind::Unary(_, op, arg, _) => {
+                // Inactive fields read through `const` have no storage to point to
+                if let c_ast::UnOp::AddressOf = op {
+                    if let Some((record, true)) = self.union_field_access(arg) {
+                        self.violation(record, "the address of a const union field is taken");
+                    }
+                }
+                if let Some((_, record)) = self.tag_read(arg) {
+                    match op {
+                        c_ast::UnOp::AddressOf
+                        | c_ast::UnOp::PreIncrement
+                        | c_ast::UnOp::PostIncrement
+                        | c_ast::UnOp::PreDecrement
+                        | c_ast::UnOp::PostDecrement => {
+                            self.violation(record, "its tag is modified in place")
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            CExprKind::InitList(ty, ..) => {
+                if let CTypeKind::Struct(record) = self.ast_context.resolve_type(ty.ctype).kind {
+                    if self.candidates.contains_key(&record) {
+                        self.violation(record, "it is built with an initializer list");
+                    }
+                }
+            }
+
+            _ => {}
+        }
+
+        self.visit_children(expr_id.into(), guards);
+    }
+
+    fn run(mut self) -> IndexMap<CRecordId, TaggedUnion> {
+        let ast_context = self.ast_context;
+        for (&decl_id, decl) in ast_context.iter_decls() {
+            match decl.kind {
+                CDeclKind::Function { body: Some(_), .. } => {
+                    self.visit_children(decl_id.into(), &[])
+                }
+                CDeclKind::Variable {
+                    initializer: Some(initializer),
+                    ..
+                } => self.visit_expr(initializer, &[]),
+                _ => {}
+            }
+        }
+
+        // C code we don't translate would see a different layout
+        for ty in foreign_types(ast_context) {
+            if let CTypeKind::Struct(record) = ast_context[ty].kind {
+                if self.candidates.contains_key(&record) {
+                    self.violation(record, "it is shared with external C code");
+                }
+            }
+        }
+
+        let mut tagged_unions = IndexMap::new();
+        for (record, candidate) in self.candidates {
+            let name = ast_context[record]
+                .kind
+                .get_name()
+                .cloned()
+                .unwrap_or_else(|| "<anonymous>".to_string());
+
+            // Each enumerator selects at most one field
+            let mut variants: Vec<(CEnumConstantId, Option<CFieldId>)> = candidate
+                .variants
+                .iter()
+                .map(|&variant| (variant, None))
+                .collect();
+            let mut violation = self.violations.get(&record).cloned();
+            for (&field, tags) in &candidate.field_guards {
+                for &mut (variant, ref mut selected) in &mut variants {
+                    if !tags.contains(&variant) {
+                        continue;
+                    }
+                    match *selected {
+                        Some(other) if other != field => {
+                            violation = violation.or_else(|| {
+                                Some("a tag value selects several union fields".to_string())
+                            })
+                        }
+                        _ => *selected = Some(field),
+                    }
+                }
+            }
+
+            if let Some(violation) = violation {
+                diag!(
+                    Diagnostic::TaggedUnions,
+                    "Translating struct {} as a union because {}",
+                    name,
+                    violation
+                );
+                continue;
+            }
+
+            let mut names = Renamer::new(&RESERVED_NAMES);
+            for name in &["tag", "set_tag"] {
+                names.pick_name(name);
+            }
+            let accessors = candidate
+                .field_guards
+                .keys()
+                .map(|&field| {
+                    let name = ast_context[field]
+                        .kind
+                        .get_name()
+                        .map_or("unnamed", |name| name.as_str());
+                    let name = names.insert(field, name).expect("Field already declared");
+                    (field, name)
+                })
+                .collect();
+
+            tagged_unions.insert(
+                record,
+                TaggedUnion {
+                    tag_field: candidate.tag_field,
+                    union_field: candidate.union_field,
+                    tag_enum: candidate.tag_enum,
+                    variants,
+                    accessors,
+                },
+            );
+        }
+
+        tagged_unions
+    }
+}
+
+/// Find the structs that can be translated into Rust enums
+pub fn find_tagged_unions(ast_context: &TypedAstContext) -> IndexMap<CRecordId, TaggedUnion> {
+    Analysis::new(ast_context).run()
+}
+
+impl<'c> Translation<'c> {
+    fn tagged_union_of_field(&self, field: CFieldId) -> Option<&TaggedUnion> {
+        self.tagged_unions
+            .values()
+            .find(|tagged| tagged.tag_field == field || tagged.union_field == field)
+    }
+
+    /// Is this expression the tag of a struct translated into an enum?
+    pub fn is_tagged_union_tag(&self, expr_id: CExprId) -> bool {
+        match self.ast_context[expr_id].kind {
+            CExprKind::Member(_, _, field, _, _) => self
+                .tagged_union_of_field(field)
+                .map_or(false, |tagged| tagged.tag_field == field),
+            _ => false,
+        }
+    }
+
+    /// Is this expression a field of the union of a struct translated into an enum?
+    pub fn is_tagged_union_field(&self, expr_id: CExprId) -> bool {
+        match self.ast_context[expr_id].kind {
+            CExprKind::Member(_, inner, _, _, _) => match self.ast_context[inner].kind {
+                CExprKind::Member(_, _, field, _, _) => self
+                    .tagged_union_of_field(field)
+                    .map_or(false, |tagged| tagged.union_field == field),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Recognize a `switch` over the tag of a tagged union that can become a `match` binding the
+    /// union fields: no case falls through into the next one, `break` only ends cases, and the
+    /// statements of each case only use the tagged union to access one union field.
+    fn tag_switch(&self, scrutinee: CExprId, body: CStmtId) -> Option<TagSwitch> {
+        let ast_context = &self.ast_context;
+        let (base, kind, record) = match ast_context[strip_implicit(ast_context, scrutinee)].kind {
+            CExprKind::Member(_, base, field, kind, _) => {
+                let (&record, _) = self
+                    .tagged_unions
+                    .iter()
+                    .find(|&(_, tagged)| tagged.tag_field == field)?;
+                (base, kind, record)
+            }
+            _ => return None,
+        };
+        let tagged = &self.tagged_unions[&record];
+        let root = lvalue_root(ast_context, base)?;
+        let stmts = match ast_context[body].kind {
+            CStmtKind::Compound(ref stmts) => stmts,
+            _ => return None,
+        };
+
+        // Split the body into the statements following each group of labels
+        let mut arms: Vec<TagSwitchArm> = vec![];
+        let mut open = false;
+        for &stmt_id in stmts {
+            let mut stmt_id = stmt_id;
+            let mut variants = Some(vec![]);
+            let mut is_labeled = false;
+            loop {
+                match ast_context[stmt_id].kind {
+                    CStmtKind::Case(_, sub_stmt, value) => {
+                        let value = const_int_value(value);
+                        if value < 0 || value >= tagged.variants.len() as i128 {
+                            return None;
+                        }
+                        if let Some(ref mut variants) = variants {
+                            variants.push(tagged.variants[value as usize].0);
+                        }
+                        stmt_id = sub_stmt;
+                    }
+                    CStmtKind::Default(sub_stmt) => {
+                        variants = None;
+                        stmt_id = sub_stmt;
+                    }
+                    _ => break,
+                }
+                is_labeled = true;
+            }
+
+            if is_labeled {
+                if open {
+                    return None;
+                }
+                arms.push(TagSwitchArm {
+                    variants,
+                    field: None,
+                    stmts: vec![],
+                });
+                open = true;
+            } else if !open {
+                return None;
+            }
+
+            let stmts = &mut arms.last_mut().unwrap().stmts;
+            match ast_context[stmt_id].kind {
+                CStmtKind::Break => open = false,
+                CStmtKind::Return(_) => {
+                    stmts.push(stmt_id);
+                    open = false;
+                }
+                // case A: { ..; break; }
+                CStmtKind::Compound(ref inner)
+                    if stmts.is_empty()
+                        && is_labeled
+                        && inner
+                            .last()
+                            .map_or(false, |&last| match ast_context[last].kind {
+                                CStmtKind::Break => true,
+                                _ => false,
+                            }) =>
+                {
+                    stmts.extend_from_slice(&inner[..inner.len() - 1]);
+                    open = false;
+                }
+                _ => stmts.push(stmt_id),
+            }
+        }
+
+        for arm in &mut arms {
+            if arm
+                .stmts
+                .iter()
+                .any(|&stmt_id| escapes(ast_context, stmt_id, false, false))
+            {
+                return None;
+            }
+
+            // Every use of the variable holding the tagged union must be a union field access
+            let mut fields = IndexMap::new();
+            let mut uses = 0;
+            let mut field_uses = 0;
+            let count_uses = |id: SomeId| {
+                DFExpr::new(ast_context, id)
+                    .filter(|&id| match id {
+                        SomeId::Expr(expr_id) => match ast_context[expr_id].kind {
+                            CExprKind::DeclRef(_, decl_id, _) => decl_id == root,
+                            _ => false,
+                        },
+                        _ => false,
+                    })
+                    .count()
+            };
+            for &stmt_id in &arm.stmts {
+                uses += count_uses(stmt_id.into());
+                for id in DFExpr::new(ast_context, stmt_id.into()) {
+                    let (inner, field) = match id {
+                        SomeId::Expr(expr_id) => match ast_context[expr_id].kind {
+                            CExprKind::Member(_, inner, field, _, _) => (inner, field),
+                            _ => continue,
+                        },
+                        _ => continue,
+                    };
+                    if let CExprKind::Member(qty, access_base, union_field, _, _) =
+                        ast_context[strip_implicit(ast_context, inner)].kind
+                    {
+                        if union_field == tagged.union_field
+                            && same_lvalue(ast_context, access_base, base)
+                        {
+                            fields.insert(field, qty.qualifiers.is_const);
+                            field_uses += count_uses(access_base.into());
+                        }
+                    }
+                }
+            }
+            if uses != field_uses || fields.len() > 1 {
+                return None;
+            }
+
+            // The variants of the arm must all carry the field
+            if let Some((&field, &is_const)) = fields.iter().next() {
+                let variants = arm.variants.as_ref()?;
+                let carry_field = variants.iter().all(|variant| {
+                    tagged
+                        .variants
+                        .iter()
+                        .any(|&(v, selected)| v == *variant && selected == Some(field))
+                });
+                if !carry_field {
+                    return None;
+                }
+                arm.field = Some((field, is_const));
+            }
+        }
+
+        Some(TagSwitch {
+            base,
+            kind,
+            record,
+            arms,
+        })
+    }
+
+    /// Can this `switch` become a `match` binding the fields of a tagged union?
+    pub fn is_tagged_union_switch(&self, scrutinee: CExprId, body: CStmtId) -> bool {
+        self.tag_switch(scrutinee, body).is_some()
+    }
+
+    /// Convert `switch (x.tag)` into `match x`, binding the union field of each case by reference
+    pub fn convert_tagged_union_switch(
+        &self,
+        ctx: ExprContext,
+        scrutinee: CExprId,
+        body: CStmtId,
+    ) -> Result<Vec<Stmt>, TranslationError> {
+        let switch = self
+            .tag_switch(scrutinee, body)
+            .ok_or_else(|| format_err!("Expected a switch over the tag of a tagged union"))?;
+        let tagged = &self.tagged_unions[&switch.record];
+        let name = self
+            .type_converter
+            .borrow()
+            .resolve_decl_name(switch.record)
+            .unwrap();
+        let (mut stmts, base) = self
+            .convert_tagged_union_base(ctx, switch.base, switch.kind)?
+            .discard_unsafe();
+
+        let mut arms = vec![];
+        let mut default_arm = None;
+        let mut covered = 0;
+        for arm in switch.arms {
+            let binding = arm.field.map(|(field, is_const)| {
+                let field_name = self.ast_context[field]
+                    .kind
+                    .get_name()
+                    .map_or("unnamed", |name| name.as_str());
+                let binding = self.renamer.borrow_mut().pick_name(field_name);
+                (field, is_const, binding)
+            });
+
+            let pats: Vec<P<Pat>> = match arm.variants {
+                Some(ref variants) => variants
+                    .iter()
+                    .map(|&variant| {
+                        let variant_name = self.enum_variant_name(variant);
+                        let path = mk().path(vec![name.as_str(), variant_name.as_str()]);
+                        let has_field = tagged
+                            .variants
+                            .iter()
+                            .any(|&(v, selected)| v == variant && selected.is_some());
+                        match binding {
+                            Some((_, is_const, ref binding)) => {
+                                let mutbl = if is_const {
+                                    Mutability::Immutable
+                                } else {
+                                    Mutability::Mutable
+                                };
+                                let pat = mk().set_mutbl(mutbl).ident_ref_pat(binding);
+                                mk().tuple_struct_pat(path, vec![pat])
+                            }
+                            None if has_field => mk().tuple_struct_pat(path, vec![mk().wild_pat()]),
+                            None => mk().qpath_pat(None, path),
+                        }
+                    })
+                    .collect(),
+                None => vec![mk().wild_pat()],
+            };
+
+            if let Some((field, _, ref binding)) = binding {
+                self.payload_bindings.borrow_mut().push(PayloadBinding {
+                    base: switch.base,
+                    field,
+                    name: binding.clone(),
+                });
+            }
+            let arm_body = self.convert_match_arm_body(ctx, &arm.stmts);
+            if binding.is_some() {
+                self.payload_bindings.borrow_mut().pop();
+            }
+            let match_arm = mk().arm(pats, None as Option<P<Expr>>, arm_body?);
+
+            // The `default` arm goes last, after the patterns of all the other cases
+            match arm.variants {
+                Some(ref variants) => {
+                    covered += variants.len();
+                    arms.push(match_arm);
+                }
+                None => default_arm = Some(match_arm),
+            }
+        }
+
+        // Cases without a label leave the `switch` right away
+        let empty_arm = || {
+            mk().arm(
+                vec![mk().wild_pat()],
+                None as Option<P<Expr>>,
+                mk().block_expr(mk().block(vec![])),
+            )
+        };
+        match default_arm {
+            Some(arm) => arms.push(arm),
+            None if covered < tagged.variants.len() => arms.push(empty_arm()),
+            None => {}
+        }
+
+        stmts.push(mk().semi_stmt(mk().match_expr(base, arms)));
+        Ok(stmts)
+    }
+
+    /// Convert the statements of a `case` into the body of a `match` arm
+    fn convert_match_arm_body(
+        &self,
+        ctx: ExprContext,
+        stmt_ids: &[CStmtId],
+    ) -> Result<P<Expr>, TranslationError> {
+        let first = match stmt_ids.first() {
+            Some(&first) => first,
+            None => return Ok(mk().block_expr(mk().block(vec![]))),
+        };
+        let name = format!("<match-arm_{:?}>", first);
+        let lbl = cfg::Label::FromC(first);
+        let mut stmts =
+            self.convert_function_body(ctx, &name, stmt_ids, cfg::ImplicitReturnType::Break(lbl))?;
+
+        // Falling off the end of the arm ends the `match`
+        let ends_arm = stmts
+            .last()
+            .and_then(|stmt| as_semi_break_stmt(stmt, &lbl))
+            .is_some();
+        if ends_arm {
+            stmts.pop();
+        }
+        if breaks_to(&stmts, &lbl) {
+            self.use_feature("label_break_value");
+            Ok(mk().labelled_block_expr(mk().block(stmts), lbl.pretty_print()))
+        } else {
+            Ok(mk().block_expr(mk().block(stmts)))
+        }
+    }
+
+    /// Convert the tagged union `base` as a place
+    fn convert_tagged_union_base(
+        &self,
+        ctx: ExprContext,
+        base: CExprId,
+        kind: MemberKind,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let base = self.convert_expr(ctx.used(), base)?;
+        Ok(base.map(|base| match kind {
+            MemberKind::Dot => base,
+            MemberKind::Arrow => mk().paren_expr(mk().unary_expr(ast::UnOp::Deref, base)),
+        }))
+    }
+
+    /// Convert a read of the tag into a call to `tag()`
+    pub fn convert_tagged_union_tag(
+        &self,
+        ctx: ExprContext,
+        expr_id: CExprId,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let (base, kind) = match self.ast_context[expr_id].kind {
+            CExprKind::Member(_, base, _, kind, _) => (base, kind),
+            _ => {
+                return Err(TranslationError::generic(
+                    "Expected a tag member expression",
+                ))
+            }
+        };
+        let base = self.convert_tagged_union_base(ctx, base, kind)?;
+        Ok(base.map(|base| mk().method_call_expr(base, "tag", vec![] as Vec<P<Expr>>)))
+    }
+
+    /// Convert `x.tag = K` into a call to `set_tag(K)`
+    pub fn convert_tagged_union_tag_assignment(
+        &self,
+        ctx: ExprContext,
+        lhs: CExprId,
+        rhs: CExprId,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let (base, kind) = match self.ast_context[lhs].kind {
+            CExprKind::Member(_, base, _, kind, _) => (base, kind),
+            _ => {
+                return Err(TranslationError::generic(
+                    "Expected a tag member expression",
+                ))
+            }
+        };
+        let base = self.convert_tagged_union_base(ctx, base, kind)?;
+        let rhs = self.convert_expr(ctx.used(), rhs)?;
+        base.and_then(|base| {
+            rhs.and_then(|rhs| {
+                let set_tag = mk().method_call_expr(base.clone(), "set_tag", vec![rhs]);
+                let val = if ctx.is_unused() {
+                    self.panic_or_err("Tag assignment is not supposed to be used")
+                } else {
+                    mk().method_call_expr(base, "tag", vec![] as Vec<P<Expr>>)
+                };
+                Ok(WithStmts::new(vec![mk().semi_stmt(set_tag)], val))
+            })
+        })
+    }
+
+    /// Convert an access to `x.u.field` into the payload bound by the enclosing `match` arm, or
+    /// else into a call to its accessor
+    pub fn convert_tagged_union_field(
+        &self,
+        ctx: ExprContext,
+        expr_id: CExprId,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let (inner, field) = match self.ast_context[expr_id].kind {
+            CExprKind::Member(_, inner, field, _, _) => (inner, field),
+            _ => return Err(TranslationError::generic("Expected a member expression")),
+        };
+        let (base, union_field, kind, is_const) = match self.ast_context[inner].kind {
+            CExprKind::Member(qty, base, union_field, kind, _) => {
+                (base, union_field, kind, qty.qualifiers.is_const)
+            }
+            _ => return Err(TranslationError::generic("Expected a member expression")),
+        };
+
+        let bound = self
+            .payload_bindings
+            .borrow()
+            .iter()
+            .rev()
+            .find(|binding| {
+                binding.field == field && same_lvalue(&self.ast_context, binding.base, base)
+            })
+            .map(|binding| binding.name.clone());
+        if let Some(name) = bound {
+            let payload = mk().unary_expr(ast::UnOp::Deref, mk().ident_expr(name));
+            return Ok(WithStmts::new_val(mk().paren_expr(payload)));
+        }
+
+        let accessor = self
+            .tagged_union_of_field(union_field)
+            .and_then(|tagged| tagged.accessors.get(&field))
+            .ok_or_else(|| format_err!("Missing accessor for tagged union field"))?;
+        let accessor = if is_const {
+            accessor.clone()
+        } else {
+            format!("{}_mut", accessor)
+        };
+
+        let base = self.convert_tagged_union_base(ctx, base, kind)?;
+        Ok(base.map(|base| {
+            let call = mk().method_call_expr(base, accessor, vec![] as Vec<P<Expr>>);
+            if is_const {
+                call
+            } else {
+                mk().paren_expr(mk().unary_expr(ast::UnOp::Deref, call))
+            }
+        }))
+    }
+
+    /// The zero value of a tagged union is its first variant with a zeroed field
+    pub fn tagged_union_zero_initializer(
+        &self,
+        record: CRecordId,
+        is_static: bool,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let name = self
+            .type_converter
+            .borrow()
+            .resolve_decl_name(record)
+            .unwrap();
+        let (variant, field) = self.tagged_unions[&record].variants[0];
+        self.tagged_union_variant(&name, variant, field, is_static)
+    }
+
+    fn tagged_union_variant(
+        &self,
+        name: &str,
+        variant: CEnumConstantId,
+        field: Option<CFieldId>,
+        is_static: bool,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let variant_name = self.enum_variant_name(variant);
+        let path = mk().path_expr(vec![name, variant_name.as_str()]);
+        match field {
+            Some(field) => {
+                let typ = match self.ast_context[field].kind {
+                    CDeclKind::Field { typ, .. } => typ,
+                    _ => return Err(TranslationError::generic("Expected a field")),
+                };
+                let zero = self.implicit_default_expr(typ.ctype, is_static)?;
+                Ok(zero.map(|zero| mk().call_expr(path, vec![zero])))
+            }
+            None => Ok(WithStmts::new_val(path)),
+        }
+    }
+
+    /// Convert a struct recognized as a tagged union into an enum and its accessors
+    pub fn convert_tagged_union(
+        &self,
+        span: Span,
+        record: CRecordId,
+        name: &str,
+    ) -> Result<ConvertedDecl, TranslationError> {
+        let tagged = &self.tagged_unions[&record];
+        let self_ty = mk().path_ty(vec![name]);
+        let tag_ty = self.convert_type(match self.ast_context[tagged.tag_field].kind {
+            CDeclKind::Field { typ, .. } => typ.ctype,
+            _ => return Err(TranslationError::generic("Expected a field")),
+        })?;
+        let field_ty = |field: CFieldId| match self.ast_context[field].kind {
+            CDeclKind::Field { typ, .. } => self.convert_type(typ.ctype),
+            _ => Err(TranslationError::generic("Expected a field")),
+        };
+        let no_args = || vec![] as Vec<P<Expr>>;
+        let tag_enum_name = self
+            .type_converter
+            .borrow()
+            .resolve_decl_name(tagged.tag_enum)
+            .expect("Enums should already be renamed");
+        let is_rust_enum = self.is_rust_enum(tagged.tag_enum);
+        let self_arg = |mutbl: Mutability| {
+            let ty = mk().set_mutbl(mutbl).ref_ty(mk().path_ty(vec!["Self"]));
+            mk().arg(ty, mk().ident_pat("self"))
+        };
+
+        let mut variants = vec![];
+        let mut tag_arms = vec![];
+        let mut set_tag_arms = vec![];
+        for (value, &(variant, field)) in tagged.variants.iter().enumerate() {
+            let variant_name = self.enum_variant_name(variant);
+            let path = mk().path(vec![name, variant_name.as_str()]);
+
+            let pat = match field {
+                Some(field) => {
+                    let data =
+                        VariantData::Tuple(vec![mk().enum_field(field_ty(field)?)], DUMMY_NODE_ID);
+                    let variant_span = self.decl_doc_span(field);
+                    variants.push(mk().span(variant_span).variant(&variant_name, data));
+                    mk().tuple_struct_pat(path, vec![mk().wild_pat()])
+                }
+                None => {
+                    variants.push(mk().unit_variant(&variant_name, None as Option<P<Expr>>));
+                    mk().qpath_pat(None, path)
+                }
+            };
+
+            // Tag values are the variant indices, and their constants may live in another module
+            let (tag_val, tag_pat) = if is_rust_enum {
+                let path = vec![tag_enum_name.as_str(), variant_name.as_str()];
+                (mk().path_expr(path.clone()), mk().qpath_pat(None, path))
+            } else {
+                let lit = mk().lit_expr(mk().int_lit(value as u128, LitIntType::Unsuffixed));
+                (lit.clone(), mk().lit_pat(lit))
+            };
+            tag_arms.push(mk().arm(vec![pat], None, tag_val));
+
+            let value = self
+                .tagged_union_variant(name, variant, field, false)?
+                .to_pure_expr()
+                .ok_or_else(|| format_err!("Expected a pure zero initializer"))?;
+            set_tag_arms.push(mk().arm(vec![tag_pat], None, value));
+        }
+        // The translated code only assigns enumerators, so other values are ignored
+        if !is_rust_enum {
+            set_tag_arms.push(mk().arm(
+                vec![mk().wild_pat()],
+                None,
+                mk().return_expr(None as Option<P<Expr>>),
+            ));
+        }
+
+        // pub fn tag(&self) -> Tag { match self { S::A(_) => A, S::B => B } }
+        let mut methods = vec![mk().pub_().method_impl_item(
+            "tag",
+            mk().fn_decl(
+                vec![self_arg(Mutability::Immutable)],
+                FunctionRetTy::Ty(tag_ty.clone()),
+                false,
+            ),
+            mk().block(vec![
+                mk().expr_stmt(mk().match_expr(mk().ident_expr("self"), tag_arms))
+            ]),
+        )];
+
+        // pub fn set_tag(&mut self, tag: Tag) {
+        //     if self.tag() != tag { *self = match tag { A => S::A(0), B => S::B, _ => return } }
+        // }
+        let changed = mk().binary_expr(
+            BinOpKind::Ne,
+            mk().method_call_expr(mk().ident_expr("self"), "tag", no_args()),
+            mk().ident_expr("tag"),
+        );
+        let reset = mk().assign_expr(
+            mk().unary_expr(ast::UnOp::Deref, mk().ident_expr("self")),
+            mk().match_expr(mk().ident_expr("tag"), set_tag_arms),
+        );
+        methods.push(mk().pub_().method_impl_item(
+            "set_tag",
+            mk().fn_decl(
+                vec![
+                    self_arg(Mutability::Mutable),
+                    mk().arg(tag_ty, mk().ident_pat("tag")),
+                ],
+                FunctionRetTy::Default(DUMMY_SP),
+                false,
+            ),
+            mk().block(vec![mk().expr_stmt(mk().ifte_expr(
+                changed,
+                mk().block(vec![mk().semi_stmt(reset)]),
+                None as Option<P<Expr>>,
+            ))]),
+        ));
+
+        // pub fn field(&self) -> T { match *self { S::A(x) | S::B(x) => x, _ => 0 } }
+        // pub fn field_mut(&mut self) -> &mut T {
+        //     loop { match *self { S::A(ref mut x) | S::B(ref mut x) => return x, _ => *self = S::A(0) } }
+        // }
+        for (&field, accessor) in &tagged.accessors {
+            let ty = field_ty(field)?;
+            let selecting: Vec<CEnumConstantId> = tagged
+                .variants
+                .iter()
+                .filter(|&&(_, selected)| selected == Some(field))
+                .map(|&(variant, _)| variant)
+                .collect();
+            let pats = |pat: &dyn Fn() -> P<Pat>| -> Vec<P<Pat>> {
+                selecting
+                    .iter()
+                    .map(|&variant| {
+                        let variant_name = self.enum_variant_name(variant);
+                        mk().tuple_struct_pat(
+                            mk().path(vec![name, variant_name.as_str()]),
+                            vec![pat()],
+                        )
+                    })
+                    .collect()
+            };
+            let self_val = || mk().unary_expr(ast::UnOp::Deref, mk().ident_expr("self"));
+            let zero = match self.ast_context[field].kind {
+                CDeclKind::Field { typ, .. } => self
+                    .implicit_default_expr(typ.ctype, false)?
+                    .to_pure_expr()
+                    .ok_or_else(|| format_err!("Expected a pure zero initializer"))?,
+                _ => return Err(TranslationError::generic("Expected a field")),
+            };
+            let first_variant = self.enum_variant_name(selecting[0]);
+            let activate = mk().call_expr(
+                mk().path_expr(vec![name, first_variant.as_str()]),
+                vec![zero.clone()],
+            );
+
+            // Inactive fields read as zero
+            let arms = vec![
+                mk().arm(pats(&|| mk().ident_pat("x")), None, mk().ident_expr("x")),
+                mk().arm(vec![mk().wild_pat()], None, zero),
+            ];
+            methods.push(mk().pub_().method_impl_item(
+                accessor,
+                mk().fn_decl(
+                    vec![self_arg(Mutability::Immutable)],
+                    FunctionRetTy::Ty(ty.clone()),
+                    false,
+                ),
+                mk().block(vec![mk().expr_stmt(mk().match_expr(self_val(), arms))]),
+            ));
+
+            // Writing an inactive field activates it, as with a C union
+            let arms = vec![
+                mk().arm(
+                    pats(&|| mk().set_mutbl(Mutability::Mutable).ident_ref_pat("x")),
+                    None,
+                    mk().return_expr(Some(mk().ident_expr("x"))),
+                ),
+                mk().arm(
+                    vec![mk().wild_pat()],
+                    None,
+                    mk().assign_expr(self_val(), activate),
+                ),
+            ];
+            methods.push(mk().pub_().method_impl_item(
+                format!("{}_mut", accessor),
+                mk().fn_decl(
+                    vec![self_arg(Mutability::Mutable)],
+                    FunctionRetTy::Ty(mk().set_mutbl(Mutability::Mutable).ref_ty(ty)),
+                    false,
+                ),
+                mk().block(vec![mk().expr_stmt(mk().loop_expr(
+                    mk().block(vec![mk().expr_stmt(mk().match_expr(self_val(), arms))]),
+                    None as Option<String>,
+                ))]),
+            ));
+        }
+
+        let enum_item = mk()
+            .span(span)
+            .pub_()
+            .call_attr("derive", vec!["Copy", "Clone"])
+            .call_attr("repr", vec!["C"])
+            .enum_item(name, variants);
+        let impl_item = mk().impl_item(self_ty, methods);
+
+        Ok(ConvertedDecl::Items(vec![enum_item, impl_item]))
+    }
+}
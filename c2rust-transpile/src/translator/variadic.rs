@@ -1,5 +1,5 @@
 use super::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Copy, Clone, Debug)]
 pub enum VaPart {
@@ -8,6 +8,53 @@ pub enum VaPart {
     Copy(CDeclId, CDeclId),
 }
 
+/// The variants of `VarArg`
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum VarArgKind {
+    Int,
+    Double,
+    Ptr,
+}
+
+impl VarArgKind {
+    fn variant(self) -> &'static str {
+        match self {
+            VarArgKind::Int => "Int",
+            VarArgKind::Double => "Double",
+            VarArgKind::Ptr => "Ptr",
+        }
+    }
+
+    /// Name of the `VarArg` method reading an argument of this kind
+    fn method(self) -> &'static str {
+        match self {
+            VarArgKind::Int => "int",
+            VarArgKind::Double => "double",
+            VarArgKind::Ptr => "ptr",
+        }
+    }
+
+    fn ty(self) -> P<Ty> {
+        match self {
+            VarArgKind::Int => mk().path_ty(vec!["i64"]),
+            VarArgKind::Double => mk().path_ty(vec!["f64"]),
+            VarArgKind::Ptr => mk().mutbl().ptr_ty(mk().path_ty(vec!["libc", "c_void"])),
+        }
+    }
+}
+
+/// The kind of `VarArg` that values of a type are passed as, if they can be passed as one at all.
+/// Arguments have gone through the default argument promotions, so there are no `bool`s or enums.
+fn var_arg_kind(ast_context: &TypedAstContext, ctype: CTypeId) -> Option<VarArgKind> {
+    match ast_context.resolve_type(ctype).kind {
+        CTypeKind::Bool | CTypeKind::Int128 | CTypeKind::UInt128 => None,
+        ref kind if kind.is_integral_type() => Some(VarArgKind::Int),
+        CTypeKind::Float | CTypeKind::Double => Some(VarArgKind::Double),
+        CTypeKind::Pointer(_) if !ast_context.is_function_pointer(ctype) => Some(VarArgKind::Ptr),
+        _ => None,
+    }
+}
+
 macro_rules! match_or {
     ([$e:expr] $p:pat => $r:tt) => {
         let $r = match $e {
@@ -130,6 +177,10 @@ impl<'c> Translation<'c> {
         ty: CQualTypeId,
         val_id: CExprId,
     ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        if self.stable_va_args().is_some() {
+            return self.convert_stable_vaarg(ctx, ty, val_id);
+        }
+
         if self.tcfg.translate_valist {
             // https://github.com/rust-lang/rust/pull/49878/files
 
@@ -277,4 +328,361 @@ impl<'c> Translation<'c> {
         }
         false
     }
+
+    /// Find the variadic function definitions that take their variadic arguments as a slice of
+    /// `VarArg`s, which unlike C variadics doesn't need nightly Rust. Only Rust code can call
+    /// these functions: they have internal linkage and are only ever called directly. Their
+    /// variadic arguments must be integers, floating point numbers or data pointers, and their
+    /// `va_list`s local variables only used with `va_start`, `va_arg`, `va_copy` and `va_end`.
+    pub fn find_stable_variadics(&self) -> IndexSet<CDeclId> {
+        let mut stable_variadics = IndexSet::new();
+        for (&decl_id, decl) in self.ast_context.iter_decls() {
+            if let CDeclKind::Function {
+                is_global: false,
+                typ,
+                ref parameters,
+                body: Some(body),
+                ..
+            } = decl.kind
+            {
+                let is_variadic = match self.ast_context.resolve_type(typ).kind {
+                    CTypeKind::Function(_, _, is_variadic, _, _) => is_variadic,
+                    _ => false,
+                };
+                // The `VarArg` enum is only emitted into the main module
+                let decl_file_path = decl.loc.as_ref().and_then(|loc| loc.file_path.as_ref());
+                let in_main_module = !self.tcfg.splits_headers()
+                    || self.module_file_path(decl_id, decl_file_path) == Some(&self.main_file);
+                let has_va_list_param =
+                    parameters
+                        .iter()
+                        .any(|&param| match self.ast_context[param].kind {
+                            CDeclKind::Variable { typ, .. } => {
+                                Self::is_inner_type_valist(&self.ast_context, typ)
+                            }
+                            _ => true,
+                        });
+                if is_variadic
+                    && in_main_module
+                    && !has_va_list_param
+                    && self.uses_va_lists_locally(body)
+                {
+                    stable_variadics.insert(decl_id);
+                }
+            }
+        }
+
+        let mut direct_callees = HashSet::new();
+        for (_, expr) in self.ast_context.iter_exprs() {
+            if let CExprKind::Call(_, func, ref args) = expr.kind {
+                if let Some((callee_ref, decl_id)) = self.direct_callee(func) {
+                    direct_callees.insert(callee_ref);
+                    if !self.passes_var_args(decl_id, args) {
+                        stable_variadics.remove(&decl_id);
+                    }
+                }
+            }
+        }
+
+        // C code may call the functions whose address is taken
+        for (expr_id, expr) in self.ast_context.iter_exprs() {
+            if let CExprKind::DeclRef(_, decl_id, _) = expr.kind {
+                if !direct_callees.contains(expr_id) {
+                    stable_variadics.remove(&decl_id);
+                }
+            }
+        }
+
+        stable_variadics
+    }
+
+    /// The `DeclRef` of the function called by a direct call, and the function
+    pub fn direct_callee(&self, func: CExprId) -> Option<(CExprId, CDeclId)> {
+        match_or! { [self.ast_context[func].kind]
+        CExprKind::ImplicitCast(_, fexp, CastKind::FunctionToPointerDecay, _, _) => fexp }
+        match_or! { [self.ast_context[fexp].kind]
+        CExprKind::DeclRef(_, decl_id, _) => decl_id }
+        Some((fexp, decl_id))
+    }
+
+    /// Can the variadic arguments of a call to this function be passed as `VarArg`s?
+    fn passes_var_args(&self, decl_id: CDeclId, args: &[CExprId]) -> bool {
+        let num_params = match self.ast_context[decl_id].kind {
+            CDeclKind::Function { ref parameters, .. } => parameters.len(),
+            _ => return false,
+        };
+        args.iter().skip(num_params).all(|&arg| {
+            self.ast_context[arg]
+                .kind
+                .get_type()
+                .and_then(|ty| var_arg_kind(&self.ast_context, ty))
+                .is_some()
+        })
+    }
+
+    /// Are the `va_list`s referenced in a function body local variables that are only used with
+    /// `va_start`, `va_copy`, `va_end` and `va_arg` of types passed as `VarArg`s?
+    fn uses_va_lists_locally(&self, body: CStmtId) -> bool {
+        // Every reference to a `va_list` has to be one of the uses we know
+        let mut refs = 0;
+        let mut uses = 0;
+
+        let mut iter = DFExpr::new(&self.ast_context, body.into());
+        while let Some(s) = iter.next() {
+            if let SomeId::Expr(e) = s {
+                if let Some(part) = self.match_vapart(e) {
+                    uses += match part {
+                        VaPart::Copy(..) => 2,
+                        VaPart::Start(_) | VaPart::End(_) => 1,
+                    };
+                }
+                match self.ast_context[e].kind {
+                    CExprKind::VAArg(ty, val) => {
+                        if var_arg_kind(&self.ast_context, ty.ctype).is_none()
+                            || self.match_vastart(val).is_none()
+                        {
+                            return false;
+                        }
+                        uses += 1;
+                    }
+                    CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                        CDeclKind::Variable {
+                            has_static_duration,
+                            has_thread_duration,
+                            typ,
+                            ..
+                        } if Self::is_inner_type_valist(&self.ast_context, typ) => {
+                            if has_static_duration || has_thread_duration {
+                                return false;
+                            }
+                            refs += 1;
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+            }
+        }
+        refs == uses
+    }
+
+    /// Does this function take its variadic arguments as a slice of `VarArg`s?
+    pub fn is_stable_variadic(&self, decl_id: CDeclId) -> bool {
+        self.stable_variadics.contains(&decl_id)
+    }
+
+    /// Name of the slice of `VarArg`s of the function being translated, if it takes one
+    pub fn stable_va_args(&self) -> Option<String> {
+        self.function_context.borrow().va_args.clone()
+    }
+
+    /// The `args: &[VarArg]` argument of a function taking its variadic arguments as a slice
+    pub fn stable_va_args_arg(&self) -> Arg {
+        let var_arg = self.var_arg_name();
+        let name = self.renamer.borrow_mut().pick_name("args");
+        self.function_context.borrow_mut().va_args = Some(name.clone());
+        let ty = mk().ref_ty(mk().slice_ty(mk().path_ty(vec![&var_arg])));
+        mk().arg(ty, mk().ident_pat(name))
+    }
+
+    /// Name of the enum that variadic arguments are passed as to functions with a stable
+    /// lowering of their variadic arguments, which is emitted on its first use
+    fn var_arg_name(&self) -> String {
+        let emitted = self.var_arg_name.borrow().clone();
+        if let Some(name) = emitted {
+            return name;
+        }
+        let name = self.type_converter.borrow_mut().pick_type_name("VarArg");
+        *self.var_arg_name.borrow_mut() = Some(name.clone());
+        self.emit_var_arg_items(&name);
+        name
+    }
+
+    /// Emit the `VarArg` enum, along with a method reading each kind of argument out of it that
+    /// panics on an argument of another kind. Integers and pointers can be read as each other.
+    fn emit_var_arg_items(&self, var_arg_name: &str) {
+        let kinds = [VarArgKind::Int, VarArgKind::Double, VarArgKind::Ptr];
+
+        let variants = kinds
+            .iter()
+            .map(|kind| {
+                let data = VariantData::Tuple(vec![mk().enum_field(kind.ty())], DUMMY_NODE_ID);
+                mk().variant(kind.variant(), data)
+            })
+            .collect();
+        let var_arg = mk()
+            .pub_()
+            .call_attr("derive", vec!["Copy", "Clone"])
+            .enum_item(var_arg_name, variants);
+
+        // pub fn int(self) -> i64 {
+        //     match self { VarArg::Int(x) => x, VarArg::Ptr(x) => x as i64, _ => panic!() }
+        // }
+        let methods = kinds
+            .iter()
+            .map(|&kind| {
+                let arm = |from: VarArgKind| {
+                    let path = mk().path(vec![var_arg_name, from.variant()]);
+                    let pat = mk().tuple_struct_pat(path, vec![mk().ident_pat("x")]);
+                    let val = if from == kind {
+                        mk().ident_expr("x")
+                    } else {
+                        mk().cast_expr(mk().ident_expr("x"), kind.ty())
+                    };
+                    mk().arm(vec![pat], None, val)
+                };
+                let (mut arms, msg) = match kind {
+                    VarArgKind::Int => (
+                        vec![arm(VarArgKind::Int), arm(VarArgKind::Ptr)],
+                        "va_arg of an integer type read a floating point argument",
+                    ),
+                    VarArgKind::Double => (
+                        vec![arm(VarArgKind::Double)],
+                        "va_arg of a floating point type read an integer or pointer argument",
+                    ),
+                    VarArgKind::Ptr => (
+                        vec![arm(VarArgKind::Ptr), arm(VarArgKind::Int)],
+                        "va_arg of a pointer type read a floating point argument",
+                    ),
+                };
+                arms.push(mk().arm(vec![mk().wild_pat()], None, self.panic(msg)));
+
+                mk().pub_().method_impl_item(
+                    kind.method(),
+                    mk().fn_decl(
+                        vec![mk().arg(mk().path_ty(vec!["Self"]), mk().ident_pat("self"))],
+                        FunctionRetTy::Ty(kind.ty()),
+                        false,
+                    ),
+                    mk().block(vec![
+                        mk().expr_stmt(mk().match_expr(mk().ident_expr("self"), arms))
+                    ]),
+                )
+            })
+            .collect();
+        let var_arg_impl = mk().impl_item(mk().path_ty(vec![var_arg_name]), methods);
+
+        let mut item_store = self.item_store.borrow_mut();
+        item_store.items.push(var_arg);
+        item_store.items.push(var_arg_impl);
+    }
+
+    /// The slice of `VarArg`s passed for the converted variadic arguments `args` of a call:
+    /// `&[VarArg::Int(x as i64), VarArg::Ptr(p as *mut libc::c_void)]`
+    pub fn convert_var_args(
+        &self,
+        c_args: &[CExprId],
+        args: Vec<P<Expr>>,
+    ) -> Result<P<Expr>, TranslationError> {
+        let var_arg_name = self.var_arg_name();
+        let var_args = c_args
+            .iter()
+            .zip(args)
+            .map(|(&c_arg, arg)| {
+                let kind = self.ast_context[c_arg]
+                    .kind
+                    .get_type()
+                    .and_then(|ty| var_arg_kind(&self.ast_context, ty))
+                    .ok_or_else(|| format_err!("Variadic argument can't be passed as a VarArg"))?;
+                let variant = mk().path_expr(vec![var_arg_name.as_str(), kind.variant()]);
+                Ok(mk().call_expr(variant, vec![mk().cast_expr(arg, kind.ty())]))
+            })
+            .collect::<Result<Vec<_>, TranslationError>>()?;
+        Ok(mk().addr_of_expr(mk().array_expr(var_args)))
+    }
+
+    fn va_list_expr(&self, va_id: CDeclId) -> P<Expr> {
+        let name = self
+            .renamer
+            .borrow()
+            .get(&va_id)
+            .expect("va_list should already be renamed");
+        mk().ident_expr(name)
+    }
+
+    /// `va_list`s of functions taking their variadic arguments as a slice of `VarArg`s are
+    /// iterators over it: `let mut ap = args.iter();`
+    pub fn convert_stable_va_list_decl(&self, name: &str) -> cfg::DeclStmtInfo {
+        let va_args = self
+            .stable_va_args()
+            .expect("Function should take a slice of VarArgs");
+        let iter = mk().method_call_expr(mk().ident_expr(va_args), "iter", vec![] as Vec<P<Expr>>);
+        let local = mk().local(
+            mk().set_mutbl(Mutability::Mutable).ident_pat(name),
+            None as Option<P<Ty>>,
+            Some(iter),
+        );
+        let stmt = mk().local_stmt(P(local));
+        cfg::DeclStmtInfo::new(vec![stmt.clone()], vec![], vec![stmt])
+    }
+
+    /// `va_arg(ap, T)` reads the next `VarArg`: `ap.next().expect(..).int() as T`
+    fn convert_stable_vaarg(
+        &self,
+        ctx: ExprContext,
+        ty: CQualTypeId,
+        val_id: CExprId,
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let kind = var_arg_kind(&self.ast_context, ty.ctype)
+            .ok_or_else(|| format_err!("va_arg of a type that isn't passed as a VarArg"))?;
+        let va_id = self
+            .match_vastart(val_id)
+            .ok_or_else(|| format_err!("Unsupported va_arg"))?;
+
+        let no_args = || vec![] as Vec<P<Expr>>;
+        let next = mk().method_call_expr(self.va_list_expr(va_id), "next", no_args());
+        let msg = mk().lit_expr(mk().str_lit("va_arg past the last variadic argument"));
+        let arg = mk().method_call_expr(next, "expect", vec![msg]);
+        let val = mk().method_call_expr(arg, kind.method(), no_args());
+        let val = mk().cast_expr(val, self.convert_type(ty.ctype)?);
+
+        if ctx.is_unused() {
+            Ok(WithStmts::new(
+                vec![mk().semi_stmt(val)],
+                self.panic_or_err("convert_vaarg unused"),
+            ))
+        } else {
+            Ok(WithStmts::new_val(val))
+        }
+    }
+
+    /// Convert the `va_start`, `va_copy` and `va_end` calls of a function taking its variadic
+    /// arguments as a slice of `VarArg`s. `va_start(ap, ..)` restarts the iteration with
+    /// `ap = args.iter()`, `va_copy(dst, src)` becomes `dst = src.clone()` and `va_end` does
+    /// nothing.
+    pub fn convert_stable_va_part(
+        &self,
+        ctx: ExprContext,
+        builtin_name: &str,
+        args: &[CExprId],
+    ) -> Result<WithStmts<P<Expr>>, TranslationError> {
+        let va_args = self
+            .stable_va_args()
+            .expect("Function should take a slice of VarArgs");
+        let no_args = || vec![] as Vec<P<Expr>>;
+
+        let assign = match builtin_name {
+            "__builtin_va_start" if args.len() == 2 => self.match_vastart(args[0]).map(|va_id| {
+                let iter = mk().method_call_expr(mk().ident_expr(va_args), "iter", no_args());
+                Some(mk().assign_expr(self.va_list_expr(va_id), iter))
+            }),
+            "__builtin_va_copy" if args.len() == 2 => {
+                self.match_vacopy(args[0], args[1]).map(|(dst_id, src_id)| {
+                    let src = self.va_list_expr(src_id);
+                    let copy = mk().method_call_expr(src, "clone", no_args());
+                    Some(mk().assign_expr(self.va_list_expr(dst_id), copy))
+                })
+            }
+            "__builtin_va_end" if args.len() == 1 => self.match_vaend(args[0]).map(|_| None),
+            _ => None,
+        };
+
+        match assign {
+            Some(assign) if ctx.is_unused() => {
+                let stmts = assign.into_iter().map(|e| mk().semi_stmt(e)).collect();
+                Ok(WithStmts::new(stmts, self.panic_or_err("va_list stub")))
+            }
+            _ => Err(format_err!("Unsupported {}", builtin_name).into()),
+        }
+    }
 }
@@ -40,6 +40,7 @@ fn main() {
         json_function_cfgs: matches.is_present("json-function-cfgs"),
         dump_cfg_liveness: matches.is_present("dump-cfgs-liveness"),
         dump_structures: matches.is_present("dump-structures"),
+        cfg_stats: matches.is_present("cfg-stats"),
         debug_ast_exporter: matches.is_present("debug-ast-exporter"),
         verbose: matches.is_present("verbose"),
 
@@ -76,15 +77,60 @@ fn main() {
         // stable rust output.
         translate_valist: true,
 
-        translate_const_macros: matches.is_present("translate-const-macros"),
+        translate_const_macros: matches.is_present("translate-const-macros")
+            || matches.is_present("bindings-for"),
         disable_refactoring: matches.is_present("disable-refactoring"),
+        translate_rust_enums: matches.is_present("rust-enums"),
+        translate_tagged_unions: matches.is_present("tagged-unions"),
+        rust_naming_conventions: matches.is_present("rust-naming-conventions"),
+        emit_source_map: matches.is_present("source-map"),
+        emit_ast_json: matches.is_present("emit-ast-json"),
+        checked: matches.is_present("checked"),
+        target: matches.value_of("target").map(String::from),
+        stack_vla_capacity: {
+            if matches.is_present("stack-vlas") {
+                let capacity = matches
+                    .value_of("stack-vla-capacity")
+                    .map(|n| match n.parse() {
+                        Ok(n) if c2rust_transpile::translator::is_small_vec_capacity(n) => n,
+                        _ => panic!(
+                            "Invalid --stack-vla-capacity: smallvec supports capacities of up \
+                             to 16, 20, 24, 32, 36 and powers of two up to 1048576"
+                        ),
+                    })
+                    .unwrap_or(256);
+                Some(capacity)
+            } else {
+                None
+            }
+        },
 
         use_c_loop_info: !matches.is_present("ignore-c-loop-info"),
         use_c_multiple_info: !matches.is_present("ignore-c-multiple-info"),
+        goto_elimination: {
+            if matches.is_present("goto-elimination") {
+                let functions = matches.value_of("goto-elimination-functions").unwrap_or("");
+                Some(Regex::new(functions).expect("Invalid --goto-elimination-functions"))
+            } else {
+                None
+            }
+        },
+        save_ast: matches.value_of("save-ast").map(PathBuf::from),
+        from_ast: matches.value_of("from-ast").map(PathBuf::from),
+        stable_variadics: matches.is_present("stable-variadics"),
+        translate_printf: matches.is_present("translate-printf"),
         simplify_structures: !matches.is_present("no-simplify-structures"),
         overwrite_existing: matches.is_present("overwrite-existing"),
         reduce_type_annotations: matches.is_present("reduce-type-annotations"),
         reorganize_definitions: matches.is_present("reorganize-definitions"),
+        header_modules: matches.is_present("header-modules"),
+        share_inline_functions: matches.is_present("share-inline-functions"),
+        link_functions: matches.is_present("link-functions"),
+        bindings_headers: matches
+            .values_of("bindings-for")
+            .map(|vals| vals.map(PathBuf::from).collect())
+            .unwrap_or_default(),
+        fixed_width_types: matches.is_present("fixed-width-types"),
         emit_modules: matches.is_present("emit-modules"),
         emit_build_files: matches.is_present("emit-build-files"),
         output_dir: matches.value_of("output-dir").map(PathBuf::from),
@@ -95,6 +141,17 @@ fn main() {
                 None
             }
         },
+        link_commands: matches.value_of("link-commands").map(PathBuf::from),
+        link_libs: matches
+            .values_of("link-lib")
+            .map(|vals| vals.map(String::from).collect())
+            .unwrap_or_default(),
+        link_search_paths: matches
+            .values_of("link-search-path")
+            .map(|vals| vals.map(PathBuf::from).collect())
+            .unwrap_or_default(),
+        workspace: matches.is_present("workspace"),
+        target_map: matches.value_of("target-map").map(PathBuf::from),
         panic_on_translator_failure: {
             match matches.value_of("invalid-code") {
                 Some("panic") => true,
@@ -107,7 +164,7 @@ fn main() {
         enabled_warnings,
     };
     // main implies emit-build-files
-    if tcfg.main != None {
+    if tcfg.main != None || tcfg.workspace {
         tcfg.emit_build_files = true
     };
     // emit-build-files implies emit-modules
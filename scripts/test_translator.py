@@ -6,6 +6,8 @@ import sys
 import logging
 import argparse
 import re
+import shutil
+import time
 
 from common import (
     config as c,
@@ -42,9 +44,14 @@ cargo = get_cmd_or_die("cargo")
 
 # Intermediate files
 intermediate_files = [
-    'cc_db', 'cbor', 'c_obj', 'c_lib', 'rust_src',
+    'cc_db', 'cbor', 'c_obj', 'c_lib', 'rust_src', 'build_files',
 ]
 
+# Where `//! ast_round_trip` saves the ASTs and translates them again, next
+# to the C files
+SAVED_AST_DIR = "saved_ast"
+RELOADED_DIR = "reloaded"
+
 
 class TestOutcome(Enum):
     Success = "successes"
@@ -67,10 +74,20 @@ class CFile:
             flags = set()
 
         self.path = path
+        self.flags = flags
+        self.group = next((flag[6:] for flag in flags
+                           if flag.startswith("group_")), None)
         self.disable_incremental_relooper = "disable_incremental_relooper" in flags
         self.disallow_current_block = "disallow_current_block" in flags
         self.translate_const_macros = "translate_const_macros" in flags
         self.reorganize_definitions = "reorganize_definitions" in flags
+        self.ast_round_trip = "ast_round_trip" in flags
+        self.run_crate = "run_crate" in flags
+        self.build_crate = "build_crate" in flags or self.run_crate
+        self.transpiler_args = sorted(flag[4:] for flag in flags
+                                      if flag.startswith("arg_"))
+        self.clang_args = sorted(flag[10:] for flag in flags
+                                 if flag.startswith("clang_arg_"))
 
     def translate(self, cc_db, extra_args: List[str] = []) -> RustFile:
         extensionless_file, _ = os.path.splitext(self.path)
@@ -83,24 +100,29 @@ class CFile:
         # run the transpiler
         transpiler = get_cmd_or_die(c.TRANSPILER)
 
-        args = [
-            cc_db,
+        options = [
             "--prefix-function-names",
             "rust_",
             "--overwrite-existing",
         ]
 
         if self.disable_incremental_relooper:
-            args.append("--no-incremental-relooper")
+            options.append("--no-incremental-relooper")
         if self.disallow_current_block:
-            args.append("--fail-on-multiple")
+            options.append("--fail-on-multiple")
         if self.translate_const_macros:
-            args.append("--translate-const-macros")
+            options.append("--translate-const-macros")
         if self.reorganize_definitions:
-            args.append("--reorganize-definitions")
+            options.append("--reorganize-definitions")
+        options.extend(self.transpiler_args)
+
+        args = [cc_db] + options
+        if self.ast_round_trip:
+            args.extend(["--save-ast", SAVED_AST_DIR])
 
         args.append("--")
         args.extend(extra_args)
+        args.extend(self.clang_args)
 
         # Add -isysroot on MacOS to get SDK directory
         if on_mac():
@@ -110,7 +132,10 @@ class CFile:
             except pb.CommandNotFound:
                 pass
 
-        with pb.local.env(RUST_BACKTRACE='1', LD_LIBRARY_PATH=ld_lib_path):
+        # Relative paths in the transpiler arguments are relative to the C file
+        directory = os.path.dirname(self.path)
+        with pb.local.cwd(directory), \
+                pb.local.env(RUST_BACKTRACE='1', LD_LIBRARY_PATH=ld_lib_path):
             # log the command in a format that's easy to re-run
             translation_cmd = "LD_LIBRARY_PATH=" + ld_lib_path + " \\\n"
             translation_cmd += str(transpiler[args])
@@ -124,8 +149,49 @@ class CFile:
         if retcode != 0:
             raise NonZeroReturn(stderr)
 
+        if self.ast_round_trip:
+            self._translate_saved_ast(transpiler, options, ld_lib_path)
+
         return RustFile(extensionless_file + ".rs")
 
+    def _translate_saved_ast(self, transpiler, options: List[str],
+                             ld_lib_path: str) -> None:
+        """
+        Translate the ASTs saved by the translation again with `--from-ast`,
+        and replace the translated files with the ones translated from the
+        saved ASTs.
+        """
+        directory = os.path.dirname(self.path)
+        saved_ast_dir = os.path.join(directory, SAVED_AST_DIR)
+        reloaded_dir = os.path.join(directory, RELOADED_DIR)
+        args = [os.path.join(saved_ast_dir, "compile_commands.json")] + options
+        args.extend(["--from-ast", saved_ast_dir, "--output-dir", reloaded_dir])
+
+        with pb.local.cwd(directory), \
+                pb.local.env(RUST_BACKTRACE='1', LD_LIBRARY_PATH=ld_lib_path):
+            logging.debug("saved AST translation command:\n %s",
+                          str(transpiler[args]))
+            retcode, stdout, stderr = (transpiler[args]).run(retcode=None)
+
+            logging.debug("stdout:\n%s", stdout)
+            logging.debug("stderr:\n%s", stderr)
+
+        if retcode != 0:
+            raise NonZeroReturn(stderr)
+
+        reloaded_src = os.path.join(reloaded_dir, "src")
+        rust_file_name = os.path.basename(
+            os.path.splitext(self.path)[0] + ".rs")
+        if not os.path.isfile(os.path.join(reloaded_src, rust_file_name)):
+            raise NonZeroReturn(
+                "{} was not translated from its saved AST".format(rust_file_name))
+
+        for entry in os.listdir(reloaded_src):
+            os.replace(os.path.join(reloaded_src, entry),
+                       os.path.join(directory, entry))
+        shutil.rmtree(saved_ast_dir)
+        shutil.rmtree(reloaded_dir)
+
 
 def build_static_library(c_files: Iterable[CFile],
                          output_path: str) -> Optional[CStaticLibrary]:
@@ -136,6 +202,7 @@ def build_static_library(c_files: Iterable[CFile],
     # create .o files
     args = ["-c", "-fPIC", "-march=native"]
 
+    args.extend(sorted({arg for c_file in c_files for arg in c_file.clang_args}))
     args.extend(c_file.path for c_file in c_files)
 
     if len(args) == 2:
@@ -208,6 +275,7 @@ class TestDirectory:
             "c_obj": [],
             "c_lib": [],
             "cc_db": [],
+            "build_files": [],
         }
 
         for entry in os.listdir(self.full_path_src):
@@ -217,7 +285,7 @@ class TestDirectory:
                 _, ext = os.path.splitext(path)
                 filename = os.path.splitext(os.path.basename(path))[0]
 
-                if ext == ".c":
+                if ext in (".c", ".cpp"):
                     c_file = self._read_c_file(path)
 
                     if c_file:
@@ -279,18 +347,20 @@ class TestDirectory:
         if message:
             sys.stdout.write(message)
 
-    def _generate_cc_db(self, c_file_path: str) -> None:
-        directory, cfile = os.path.split(c_file_path)
+    def _generate_cc_db(self, c_file_paths: List[str]) -> None:
+        directory, _ = os.path.split(c_file_paths[0])
 
-        compile_commands = """ \
-        [
+        entries = []
+        for c_file_path in c_file_paths:
+            _, cfile = os.path.split(c_file_path)
+            entries.append("""
           {{
             "arguments": [ "cc", "-D_FORTIFY_SOURCE=0", "-c", "{0}" ],
             "directory": "{1}",
             "file": "{0}"
-          }}
-        ]
-        """.format(cfile, directory)
+          }}""".format(cfile, directory))
+
+        compile_commands = "[{}\n]\n".format(",".join(entries))
 
         cc_db = os.path.join(directory, "compile_commands.json")
 
@@ -302,6 +372,63 @@ class TestDirectory:
         with open(cc_db, 'w') as fh:
             fh.write(compile_commands)
 
+    def _translation_groups(self) -> List[List[CFile]]:
+        """
+        The C files to translate together. Files with the same `group_X` flag
+        are translated by one transpiler run, the others on their own.
+        """
+        groups = []
+        named_groups = {}
+
+        for c_file in self.c_files:
+            if c_file.group is None:
+                groups.append([c_file])
+            elif c_file.group in named_groups:
+                named_groups[c_file.group].append(c_file)
+            else:
+                named_groups[c_file.group] = [c_file]
+                groups.append(named_groups[c_file.group])
+
+        return groups
+
+    def _shared_modules(self, translated_rust_files: List[RustFile],
+                        since: float) -> List[RustFile]:
+        """
+        The Rust files written by a transpiler run besides the translations
+        of its C files.
+        """
+        translated_paths = {rust_file.path for rust_file in translated_rust_files}
+        shared_modules = []
+
+        for entry in sorted(os.listdir(self.full_path_src)):
+            path = os.path.join(self.full_path_src, entry)
+            _, ext = os.path.splitext(entry)
+
+            if (ext == ".rs" and not entry.startswith("test_") and
+                    entry != "main.rs" and path not in translated_paths and
+                    os.path.getmtime(path) >= since):
+                shared_modules.append(RustFile(path))
+
+        return shared_modules
+
+    def _build_crate(self, c_file: CFile) -> None:
+        """
+        Build the crate or workspace the transpiler emitted next to the C
+        files, and run it with `//! run_crate`. The `pkg-config` packages
+        the emitted `build.rs` probes for are looked up next to the C files.
+        """
+        args = ["run"] if c_file.run_crate else ["build"]
+
+        with pb.local.cwd(self.full_path_src), \
+                pb.local.env(PKG_CONFIG_PATH=self.full_path_src):
+            logging.debug("crate build command:\n %s", str(cargo[args]))
+            retcode, stdout, stderr = cargo[args].run(retcode=None)
+
+        logging.debug("stdout:\n%s", stdout)
+
+        if retcode != 0:
+            raise NonZeroReturn(stderr)
+
     def run(self) -> List[TestOutcome]:
         outcomes = []
 
@@ -347,19 +474,27 @@ class TestDirectory:
         rust_file_builder.add_features(["libc", "extern_types", "simd_ffi", "stdsimd", "const_transmute", "nll", "custom_attribute"])
 
         # .c -> .rs
-        for c_file in self.c_files:
-            _, c_file_short = os.path.split(c_file.path)
+        for c_files in self._translation_groups():
+            c_file_short = ", ".join(os.path.basename(c_file.path)
+                                     for c_file in c_files)
             description = "{}: translating the C file into Rust...".format(
                 c_file_short)
 
             # Run the step
             self.print_status(Colors.WARNING, "RUNNING", description)
 
-            self._generate_cc_db(c_file.path)
+            self._generate_cc_db([c_file.path for c_file in c_files])
+
+            # Grouped files are translated by a single transpiler run, with
+            # the flags of all of them
+            flags = {flag for c_file in c_files for flag in c_file.flags}
+            group_file = CFile(c_files[0].path, flags)
+            translation_start = time.time()
+            entries_before = set(os.listdir(self.full_path_src))
 
             try:
-                translated_rust_file = c_file.translate(self.generated_files["cc_db"],
-                                                        extra_args=["-march=native"])
+                group_file.translate(
+                    self.generated_files["cc_db"], extra_args=["-march=native"])
             except NonZeroReturn as exception:
                 self.print_status(Colors.FAIL, "FAILED", "translate " +
                                   c_file_short)
@@ -369,13 +504,54 @@ class TestDirectory:
                 outcomes.append(TestOutcome.UnexpectedFailure)
                 continue
 
-            self.generated_files["rust_src"].append(translated_rust_file)
+            translated_rust_files = [
+                RustFile(os.path.splitext(c_file.path)[0] + ".rs")
+                for c_file in c_files
+            ]
+            # Runs with several translation units can also write modules
+            # shared by them, e.g. with `--header-modules`
+            if len(c_files) > 1:
+                translated_rust_files.extend(
+                    self._shared_modules(translated_rust_files, translation_start))
 
-            _, rust_file_short = os.path.split(translated_rust_file.path)
-            extensionless_rust_file, _ = os.path.splitext(rust_file_short)
+            for translated_rust_file in translated_rust_files:
+                self.generated_files["rust_src"].append(translated_rust_file)
 
-            rust_file_builder.add_mod(RustMod(extensionless_rust_file,
-                                              RustVisibility.Public))
+                _, rust_file_short = os.path.split(translated_rust_file.path)
+                extensionless_rust_file, _ = os.path.splitext(rust_file_short)
+
+                rust_file_builder.add_mod(RustMod(extensionless_rust_file,
+                                                  RustVisibility.Public))
+
+            if group_file.build_crate:
+                description = "{}: building the emitted crate...".format(
+                    c_file_short)
+                self.print_status(Colors.WARNING, "RUNNING", description)
+
+                try:
+                    self._build_crate(group_file)
+
+                    self.print_status(Colors.OKGREEN, "OK",
+                                      "    build crate of " + c_file_short)
+                    sys.stdout.write('\n')
+
+                    outcomes.append(TestOutcome.Success)
+                except NonZeroReturn as exception:
+                    self.print_status(Colors.FAIL, "FAILED",
+                                      "build crate of " + c_file_short)
+                    sys.stdout.write('\n')
+                    sys.stdout.write(str(exception))
+
+                    outcomes.append(TestOutcome.UnexpectedFailure)
+
+                # Everything written besides the translated modules, e.g.
+                # `Cargo.toml`, `build.rs` and the `target` directory
+                translated_names = {os.path.basename(rust_file.path)
+                                    for rust_file in translated_rust_files}
+                for entry in sorted(set(os.listdir(self.full_path_src)) -
+                                    entries_before - translated_names):
+                    self.generated_files["build_files"].append(
+                        os.path.join(self.full_path_src, entry))
 
         match_arms = []
         rustc_extra_args = ["-C", "target-cpu=native"]
@@ -516,10 +692,14 @@ class TestDirectory:
 
             # Try remove files and don't barf if they don't exist
             for file_path in file_paths:
+                # FIXME: Hacky. Some items are string paths,
+                # others are classes with a path attribute
+                file_path = getattr(file_path, "path", file_path)
                 try:
-                    # FIXME: Hacky. Some items are string paths,
-                    # others are classes with a path attribute
-                    os.remove(getattr(file_path, "path", file_path))
+                    if os.path.isdir(file_path):
+                        shutil.rmtree(file_path)
+                    else:
+                        os.remove(file_path)
                 except OSError:
                     pass
 
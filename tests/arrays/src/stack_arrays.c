@@ -0,0 +1,41 @@
+//! arg_--stack-vlas, arg_--stack-vla-capacity=64
+
+#include <alloca.h>
+
+// The bounds of these arrays aren't capacities smallvec supports, so they are rounded up to one
+static int sum_bounded(unsigned n) {
+  int small[n % 18];
+  int medium[n & 40];
+  int sum = 0;
+
+  for (unsigned i = 0; i < n % 18; i++) {
+    small[i] = i;
+    sum += small[i];
+  }
+  for (unsigned i = 0; i < (n & 40); i++) {
+    medium[i] = 2 * i;
+    sum += medium[i];
+  }
+  return sum;
+}
+
+// Bounded by the range of `unsigned char`, which is above the capacity
+static int sum_bytes(unsigned char n) {
+  char *bytes = alloca(n);
+  int sum = 0;
+
+  for (int i = 0; i < n; i++) {
+    bytes[i] = i % 7;
+    sum += bytes[i];
+  }
+  return sum;
+}
+
+void stack_arrays(const unsigned buffer_size, int buffer[]) {
+  if (buffer_size < 4) { return; }
+
+  buffer[0] = sum_bounded(17);
+  buffer[1] = sum_bounded(40);
+  buffer[2] = sum_bytes(100);
+  buffer[3] = sum_bytes(255);
+}
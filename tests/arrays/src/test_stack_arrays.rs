@@ -0,0 +1,29 @@
+//! extern_crate_smallvec
+
+extern crate libc;
+
+use stack_arrays::rust_stack_arrays;
+
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn stack_arrays(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 4;
+
+pub fn test_stack_arrays() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [136, 1566, 295, 759];
+
+    unsafe {
+        stack_arrays(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_stack_arrays(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
@@ -0,0 +1,12 @@
+use std::env;
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+
+    println!("cargo:rustc-link-search=native={}", manifest_dir);
+
+    // The blocks runtime is part of libSystem on Apple platforms
+    if env::var("CARGO_CFG_TARGET_OS").unwrap() != "macos" {
+        println!("cargo:rustc-link-lib=BlocksRuntime");
+    }
+}
@@ -0,0 +1,41 @@
+//! clang_arg_-fblocks
+
+typedef int (^int_block)(int);
+
+static int_block saved;
+
+int apply(int_block block, int x) {
+  return block(x);
+}
+
+// Keeps the block beyond the call. Blocks without captures are global in C, but the Rust
+// translation has to keep a reference to the heap block it receives.
+void save_block(int_block block) {
+  saved = block;
+}
+
+int call_saved(int x) {
+  return saved(x);
+}
+
+void blocks(const unsigned buffer_size, int buffer[]) {
+  if (buffer_size < 6) { return; }
+
+  __block int counter = 0;
+  int offset = 10;
+  int_block add = ^(int x) {
+    counter++;
+    return x + offset;
+  };
+
+  buffer[0] = apply(add, 1);
+  buffer[1] = apply(add, 2);
+  buffer[2] = counter;
+
+  save_block(^(int x) { return x * 3; });
+  buffer[3] = call_saved(4);
+  buffer[4] = call_saved(5);
+
+  save_block(0);
+  buffer[5] = saved == 0;
+}
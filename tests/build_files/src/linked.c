@@ -0,0 +1,9 @@
+//! arg_--emit-build-files, arg_--main=linked, arg_--link-commands=link_commands.json, run_crate
+
+// `native_answer` comes from the library that the emitted build.rs links in, which is found
+// through the search path of the link command
+int native_answer(void);
+
+int main(void) {
+  return native_answer() == 42 ? 0 : 1;
+}
@@ -0,0 +1,30 @@
+extern crate libc;
+
+use native::rust_native_answer;
+
+use self::libc::c_int;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn native_answer() -> c_int;
+}
+
+const BUILD_RS: &str = include_str!("build.rs");
+
+pub fn test_build_rs() {
+    assert!(BUILD_RS.contains("pkg_config::probe_library(\"answer\")"));
+    assert!(BUILD_RS.contains("cargo:rustc-link-lib=test"));
+
+    // The relative search path of the link command is resolved, since cargo runs build.rs in
+    // the directory of the emitted crate
+    let search_path = BUILD_RS
+        .lines()
+        .find(|line| line.contains("cargo:rustc-link-search=native="))
+        .expect("no search path");
+    assert!(search_path.contains("=native=/"));
+
+    unsafe {
+        assert_eq!(rust_native_answer(), native_answer());
+    }
+}
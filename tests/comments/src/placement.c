@@ -0,0 +1,20 @@
+int sum_with_comments(int a, int b) {
+  int total = a + /* the second operand */ b;
+  return total;
+}
+
+int early_exit(int x) {
+  if (x > 10) {
+    x -= 10;
+    return x;
+    /* after an early return */
+  }
+  while (x < 5) {
+    x += 2;
+    if (x == 4) {
+      break;
+      /* after a break */
+    }
+  }
+  return x;
+}
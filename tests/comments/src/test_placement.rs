@@ -0,0 +1,43 @@
+extern crate libc;
+
+use self::libc::c_int;
+use placement::{rust_early_exit, rust_sum_with_comments};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn sum_with_comments(_: c_int, _: c_int) -> c_int;
+    #[no_mangle]
+    fn early_exit(_: c_int) -> c_int;
+}
+
+const TRANSLATION: &str = include_str!("placement.rs");
+
+fn position(text: &str) -> usize {
+    TRANSLATION
+        .find(text)
+        .unwrap_or_else(|| panic!("`{}` is missing from the translation", text))
+}
+
+pub fn test_expression_comments() {
+    unsafe {
+        assert_eq!(rust_sum_with_comments(1, 2), sum_with_comments(1, 2));
+    }
+
+    // The comment stays in front of the operand instead of moving in front of the statement
+    let comment = position("/* the second operand */");
+    assert!(position("let mut total") < comment);
+    assert!(comment < position("return total"));
+}
+
+pub fn test_block_end_comments() {
+    for x in 0..15 {
+        unsafe {
+            assert_eq!(rust_early_exit(x), early_exit(x));
+        }
+    }
+
+    // Comments after statements that don't fall through are kept
+    assert!(position("x -= 10") < position("/* after an early return */"));
+    assert!(position("x += 2") < position("/* after a break */"));
+}
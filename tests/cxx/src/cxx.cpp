@@ -0,0 +1,65 @@
+namespace ns1 {
+int value(int x) { return x + 1; }
+} // namespace ns1
+
+namespace ns2 {
+int value(int x) { return x * 2; }
+
+namespace nested {
+int value(int x) { return ns1::value(x) + ns2::value(x); }
+} // namespace nested
+} // namespace ns2
+
+// Overloads of the same name
+int twice(int x) { return x * 2; }
+long twice(long x) { return x * 2 + 1; }
+
+static void increment(int &x) { x += 1; }
+
+static int &larger(int &a, int &b) { return a > b ? a : b; }
+
+struct point {
+  int x;
+  int y;
+};
+
+static point moved(point p, int dx) {
+  point q = p;
+  q.x += dx;
+  return q;
+}
+
+extern "C" {
+
+int namespaces(int x) {
+  return ns1::value(x) * 10000 + ns2::value(x) * 100 + ns2::nested::value(x);
+}
+
+long overloads(int x) { return twice(x) * 100 + twice((long)x); }
+
+// References as parameters, locals and return values
+int references(int a, int b) {
+  increment(a);
+  int &r = larger(a, b);
+  r = 100;
+  int &alias = b;
+  alias += 1;
+  return a * 1000 + b;
+}
+
+int null_pointers(int *p) {
+  int *q = nullptr;
+  if (p == nullptr)
+    return 0;
+  return q == nullptr ? *p : -1;
+}
+
+// Trivial copy constructions and assignments of structs
+int struct_copies(int dx) {
+  point p = {1, 2};
+  point q = {0, 0};
+  q = moved(p, dx);
+  return p.x * 100 + q.x * 10 + q.y;
+}
+
+}
@@ -0,0 +1,60 @@
+extern crate libc;
+
+use cxx::{rust_namespaces, rust_null_pointers, rust_overloads, rust_references,
+          rust_struct_copies};
+
+use self::libc::{c_int, c_long};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn namespaces(_: c_int) -> c_int;
+    #[no_mangle]
+    fn overloads(_: c_int) -> c_long;
+    #[no_mangle]
+    fn references(_: c_int, _: c_int) -> c_int;
+    #[no_mangle]
+    fn null_pointers(_: *mut c_int) -> c_int;
+    #[no_mangle]
+    fn struct_copies(_: c_int) -> c_int;
+}
+
+pub fn test_namespaces() {
+    unsafe {
+        assert_eq!(namespaces(3), rust_namespaces(3));
+        assert_eq!(rust_namespaces(3), 40610);
+    }
+}
+
+pub fn test_overloads() {
+    unsafe {
+        assert_eq!(overloads(3), rust_overloads(3));
+        assert_eq!(rust_overloads(3), 607);
+    }
+}
+
+pub fn test_references() {
+    unsafe {
+        assert_eq!(references(1, 5), rust_references(1, 5));
+        assert_eq!(references(7, 5), rust_references(7, 5));
+        assert_eq!(rust_references(1, 5), 2101);
+        assert_eq!(rust_references(7, 5), 100006);
+    }
+}
+
+pub fn test_null_pointers() {
+    let mut x = 42;
+
+    unsafe {
+        assert_eq!(null_pointers(0 as *mut c_int), rust_null_pointers(0 as *mut c_int));
+        assert_eq!(null_pointers(&mut x), rust_null_pointers(&mut x));
+        assert_eq!(rust_null_pointers(&mut x), 42);
+    }
+}
+
+pub fn test_struct_copies() {
+    unsafe {
+        assert_eq!(struct_copies(4), rust_struct_copies(4));
+        assert_eq!(rust_struct_copies(4), 152);
+    }
+}
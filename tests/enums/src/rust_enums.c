@@ -0,0 +1,72 @@
+//! arg_--rust-enums
+
+enum shape { CIRCLE, SQUARE, TRIANGLE };
+
+// Only translated code calls these, so `shape` becomes a Rust enum that switches match on
+static int sides(enum shape s) {
+  switch (s) {
+  case CIRCLE:
+    return 0;
+  case SQUARE:
+    return 4;
+  default:
+    return 3;
+  }
+}
+
+static enum shape next(enum shape s) {
+  switch (s) {
+  case CIRCLE:
+    return SQUARE;
+  case SQUARE:
+    return TRIANGLE;
+  case TRIANGLE:
+    break;
+  }
+  return CIRCLE;
+}
+
+// C code can pass any integer as a `visible`, so it stays an integer
+enum visible { HIDDEN, SHOWN };
+
+int is_shown(enum visible v) {
+  return v == SHOWN;
+}
+
+// C code can call `brightness` with any integer, so it takes its `color` as an integer that it
+// converts, just like the bitfield holding a `color`
+enum color { RED, GREEN, BLUE };
+
+struct light {
+  enum color color : 2;
+  unsigned on : 1;
+};
+
+int brightness(enum color c) {
+  switch (c) {
+  case RED:
+    return 1;
+  case GREEN:
+    return 2;
+  case BLUE:
+    return 3;
+  }
+  return 0;
+}
+
+void rust_enums(const unsigned buffer_size, int buffer[]) {
+  if (buffer_size < 8) { return; }
+
+  enum shape s = CIRCLE;
+  for (int i = 0; i < 4; i++) {
+    buffer[i] = sides(s);
+    s = next(s);
+  }
+  buffer[4] = is_shown(SHOWN);
+  buffer[5] = is_shown(7);
+
+  struct light l = { GREEN, 1 };
+  buffer[6] = brightness(l.color);
+  l.color = BLUE;
+  buffer[7] = brightness(l.color);
+}
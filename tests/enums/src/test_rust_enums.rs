@@ -0,0 +1,44 @@
+extern crate libc;
+
+use rust_enums::{color, rust_brightness, rust_rust_enums, shape, visible};
+
+use self::libc::{c_int, c_uint};
+use std::panic;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn rust_enums(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 8;
+
+pub fn test_rust_enum_types() {
+    // `shape` is a Rust enum, `visible` keeps the integer translation
+    assert_eq!(shape::TRIANGLE as u32, 2);
+    let invalid: visible = 7;
+    assert_eq!(invalid, 7);
+}
+
+pub fn test_rust_enum_boundary() {
+    // `color` is a Rust enum that crosses the boundary as an integer, which is checked
+    unsafe {
+        assert_eq!(rust_brightness(color::BLUE as c_uint), 3);
+    }
+    let invalid = panic::catch_unwind(|| unsafe { rust_brightness(7) });
+    assert!(invalid.is_err());
+}
+
+pub fn test_rust_enums() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [0, 4, 3, 0, 1, 0, 2, 3];
+
+    unsafe {
+        rust_enums(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_rust_enums(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
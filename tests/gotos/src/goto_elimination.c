@@ -0,0 +1,77 @@
+//! disallow_current_block, arg_--goto-elimination
+
+// Cleanup chains become labelled blocks
+int cleanup_chain(int fail_at) {
+  int acquired = 0;
+  if (fail_at == 0)
+    goto out;
+  acquired |= 1;
+  if (fail_at == 1)
+    goto release_a;
+  acquired |= 2;
+  if (fail_at == 2)
+    goto release_b;
+  acquired |= 4;
+  return acquired;
+
+release_b:
+  acquired |= 16;
+release_a:
+  acquired |= 32;
+out:
+  return acquired;
+}
+
+// Backward gotos become loops, and gotos leaving them `break`s
+int goto_loop(int n) {
+  int sum = 0;
+  int i = 0;
+top:
+  if (i >= n)
+    goto done;
+  sum += i;
+  i++;
+  if (sum > 50)
+    goto done;
+  goto top;
+done:
+  return sum;
+}
+
+// Loops with several exits leave through labelled blocks
+int multi_exit(int n) {
+  int i;
+  for (i = 0; i < n; i++) {
+    if (i * i > 40)
+      goto big;
+    if (n % 3 == 0 && i == n - 2)
+      goto seven;
+  }
+  return i;
+big:
+  return 100 + i;
+seven:
+  return 200 + i;
+}
+
+// Irreducible control flow is made reducible by splitting nodes
+int split_irreducible(int x) {
+  if (x % 2)
+    goto l2;
+l1:
+  if (x < 6) {
+    x += 1;
+    goto l3;
+  }
+l2:
+  if (x < 9) {
+    x += 2;
+    goto l1;
+  }
+l3:
+  if (x < 20) {
+    x += 90;
+    goto l2;
+  }
+  return x;
+}
@@ -0,0 +1,43 @@
+extern crate libc;
+
+use self::libc::c_int;
+use goto_elimination::{
+    rust_cleanup_chain, rust_goto_loop, rust_multi_exit, rust_split_irreducible,
+};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn cleanup_chain(_: c_int) -> c_int;
+    #[no_mangle]
+    fn goto_loop(_: c_int) -> c_int;
+    #[no_mangle]
+    fn multi_exit(_: c_int) -> c_int;
+    #[no_mangle]
+    fn split_irreducible(_: c_int) -> c_int;
+}
+
+pub fn test_cleanup_chain() {
+    for fail_at in 0..4 {
+        unsafe {
+            assert_eq!(rust_cleanup_chain(fail_at), cleanup_chain(fail_at));
+        }
+    }
+}
+
+pub fn test_loops() {
+    for n in 0..20 {
+        unsafe {
+            assert_eq!(rust_goto_loop(n), goto_loop(n));
+            assert_eq!(rust_multi_exit(n), multi_exit(n));
+        }
+    }
+}
+
+pub fn test_split_irreducible() {
+    for x in 0..25 {
+        unsafe {
+            assert_eq!(rust_split_irreducible(x), split_irreducible(x));
+        }
+    }
+}
@@ -0,0 +1,23 @@
+//! arg_--checked
+
+int checked_negate(int x) { return -x; }
+
+int checked_post_increment(int x) {
+  int old = x++;
+  return old == x - 1;
+}
+
+int checked_pre_decrement(int x) { return --x; }
+
+int checked_shift_left(int x, int n) { return x << n; }
+
+int checked_index(int i) {
+  int values[4] = {1, 2, 3, 4};
+  return values[i];
+}
+
+int checked_index_address(int i) {
+  int values[4] = {1, 2, 3, 4};
+  int *p = &values[i];
+  return p == values + 4 ? 0 : *p;
+}
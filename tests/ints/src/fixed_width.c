@@ -0,0 +1,36 @@
+//! arg_--fixed-width-types
+
+#include <stdlib.h>
+#include <string.h>
+
+size_t fixed_width_sum(const int *values, size_t len) {
+  size_t total = 0;
+  for (size_t i = 0; i < len; i++) {
+    total += values[i];
+  }
+  return total;
+}
+
+int fixed_width_alloc(size_t n) {
+  int *buf = malloc(n * sizeof(int));
+  if (buf == NULL) {
+    return -1;
+  }
+  memset(buf, 0, n * sizeof *buf);
+  for (size_t i = 0; i < n; i++) {
+    buf[i] = (int) i;
+  }
+  int total = (int) fixed_width_sum(buf, n);
+  free(buf);
+  return total + (int) strlen("abc");
+}
+
+unsigned fixed_width_sizes(void) {
+  size_t size = sizeof(double);
+  unsigned long length = sizeof(short[3]);
+  return (unsigned) (size + length + (size < length) + !length);
+}
+
+long fixed_width_long(long x, unsigned long y) {
+  return x + (long) (y / sizeof(long));
+}
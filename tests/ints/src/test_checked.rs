@@ -0,0 +1,119 @@
+extern crate libc;
+
+use self::libc::c_int;
+use checked::{
+    rust_checked_index, rust_checked_index_address, rust_checked_negate,
+    rust_checked_post_increment, rust_checked_pre_decrement, rust_checked_shift_left,
+};
+use std::panic;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn checked_negate(_: c_int) -> c_int;
+    #[no_mangle]
+    fn checked_post_increment(_: c_int) -> c_int;
+    #[no_mangle]
+    fn checked_pre_decrement(_: c_int) -> c_int;
+    #[no_mangle]
+    fn checked_shift_left(_: c_int, _: c_int) -> c_int;
+    #[no_mangle]
+    fn checked_index(_: c_int) -> c_int;
+    #[no_mangle]
+    fn checked_index_address(_: c_int) -> c_int;
+}
+
+fn panics<F: FnOnce() -> c_int + panic::UnwindSafe>(f: F) -> bool {
+    panic::catch_unwind(f).is_err()
+}
+
+fn panic_message<F: FnOnce() -> c_int + panic::UnwindSafe>(f: F) -> String {
+    let payload = panic::catch_unwind(f).expect_err("no panic");
+    match payload.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(payload) => payload
+            .downcast::<&str>()
+            .map(|msg| msg.to_string())
+            .unwrap_or_default(),
+    }
+}
+
+pub fn test_negation() {
+    for &x in &[0, 1, -7, c_int::max_value()] {
+        unsafe {
+            assert_eq!(rust_checked_negate(x), checked_negate(x));
+        }
+    }
+    assert!(panics(|| unsafe {
+        rust_checked_negate(c_int::min_value())
+    }));
+}
+
+pub fn test_increments() {
+    for &x in &[0, -1, 41, c_int::min_value()] {
+        unsafe {
+            assert_eq!(rust_checked_post_increment(x), checked_post_increment(x));
+        }
+    }
+    for &x in &[0, 1, c_int::max_value()] {
+        unsafe {
+            assert_eq!(rust_checked_pre_decrement(x), checked_pre_decrement(x));
+        }
+    }
+    assert!(panics(|| unsafe {
+        rust_checked_post_increment(c_int::max_value())
+    }));
+    assert!(panics(|| unsafe {
+        rust_checked_pre_decrement(c_int::min_value())
+    }));
+}
+
+pub fn test_shifts() {
+    for &(x, n) in &[(0, 31), (1, 30), (3, 4), (0x3fff_ffff, 1)] {
+        unsafe {
+            assert_eq!(rust_checked_shift_left(x, n), checked_shift_left(x, n));
+        }
+    }
+
+    // Out of range amounts, negative values and shifting into or past the sign bit
+    for &(x, n) in &[
+        (1, 32),
+        (1, -1),
+        (-1, 1),
+        (1, 31),
+        (0x4000_0000, 1),
+        (3, 30),
+    ] {
+        assert!(
+            panics(|| unsafe { rust_checked_shift_left(x, n) }),
+            "{} << {}",
+            x,
+            n
+        );
+    }
+}
+
+pub fn test_indexing() {
+    for i in 0..4 {
+        unsafe {
+            assert_eq!(rust_checked_index(i), checked_index(i));
+        }
+    }
+    assert!(panics(|| unsafe { rust_checked_index(4) }));
+    assert!(panics(|| unsafe { rust_checked_index(-1) }));
+
+    // The check itself names the C location, rather than relying on Rust's indexing
+    let msg = panic_message(|| unsafe { rust_checked_index(4) });
+    assert!(msg.contains("checked.c"), "{}", msg);
+    assert!(msg.contains("array index out of bounds"), "{}", msg);
+}
+
+pub fn test_index_address() {
+    for i in 0..5 {
+        unsafe {
+            assert_eq!(rust_checked_index_address(i), checked_index_address(i));
+        }
+    }
+    assert!(panics(|| unsafe { rust_checked_index_address(5) }));
+    assert!(panics(|| unsafe { rust_checked_index_address(-1) }));
+}
@@ -0,0 +1,50 @@
+extern crate libc;
+
+use fixed_width::{
+    rust_fixed_width_alloc, rust_fixed_width_long, rust_fixed_width_sizes, rust_fixed_width_sum,
+};
+use self::libc::{c_int, c_long, c_uint, c_ulong};
+use std::mem;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn fixed_width_alloc(_: usize) -> c_int;
+
+    #[no_mangle]
+    fn fixed_width_sizes() -> c_uint;
+}
+
+const TRANSLATION: &str = include_str!("fixed_width.rs");
+
+pub fn test_size_t_is_usize() {
+    // Only the typedefs of pointer-sized integers become `usize`
+    let _: unsafe extern "C" fn(*const i32, usize) -> usize = rust_fixed_width_sum;
+    let _: unsafe extern "C" fn(c_long, c_ulong) -> c_long = rust_fixed_width_long;
+    // Declarations of the functions defined in C keep the `libc` aliases
+    assert!(TRANSLATION.contains("libc::size_t"));
+    assert!(TRANSLATION.contains("libc::c_void"));
+    assert!(!TRANSLATION.contains("libc::c_ulong"));
+}
+
+pub fn test_long() {
+    let x: c_long = -5;
+    let y = 4 * mem::size_of::<c_long>() as c_ulong;
+    unsafe {
+        assert_eq!(rust_fixed_width_long(x, y), x + 4);
+    }
+}
+
+pub fn test_libc_calls() {
+    unsafe {
+        assert_eq!(rust_fixed_width_alloc(10), fixed_width_alloc(10));
+        assert_eq!(rust_fixed_width_alloc(10), 48);
+    }
+}
+
+pub fn test_sizeof() {
+    unsafe {
+        assert_eq!(rust_fixed_width_sizes(), fixed_width_sizes());
+        assert_eq!(rust_fixed_width_sizes(), 14);
+    }
+}
@@ -0,0 +1,14 @@
+//! group_linked_units, arg_--link-functions
+
+struct pair {
+  int first;
+  int second;
+};
+
+int pair_sum(struct pair *p) {
+  return p->first + p->second;
+}
+
+int scaled(int x, int k) {
+  return x * k;
+}
@@ -0,0 +1,18 @@
+//! group_linked_units, arg_--link-functions
+
+// Same name as the record of the other unit, but different fields
+struct pair {
+  long first;
+  long second;
+};
+
+int pair_sum(struct pair *p);
+int scaled(int x, int k);
+
+int scaled_twice(int x) {
+  return scaled(scaled(x, 2), 2);
+}
+
+int pair_total(struct pair *p) {
+  return pair_sum(p);
+}
@@ -0,0 +1,12 @@
+//! arg_--rust-naming-conventions
+
+/* Defined in naming_helpers.c */
+extern int BaseOffset;
+int DoubleValue(int Value);
+
+int CallCount = 0;
+
+int AddValues(int FirstValue, int SecondValue) {
+  CallCount += 1;
+  return DoubleValue(FirstValue) + SecondValue + BaseOffset;
+}
@@ -0,0 +1,5 @@
+int BaseOffset = 10;
+
+int DoubleValue(int Value) {
+  return Value * 2;
+}
@@ -0,0 +1,51 @@
+//! arg_--stable-variadics
+
+#include <stdarg.h>
+
+// Clashes with the name of the enum that variadic arguments are passed as
+typedef int VarArg;
+
+static long sum(VarArg count, ...) {
+  va_list ap;
+  va_start(ap, count);
+  long total = 0;
+  for (int i = 0; i < count; i++)
+    total += va_arg(ap, int);
+  va_end(ap);
+  return total;
+}
+
+// Pointers and floating point numbers
+static double weigh(int count, ...) {
+  va_list ap;
+  va_start(ap, count);
+  double total = 0;
+  for (int i = 0; i < count; i++) {
+    int *p = va_arg(ap, int *);
+    double weight = va_arg(ap, double);
+    total += *p * weight;
+  }
+  va_end(ap);
+  return total;
+}
+
+// `va_copy` reads the arguments a second time
+static long sum_twice(int count, ...) {
+  va_list ap, aq;
+  va_start(ap, count);
+  va_copy(aq, ap);
+  long total = 0;
+  for (int i = 0; i < count; i++)
+    total += va_arg(ap, int);
+  for (int i = 0; i < count; i++)
+    total += 10 * va_arg(aq, int);
+  va_end(aq);
+  va_end(ap);
+  return total;
+}
+
+long stable_varargs(void) {
+  int x = 2, y = 3;
+  return sum(3, 1, 2, 3) * 10000 + (long)weigh(2, &x, 1.5, &y, 2.0) * 100 +
+         sum_twice(2, 4, 5);
+}
@@ -0,0 +1,28 @@
+extern crate libc;
+
+use linked_units_b::rust_scaled_twice;
+use self::libc::c_int;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn scaled_twice(_: c_int) -> c_int;
+}
+
+const UNIT_B: &str = include_str!("linked_units_b.rs");
+
+pub fn test_linked_declarations() {
+    // The matching declaration is imported from the defining unit
+    assert!(UNIT_B.contains("use crate::linked_units_a::rust_scaled as scaled;"));
+    assert!(!UNIT_B.contains("fn scaled("));
+
+    // The declaration using a different `struct pair` stays extern
+    assert!(UNIT_B.contains("fn pair_sum("));
+}
+
+pub fn test_linked_calls() {
+    unsafe {
+        assert_eq!(rust_scaled_twice(3), scaled_twice(3));
+        assert_eq!(rust_scaled_twice(3), 12);
+    }
+}
@@ -0,0 +1,36 @@
+extern crate libc;
+
+use naming_conventions::rust_add_values;
+
+use self::libc::c_int;
+
+#[link(name = "test")]
+#[allow(non_snake_case, non_upper_case_globals)]
+extern "C" {
+    fn AddValues(_: c_int, _: c_int) -> c_int;
+
+    // The translated function and variable, through the C names they are exported under
+    fn rust_AddValues(_: c_int, _: c_int) -> c_int;
+    static rust_CallCount: c_int;
+}
+
+const TRANSLATION: &str = include_str!("naming_conventions.rs");
+const RENAME_MAP: &str = include_str!("naming_conventions.renames.json");
+
+pub fn test_c_symbols() {
+    unsafe {
+        assert_eq!(rust_AddValues(1, 2), AddValues(1, 2));
+        assert_eq!(rust_add_values(1, 2), AddValues(1, 2));
+        assert_eq!(rust_CallCount, 2);
+    }
+
+    assert!(TRANSLATION.contains("#[export_name = \"rust_AddValues\"]"));
+    assert!(TRANSLATION.contains("#[link_name = \"DoubleValue\"]"));
+}
+
+pub fn test_rename_map() {
+    for name in &["rust_AddValues", "rust_CallCount", "DoubleValue"] {
+        let entry = format!("\"c_name\": \"{}\"", name);
+        assert!(RENAME_MAP.contains(&entry), "no entry for {}", name);
+    }
+}
@@ -0,0 +1,18 @@
+extern crate libc;
+
+use stable_varargs::rust_stable_varargs;
+
+use self::libc::c_long;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn stable_varargs() -> c_long;
+}
+
+pub fn test_stable_varargs() {
+    unsafe {
+        assert_eq!(stable_varargs(), rust_stable_varargs());
+        assert_eq!(rust_stable_varargs(), 60999);
+    }
+}
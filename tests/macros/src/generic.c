@@ -0,0 +1,19 @@
+//! translate_const_macros
+
+// Selects the same association at every expansion, so it can become a const
+#define INT_KIND _Generic(0, int: 1, double: 2, default: 0)
+
+// Selects by the type of `x` at each expansion site
+#define X_KIND _Generic(x, int: 1, double: 2, default: 0)
+
+int generic_int(int x) {
+  return X_KIND * 10 + INT_KIND;
+}
+
+int generic_double(double x) {
+  return X_KIND * 10 + INT_KIND;
+}
+
+int generic_default(char *x) {
+  return X_KIND * 10 + INT_KIND;
+}
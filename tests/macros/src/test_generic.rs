@@ -0,0 +1,31 @@
+extern crate libc;
+
+use generic::{INT_KIND, rust_generic_default, rust_generic_double, rust_generic_int};
+use self::libc::{c_char, c_double, c_int};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn generic_int(_: c_int) -> c_int;
+    #[no_mangle]
+    fn generic_double(_: c_double) -> c_int;
+    #[no_mangle]
+    fn generic_default(_: *mut c_char) -> c_int;
+}
+
+pub fn test_generic_const() {
+    assert_eq!(INT_KIND, 1);
+}
+
+pub fn test_generic_selections() {
+    let mut c = 0 as c_char;
+
+    unsafe {
+        assert_eq!(generic_int(0), rust_generic_int(0));
+        assert_eq!(generic_double(0.0), rust_generic_double(0.0));
+        assert_eq!(generic_default(&mut c), rust_generic_default(&mut c));
+        assert_eq!(rust_generic_int(0), 11);
+        assert_eq!(rust_generic_double(0.0), 21);
+        assert_eq!(rust_generic_default(&mut c), 1);
+    }
+}
@@ -0,0 +1,11 @@
+//! ast_round_trip, translate_const_macros
+
+#define SCALE 3
+
+/* Comments are saved along with the AST */
+int reloaded_sum(const int *values, int n) {
+  int total = 0;
+  for (int i = 0; i < n; i++)
+    total += values[i] * SCALE;
+  return total;
+}
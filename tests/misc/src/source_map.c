@@ -0,0 +1,9 @@
+//! arg_--source-map
+
+int mapped(int x) {
+  int y = ({
+    int t = x * 2;
+    t + 1;
+  });
+  return y;
+}
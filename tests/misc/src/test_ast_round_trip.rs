@@ -0,0 +1,27 @@
+extern crate libc;
+
+use ast_round_trip::rust_reloaded_sum;
+
+use self::libc::c_int;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn reloaded_sum(_: *const c_int, _: c_int) -> c_int;
+}
+
+const TRANSLATION: &str = include_str!("ast_round_trip.rs");
+
+pub fn test_translation_from_saved_ast() {
+    let values = [1, 2, 3, 4];
+
+    unsafe {
+        let n = values.len() as c_int;
+        assert_eq!(rust_reloaded_sum(values.as_ptr(), n), reloaded_sum(values.as_ptr(), n));
+        assert_eq!(rust_reloaded_sum(values.as_ptr(), n), 30);
+    }
+
+    // The comments and macros come from the saved AST as well
+    assert!(TRANSLATION.contains("Comments are saved along with the AST"));
+    assert!(TRANSLATION.contains("pub const SCALE"));
+}
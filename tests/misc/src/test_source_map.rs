@@ -0,0 +1,29 @@
+extern crate libc;
+
+use self::libc::c_int;
+use source_map::rust_mapped;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn mapped(_: c_int) -> c_int;
+}
+
+const TRANSLATION: &str = include_str!("source_map.rs");
+const SOURCE_MAP: &str = include_str!("source_map.srcmap.json");
+
+pub fn test_source_map() {
+    unsafe {
+        assert_eq!(rust_mapped(3), mapped(3));
+    }
+
+    // The markers the map is built from are taken back out
+    assert!(!TRANSLATION.contains("c2rust-source-map"));
+
+    // The function, the declaration of `y`, the statements of the statement expression (including
+    // its value) and the `return`
+    for line in &[3, 4, 5, 6, 8] {
+        let entry = format!("\"c_line\": {},", line);
+        assert!(SOURCE_MAP.contains(&entry), "no entry for line {}", line);
+    }
+}
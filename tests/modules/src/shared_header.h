@@ -0,0 +1,17 @@
+struct point {
+  int x;
+  int y;
+};
+
+typedef struct point point_t;
+
+/** The points between two corners */
+struct rect {
+  /* The bounds along each axis are anonymous structs */
+  struct { int lo; int hi; } x;
+  struct { int lo; int hi; } y;
+};
+
+int point_sum(point_t p);
+point_t point_scale(point_t p, int k);
+int rect_area(struct rect r);
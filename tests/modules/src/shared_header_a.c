@@ -0,0 +1,11 @@
+//! group_shared_header, arg_--header-modules
+
+#include "shared_header.h"
+
+int point_sum(point_t p) {
+  return p.x + p.y;
+}
+
+int rect_area(struct rect r) {
+  return (r.x.hi - r.x.lo) * (r.y.hi - r.y.lo);
+}
@@ -0,0 +1,20 @@
+//! group_shared_header, arg_--header-modules
+
+/* Anonymous types of this unit come first, so the ones of the header are numbered differently */
+static struct { int scale; } defaults = { 2 };
+
+#include "shared_header.h"
+
+point_t point_scale(point_t p, int k) {
+  point_t scaled = { p.x * k, p.y * k };
+  return scaled;
+}
+
+int scaled_sum(point_t p, int k) {
+  return point_sum(point_scale(p, k));
+}
+
+int default_area(void) {
+  struct rect r = { { 0, defaults.scale }, { 0, defaults.scale } };
+  return rect_area(r);
+}
@@ -0,0 +1,7 @@
+//! group_shared_inline, arg_--header-modules, arg_--share-inline-functions
+
+#include "shared_inline.h"
+
+int darken(int level, int amount) {
+  return clamp_byte(level - amount);
+}
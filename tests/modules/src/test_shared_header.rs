@@ -0,0 +1,56 @@
+extern crate libc;
+
+use shared_header_a::rust_point_sum;
+use shared_header_b::{rust_default_area, rust_point_scale, rust_scaled_sum};
+use shared_header_h::point_t;
+
+use self::libc::c_int;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn scaled_sum(_: point_t, _: c_int) -> c_int;
+}
+
+const HEADER_MODULE: &str = include_str!("shared_header_h.rs");
+const UNIT_A: &str = include_str!("shared_header_a.rs");
+const UNIT_B: &str = include_str!("shared_header_b.rs");
+
+pub fn test_header_module_is_shared() {
+    assert_eq!(HEADER_MODULE.matches("pub struct point ").count(), 1);
+    assert_eq!(HEADER_MODULE.matches("fn point_sum(").count(), 1);
+    assert_eq!(HEADER_MODULE.matches("pub struct rect ").count(), 1);
+
+    for unit in &[UNIT_A, UNIT_B] {
+        assert!(unit.contains("crate::shared_header_h::"));
+        assert!(!unit.contains("pub struct point "));
+        assert!(!unit.contains("pub type point_t "));
+        assert!(!unit.contains("pub struct rect "));
+    }
+}
+
+pub fn test_header_module_keeps_comments() {
+    assert!(HEADER_MODULE.contains("/// The points between two corners"));
+    assert!(HEADER_MODULE.contains("The bounds along each axis are anonymous structs"));
+}
+
+pub fn test_anonymous_types() {
+    // The anonymous structs of the header are numbered differently in the second unit, which
+    // imports them under its own names
+    assert!(UNIT_B.contains(" as unnamed_0;"));
+
+    unsafe {
+        assert_eq!(rust_default_area(), 4);
+    }
+}
+
+pub fn test_shared_types() {
+    let p = point_t { x: 2, y: 3 };
+
+    unsafe {
+        // The units exchange values of the types from the shared module
+        let scaled = rust_point_scale(p, 4);
+        assert_eq!(rust_point_sum(scaled), 20);
+        assert_eq!(rust_scaled_sum(p, 4), scaled_sum(p, 4));
+    }
+}
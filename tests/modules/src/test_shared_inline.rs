@@ -0,0 +1,49 @@
+extern crate libc;
+
+use shared_inline_a::rust_brighten;
+use shared_inline_b::rust_darken;
+
+use self::libc::c_int;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn brighten(_: c_int, _: c_int) -> c_int;
+    #[no_mangle]
+    fn darken(_: c_int, _: c_int) -> c_int;
+}
+
+const HEADER_MODULE: &str = include_str!("shared_inline_h.rs");
+const UNIT_A: &str = include_str!("shared_inline_a.rs");
+const UNIT_B: &str = include_str!("shared_inline_b.rs");
+
+pub fn test_inline_functions_are_shared() {
+    // The header module has the only copy of each function, which both units import
+    assert_eq!(
+        HEADER_MODULE
+            .matches("pub(crate) unsafe extern \"C\" fn rust_clamp(")
+            .count(),
+        1
+    );
+    assert_eq!(
+        HEADER_MODULE
+            .matches("pub(crate) unsafe extern \"C\" fn rust_clamp_byte(")
+            .count(),
+        1
+    );
+
+    for unit in &[UNIT_A, UNIT_B] {
+        assert!(unit.contains("crate::shared_inline_h::"));
+        assert!(!unit.contains("fn rust_clamp("));
+        assert!(!unit.contains("fn rust_clamp_byte("));
+    }
+}
+
+pub fn test_shared_inline_calls() {
+    for &(level, amount) in &[(0, 0), (100, 50), (200, 100), (50, 100), (-5, 300)] {
+        unsafe {
+            assert_eq!(rust_brighten(level, amount), brighten(level, amount));
+            assert_eq!(rust_darken(level, amount), darken(level, amount));
+        }
+    }
+}
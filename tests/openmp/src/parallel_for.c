@@ -0,0 +1,62 @@
+//! clang_arg_-fopenmp
+
+#include <limits.h>
+
+// A `<=` loop up to the largest value of its type
+int count_to_max(void) {
+  int count = 0;
+#pragma omp parallel for reduction(+:count)
+  for (int i = INT_MAX - 3; i <= INT_MAX; i++)
+    count += 1;
+  return count;
+}
+
+// `continue` ends the iteration
+int sum_odd(int n) {
+  int sum = 0;
+#pragma omp parallel for reduction(+:sum)
+  for (int i = 0; i < n; i++) {
+    if (i % 2 == 0)
+      continue;
+    sum += i;
+  }
+  return sum;
+}
+
+// Several reductions, of which `min` and `max` start from the values before the loop
+long min_max(unsigned n, const int values[], int *min, int *max) {
+  int lo = values[0], hi = values[0];
+  long total = 0;
+  unsigned i;
+#pragma omp parallel for reduction(min:lo) reduction(max:hi) reduction(+:total)
+  for (i = 0; i < n; i++) {
+    if (values[i] < lo)
+      lo = values[i];
+    if (values[i] > hi)
+      hi = values[i];
+    total += values[i];
+  }
+  *min = lo;
+  *max = hi;
+  return total;
+}
+
+// Clashes with the name of the struct that shared variables are copied into
+typedef int ParallelForShared;
+
+// `factor` and `buffer` are shared, `tmp` is private to every iteration
+void scale(unsigned n, ParallelForShared factor, int buffer[]) {
+  int tmp = -1;
+#pragma omp parallel for private(tmp)
+  for (unsigned i = 0; i < n; i++) {
+    tmp = buffer[i] * factor;
+    buffer[i] = tmp + 1;
+  }
+}
+
+// `schedule` is not supported, so the loop is translated sequentially
+void squares(unsigned n, int buffer[]) {
+#pragma omp parallel for schedule(static)
+  for (unsigned i = 0; i < n; i++)
+    buffer[i] = i * i;
+}
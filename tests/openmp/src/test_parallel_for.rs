@@ -0,0 +1,85 @@
+//! extern_crate_rayon, feature_label_break_value
+
+extern crate libc;
+
+use parallel_for::{rust_count_to_max, rust_min_max, rust_scale, rust_squares, rust_sum_odd};
+
+use self::libc::{c_int, c_long, c_uint};
+
+#[link(name = "omp")]
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn count_to_max() -> c_int;
+    #[no_mangle]
+    fn sum_odd(_: c_int) -> c_int;
+    #[no_mangle]
+    fn min_max(_: c_uint, _: *const c_int, _: *mut c_int, _: *mut c_int) -> c_long;
+    #[no_mangle]
+    fn scale(_: c_uint, _: c_int, _: *mut c_int);
+    #[no_mangle]
+    fn squares(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 100;
+
+pub fn test_inclusive_range() {
+    unsafe {
+        assert_eq!(count_to_max(), rust_count_to_max());
+        assert_eq!(rust_count_to_max(), 4);
+    }
+}
+
+pub fn test_continue() {
+    unsafe {
+        assert_eq!(sum_odd(100), rust_sum_odd(100));
+        assert_eq!(rust_sum_odd(100), 2500);
+    }
+}
+
+pub fn test_reductions() {
+    let values: Vec<c_int> = (0..BUFFER_SIZE as c_int).map(|i| (i * 37) % 101 - 50).collect();
+    let (mut min, mut max) = (0, 0);
+    let (mut rust_min, mut rust_max) = (0, 0);
+
+    let (total, rust_total) = unsafe {
+        (
+            min_max(BUFFER_SIZE as c_uint, values.as_ptr(), &mut min, &mut max),
+            rust_min_max(BUFFER_SIZE as c_uint, values.as_ptr(), &mut rust_min, &mut rust_max),
+        )
+    };
+
+    assert_eq!((min, max, total), (rust_min, rust_max, rust_total));
+    assert_eq!(min, *values.iter().min().unwrap());
+    assert_eq!(max, *values.iter().max().unwrap());
+}
+
+pub fn test_shared_and_private() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    for i in 0..BUFFER_SIZE {
+        buffer[i] = i as c_int;
+        rust_buffer[i] = i as c_int;
+    }
+
+    unsafe {
+        scale(BUFFER_SIZE as c_uint, 3, buffer.as_mut_ptr());
+        rust_scale(BUFFER_SIZE as c_uint, 3, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(&buffer[..], &rust_buffer[..]);
+    assert_eq!(rust_buffer[10], 31);
+}
+
+pub fn test_sequential_fallback() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+
+    unsafe {
+        squares(BUFFER_SIZE as c_uint, buffer.as_mut_ptr());
+        rust_squares(BUFFER_SIZE as c_uint, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(&buffer[..], &rust_buffer[..]);
+    assert_eq!(rust_buffer[9], 81);
+}
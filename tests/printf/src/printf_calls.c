@@ -0,0 +1,30 @@
+//! arg_--translate-printf
+
+#include <stdio.h>
+
+// Flags, `%%`, and widths and precisions taken from arguments
+int print_flags(char *buf, size_t size, int n, unsigned u) {
+  return snprintf(buf, size, "[%-05d|%05d|%+d|%+u|%08X|%%|%*.*d|%-*d]", n, n,
+                  n, u, u, 6, 3, n, 4, n);
+}
+
+// Arguments are cast to the types of the length modifiers
+int print_lengths(char *buf, int n) {
+  return sprintf(buf, "%hhd %hhu %hd %lx", n, n, n, n);
+}
+
+// Calls with `%n` stay calls to `sprintf`
+int print_count(char *buf, int *count) {
+  return sprintf(buf, "abc%n%d", count, 7);
+}
+
+// So do calls with positional arguments
+int print_positional(char *buf) {
+  return sprintf(buf, "%2$s %1$s", "world", "hello");
+}
+
+// The number of characters printed is returned
+int print_stdout(int n) {
+  int written = printf("%d bottles\n", n);
+  return written;
+}
@@ -0,0 +1,110 @@
+//! extern_crate_c2rust_printf
+
+extern crate libc;
+
+use printf_calls::{
+    rust_print_count, rust_print_flags, rust_print_lengths, rust_print_positional,
+    rust_print_stdout,
+};
+use std::ffi::CStr;
+
+use self::libc::{c_char, c_int, c_uint, size_t};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn print_flags(_: *mut c_char, _: size_t, _: c_int, _: c_uint) -> c_int;
+    #[no_mangle]
+    fn print_lengths(_: *mut c_char, _: c_int) -> c_int;
+    #[no_mangle]
+    fn print_count(_: *mut c_char, _: *mut c_int) -> c_int;
+    #[no_mangle]
+    fn print_positional(_: *mut c_char) -> c_int;
+    #[no_mangle]
+    fn print_stdout(_: c_int) -> c_int;
+}
+
+const BUFFER_SIZE: usize = 64;
+
+fn to_string(buffer: &[c_char]) -> String {
+    let s = unsafe { CStr::from_ptr(buffer.as_ptr()) };
+    s.to_str().unwrap().to_owned()
+}
+
+pub fn test_flags() {
+    for &(n, u, size) in &[(42, 42, BUFFER_SIZE), (-7, 4000000000, BUFFER_SIZE), (42, 42, 8)] {
+        let mut buffer = [0; BUFFER_SIZE];
+        let mut rust_buffer = [0; BUFFER_SIZE];
+
+        let (ret, rust_ret) = unsafe {
+            (
+                print_flags(buffer.as_mut_ptr(), size, n, u),
+                rust_print_flags(rust_buffer.as_mut_ptr(), size, n, u),
+            )
+        };
+
+        assert_eq!(ret, rust_ret);
+        assert_eq!(to_string(&buffer), to_string(&rust_buffer));
+    }
+
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let rust_ret = unsafe { rust_print_flags(rust_buffer.as_mut_ptr(), BUFFER_SIZE, 42, 42) };
+    assert_eq!(to_string(&rust_buffer), "[42   |00042|+42|42|0000002A|%|   042|42  ]");
+    assert_eq!(rust_ret, 43);
+
+    // `snprintf` truncates its output, but returns the length of all of it
+    let rust_ret = unsafe { rust_print_flags(rust_buffer.as_mut_ptr(), 8, 42, 42) };
+    assert_eq!(to_string(&rust_buffer), "[42   |");
+    assert_eq!(rust_ret, 43);
+}
+
+pub fn test_length_modifiers() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+
+    let (ret, rust_ret) = unsafe {
+        (
+            print_lengths(buffer.as_mut_ptr(), 65537),
+            rust_print_lengths(rust_buffer.as_mut_ptr(), 65537),
+        )
+    };
+
+    assert_eq!(ret, rust_ret);
+    assert_eq!(to_string(&buffer), to_string(&rust_buffer));
+    assert_eq!(to_string(&rust_buffer), "1 1 1 10001");
+}
+
+pub fn test_untranslated_calls() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let (mut count, mut rust_count) = (0, 0);
+
+    let (ret, rust_ret) = unsafe {
+        (
+            print_count(buffer.as_mut_ptr(), &mut count),
+            rust_print_count(rust_buffer.as_mut_ptr(), &mut rust_count),
+        )
+    };
+
+    assert_eq!((ret, count), (rust_ret, rust_count));
+    assert_eq!(to_string(&buffer), to_string(&rust_buffer));
+    assert_eq!((to_string(&rust_buffer).as_str(), rust_count), ("abc7", 3));
+
+    let (ret, rust_ret) = unsafe {
+        (
+            print_positional(buffer.as_mut_ptr()),
+            rust_print_positional(rust_buffer.as_mut_ptr()),
+        )
+    };
+
+    assert_eq!(ret, rust_ret);
+    assert_eq!(to_string(&buffer), to_string(&rust_buffer));
+    assert_eq!(to_string(&rust_buffer), "hello world");
+}
+
+pub fn test_return_value() {
+    unsafe {
+        assert_eq!(print_stdout(99), rust_print_stdout(99));
+        assert_eq!(rust_print_stdout(99), 11);
+    }
+}
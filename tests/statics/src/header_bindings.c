@@ -0,0 +1,9 @@
+//! arg_--bindings-for=header_bindings.h
+
+#include "header_bindings.h"
+
+int bound_limit = 10;
+
+int bound_next(void) {
+  return ++bound_counter < bound_limit ? bound_counter : bound_limit;
+}
@@ -0,0 +1,4 @@
+int bound_counter = 7;
+extern int bound_limit;
+
+int bound_next(void);
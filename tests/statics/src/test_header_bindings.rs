@@ -0,0 +1,12 @@
+const BINDINGS: &str = include_str!("header_bindings.rs");
+
+pub fn test_header_variables_are_declared() {
+    // The variable defined in the header is only declared, like the `extern` one
+    assert!(BINDINGS.contains("bound_counter: libc::c_int;"));
+    assert!(!BINDINGS.contains("bound_counter: libc::c_int ="));
+    assert!(BINDINGS.contains("bound_limit: libc::c_int;"));
+    assert!(!BINDINGS.contains("bound_limit: libc::c_int ="));
+
+    // Function bodies are left out
+    assert!(BINDINGS.contains("bound_next() -> libc::c_int;"));
+}
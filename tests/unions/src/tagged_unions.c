@@ -0,0 +1,144 @@
+//! arg_--tagged-unions
+
+enum kind { CIRCLE, SQUARE, RECT, EMPTY };
+
+struct rect {
+  int w;
+  int h;
+};
+
+// Only translated code uses `shape`, so it becomes a Rust enum
+struct shape {
+  enum kind kind;
+  union {
+    int radius;
+    int side;
+    struct rect rect;
+  } u;
+};
+
+static struct shape square(int side) {
+  struct shape s;
+  s.kind = SQUARE;
+  s.u.side = side;
+  return s;
+}
+
+static struct shape circle(int radius) {
+  struct shape s;
+  s.kind = CIRCLE;
+  s.u.radius = radius;
+  return s;
+}
+
+// A switch whose cases don't fall through becomes a `match` binding the union fields
+static int area(const struct shape *s) {
+  int area = 0;
+  switch (s->kind) {
+  case CIRCLE:
+    area = 3 * s->u.radius * s->u.radius;
+    break;
+  case SQUARE:
+    area = s->u.side * s->u.side;
+    break;
+  case RECT: {
+    int w = s->u.rect.w;
+    area = w * s->u.rect.h;
+    break;
+  }
+  default:
+    break;
+  }
+  return area;
+}
+
+static void grow(struct shape *s) {
+  switch (s->kind) {
+  case CIRCLE:
+    s->u.radius += 1;
+    break;
+  case SQUARE:
+    for (int i = 0; i < 2; i++) {
+      if (s->u.side > 10) break;
+      s->u.side *= 2;
+    }
+    break;
+  case RECT:
+    s->u.rect.w += 1;
+    return;
+  default:
+    break;
+  }
+}
+
+// Cases falling through use the accessors
+static int perimeter(struct shape s) {
+  int scale = 1;
+  switch (s.kind) {
+  case EMPTY:
+    scale = 0;
+  case SQUARE:
+    return scale * 4 * s.u.side;
+  case RECT:
+    return 2 * (s.u.rect.w + s.u.rect.h);
+  case CIRCLE:
+    return 6 * s.u.radius;
+  }
+  return -1;
+}
+
+static int radius_or_zero(struct shape s) {
+  if (s.kind == CIRCLE) {
+    return s.u.radius;
+  }
+  return 0;
+}
+
+// C code can reach `shared`, so it stays a struct
+struct shared {
+  enum kind tag;
+  union {
+    int i;
+    float f;
+  } u;
+};
+
+int shared_value(struct shared *s) {
+  switch (s->tag) {
+  case CIRCLE:
+    return s->u.i;
+  default:
+    return 0;
+  }
+}
+
+void tagged_unions(const unsigned buffer_size, int buffer[]) {
+  if (buffer_size < 10) { return; }
+
+  struct shape c = circle(2);
+  struct shape q = square(3);
+  struct shape r;
+  r.kind = RECT;
+  r.u.rect.w = 4;
+  r.u.rect.h = 5;
+  struct shape e;
+  e.kind = EMPTY;
+
+  buffer[0] = area(&c);
+  buffer[1] = area(&q);
+  buffer[2] = area(&r);
+  buffer[3] = area(&e);
+  grow(&c);
+  grow(&q);
+  grow(&r);
+  buffer[4] = area(&c) + area(&q) + area(&r);
+  buffer[5] = perimeter(q);
+  buffer[6] = perimeter(r);
+  buffer[7] = radius_or_zero(c);
+  buffer[8] = radius_or_zero(q);
+
+  struct shared s;
+  s.tag = CIRCLE;
+  s.u.i = 7;
+  buffer[9] = shared_value(&s);
+}
@@ -0,0 +1,45 @@
+extern crate libc;
+
+use tagged_unions::{rust_tagged_unions, shape, shared};
+
+use self::libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn tagged_unions(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 10;
+
+pub fn test_tagged_union_types() {
+    // `shape` is a Rust enum whose inactive fields read as zero
+    let mut s = shape::SQUARE(2);
+    assert_eq!(s.side(), 2);
+    assert_eq!(s.radius(), 0);
+
+    // Writing an inactive field activates it
+    *s.radius_mut() = 5;
+    match s {
+        shape::CIRCLE(radius) => assert_eq!(radius, 5),
+        _ => panic!("expected a circle"),
+    }
+
+    // C code can reach `shared`, so it keeps its fields
+    let tag = |s: shared| s.tag;
+    let _ = tag;
+}
+
+pub fn test_tagged_unions() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [12, 9, 20, 0, 196, 48, 20, 3, 0, 7];
+
+    unsafe {
+        tagged_unions(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_tagged_unions(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
@@ -0,0 +1,10 @@
+//! group_workspace
+
+#include "workspace.h"
+
+// `counted` comes from the crate of the `counter` library, `scaled` from a module of the same
+// crate
+int app_total(int n) {
+  struct counter c = {counted(n, 3), 1};
+  return scaled(&c, 2);
+}
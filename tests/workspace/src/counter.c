@@ -0,0 +1,10 @@
+//! group_workspace, arg_--workspace, arg_--target-map=targets.json, arg_--header-modules, arg_--link-functions, build_crate
+
+#include "workspace.h"
+
+int counted(int n, int step) {
+  struct counter c = {0, step};
+  for (int i = 0; i < n; i++)
+    c.count += c.step;
+  return c.count;
+}
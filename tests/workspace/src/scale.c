@@ -0,0 +1,7 @@
+//! group_workspace
+
+#include "workspace.h"
+
+int scaled(struct counter *c, int factor) {
+  return c->count * factor;
+}
@@ -0,0 +1,30 @@
+//! extern_crate_c2rust_headers
+
+extern crate libc;
+
+use app::rust_app_total;
+use c2rust_headers::workspace_h::counter;
+use scale::rust_scaled;
+
+use self::libc::c_int;
+
+#[link(name = "test")]
+extern "C" {
+    #[no_mangle]
+    fn app_total(_: c_int) -> c_int;
+}
+
+pub fn test_crates() {
+    unsafe {
+        assert_eq!(app_total(5), rust_app_total(5));
+        assert_eq!(rust_app_total(5), 30);
+    }
+}
+
+pub fn test_shared_header_module() {
+    // The modules import the types of the header from the crate shared by the workspace
+    let mut c = counter { count: 4, step: 1 };
+    unsafe {
+        assert_eq!(rust_scaled(&mut c, 3), 12);
+    }
+}
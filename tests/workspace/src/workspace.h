@@ -0,0 +1,7 @@
+struct counter {
+  int count;
+  int step;
+};
+
+int counted(int n, int step);
+int scaled(struct counter *c, int factor);